@@ -0,0 +1,142 @@
+//! Game loop for bots: the bot only decides on moves, the loop follows the
+//! game and plays them.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reversi_model::*;
+
+use crate::client::Client;
+use crate::error::ClientError;
+use crate::events::EventSubscription;
+
+/// How long to wait for an event before polling the game status anyway.
+/// Events only come from the instance the subscription is connected to.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How often to repeat the matchmaking request while waiting for a pairing.
+const MATCHMAKING_INTERVAL: Duration = Duration::from_secs(1);
+/// Pause before subscribing again after a subscription ended.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+const FINISHED: [&str; 3] = ["black_won", "white_won", "draw"];
+
+/// What a bot sees when it has to move or when the game has ended.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameState {
+    pub game_id: String,
+    /// The bot's color, "black" or "white".
+    pub color: String,
+    /// "black" or "white" to move, or the result once the game has ended.
+    pub status: String,
+    /// All moves so far in algebraic notation, "pass" for a pass, including
+    /// the moves of the opening.
+    pub moves: Vec<String>,
+}
+
+#[async_trait]
+pub trait Bot: Send {
+    /// The move to play: a square such as "f5", "pass" or "resign".
+    async fn choose_move(&mut self, state: &GameState) -> String;
+
+    /// Called once with the final state of the game.
+    async fn game_over(&mut self, _state: &GameState) {}
+
+    /// Plays a game the player created or joined with the given color until it
+    /// ends and returns the result: "black_won", "white_won" or "draw".
+    async fn play_game(
+        &mut self,
+        client: &Client,
+        player_id: &str,
+        game_id: &str,
+        color: &str,
+    ) -> Result<String, ClientError> {
+        let request: GameRequest = GameRequest {
+            player_id: player_id.to_string(),
+            game_id: game_id.to_string(),
+        };
+        // Subscribe before the first status request so that no move is missed.
+        let mut events: Option<EventSubscription> = client.subscribe(game_id).await.ok();
+        loop {
+            let status: GameStatusResult = client.game_status(&request).await?;
+            let finished: bool = FINISHED.contains(&status.status.as_str());
+            if finished || status.status == color {
+                let history: GameHistoryResult = client.game_history(&request).await?;
+                let state: GameState = GameState {
+                    game_id: game_id.to_string(),
+                    color: color.to_string(),
+                    status: status.status.clone(),
+                    moves: history.moves,
+                };
+                if finished {
+                    self.game_over(&state).await;
+                    return Ok(status.status);
+                }
+                let played: String = self.choose_move(&state).await;
+                let request: MoveRequest = MoveRequest {
+                    player_id: player_id.to_string(),
+                    game_id: game_id.to_string(),
+                    r#move: played,
+                    idempotency_key: String::new(),
+                };
+                client.play_move(&request).await?;
+                continue;
+            }
+            wait_for_update(client, game_id, &mut events).await;
+        }
+    }
+
+    /// Waits in the matchmaking queue for an opponent and plays the game.
+    async fn play_matchmaking(
+        &mut self,
+        client: &Client,
+        player_id: &str,
+        time_control: &str,
+    ) -> Result<String, ClientError> {
+        let request: MatchmakingRequest = MatchmakingRequest {
+            player_id: player_id.to_string(),
+            time_control: time_control.to_string(),
+        };
+        loop {
+            let pairing: MatchmakingResult = client.matchmaking_enqueue(&request).await?;
+            if pairing.matched {
+                return self
+                    .play_game(client, player_id, &pairing.game_id, &pairing.color)
+                    .await;
+            }
+            tokio::time::sleep(MATCHMAKING_INTERVAL).await;
+        }
+    }
+}
+
+/// Returns after the next event of the game or after `POLL_INTERVAL`. A
+/// subscription that ended is replaced.
+async fn wait_for_update(client: &Client, game_id: &str, events: &mut Option<EventSubscription>) {
+    if events.is_none() {
+        *events = client.subscribe(game_id).await.ok();
+    }
+    match events {
+        Some(subscription) => {
+            match tokio::time::timeout(POLL_INTERVAL, subscription.next()).await {
+                Ok(Some(Ok(_))) | Err(_) => {}
+                Ok(Some(Err(_))) | Ok(None) => {
+                    *events = None;
+                    tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+                }
+            }
+        }
+        None => tokio::time::sleep(POLL_INTERVAL).await,
+    }
+}
+
+/// A bot made of a move function.
+pub struct FnBot<F>(pub F);
+
+#[async_trait]
+impl<F> Bot for FnBot<F>
+where
+    F: FnMut(&GameState) -> String + Send,
+{
+    async fn choose_move(&mut self, state: &GameState) -> String {
+        (self.0)(state)
+    }
+}
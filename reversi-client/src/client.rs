@@ -0,0 +1,604 @@
+use std::time::Duration;
+
+use reqwest::header::{ACCEPT, RETRY_AFTER};
+use reqwest::{Method, RequestBuilder, StatusCode};
+use reversi_model::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::error::ClientError;
+use crate::events::EventSubscription;
+
+const API: &str = "/reversi/v1";
+
+/// How often and how long to retry a failed request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts per request including the first one; 1 turns retries off.
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for every further one.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Wait after the given failed attempt, counting from 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor: u32 = 1 << attempt.saturating_sub(1).min(16);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Which failures a request may be retried after.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Retry {
+    /// Reads and moves with an idempotency key, which are safe to repeat.
+    Always,
+    /// Other writes, only when the server cannot have handled them: the
+    /// connection failed or the request was rate limited.
+    NotHandled,
+}
+
+impl Retry {
+    fn after_error(self, e: &reqwest::Error) -> bool {
+        e.is_connect() || (self == Retry::Always && (e.is_timeout() || e.is_request()))
+    }
+
+    fn after_status(self, status: StatusCode) -> bool {
+        self == Retry::Always
+            && matches!(
+                status,
+                StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            )
+    }
+}
+
+/// Responses that wrap their result in `status`, `error` and `result`.
+trait Envelope: DeserializeOwned {
+    type Result;
+
+    fn into_result(self) -> Result<Self::Result, ClientError>;
+}
+
+macro_rules! envelope {
+    ($($response:ty => $result:ty),* $(,)?) => {
+        $(
+            impl Envelope for $response {
+                type Result = $result;
+
+                fn into_result(self) -> Result<$result, ClientError> {
+                    if self.status == "ok" {
+                        Ok(self.result)
+                    } else {
+                        Err(ClientError::Api {
+                            code: self.error.code,
+                            message: self.error.message,
+                        })
+                    }
+                }
+            }
+        )*
+    };
+}
+
+envelope! {
+    PlayerResponse => Vec<User>,
+    NewPlayerResponse => User,
+    NewGameResponse => NewGameResult,
+    GameListResponse => Vec<AvailableGame>,
+    GameStatusResponse => GameStatusResult,
+    GameHistoryResponse => GameHistoryResult,
+    AnalyzeResponse => AnalyzeResult,
+    GameReportResponse => GameReportResult,
+    GameJoinResponse => GameJoinResult,
+    MoveResponse => MoveResult,
+    MatchmakingResponse => MatchmakingResult,
+    DequeueResponse => DequeueResult,
+    LeaderboardResponse => LeaderboardResult,
+    PlayerStatsResponse => PlayerStats,
+    NewTournamentResponse => NewTournamentResult,
+    TournamentRegisterResponse => TournamentRegisterResult,
+    TournamentResponse => TournamentInfo,
+    StandingsResponse => Vec<Standing>,
+    BracketResponse => BracketInfo,
+    MatchResponse => MatchInfo,
+}
+
+pub struct ClientBuilder {
+    base_url: String,
+    token: Option<String>,
+    retry: RetryPolicy,
+    timeout: Duration,
+}
+
+impl ClientBuilder {
+    /// `base_url` is the server root, e.g. `http://localhost:8000`.
+    pub fn new(base_url: &str) -> ClientBuilder {
+        ClientBuilder {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: None,
+            retry: RetryPolicy::default(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Sends `Authorization: Bearer <token>` with every request. The server
+    /// does not check it; it is meant for a proxy that authenticates clients
+    /// in front of the API.
+    pub fn bearer_token(mut self, token: &str) -> ClientBuilder {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> ClientBuilder {
+        self.retry = retry;
+        self
+    }
+
+    /// Timeout of a single attempt. Event subscriptions only use it to
+    /// connect.
+    pub fn timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn build(self) -> Result<Client, ClientError> {
+        let http: reqwest::Client = reqwest::Client::builder()
+            .connect_timeout(self.timeout)
+            .user_agent(concat!("reversi-client/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Client {
+            http,
+            base_url: self.base_url,
+            token: self.token,
+            retry: self.retry,
+            timeout: self.timeout,
+        })
+    }
+}
+
+/// Typed access to the `/reversi/v1` API. Cloning is cheap and clones share
+/// their connections.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+    retry: RetryPolicy,
+    timeout: Duration,
+}
+
+impl Client {
+    /// A client with the default settings, see `ClientBuilder`.
+    pub fn new(base_url: &str) -> Result<Client, ClientError> {
+        ClientBuilder::new(base_url).build()
+    }
+
+    pub fn builder(base_url: &str) -> ClientBuilder {
+        ClientBuilder::new(base_url)
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request: RequestBuilder = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Sends the request `build` makes, again after failures `retry` allows,
+    /// and decodes the JSON body.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        retry: Retry,
+        build: impl Fn() -> RequestBuilder,
+    ) -> Result<T, ClientError> {
+        let mut attempt: u32 = 1;
+        loop {
+            let last: bool = attempt >= self.retry.max_attempts;
+            let backoff: Duration = self.retry.backoff(attempt);
+            match build().timeout(self.timeout).send().await {
+                Err(e) => {
+                    if last || !retry.after_error(&e) {
+                        return Err(ClientError::Http(e));
+                    }
+                    tokio::time::sleep(backoff).await;
+                }
+                Ok(response) => {
+                    let status: StatusCode = response.status();
+                    if status == StatusCode::TOO_MANY_REQUESTS {
+                        // Rate limited requests never reach the handler.
+                        let retry_after: Duration = retry_after(&response).unwrap_or(backoff);
+                        if last {
+                            return Err(ClientError::RateLimited { retry_after });
+                        }
+                        tokio::time::sleep(retry_after).await;
+                    } else if !last && retry.after_status(status) {
+                        tokio::time::sleep(backoff).await;
+                    } else {
+                        let body = response.bytes().await?;
+                        return match serde_json::from_slice::<T>(&body) {
+                            Ok(body) => Ok(body),
+                            Err(_) if !status.is_success() => {
+                                Err(ClientError::Status(status.as_u16()))
+                            }
+                            Err(e) => Err(ClientError::Decode(e.to_string())),
+                        };
+                    }
+                }
+            }
+            attempt += 1;
+        }
+    }
+
+    async fn get<R: Envelope>(&self, path: &str, retry: Retry) -> Result<R::Result, ClientError> {
+        let path: String = format!("{}{}", API, path);
+        let response: R = self
+            .send(retry, || self.request(Method::GET, &path))
+            .await?;
+        response.into_result()
+    }
+
+    async fn post<R: Envelope>(
+        &self,
+        path: &str,
+        body: &impl Serialize,
+        retry: Retry,
+    ) -> Result<R::Result, ClientError> {
+        let path: String = format!("{}{}", API, path);
+        let body: Value =
+            serde_json::to_value(body).map_err(|e| ClientError::Decode(e.to_string()))?;
+        let response: R = self
+            .send(retry, || self.request(Method::POST, &path).json(&body))
+            .await?;
+        response.into_result()
+    }
+
+    pub async fn players(&self) -> Result<Vec<User>, ClientError> {
+        self.get::<PlayerResponse>("/players", Retry::Always).await
+    }
+
+    /// Registers a player; the result carries the id the server gave them.
+    pub async fn register(&self, request: &NewPlayerRequest) -> Result<User, ClientError> {
+        self.post::<NewPlayerResponse>("/players/register", request, Retry::NotHandled)
+            .await
+    }
+
+    pub async fn create_game(
+        &self,
+        request: &NewGameRequest,
+    ) -> Result<NewGameResult, ClientError> {
+        self.post::<NewGameResponse>("/create_game", request, Retry::NotHandled)
+            .await
+    }
+
+    pub async fn game_list(
+        &self,
+        request: &NewGameRequest,
+    ) -> Result<Vec<AvailableGame>, ClientError> {
+        self.post::<GameListResponse>("/game_list", request, Retry::Always)
+            .await
+    }
+
+    pub async fn game_status(
+        &self,
+        request: &GameRequest,
+    ) -> Result<GameStatusResult, ClientError> {
+        self.post::<GameStatusResponse>("/game_status", request, Retry::Always)
+            .await
+    }
+
+    pub async fn game_history(
+        &self,
+        request: &GameRequest,
+    ) -> Result<GameHistoryResult, ClientError> {
+        self.post::<GameHistoryResponse>("/game_history", request, Retry::Always)
+            .await
+    }
+
+    pub async fn analyze(&self, request: &AnalyzeRequest) -> Result<AnalyzeResult, ClientError> {
+        self.post::<AnalyzeResponse>("/analyze", request, Retry::Always)
+            .await
+    }
+
+    pub async fn game_report(
+        &self,
+        request: &GameRequest,
+    ) -> Result<GameReportResult, ClientError> {
+        self.post::<GameReportResponse>("/game_report", request, Retry::Always)
+            .await
+    }
+
+    pub async fn join(&self, request: &GameRequest) -> Result<GameJoinResult, ClientError> {
+        self.post::<GameJoinResponse>("/join", request, Retry::NotHandled)
+            .await
+    }
+
+    /// Plays a move, "pass" or "resign". Without an `idempotency_key` in the
+    /// request a fresh one is used, so that retries after a timeout cannot
+    /// play the move twice.
+    pub async fn play_move(&self, request: &MoveRequest) -> Result<MoveResult, ClientError> {
+        let key: String = if request.idempotency_key.is_empty() {
+            Uuid::new_v4().to_string()
+        } else {
+            request.idempotency_key.clone()
+        };
+        let path: String = format!("{}/move", API);
+        let body: Value =
+            serde_json::to_value(request).map_err(|e| ClientError::Decode(e.to_string()))?;
+        let response: MoveResponse = self
+            .send(Retry::Always, || {
+                self.request(Method::POST, &path)
+                    .header(IDEMPOTENCY_KEY_HEADER, key.as_str())
+                    .json(&body)
+            })
+            .await?;
+        response.into_result()
+    }
+
+    /// Joins the matchmaking queue or returns the game the player was paired
+    /// into. Repeat it to poll while `matched` is false.
+    pub async fn matchmaking_enqueue(
+        &self,
+        request: &MatchmakingRequest,
+    ) -> Result<MatchmakingResult, ClientError> {
+        self.post::<MatchmakingResponse>("/matchmaking/enqueue", request, Retry::Always)
+            .await
+    }
+
+    pub async fn matchmaking_dequeue(
+        &self,
+        request: &NewGameRequest,
+    ) -> Result<DequeueResult, ClientError> {
+        self.post::<DequeueResponse>("/matchmaking/dequeue", request, Retry::NotHandled)
+            .await
+    }
+
+    pub async fn leaderboard(
+        &self,
+        page: Option<u64>,
+        per_page: Option<u64>,
+        time_control: Option<&str>,
+    ) -> Result<LeaderboardResult, ClientError> {
+        let mut query: Vec<(&str, String)> = Vec::new();
+        if let Some(page) = page {
+            query.push(("page", page.to_string()));
+        }
+        if let Some(per_page) = per_page {
+            query.push(("per_page", per_page.to_string()));
+        }
+        if let Some(time_control) = time_control {
+            query.push(("time_control", time_control.to_string()));
+        }
+        let path: String = format!("{}/leaderboard", API);
+        let response: LeaderboardResponse = self
+            .send(Retry::Always, || {
+                self.request(Method::GET, &path).query(&query)
+            })
+            .await?;
+        response.into_result()
+    }
+
+    pub async fn player_stats(&self, player_id: &str) -> Result<PlayerStats, ClientError> {
+        self.get::<PlayerStatsResponse>(&format!("/players/{}/stats", player_id), Retry::Always)
+            .await
+    }
+
+    pub async fn tournament_create(
+        &self,
+        request: &NewTournamentRequest,
+    ) -> Result<NewTournamentResult, ClientError> {
+        self.post::<NewTournamentResponse>("/tournaments/create", request, Retry::NotHandled)
+            .await
+    }
+
+    pub async fn tournament_register(
+        &self,
+        request: &TournamentRegisterRequest,
+    ) -> Result<TournamentRegisterResult, ClientError> {
+        self.post::<TournamentRegisterResponse>("/tournaments/register", request, Retry::NotHandled)
+            .await
+    }
+
+    pub async fn tournament_start(
+        &self,
+        request: &TournamentStartRequest,
+    ) -> Result<TournamentInfo, ClientError> {
+        self.post::<TournamentResponse>("/tournaments/start", request, Retry::NotHandled)
+            .await
+    }
+
+    pub async fn tournament(&self, tournament_id: &str) -> Result<TournamentInfo, ClientError> {
+        self.get::<TournamentResponse>(&format!("/tournaments/{}", tournament_id), Retry::Always)
+            .await
+    }
+
+    pub async fn tournament_standings(
+        &self,
+        tournament_id: &str,
+    ) -> Result<Vec<Standing>, ClientError> {
+        self.get::<StandingsResponse>(
+            &format!("/tournaments/{}/standings", tournament_id),
+            Retry::Always,
+        )
+        .await
+    }
+
+    pub async fn tournament_bracket(
+        &self,
+        tournament_id: &str,
+    ) -> Result<BracketInfo, ClientError> {
+        self.get::<BracketResponse>(
+            &format!("/tournaments/{}/bracket", tournament_id),
+            Retry::Always,
+        )
+        .await
+    }
+
+    pub async fn match_create(&self, request: &NewMatchRequest) -> Result<MatchInfo, ClientError> {
+        self.post::<MatchResponse>("/matches/create", request, Retry::NotHandled)
+            .await
+    }
+
+    pub async fn match_details(&self, match_id: &str) -> Result<MatchInfo, ClientError> {
+        self.get::<MatchResponse>(&format!("/matches/{}", match_id), Retry::Always)
+            .await
+    }
+
+    /// The OpenAPI document of the server.
+    pub async fn openapi(&self) -> Result<Value, ClientError> {
+        let path: String = format!("{}/openapi.json", API);
+        self.send(Retry::Always, || self.request(Method::GET, &path))
+            .await
+    }
+
+    pub async fn health(&self) -> Result<HealthResponse, ClientError> {
+        self.send(Retry::Always, || self.request(Method::GET, "/healthz"))
+            .await
+    }
+
+    /// The readiness checks; `status` is "not_ready" while the server drains
+    /// or its database is down.
+    pub async fn readiness(&self) -> Result<ReadinessResponse, ClientError> {
+        self.send(Retry::NotHandled, || self.request(Method::GET, "/readyz"))
+            .await
+    }
+
+    /// Subscribes to the joins and moves of a game from now on.
+    pub async fn subscribe(&self, game_id: &str) -> Result<EventSubscription, ClientError> {
+        let path: String = format!("{}/games/{}/events", API, game_id);
+        let response: reqwest::Response = self
+            .request(Method::GET, &path)
+            .header(ACCEPT, "text/event-stream")
+            .send()
+            .await?;
+        let status: StatusCode = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after: Duration =
+                retry_after(&response).unwrap_or(self.retry.initial_backoff);
+            return Err(ClientError::RateLimited { retry_after });
+        }
+        if !status.is_success() {
+            return Err(ClientError::Status(status.as_u16()));
+        }
+        Ok(EventSubscription::new(response))
+    }
+}
+
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let value: &str = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let policy: RetryPolicy = RetryPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_millis(250));
+        assert_eq!(policy.backoff(3), Duration::from_secs(1));
+        assert_eq!(policy.backoff(10), Duration::from_secs(5));
+    }
+
+    fn json_response(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    }
+
+    /// Answers the requests with `responses` in turn and returns the requests.
+    async fn serve(responses: Vec<String>) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url: String = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut requests: Vec<String> = Vec::new();
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buffer: Vec<u8> = vec![0; 8192];
+                let n: usize = socket.read(&mut buffer).await.unwrap();
+                requests.push(String::from_utf8_lossy(&buffer[..n]).to_lowercase());
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn retries_moves_with_the_same_idempotency_key() {
+        let body: &str = r#"{"status":"ok","error":{"code":200,"message":""},"result":{"ok":true,"continue":true,"winner":""}}"#;
+        let limited: String =
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                .to_string();
+        let (url, server) = serve(vec![limited, json_response(body)]).await;
+
+        let client: Client = Client::builder(&url)
+            .bearer_token("secret")
+            .build()
+            .unwrap();
+        let request: MoveRequest = MoveRequest {
+            player_id: "p".to_string(),
+            game_id: "g".to_string(),
+            r#move: "c4".to_string(),
+            idempotency_key: String::new(),
+        };
+        let result: MoveResult = client.play_move(&request).await.unwrap();
+        assert!(result.ok && result.r#continue);
+
+        let requests: Vec<String> = server.await.unwrap();
+        assert_eq!(requests.len(), 2);
+        let key = |request: &str| {
+            request
+                .lines()
+                .find(|l| l.starts_with("idempotency-key:"))
+                .map(|l| l.to_string())
+        };
+        assert!(key(&requests[0]).is_some());
+        assert_eq!(key(&requests[0]), key(&requests[1]));
+        assert!(requests[1].contains("authorization: bearer secret"));
+    }
+
+    #[tokio::test]
+    async fn reports_api_errors() {
+        let body: &str = r#"{"status":"error","error":{"code":404,"message":"Game UUID not found"},"result":{"result":false,"color":""}}"#;
+        let (url, _server) = serve(vec![json_response(body)]).await;
+        let client: Client = Client::new(&url).unwrap();
+        let request: GameRequest = GameRequest {
+            player_id: "p".to_string(),
+            game_id: "g".to_string(),
+        };
+        let error: ClientError = client.join(&request).await.unwrap_err();
+        assert_eq!(error.code(), Some(404));
+    }
+}
@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    /// The server answered with `"status": "error"`.
+    #[error("API error {code}: {message}")]
+    Api { code: u32, message: String },
+    /// Still rate limited after the last retry.
+    #[error("Rate limited, retry in {}s", retry_after.as_secs())]
+    RateLimited { retry_after: Duration },
+    #[error("Unexpected HTTP status {0}")]
+    Status(u16),
+    #[error("Invalid response: {0}")]
+    Decode(String),
+}
+
+impl ClientError {
+    /// The API error code, e.g. 404 for an unknown game.
+    pub fn code(&self) -> Option<u32> {
+        match self {
+            ClientError::Api { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
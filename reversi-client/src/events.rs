@@ -0,0 +1,142 @@
+//! Subscription to the Server-Sent Events of a game.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+
+use futures_util::{Stream, StreamExt};
+use reversi_model::GameEvent;
+
+use crate::error::ClientError;
+
+/// An event of `/games/<id>/events`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// A player joined or moved.
+    Game(GameEvent),
+    /// The subscription fell behind and missed events; fetch the game again.
+    Resync,
+}
+
+type ByteStream = Pin<Box<dyn Stream<Item = reqwest::Result<bytes::Bytes>> + Send>>;
+
+/// Events of one game as they happen. Events only come from the server
+/// instance the subscription is connected to, so clients behind a load
+/// balancer should poll now and then as well.
+pub struct EventSubscription {
+    body: ByteStream,
+    parser: SseParser,
+}
+
+impl EventSubscription {
+    pub(crate) fn new(response: reqwest::Response) -> EventSubscription {
+        EventSubscription {
+            body: Box::pin(response.bytes_stream()),
+            parser: SseParser::default(),
+        }
+    }
+
+    /// The next event, or `None` once the server closed the stream, e.g. when
+    /// it shuts down.
+    pub async fn next(&mut self) -> Option<Result<StreamEvent, ClientError>> {
+        loop {
+            if let Some(event) = self.parser.pop() {
+                return Some(event);
+            }
+            match self.body.next().await {
+                Some(Ok(chunk)) => self.parser.feed(&chunk),
+                Some(Err(e)) => return Some(Err(ClientError::Http(e))),
+                None => return None,
+            }
+        }
+    }
+}
+
+/// Splits a `text/event-stream` body into events. Comments (heartbeats) and
+/// events of unknown types are skipped.
+#[derive(Default)]
+struct SseParser {
+    line: Vec<u8>,
+    event: String,
+    data: String,
+    ready: VecDeque<Result<StreamEvent, ClientError>>,
+}
+
+impl SseParser {
+    fn feed(&mut self, chunk: &[u8]) {
+        for byte in chunk {
+            if *byte == b'\n' {
+                let line: Vec<u8> = std::mem::take(&mut self.line);
+                self.line_done(String::from_utf8_lossy(&line).trim_end_matches('\r'));
+            } else {
+                self.line.push(*byte);
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<Result<StreamEvent, ClientError>> {
+        self.ready.pop_front()
+    }
+
+    fn line_done(&mut self, line: &str) {
+        if line.is_empty() {
+            self.dispatch();
+            return;
+        }
+        if line.starts_with(':') {
+            return;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => {
+                if !self.data.is_empty() {
+                    self.data.push('\n');
+                }
+                self.data.push_str(value);
+            }
+            _ => {}
+        }
+    }
+
+    fn dispatch(&mut self) {
+        let event: String = std::mem::take(&mut self.event);
+        let data: String = std::mem::take(&mut self.data);
+        match event.as_str() {
+            "resync" => self.ready.push_back(Ok(StreamEvent::Resync)),
+            "" | "message" if !data.is_empty() => {
+                let parsed = serde_json::from_str::<GameEvent>(&data)
+                    .map(StreamEvent::Game)
+                    .map_err(|e| ClientError::Decode(e.to_string()));
+                self.ready.push_back(parsed);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_events_split_across_chunks() {
+        let mut parser: SseParser = SseParser::default();
+        parser.feed(b":\n\ndata: {\"game_id\":\"g\",\"status\":\"wh");
+        assert!(parser.pop().is_none());
+        parser.feed(b"ite\",\"last_move\":\"c4\",\"player_id\":\"p\"}\r\n\r\nevent: resync\n\n");
+        parser.feed(b"event: other\ndata: {}\n\n");
+
+        let event: GameEvent = GameEvent {
+            game_id: "g".to_string(),
+            status: "white".to_string(),
+            last_move: "c4".to_string(),
+            player_id: "p".to_string(),
+        };
+        assert_eq!(parser.pop().unwrap().unwrap(), StreamEvent::Game(event));
+        assert_eq!(parser.pop().unwrap().unwrap(), StreamEvent::Resync);
+        assert!(parser.pop().is_none());
+    }
+}
@@ -0,0 +1,36 @@
+//! Async client for the reversi server API.
+//!
+//! `Client` wraps every endpoint below `/reversi/v1` in a typed method that
+//! takes and returns the request and result types of the `reversi-model` crate.
+//! Reads and moves are retried after connection errors, rate limiting and
+//! unavailable servers; moves carry an idempotency key, so a retried move is
+//! never played twice. Other writes are only retried when the server cannot
+//! have handled them.
+//!
+//! A bot implements `Bot::choose_move` and leaves the game loop to
+//! `Bot::play_game` or `Bot::play_matchmaking`:
+//!
+//! ```no_run
+//! use reversi_client::{Bot, Client, FnBot, GameState};
+//!
+//! # async fn run() -> Result<(), reversi_client::ClientError> {
+//! let client: Client = Client::new("http://localhost:8000")?;
+//! let mut bot = FnBot(|_state: &GameState| "pass".to_string());
+//! let result: String = bot
+//!     .play_matchmaking(&client, "f1c9a4f0-8d62-4c3e-9f4b-7a0c2d5e6b11", "")
+//!     .await?;
+//! println!("{}", result);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod bot;
+pub mod client;
+pub mod error;
+pub mod events;
+
+pub use bot::{Bot, FnBot, GameState};
+pub use client::{Client, ClientBuilder, RetryPolicy};
+pub use error::ClientError;
+pub use events::{EventSubscription, StreamEvent};
+pub use reversi_model as model;
@@ -0,0 +1,711 @@
+//! Types of the reversi API shared by the server and its clients: the stored
+//! games, players and tournaments and the request and response bodies of the
+//! endpoints below `/reversi/v1`.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Header that carries the idempotency key of a move.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+pub const INITIAL_POSITION_BLACK: u64 = 0x0000000810000000u64;
+pub const INITIAL_POSITION_WHITE: u64 = 0x0000001008000000u64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game {
+    pub game_uuid: String,
+    pub black_uuid: String,
+    pub white_uuid: String,
+    pub position_black: u64,
+    pub position_white: u64,
+    pub state: u64,
+    pub time_control: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tournament {
+    pub tournament_uuid: String,
+    pub name: String,
+    pub format: String,
+    pub rounds: u64,
+    pub current_round: u64,
+    pub state: u64,
+    pub time_control: String,
+    /// Games per match in elimination formats.
+    pub best_of: u64,
+    /// How elimination matches still tied after `best_of` games are decided.
+    pub tiebreak: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TournamentPairing {
+    pub round: u64,
+    pub black_uuid: String,
+    /// Empty when `black_uuid` has a bye in this round.
+    pub white_uuid: String,
+    /// Empty for a bye.
+    pub game_uuid: String,
+    pub game_state: u64,
+    pub position_black: u64,
+    pub position_white: u64,
+    /// Bracket match the game belongs to in elimination formats, empty otherwise.
+    pub bracket_match: String,
+    /// Number of the game within its bracket match, starting from 1.
+    pub game_no: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameMatch {
+    pub match_uuid: String,
+    pub player_a_uuid: String,
+    pub player_b_uuid: String,
+    /// Number of games in the match.
+    pub games: u64,
+    pub time_control: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchGame {
+    /// Number of the game within its match, starting from 1.
+    pub game_no: u64,
+    pub game_uuid: String,
+    pub black_uuid: String,
+    pub white_uuid: String,
+    /// Opening moves the game started from, empty for the initial position.
+    pub opening: String,
+    pub game_state: u64,
+    pub position_black: u64,
+    pub position_white: u64,
+}
+
+/// Evaluation of a move played in a game compared to the best move.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveAnnotation {
+    pub move_number: u64,
+    pub played_move: u64,
+    pub best_move: u64,
+    /// Scores from the mover's point of view, final disc differentials if `exact`
+    /// and engine scores otherwise.
+    pub best_score: i32,
+    pub played_score: i32,
+    /// Whether the scores come from the endgame solver.
+    pub exact: bool,
+}
+
+/// Pushed to the subscribers of a game when a player joins or moves.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct GameEvent {
+    pub game_id: String,
+    /// The game state afterwards, e.g. "white" after a move of black.
+    pub status: String,
+    /// The move, "pass" or "resign", or empty when a player joined.
+    pub last_move: String,
+    /// The player who joined or moved.
+    pub player_id: String,
+}
+
+/// A move sent with an idempotency key and the response it got.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveRecord {
+    pub game_uuid: String,
+    pub idempotency_key: String,
+    pub player_uuid: String,
+    pub played_move: String,
+    /// The `MoveResponse` as JSON.
+    pub response: String,
+}
+
+/// Background annotation of a finished game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnotationJob {
+    pub game_uuid: String,
+    /// "queued", "running", "done" or "failed".
+    pub status: String,
+    pub error: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ResponseError {
+    pub code: u32,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct PlayerResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: Vec<User>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct User {
+    pub player_uuid: String,
+    pub comment: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct NewPlayerRequest {
+    /// Shown next to the player id, e.g. a name. Up to 1024 characters.
+    #[serde(default)]
+    pub comment: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct NewPlayerResponse {
+    pub status: String,
+    pub error: ResponseError,
+    /// The new player, with the id to use in the other requests.
+    pub result: User,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct NewGameRequest {
+    pub player_id: String,
+    /// Opening name from the book or a move prefix such as "f5d6c3". The game
+    /// starts after these forced moves.
+    #[serde(default)]
+    pub opening: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GameRequest {
+    pub player_id: String,
+    pub game_id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct NewGameResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: NewGameResult,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct AvailableGame {
+    pub game_id: String,
+    pub first_player: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GameListResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: Vec<AvailableGame>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct NewGameResult {
+    pub game_id: String,
+    pub color: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GameStatusResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: GameStatusResult,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GameStatusResult {
+    /// "pending", "black" or "white" to move, "black_won", "white_won" or "draw".
+    pub status: String,
+    pub last_move: String,
+    /// Name of the book opening the game started with, empty if unknown.
+    pub opening: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GameHistoryResult {
+    /// Moves in algebraic notation, "pass" for a pass.
+    pub moves: Vec<String>,
+    pub opening: String,
+    /// Moves annotated once the game finished.
+    pub annotations: Vec<MoveAnnotationInfo>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct MoveAnnotationInfo {
+    pub move_number: u64,
+    pub played_move: String,
+    pub best_move: String,
+    /// Scores from the mover's point of view.
+    pub best_score: i32,
+    pub played_score: i32,
+    /// How much worse the played move is than the best one.
+    pub loss: i32,
+    /// Scores are exact final disc differentials from the endgame solver.
+    pub exact: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GameHistoryResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: GameHistoryResult,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GameJoinResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: GameJoinResult,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GameJoinResult {
+    pub result: bool,
+    pub color: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct MoveRequest {
+    pub player_id: String,
+    pub game_id: String,
+    pub r#move: String,
+    /// Used when the `Idempotency-Key` header is missing.
+    #[serde(default)]
+    pub idempotency_key: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct MoveResult {
+    pub ok: bool,
+    /// False once the move ended the game.
+    pub r#continue: bool,
+    /// "black", "white" or "draw" when a resignation or a pass ended the game,
+    /// empty otherwise.
+    pub winner: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct MoveResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: MoveResult,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct MatchmakingRequest {
+    pub player_id: String,
+    #[serde(default)]
+    pub time_control: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct MatchmakingResult {
+    pub matched: bool,
+    pub game_id: String,
+    pub color: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct MatchmakingResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: MatchmakingResult,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct DequeueResult {
+    pub result: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct DequeueResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: DequeueResult,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct PlayerStats {
+    pub player_id: String,
+    pub rating: u32,
+    pub games_played: u64,
+    pub wins_black: u64,
+    pub losses_black: u64,
+    pub draws_black: u64,
+    pub wins_white: u64,
+    pub losses_white: u64,
+    pub draws_white: u64,
+    pub average_disc_differential: f64,
+}
+
+impl PlayerStats {
+    /// Accounts a finished game for the player, unfinished games are ignored.
+    pub fn record_game(&mut self, game: &Game) {
+        if game.state < 3 || game.state > 5 {
+            return;
+        }
+        let black: i64 = game.position_black.count_ones() as i64;
+        let white: i64 = game.position_white.count_ones() as i64;
+        let differential: i64;
+        if game.black_uuid == self.player_id {
+            match game.state {
+                3 => self.wins_black += 1,
+                4 => self.losses_black += 1,
+                _ => self.draws_black += 1,
+            }
+            differential = black - white;
+        } else if game.white_uuid == self.player_id {
+            match game.state {
+                4 => self.wins_white += 1,
+                3 => self.losses_white += 1,
+                _ => self.draws_white += 1,
+            }
+            differential = white - black;
+        } else {
+            return;
+        }
+        let total: f64 = self.average_disc_differential * self.games_played as f64;
+        self.games_played += 1;
+        self.average_disc_differential = (total + differential as f64) / self.games_played as f64;
+    }
+
+    pub fn wins(&self) -> u64 {
+        self.wins_black + self.wins_white
+    }
+
+    pub fn losses(&self) -> u64 {
+        self.losses_black + self.losses_white
+    }
+
+    pub fn draws(&self) -> u64 {
+        self.draws_black + self.draws_white
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct PlayerStatsResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: PlayerStats,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct LeaderboardEntry {
+    pub rank: u64,
+    pub player_id: String,
+    pub rating: u32,
+    pub games_played: u64,
+    pub wins: u64,
+    pub losses: u64,
+    pub draws: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct LeaderboardResult {
+    pub page: u64,
+    pub per_page: u64,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct LeaderboardResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: LeaderboardResult,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct NewTournamentRequest {
+    pub name: String,
+    pub format: String,
+    /// Number of rounds for Swiss tournaments, 0 picks one from the number of players.
+    #[serde(default)]
+    pub rounds: u64,
+    #[serde(default)]
+    pub time_control: String,
+    /// Games per match for elimination formats, defaults to 1.
+    #[serde(default)]
+    pub best_of: u64,
+    /// "armageddon" (default) or "extra_games", for elimination formats.
+    #[serde(default)]
+    pub tiebreak: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct NewTournamentResult {
+    pub tournament_id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct NewTournamentResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: NewTournamentResult,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct TournamentRegisterRequest {
+    pub tournament_id: String,
+    pub player_id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct TournamentRegisterResult {
+    pub result: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct TournamentRegisterResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: TournamentRegisterResult,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct TournamentStartRequest {
+    pub tournament_id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct TournamentPairingInfo {
+    pub round: u64,
+    pub match_id: String,
+    pub black: String,
+    pub white: String,
+    pub game_id: String,
+    pub result: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
+pub struct TournamentInfo {
+    pub tournament_id: String,
+    pub name: String,
+    pub format: String,
+    pub rounds: u64,
+    pub current_round: u64,
+    pub status: String,
+    pub time_control: String,
+    pub players: Vec<String>,
+    pub pairings: Vec<TournamentPairingInfo>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct TournamentResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: TournamentInfo,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct Standing {
+    pub rank: u64,
+    pub player_id: String,
+    pub score: f64,
+    pub games_played: u64,
+    pub wins: u64,
+    pub draws: u64,
+    pub losses: u64,
+    pub byes: u64,
+    pub buchholz: f64,
+    pub sonneborn_berger: f64,
+    pub disc_differential: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct StandingsResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: Vec<Standing>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct BracketMatch {
+    pub match_id: String,
+    pub bracket: String,
+    pub round: u64,
+    /// Empty while the player is not known yet, "bye" for a walkover.
+    pub player_a: String,
+    pub player_b: String,
+    pub score_a: f64,
+    pub score_b: f64,
+    pub games: Vec<String>,
+    pub status: String,
+    pub winner: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
+pub struct BracketInfo {
+    pub tournament_id: String,
+    pub format: String,
+    pub best_of: u64,
+    pub tiebreak: String,
+    pub status: String,
+    pub champion: String,
+    pub matches: Vec<BracketMatch>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct BracketResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: BracketInfo,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct NewMatchRequest {
+    pub player_a: String,
+    pub player_b: String,
+    /// Number of games, defaults to 2.
+    #[serde(default)]
+    pub games: u64,
+    /// Openings as book names or move sequences, e.g. "f5d6c3". Every pair of games
+    /// is played from an opening drawn from this list with colors swapped.
+    #[serde(default)]
+    pub openings: Vec<String>,
+    #[serde(default)]
+    pub time_control: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct MatchGameInfo {
+    pub game_no: u64,
+    pub game_id: String,
+    pub black: String,
+    pub white: String,
+    pub opening: String,
+    pub result: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
+pub struct MatchInfo {
+    pub match_id: String,
+    pub player_a: String,
+    pub player_b: String,
+    pub games_total: u64,
+    pub games_finished: u64,
+    pub score_a: f64,
+    pub score_b: f64,
+    pub status: String,
+    pub time_control: String,
+    pub games: Vec<MatchGameInfo>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct MatchResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: MatchInfo,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct AnalyzeRequest {
+    pub game_id: String,
+    /// Only analyze the position after this many moves, all positions if omitted.
+    #[serde(default)]
+    pub ply: Option<u64>,
+    /// Maximum search depth, 0 for the default.
+    #[serde(default)]
+    pub depth: u32,
+    /// Time budget for the whole request in milliseconds, 0 for the default.
+    #[serde(default)]
+    pub time_ms: u64,
+    /// Positions with at most this many empty squares are solved exactly.
+    #[serde(default)]
+    pub solve_empties: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct PositionAnalysis {
+    pub ply: u64,
+    /// "black" or "white", empty once the game is over.
+    pub to_move: String,
+    /// Move played from this position in the game, empty after the last move.
+    pub move_played: String,
+    /// Evaluation from black's point of view.
+    pub score: i32,
+    pub best_move: String,
+    pub principal_variation: Vec<String>,
+    /// Search depth, the number of empty squares for solved positions.
+    pub depth: u32,
+    /// The score is the exact result of perfect play.
+    pub exact: bool,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
+pub struct AnalyzeResult {
+    pub game_id: String,
+    pub positions: Vec<PositionAnalysis>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct AnalyzeResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: AnalyzeResult,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct MoveReport {
+    pub move_number: u64,
+    /// "black" or "white".
+    pub player: String,
+    pub played_move: String,
+    pub best_move: String,
+    /// Evaluation after the move from the mover's point of view.
+    pub evaluation: i32,
+    pub loss: i32,
+    /// "best", "good", "inaccuracy" or "blunder".
+    pub label: String,
+    pub exact: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct PlayerAccuracy {
+    pub moves: u64,
+    pub best_moves: u64,
+    pub inaccuracies: u64,
+    pub blunders: u64,
+    /// Percentage of moves that are neither inaccuracies nor blunders.
+    pub accuracy: f64,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
+pub struct GameReportResult {
+    pub game_id: String,
+    /// Annotation job status: "none", "queued", "running", "done" or "failed".
+    pub status: String,
+    /// "black_won", "white_won" or "draw", empty while the game is running.
+    pub outcome: String,
+    /// Discs of each color, the final count once the game has finished.
+    pub black_discs: u64,
+    pub white_discs: u64,
+    pub moves: Vec<MoveReport>,
+    pub black: PlayerAccuracy,
+    pub white: PlayerAccuracy,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct GameReportResponse {
+    pub status: String,
+    pub error: ResponseError,
+    pub result: GameReportResult,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct RateLimitedResponse {
+    pub status: String,
+    pub error: ResponseError,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct HealthResponse {
+    pub status: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct ReadinessCheck {
+    pub name: String,
+    pub ok: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ReadinessResponse {
+    /// "ready" or "not_ready".
+    pub status: String,
+    pub checks: Vec<ReadinessCheck>,
+}
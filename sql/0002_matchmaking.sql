@@ -0,0 +1,2 @@
+ALTER TABLE players add column rating INT UNSIGNED NOT NULL default 1500;
+ALTER TABLE games add column time_control VARCHAR(32) NOT NULL default '';
@@ -0,0 +1,32 @@
+CREATE TABLE tournaments (
+    tournament_uuid BINARY(16) NOT NULL PRIMARY KEY,
+    name VARCHAR(255) CHARACTER SET utf8mb4 COLLATE utf8mb4_general_ci NOT NULL,
+    format VARCHAR(32) NOT NULL,
+    rounds BIGINT UNSIGNED NOT NULL DEFAULT 0,
+    current_round BIGINT UNSIGNED NOT NULL DEFAULT 0,
+    state BIGINT UNSIGNED NOT NULL DEFAULT 0,
+    time_control VARCHAR(32) NOT NULL DEFAULT '',
+    start_date DATETIME DEFAULT '1970-01-01 00:00:00'
+) ENGINE=InnoDB;
+
+CREATE TABLE tournament_players (
+    tournament_uuid BINARY(16) NOT NULL,
+    player_uuid BINARY(16) NOT NULL,
+    registration_date DATETIME DEFAULT '1970-01-01 00:00:00',
+    PRIMARY KEY (tournament_uuid, player_uuid),
+    FOREIGN KEY (tournament_uuid) REFERENCES tournaments(tournament_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (player_uuid) REFERENCES players(player_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+) ENGINE=InnoDB;
+
+CREATE TABLE tournament_pairings (
+    pairing_id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    tournament_uuid BINARY(16) NOT NULL,
+    round BIGINT UNSIGNED NOT NULL,
+    black_uuid BINARY(16) NOT NULL,
+    white_uuid BINARY(16) DEFAULT NULL,
+    game_uuid BINARY(16) DEFAULT NULL,
+    FOREIGN KEY (tournament_uuid) REFERENCES tournaments(tournament_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    INDEX (tournament_uuid, round),
+    INDEX (game_uuid)
+) ENGINE=InnoDB;
@@ -0,0 +1,5 @@
+ALTER TABLE tournaments add column best_of BIGINT UNSIGNED NOT NULL default 1;
+ALTER TABLE tournaments add column tiebreak VARCHAR(16) NOT NULL default 'armageddon';
+ALTER TABLE tournament_pairings add column bracket_match VARCHAR(16) default NULL;
+ALTER TABLE tournament_pairings add column game_no BIGINT UNSIGNED NOT NULL default 0;
+ALTER TABLE tournament_pairings add UNIQUE INDEX bracket_game (tournament_uuid, bracket_match, game_no);
@@ -0,0 +1,21 @@
+CREATE TABLE matches (
+    match_uuid BINARY(16) NOT NULL PRIMARY KEY,
+    player_a_uuid BINARY(16) NOT NULL,
+    player_b_uuid BINARY(16) NOT NULL,
+    games BIGINT UNSIGNED NOT NULL,
+    time_control VARCHAR(32) NOT NULL DEFAULT '',
+    start_date DATETIME DEFAULT '1970-01-01 00:00:00',
+    FOREIGN KEY (player_a_uuid) REFERENCES players(player_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (player_b_uuid) REFERENCES players(player_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+) ENGINE=InnoDB;
+
+CREATE TABLE match_games (
+    match_uuid BINARY(16) NOT NULL,
+    game_no BIGINT UNSIGNED NOT NULL,
+    game_uuid BINARY(16) NOT NULL,
+    opening VARCHAR(255) NOT NULL DEFAULT '',
+    PRIMARY KEY (match_uuid, game_no),
+    FOREIGN KEY (match_uuid) REFERENCES matches(match_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    INDEX (game_uuid)
+) ENGINE=InnoDB;
@@ -0,0 +1,11 @@
+CREATE TABLE move_annotations (
+    game_uuid BINARY(16) NOT NULL,
+    move_number BIGINT UNSIGNED NOT NULL,
+    played_move BIGINT UNSIGNED NOT NULL,
+    best_move BIGINT UNSIGNED NOT NULL,
+    best_score INT NOT NULL,
+    played_score INT NOT NULL,
+    exact TINYINT(1) NOT NULL DEFAULT 0,
+    PRIMARY KEY (game_uuid, move_number),
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+) ENGINE=InnoDB;
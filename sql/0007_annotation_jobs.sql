@@ -0,0 +1,9 @@
+CREATE TABLE annotation_jobs (
+    game_uuid BINARY(16) NOT NULL PRIMARY KEY,
+    status VARCHAR(16) NOT NULL DEFAULT 'queued',
+    error TEXT NULL,
+    queued_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at DATETIME NULL,
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    INDEX (status)
+) ENGINE=InnoDB;
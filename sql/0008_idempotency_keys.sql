@@ -0,0 +1,11 @@
+CREATE TABLE move_idempotency_keys (
+    game_uuid BINARY(16) NOT NULL,
+    idempotency_key VARCHAR(255) NOT NULL,
+    player_uuid BINARY(16) NOT NULL,
+    played_move VARCHAR(16) NOT NULL,
+    response TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (game_uuid, idempotency_key),
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    INDEX (created_at)
+) ENGINE=InnoDB;
@@ -0,0 +1,27 @@
+CREATE TABLE players (
+    player_uuid UUID NOT NULL PRIMARY KEY,
+    comment VARCHAR(1024)
+);
+
+CREATE TABLE games (
+    game_uuid UUID NOT NULL PRIMARY KEY,
+    black_uuid UUID DEFAULT NULL,
+    white_uuid UUID DEFAULT NULL,
+    state BIGINT,
+    position_black BIGINT,
+    position_white BIGINT,
+    FOREIGN KEY (black_uuid) REFERENCES players(player_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (white_uuid) REFERENCES players(player_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE moves (
+    move_id BIGSERIAL PRIMARY KEY,
+    game_uuid UUID NOT NULL,
+    move_number BIGINT NOT NULL,
+    move_position BIGINT NOT NULL,
+    position_black BIGINT NOT NULL,
+    position_white BIGINT NOT NULL,
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX moves_game_uuid ON moves (game_uuid);
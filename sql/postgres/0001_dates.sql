@@ -0,0 +1,3 @@
+ALTER TABLE games ADD COLUMN start_date TIMESTAMP DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE games ADD COLUMN end_date TIMESTAMP DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE moves ADD COLUMN move_date TIMESTAMP DEFAULT '1970-01-01 00:00:00';
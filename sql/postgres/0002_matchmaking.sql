@@ -0,0 +1,2 @@
+ALTER TABLE players ADD COLUMN rating INTEGER NOT NULL DEFAULT 1500 CHECK (rating >= 0);
+ALTER TABLE games ADD COLUMN time_control VARCHAR(32) NOT NULL DEFAULT '';
@@ -0,0 +1,33 @@
+CREATE TABLE tournaments (
+    tournament_uuid UUID NOT NULL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    format VARCHAR(32) NOT NULL,
+    rounds BIGINT NOT NULL DEFAULT 0,
+    current_round BIGINT NOT NULL DEFAULT 0,
+    state BIGINT NOT NULL DEFAULT 0,
+    time_control VARCHAR(32) NOT NULL DEFAULT '',
+    start_date TIMESTAMP DEFAULT '1970-01-01 00:00:00'
+);
+
+CREATE TABLE tournament_players (
+    tournament_uuid UUID NOT NULL,
+    player_uuid UUID NOT NULL,
+    registration_date TIMESTAMP DEFAULT '1970-01-01 00:00:00',
+    PRIMARY KEY (tournament_uuid, player_uuid),
+    FOREIGN KEY (tournament_uuid) REFERENCES tournaments(tournament_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (player_uuid) REFERENCES players(player_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE tournament_pairings (
+    pairing_id BIGSERIAL PRIMARY KEY,
+    tournament_uuid UUID NOT NULL,
+    round BIGINT NOT NULL,
+    black_uuid UUID NOT NULL,
+    white_uuid UUID DEFAULT NULL,
+    game_uuid UUID DEFAULT NULL,
+    FOREIGN KEY (tournament_uuid) REFERENCES tournaments(tournament_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX tournament_pairings_round ON tournament_pairings (tournament_uuid, round);
+CREATE INDEX tournament_pairings_game_uuid ON tournament_pairings (game_uuid);
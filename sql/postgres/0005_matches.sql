@@ -0,0 +1,22 @@
+CREATE TABLE matches (
+    match_uuid UUID NOT NULL PRIMARY KEY,
+    player_a_uuid UUID NOT NULL,
+    player_b_uuid UUID NOT NULL,
+    games BIGINT NOT NULL,
+    time_control VARCHAR(32) NOT NULL DEFAULT '',
+    start_date TIMESTAMP DEFAULT '1970-01-01 00:00:00',
+    FOREIGN KEY (player_a_uuid) REFERENCES players(player_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (player_b_uuid) REFERENCES players(player_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE match_games (
+    match_uuid UUID NOT NULL,
+    game_no BIGINT NOT NULL,
+    game_uuid UUID NOT NULL,
+    opening VARCHAR(255) NOT NULL DEFAULT '',
+    PRIMARY KEY (match_uuid, game_no),
+    FOREIGN KEY (match_uuid) REFERENCES matches(match_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX match_games_game_uuid ON match_games (game_uuid);
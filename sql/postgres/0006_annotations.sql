@@ -0,0 +1,11 @@
+CREATE TABLE move_annotations (
+    game_uuid UUID NOT NULL,
+    move_number BIGINT NOT NULL,
+    played_move BIGINT NOT NULL,
+    best_move BIGINT NOT NULL,
+    best_score INTEGER NOT NULL,
+    played_score INTEGER NOT NULL,
+    exact BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (game_uuid, move_number),
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
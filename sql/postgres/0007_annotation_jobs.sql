@@ -0,0 +1,10 @@
+CREATE TABLE annotation_jobs (
+    game_uuid UUID NOT NULL PRIMARY KEY,
+    status VARCHAR(16) NOT NULL DEFAULT 'queued',
+    error TEXT NULL,
+    queued_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMP NULL,
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX annotation_jobs_status ON annotation_jobs (status);
@@ -0,0 +1,12 @@
+CREATE TABLE move_idempotency_keys (
+    game_uuid UUID NOT NULL,
+    idempotency_key VARCHAR(255) NOT NULL,
+    player_uuid UUID NOT NULL,
+    played_move VARCHAR(16) NOT NULL,
+    response TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (game_uuid, idempotency_key),
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX move_idempotency_keys_created_at ON move_idempotency_keys (created_at);
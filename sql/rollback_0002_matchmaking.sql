@@ -0,0 +1,2 @@
+ALTER TABLE players DROP column rating;
+ALTER TABLE games DROP column time_control;
@@ -0,0 +1,3 @@
+DROP TABLE tournament_pairings;
+DROP TABLE tournament_players;
+DROP TABLE tournaments;
@@ -0,0 +1,5 @@
+ALTER TABLE tournament_pairings DROP INDEX bracket_game;
+ALTER TABLE tournament_pairings DROP column game_no;
+ALTER TABLE tournament_pairings DROP column bracket_match;
+ALTER TABLE tournaments DROP column tiebreak;
+ALTER TABLE tournaments DROP column best_of;
@@ -0,0 +1 @@
+DROP TABLE move_idempotency_keys;
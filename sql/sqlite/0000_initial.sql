@@ -0,0 +1,27 @@
+CREATE TABLE players (
+    player_uuid TEXT NOT NULL PRIMARY KEY,
+    comment TEXT
+);
+
+CREATE TABLE games (
+    game_uuid TEXT NOT NULL PRIMARY KEY,
+    black_uuid TEXT DEFAULT NULL,
+    white_uuid TEXT DEFAULT NULL,
+    state INTEGER,
+    position_black INTEGER,
+    position_white INTEGER,
+    FOREIGN KEY (black_uuid) REFERENCES players(player_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (white_uuid) REFERENCES players(player_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE moves (
+    move_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    game_uuid TEXT NOT NULL,
+    move_number INTEGER NOT NULL,
+    move_position INTEGER NOT NULL,
+    position_black INTEGER NOT NULL,
+    position_white INTEGER NOT NULL,
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX moves_game_uuid ON moves (game_uuid);
@@ -0,0 +1,33 @@
+CREATE TABLE tournaments (
+    tournament_uuid TEXT NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL,
+    format TEXT NOT NULL,
+    rounds INTEGER NOT NULL DEFAULT 0,
+    current_round INTEGER NOT NULL DEFAULT 0,
+    state INTEGER NOT NULL DEFAULT 0,
+    time_control TEXT NOT NULL DEFAULT '',
+    start_date TEXT DEFAULT '1970-01-01 00:00:00'
+);
+
+CREATE TABLE tournament_players (
+    tournament_uuid TEXT NOT NULL,
+    player_uuid TEXT NOT NULL,
+    registration_date TEXT DEFAULT '1970-01-01 00:00:00',
+    PRIMARY KEY (tournament_uuid, player_uuid),
+    FOREIGN KEY (tournament_uuid) REFERENCES tournaments(tournament_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (player_uuid) REFERENCES players(player_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE tournament_pairings (
+    pairing_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tournament_uuid TEXT NOT NULL,
+    round INTEGER NOT NULL,
+    black_uuid TEXT NOT NULL,
+    white_uuid TEXT DEFAULT NULL,
+    game_uuid TEXT DEFAULT NULL,
+    FOREIGN KEY (tournament_uuid) REFERENCES tournaments(tournament_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX tournament_pairings_round ON tournament_pairings (tournament_uuid, round);
+CREATE INDEX tournament_pairings_game_uuid ON tournament_pairings (game_uuid);
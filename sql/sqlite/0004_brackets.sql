@@ -0,0 +1,5 @@
+ALTER TABLE tournaments ADD COLUMN best_of INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE tournaments ADD COLUMN tiebreak TEXT NOT NULL DEFAULT 'armageddon';
+ALTER TABLE tournament_pairings ADD COLUMN bracket_match TEXT DEFAULT NULL;
+ALTER TABLE tournament_pairings ADD COLUMN game_no INTEGER NOT NULL DEFAULT 0;
+CREATE UNIQUE INDEX tournament_pairings_bracket_game ON tournament_pairings (tournament_uuid, bracket_match, game_no);
@@ -0,0 +1,22 @@
+CREATE TABLE matches (
+    match_uuid TEXT NOT NULL PRIMARY KEY,
+    player_a_uuid TEXT NOT NULL,
+    player_b_uuid TEXT NOT NULL,
+    games INTEGER NOT NULL,
+    time_control TEXT NOT NULL DEFAULT '',
+    start_date TEXT DEFAULT '1970-01-01 00:00:00',
+    FOREIGN KEY (player_a_uuid) REFERENCES players(player_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (player_b_uuid) REFERENCES players(player_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE match_games (
+    match_uuid TEXT NOT NULL,
+    game_no INTEGER NOT NULL,
+    game_uuid TEXT NOT NULL,
+    opening TEXT NOT NULL DEFAULT '',
+    PRIMARY KEY (match_uuid, game_no),
+    FOREIGN KEY (match_uuid) REFERENCES matches(match_uuid) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX match_games_game_uuid ON match_games (game_uuid);
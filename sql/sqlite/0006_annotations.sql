@@ -0,0 +1,11 @@
+CREATE TABLE move_annotations (
+    game_uuid TEXT NOT NULL,
+    move_number INTEGER NOT NULL,
+    played_move INTEGER NOT NULL,
+    best_move INTEGER NOT NULL,
+    best_score INTEGER NOT NULL,
+    played_score INTEGER NOT NULL,
+    exact INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (game_uuid, move_number),
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
@@ -0,0 +1,10 @@
+CREATE TABLE annotation_jobs (
+    game_uuid TEXT NOT NULL PRIMARY KEY,
+    status TEXT NOT NULL DEFAULT 'queued',
+    error TEXT NULL,
+    queued_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TEXT NULL,
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX annotation_jobs_status ON annotation_jobs (status);
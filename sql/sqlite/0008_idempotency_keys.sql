@@ -0,0 +1,12 @@
+CREATE TABLE move_idempotency_keys (
+    game_uuid TEXT NOT NULL,
+    idempotency_key TEXT NOT NULL,
+    player_uuid TEXT NOT NULL,
+    played_move TEXT NOT NULL,
+    response TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (game_uuid, idempotency_key),
+    FOREIGN KEY (game_uuid) REFERENCES games(game_uuid) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX move_idempotency_keys_created_at ON move_idempotency_keys (created_at);
@@ -0,0 +1,3 @@
+ALTER TABLE games DROP COLUMN start_date;
+ALTER TABLE games DROP COLUMN end_date;
+ALTER TABLE moves DROP COLUMN move_date;
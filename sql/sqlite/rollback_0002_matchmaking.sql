@@ -0,0 +1,2 @@
+ALTER TABLE players DROP COLUMN rating;
+ALTER TABLE games DROP COLUMN time_control;
@@ -0,0 +1,5 @@
+DROP INDEX tournament_pairings_bracket_game;
+ALTER TABLE tournament_pairings DROP COLUMN game_no;
+ALTER TABLE tournament_pairings DROP COLUMN bracket_match;
+ALTER TABLE tournaments DROP COLUMN tiebreak;
+ALTER TABLE tournaments DROP COLUMN best_of;
@@ -0,0 +1,2 @@
+DROP TABLE match_games;
+DROP TABLE matches;
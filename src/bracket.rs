@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+
+use crate::model::{BracketMatch, TournamentPairing};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tiebreak {
+    /// A single extra game in which a draw counts as a win for white.
+    Armageddon,
+    /// Extra games are played until one of them is decisive.
+    ExtraGames,
+}
+
+impl Tiebreak {
+    pub fn parse(tiebreak: &str) -> Option<Tiebreak> {
+        match tiebreak {
+            "armageddon" => Some(Tiebreak::Armageddon),
+            "extra_games" => Some(Tiebreak::ExtraGames),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Tiebreak::Armageddon => "armageddon",
+            Tiebreak::ExtraGames => "extra_games",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BracketConfig {
+    pub double_elimination: bool,
+    pub best_of: u64,
+    pub tiebreak: Tiebreak,
+}
+
+/// A game that has to be created for a bracket match to go on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BracketGame {
+    pub match_id: String,
+    pub round: u64,
+    pub game_no: u64,
+    pub black_uuid: String,
+    pub white_uuid: String,
+}
+
+#[derive(Debug)]
+pub struct Bracket {
+    pub matches: Vec<BracketMatch>,
+    pub champion: Option<String>,
+    pub next_games: Vec<BracketGame>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Slot {
+    Pending,
+    Bye,
+    Player(String),
+}
+
+impl Slot {
+    fn name(&self) -> String {
+        match self {
+            Slot::Pending => String::new(),
+            Slot::Bye => "bye".to_string(),
+            Slot::Player(p) => p.clone(),
+        }
+    }
+}
+
+/// Number of winners bracket rounds for `players` participants.
+pub fn bracket_rounds(players: usize) -> u64 {
+    players.next_power_of_two().max(2).trailing_zeros() as u64
+}
+
+/// Seeds (1-based) in bracket order, so that the top seeds can only meet late.
+fn seed_order(size: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = vec![1];
+    while order.len() < size {
+        let n: usize = order.len() * 2;
+        order = order.iter().flat_map(|s| [*s, n + 1 - *s]).collect();
+    }
+    order
+}
+
+struct Builder<'a> {
+    config: BracketConfig,
+    games: HashMap<&'a str, Vec<&'a TournamentPairing>>,
+    results: HashMap<String, (Slot, Slot)>,
+    matches: Vec<BracketMatch>,
+    next_games: Vec<BracketGame>,
+}
+
+impl<'a> Builder<'a> {
+    fn winner(&self, match_id: &str) -> Slot {
+        self.results
+            .get(match_id)
+            .map(|r| r.0.clone())
+            .unwrap_or(Slot::Pending)
+    }
+
+    fn loser(&self, match_id: &str) -> Slot {
+        self.results
+            .get(match_id)
+            .map(|r| r.1.clone())
+            .unwrap_or(Slot::Pending)
+    }
+
+    fn resolve(&mut self, match_id: String, bracket: &str, round: u64, a: Slot, b: Slot) {
+        let games: Vec<&TournamentPairing> = self
+            .games
+            .get(match_id.as_str())
+            .cloned()
+            .unwrap_or_default();
+        let mut entry: BracketMatch = BracketMatch {
+            match_id: match_id.clone(),
+            bracket: bracket.to_string(),
+            round,
+            player_a: a.name(),
+            player_b: b.name(),
+            score_a: 0.0,
+            score_b: 0.0,
+            games: games.iter().map(|g| g.game_uuid.clone()).collect(),
+            status: "pending".to_string(),
+            winner: String::new(),
+        };
+
+        let (winner, loser): (Slot, Slot) = match (&a, &b) {
+            (Slot::Pending, _) | (_, Slot::Pending) => (Slot::Pending, Slot::Pending),
+            (Slot::Bye, Slot::Bye) => {
+                entry.status = "bye".to_string();
+                (Slot::Bye, Slot::Bye)
+            }
+            (Slot::Player(p), Slot::Bye) | (Slot::Bye, Slot::Player(p)) => {
+                entry.status = "bye".to_string();
+                entry.winner = p.clone();
+                (Slot::Player(p.clone()), Slot::Bye)
+            }
+            (Slot::Player(pa), Slot::Player(pb)) => {
+                let series: Series = play_series(self.config, pa, &games);
+                entry.score_a = series.score_a;
+                entry.score_b = series.score_b;
+                match series.winner_is_a {
+                    Some(a_won) => {
+                        let (w, l) = if a_won { (pa, pb) } else { (pb, pa) };
+                        entry.status = "finished".to_string();
+                        entry.winner = w.clone();
+                        (Slot::Player(w.clone()), Slot::Player(l.clone()))
+                    }
+                    None => {
+                        entry.status = "playing".to_string();
+                        if !series.game_in_progress {
+                            let game_no: u64 = games.len() as u64 + 1;
+                            // Colors alternate, player A has black in odd games.
+                            let (black, white) = if game_no % 2 == 1 { (pa, pb) } else { (pb, pa) };
+                            self.next_games.push(BracketGame {
+                                match_id: match_id.clone(),
+                                round,
+                                game_no,
+                                black_uuid: black.clone(),
+                                white_uuid: white.clone(),
+                            });
+                        }
+                        (Slot::Pending, Slot::Pending)
+                    }
+                }
+            }
+        };
+        self.results.insert(match_id, (winner, loser));
+        self.matches.push(entry);
+    }
+}
+
+struct Series {
+    score_a: f64,
+    score_b: f64,
+    winner_is_a: Option<bool>,
+    game_in_progress: bool,
+}
+
+fn play_series(config: BracketConfig, a: &str, games: &[&TournamentPairing]) -> Series {
+    let mut series: Series = Series {
+        score_a: 0.0,
+        score_b: 0.0,
+        winner_is_a: None,
+        game_in_progress: false,
+    };
+    let best_of: u64 = config.best_of.max(1);
+    for game in games {
+        if !(3..=5).contains(&game.game_state) {
+            series.game_in_progress = true;
+            break;
+        }
+        let a_is_black: bool = game.black_uuid == a;
+        // Points of player A in this game.
+        let a_points: f64 = match (game.game_state, a_is_black) {
+            (3, true) | (4, false) => 1.0,
+            (5, _) => 0.5,
+            _ => 0.0,
+        };
+        if game.game_no <= best_of {
+            series.score_a += a_points;
+            series.score_b += 1.0 - a_points;
+            let half: f64 = best_of as f64 / 2.0;
+            if series.score_a > half {
+                series.winner_is_a = Some(true);
+                break;
+            }
+            if series.score_b > half {
+                series.winner_is_a = Some(false);
+                break;
+            }
+            continue;
+        }
+        match config.tiebreak {
+            Tiebreak::Armageddon => {
+                // White has draw odds.
+                series.winner_is_a = Some(match game.game_state {
+                    3 => a_is_black,
+                    _ => !a_is_black,
+                });
+                break;
+            }
+            Tiebreak::ExtraGames => {
+                if a_points != 0.5 {
+                    series.winner_is_a = Some(a_points == 1.0);
+                    break;
+                }
+            }
+        }
+    }
+    series
+}
+
+/// Replays the elimination bracket of `players` (in seeding order) from the games
+/// played so far: every match with its current score, the champion once the final is
+/// decided and the games that have to be created next.
+pub fn compute_bracket(
+    players: &[String],
+    pairings: &[TournamentPairing],
+    config: BracketConfig,
+) -> Bracket {
+    let size: usize = players.len().next_power_of_two().max(2);
+    let rounds: u64 = bracket_rounds(players.len());
+    let mut games: HashMap<&str, Vec<&TournamentPairing>> = HashMap::new();
+    for p in pairings {
+        if !p.bracket_match.is_empty() {
+            games.entry(p.bracket_match.as_str()).or_default().push(p);
+        }
+    }
+    for g in games.values_mut() {
+        g.sort_by_key(|p| p.game_no);
+    }
+    let mut builder: Builder = Builder {
+        config,
+        games,
+        results: HashMap::new(),
+        matches: Vec::new(),
+        next_games: Vec::new(),
+    };
+
+    let seed = |s: usize| -> Slot {
+        match players.get(s - 1) {
+            Some(p) => Slot::Player(p.clone()),
+            None => Slot::Bye,
+        }
+    };
+    let order: Vec<usize> = seed_order(size);
+    for i in 0..size / 2 {
+        builder.resolve(
+            format!("W1-{}", i + 1),
+            "winners",
+            1,
+            seed(order[2 * i]),
+            seed(order[2 * i + 1]),
+        );
+    }
+    for r in 2..=rounds {
+        for i in 0..(size >> r) {
+            let a: Slot = builder.winner(&format!("W{}-{}", r - 1, 2 * i + 1));
+            let b: Slot = builder.winner(&format!("W{}-{}", r - 1, 2 * i + 2));
+            builder.resolve(format!("W{}-{}", r, i + 1), "winners", r, a, b);
+        }
+    }
+    let winners_champion: Slot = builder.winner(&format!("W{}-1", rounds));
+
+    let champion: Slot = if config.double_elimination {
+        // Losers bracket: odd rounds pair the survivors among themselves (the
+        // first one pairs the losers of the first winners round), even rounds let
+        // the losers of the next winners round drop in, in reverse order to delay
+        // rematches.
+        for k in 1..rounds {
+            let matches: usize = size >> (k + 1);
+            let odd: u64 = 2 * k - 1;
+            for i in 0..matches {
+                let (a, b) = if k == 1 {
+                    (
+                        builder.loser(&format!("W1-{}", 2 * i + 1)),
+                        builder.loser(&format!("W1-{}", 2 * i + 2)),
+                    )
+                } else {
+                    (
+                        builder.winner(&format!("L{}-{}", odd - 1, 2 * i + 1)),
+                        builder.winner(&format!("L{}-{}", odd - 1, 2 * i + 2)),
+                    )
+                };
+                builder.resolve(format!("L{}-{}", odd, i + 1), "losers", odd, a, b);
+            }
+            for i in 0..matches {
+                let a: Slot = builder.winner(&format!("L{}-{}", odd, i + 1));
+                let b: Slot = builder.loser(&format!("W{}-{}", k + 1, matches - i));
+                builder.resolve(format!("L{}-{}", odd + 1, i + 1), "losers", odd + 1, a, b);
+            }
+        }
+        let losers_champion: Slot = if rounds == 1 {
+            builder.loser("W1-1")
+        } else {
+            builder.winner(&format!("L{}-1", 2 * rounds - 2))
+        };
+        builder.resolve(
+            "GF-1".to_string(),
+            "grand_final",
+            1,
+            winners_champion.clone(),
+            losers_champion.clone(),
+        );
+        let final_winner: Slot = builder.winner("GF-1");
+        if let (Slot::Player(_), Slot::Player(_)) = (&winners_champion, &losers_champion) {
+            if final_winner == losers_champion {
+                // The winners bracket champion has lost once, the final is replayed.
+                builder.resolve(
+                    "GF-2".to_string(),
+                    "grand_final",
+                    2,
+                    winners_champion,
+                    losers_champion,
+                );
+                builder.winner("GF-2")
+            } else {
+                final_winner
+            }
+        } else {
+            final_winner
+        }
+    } else {
+        winners_champion
+    };
+
+    Bracket {
+        matches: builder.matches,
+        champion: match champion {
+            Slot::Player(p) => Some(p),
+            _ => None,
+        },
+        next_games: builder.next_games,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(n: usize) -> Vec<String> {
+        (1..=n).map(|i| format!("s{}", i)).collect()
+    }
+
+    fn config(double: bool, best_of: u64, tiebreak: Tiebreak) -> BracketConfig {
+        BracketConfig {
+            double_elimination: double,
+            best_of,
+            tiebreak,
+        }
+    }
+
+    /// Keeps creating and finishing the pending games with the state returned by
+    /// `result` until the bracket is decided.
+    fn play_out(
+        players: &[String],
+        config: BracketConfig,
+        result: impl Fn(&BracketGame) -> u64,
+    ) -> (Bracket, Vec<TournamentPairing>) {
+        let mut pairings: Vec<TournamentPairing> = Vec::new();
+        for _ in 0..200 {
+            let bracket = compute_bracket(players, &pairings, config);
+            if bracket.champion.is_some() || bracket.next_games.is_empty() {
+                return (bracket, pairings);
+            }
+            for g in bracket.next_games {
+                pairings.push(TournamentPairing {
+                    round: g.round,
+                    black_uuid: g.black_uuid.clone(),
+                    white_uuid: g.white_uuid.clone(),
+                    game_uuid: format!("{}#{}", g.match_id, g.game_no),
+                    game_state: result(&g),
+                    position_black: 0,
+                    position_white: 0,
+                    bracket_match: g.match_id.clone(),
+                    game_no: g.game_no,
+                });
+            }
+        }
+        panic!("bracket did not finish");
+    }
+
+    /// Lower seed number always wins.
+    fn favourite_wins(g: &BracketGame) -> u64 {
+        let black: u64 = g.black_uuid[1..].parse().unwrap();
+        let white: u64 = g.white_uuid[1..].parse().unwrap();
+        if black < white {
+            3
+        } else {
+            4
+        }
+    }
+
+    #[test]
+    fn seeds_are_spread_over_the_bracket() {
+        assert_eq!(seed_order(2), vec![1, 2]);
+        assert_eq!(seed_order(4), vec![1, 4, 2, 3]);
+        assert_eq!(seed_order(8), vec![1, 8, 4, 5, 2, 7, 3, 6]);
+    }
+
+    #[test]
+    fn single_elimination_with_byes() {
+        let players = names(5);
+        let (bracket, pairings) = play_out(
+            &players,
+            config(false, 1, Tiebreak::Armageddon),
+            favourite_wins,
+        );
+        assert_eq!(bracket.champion.as_deref(), Some("s1"));
+        // 5 players need 4 games to find a winner, byes are not played.
+        assert_eq!(pairings.len(), 4);
+        let first_round_byes = bracket
+            .matches
+            .iter()
+            .filter(|m| m.round == 1 && m.status == "bye")
+            .count();
+        assert_eq!(first_round_byes, 3);
+    }
+
+    #[test]
+    fn best_of_alternates_colors_and_stops_early() {
+        let players = names(2);
+        let (bracket, pairings) = play_out(
+            &players,
+            config(false, 3, Tiebreak::Armageddon),
+            favourite_wins,
+        );
+        assert_eq!(bracket.champion.as_deref(), Some("s1"));
+        assert_eq!(pairings.len(), 2);
+        assert_eq!(pairings[0].black_uuid, "s1");
+        assert_eq!(pairings[1].black_uuid, "s2");
+        assert_eq!(bracket.matches[0].score_a, 2.0);
+    }
+
+    #[test]
+    fn armageddon_gives_white_draw_odds() {
+        let players = names(2);
+        // Two drawn games, then the armageddon game is drawn as well.
+        let (bracket, pairings) = play_out(&players, config(false, 2, Tiebreak::Armageddon), |_| 5);
+        assert_eq!(pairings.len(), 3);
+        assert_eq!(
+            bracket.champion.as_deref(),
+            Some(pairings[2].white_uuid.as_str())
+        );
+    }
+
+    #[test]
+    fn extra_games_until_decisive() {
+        let players = names(2);
+        let (bracket, pairings) = play_out(&players, config(false, 1, Tiebreak::ExtraGames), |g| {
+            if g.game_no < 4 {
+                5
+            } else {
+                3
+            }
+        });
+        assert_eq!(pairings.len(), 4);
+        assert_eq!(
+            bracket.champion.as_deref(),
+            Some(pairings[3].black_uuid.as_str())
+        );
+    }
+
+    #[test]
+    fn double_elimination_needs_two_losses() {
+        for n in 2..=8 {
+            let players = names(n);
+            let (bracket, pairings) = play_out(
+                &players,
+                config(true, 1, Tiebreak::Armageddon),
+                favourite_wins,
+            );
+            assert_eq!(bracket.champion.as_deref(), Some("s1"), "{} players", n);
+            // Everybody but the champion loses twice: 2 * (n - 1) games.
+            assert_eq!(pairings.len(), 2 * (n - 1), "{} players", n);
+        }
+    }
+
+    #[test]
+    fn double_elimination_bracket_reset() {
+        let players = names(4);
+        // s2 beats s1 whenever they meet, otherwise the favourite wins.
+        let (bracket, pairings) = play_out(&players, config(true, 1, Tiebreak::Armageddon), |g| {
+            let pair = (g.black_uuid.as_str(), g.white_uuid.as_str());
+            match pair {
+                ("s1", "s2") => 4,
+                ("s2", "s1") => 3,
+                _ => favourite_wins(g),
+            }
+        });
+        // s1 and s2 meet in the winners final, s1 comes back through the losers
+        // bracket and loses the grand final as well.
+        assert_eq!(bracket.champion.as_deref(), Some("s2"));
+        assert!(!pairings.iter().any(|p| p.bracket_match == "GF-2"));
+        let gf = bracket
+            .matches
+            .iter()
+            .find(|m| m.match_id == "GF-1")
+            .unwrap();
+        assert_eq!(gf.player_a, "s2");
+        assert_eq!(gf.player_b, "s1");
+    }
+
+    #[test]
+    fn losers_bracket_champion_forces_reset() {
+        let players = names(2);
+        // s1 wins the first game with black, s2 comes back from the losers bracket
+        // and wins the grand final with white, s1 wins the replay.
+        let (bracket, pairings) = play_out(&players, config(true, 1, Tiebreak::Armageddon), |g| {
+            if g.match_id == "GF-1" {
+                4
+            } else {
+                3
+            }
+        });
+        assert_eq!(pairings.len(), 3);
+        assert_eq!(pairings[2].bracket_match, "GF-2");
+        assert_eq!(bracket.champion.as_deref(), Some("s1"));
+    }
+}
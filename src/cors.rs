@@ -0,0 +1,128 @@
+//! Cross-origin requests to the API, so that browser clients served from
+//! another origin can call it. Only origins listed in the settings get the
+//! `Access-Control-*` headers; browsers refuse the responses for the others.
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Method};
+use rocket::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+/// Response headers scripts may read besides the basic ones.
+const EXPOSED_HEADERS: &str = "Retry-After, X-Request-Id";
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins such as `https://play.example.com`, or `*` for any. Empty
+    /// turns CORS off.
+    pub allowed_origins: Vec<String>,
+    /// Request headers browsers may send.
+    pub allowed_headers: Vec<String>,
+    /// How long browsers may cache a preflight response.
+    pub max_age_secs: u32,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            allowed_origins: Vec::new(),
+            allowed_headers: vec![
+                "Content-Type".to_string(),
+                "Idempotency-Key".to_string(),
+                "X-Request-Id".to_string(),
+            ],
+            max_age_secs: 3600,
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.allowed_origins.is_empty()
+    }
+
+    /// The `Access-Control-Allow-Origin` value for a request from `origin`.
+    pub fn allow_origin(&self, origin: &str) -> Option<String> {
+        if self.allowed_origins.iter().any(|o| o == "*") {
+            Some("*".to_string())
+        } else if self.allowed_origins.iter().any(|o| o == origin) {
+            Some(origin.to_string())
+        } else {
+            None
+        }
+    }
+}
+
+/// Adds the CORS headers to the responses of routes below `prefix`.
+pub struct CorsFairing {
+    pub config: CorsConfig,
+    pub prefix: &'static str,
+}
+
+#[rocket::async_trait]
+impl Fairing for CorsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if !request.uri().path().starts_with(self.prefix) {
+            return;
+        }
+        let origin: &str = match request.headers().get_one("Origin") {
+            Some(o) => o,
+            None => return,
+        };
+        let allowed: String = match self.config.allow_origin(origin) {
+            Some(a) => a,
+            None => return,
+        };
+        response.set_header(Header::new("Access-Control-Allow-Origin", allowed));
+        response.set_header(Header::new("Vary", "Origin"));
+        response.set_header(Header::new(
+            "Access-Control-Expose-Headers",
+            EXPOSED_HEADERS,
+        ));
+        if request.method() == Method::Options {
+            response.set_header(Header::new(
+                "Access-Control-Allow-Methods",
+                "GET, POST, OPTIONS",
+            ));
+            response.set_header(Header::new(
+                "Access-Control-Allow-Headers",
+                self.config.allowed_headers.join(", "),
+            ));
+            response.set_header(Header::new(
+                "Access-Control-Max-Age",
+                self.config.max_age_secs.to_string(),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_listed_origins_only() {
+        assert!(!CorsConfig::default().is_enabled());
+        let mut config: CorsConfig = CorsConfig {
+            allowed_origins: vec!["https://play.example.com".to_string()],
+            ..CorsConfig::default()
+        };
+        assert_eq!(
+            config.allow_origin("https://play.example.com"),
+            Some("https://play.example.com".to_string())
+        );
+        assert_eq!(config.allow_origin("https://evil.example.com"), None);
+        config.allowed_origins.push("*".to_string());
+        assert_eq!(
+            config.allow_origin("https://evil.example.com"),
+            Some("*".to_string())
+        );
+    }
+}
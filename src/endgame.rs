@@ -0,0 +1,278 @@
+use std::time::Instant;
+
+use crate::engine::{differential_score, flips, legal_moves, Analysis, Position, PASS};
+use crate::model::MoveAnnotation;
+
+/// Positions with more empty squares are not solved by default.
+pub const DEFAULT_SOLVE_EMPTIES: u32 = 14;
+/// Upper bound for the number of empty squares a request may ask to solve.
+pub const MAX_SOLVE_EMPTIES: u32 = 20;
+
+const NODES_PER_TIME_CHECK: u64 = 4096;
+/// Below this many empties move ordering costs more than it saves.
+const ORDERING_MIN_EMPTIES: u32 = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    /// Final disc differential with perfect play, from the side to move's point of view.
+    pub disc_differential: i32,
+    /// Optimal move, `PASS` if the side to move has to pass and `None` if the game is over.
+    pub best_move: Option<u64>,
+    pub nodes: u64,
+}
+
+struct Solver {
+    deadline: Instant,
+    nodes: u64,
+    aborted: bool,
+}
+
+fn moves_of(bits: u64) -> Vec<u64> {
+    let mut moves: Vec<u64> = Vec::new();
+    let mut remaining: u64 = bits;
+    while remaining != 0 {
+        moves.push(remaining & remaining.wrapping_neg());
+        remaining &= remaining - 1;
+    }
+    moves
+}
+
+impl Solver {
+    /// Exact disc differential for the side to move, together with the best move.
+    fn solve(
+        &mut self,
+        player: u64,
+        opponent: u64,
+        mut alpha: i32,
+        beta: i32,
+    ) -> (i32, Option<u64>) {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(NODES_PER_TIME_CHECK) && Instant::now() >= self.deadline {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return (0, None);
+        }
+
+        let moves: u64 = legal_moves(player, opponent);
+        if moves == 0 {
+            if legal_moves(opponent, player) == 0 {
+                return (
+                    player.count_ones() as i32 - opponent.count_ones() as i32,
+                    None,
+                );
+            }
+            let (score, _) = self.solve(opponent, player, -beta, -alpha);
+            return (-score, Some(PASS));
+        }
+
+        let mut candidates: Vec<u64> = moves_of(moves);
+        if (player | opponent).count_zeros() > ORDERING_MIN_EMPTIES {
+            // Fastest first: replies that leave the opponent few moves tend to cut off.
+            candidates.sort_by_cached_key(|&mv| {
+                let flipped: u64 = flips(player, opponent, mv);
+                legal_moves(opponent & !flipped, player | mv | flipped).count_ones()
+            });
+        }
+
+        let mut best: (i32, Option<u64>) = (-65, None);
+        for mv in candidates {
+            let flipped: u64 = flips(player, opponent, mv);
+            let (score, _) = self.solve(opponent & !flipped, player | mv | flipped, -beta, -alpha);
+            let score: i32 = -score;
+            if score > best.0 {
+                best = (score, Some(mv));
+                alpha = alpha.max(score);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Solves the position exactly, `None` if it cannot be done before `deadline`.
+pub fn solve(position: &Position, deadline: Instant) -> Option<Solution> {
+    let (player, opponent) = position.player_and_opponent();
+    let mut solver: Solver = Solver {
+        deadline,
+        nodes: 0,
+        aborted: false,
+    };
+    let (disc_differential, best_move) = solver.solve(player, opponent, -64, 64);
+    if solver.aborted {
+        return None;
+    }
+    Some(Solution {
+        disc_differential,
+        best_move,
+        nodes: solver.nodes,
+    })
+}
+
+/// Exact analysis of a position in the format of the heuristic search: the score
+/// is from black's point of view and the principal variation is an optimal line.
+pub fn solve_analysis(position: &Position, deadline: Instant) -> Option<Analysis> {
+    let solution: Solution = solve(position, deadline)?;
+    let sign: i32 = if position.black_to_move { 1 } else { -1 };
+
+    // Every position along an optimal line is smaller than the root, so following
+    // the best moves costs a fraction of the first solve. The line is cut short if
+    // time runs out.
+    let mut principal_variation: Vec<u64> = Vec::new();
+    let mut nodes: u64 = solution.nodes;
+    let mut current: Position = *position;
+    let mut next_move: Option<u64> = solution.best_move;
+    while let Some(mv) = next_move {
+        principal_variation.push(mv);
+        current = current.play(mv);
+        match solve(&current, deadline) {
+            Some(s) => {
+                nodes += s.nodes;
+                next_move = s.best_move;
+            }
+            None => break,
+        }
+    }
+
+    Some(Analysis {
+        score: sign * differential_score(solution.disc_differential),
+        best_move: solution.best_move,
+        principal_variation,
+        depth: position.empties(),
+        nodes,
+    })
+}
+
+/// Mistakes of the moves played with at most `max_empties` empty squares on the
+/// board: for each of them the optimal move and how many discs of the final
+/// result were lost compared to it. Passes are forced and skipped. Stops at the
+/// first position that cannot be solved before `deadline`.
+pub fn annotate_endgame(moves: &[u64], max_empties: u32, deadline: Instant) -> Vec<MoveAnnotation> {
+    let positions: Vec<Position> = crate::engine::game_positions(moves);
+    // Exact result of every late position for black, solved from the end of the
+    // game so that each solve is smaller than the next one.
+    let mut values: Vec<Option<Solution>> = vec![None; positions.len()];
+    for idx in (0..positions.len()).rev() {
+        if positions[idx].empties() > max_empties {
+            break;
+        }
+        match solve(&positions[idx], deadline) {
+            Some(s) => values[idx] = Some(s),
+            None => break,
+        }
+    }
+
+    let mut annotations: Vec<MoveAnnotation> = Vec::new();
+    for (idx, &mv) in moves.iter().enumerate() {
+        if mv == PASS {
+            continue;
+        }
+        if let (Some(before), Some(after)) = (&values[idx], &values[idx + 1]) {
+            // The side to move changes after every move, so the value after the move
+            // is negated to get the mover's point of view.
+            let best_score: i32 = before.disc_differential;
+            let played_score: i32 = -after.disc_differential;
+            annotations.push(MoveAnnotation {
+                move_number: idx as u64 + 1,
+                played_move: mv,
+                best_move: before.best_move.unwrap_or(mv),
+                best_score,
+                played_score,
+                exact: true,
+            });
+        }
+    }
+    annotations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{analyze, SearchLimits, WIN_SCORE};
+    use std::time::Duration;
+
+    fn bit(file: u32, rank: u32) -> u64 {
+        1u64 << (rank * 8 + file)
+    }
+
+    fn far_deadline() -> Instant {
+        Instant::now() + Duration::from_secs(60)
+    }
+
+    /// Plays `count` moves from the initial position, always taking the lowest legal
+    /// move (or passing), which gives a reproducible position with few empties.
+    fn play_lowest(count: usize) -> Vec<u64> {
+        let mut moves: Vec<u64> = Vec::new();
+        let mut position: Position = Position::initial();
+        for _ in 0..count {
+            if position.is_game_over() {
+                break;
+            }
+            let (player, opponent) = position.player_and_opponent();
+            let legal: u64 = legal_moves(player, opponent);
+            let mv: u64 = if legal == 0 {
+                PASS
+            } else {
+                legal & legal.wrapping_neg()
+            };
+            moves.push(mv);
+            position = position.play(mv);
+        }
+        moves
+    }
+
+    #[test]
+    fn solves_small_endgames() {
+        // Black a1, white b1, c1 empty: black takes c1 and wins 3-0 after white passes.
+        let position: Position = Position {
+            black: bit(0, 0),
+            white: bit(1, 0),
+            black_to_move: true,
+        };
+        let solution: Solution = solve(&position, far_deadline()).unwrap();
+        assert_eq!(solution.disc_differential, 3);
+        assert_eq!(solution.best_move, Some(bit(2, 0)));
+
+        let analysis: Analysis = solve_analysis(&position, far_deadline()).unwrap();
+        assert_eq!(analysis.score, WIN_SCORE + 3);
+        assert_eq!(analysis.principal_variation, vec![bit(2, 0)]);
+    }
+
+    #[test]
+    fn agrees_with_a_full_depth_search() {
+        let moves: Vec<u64> = play_lowest(50);
+        let position: Position = crate::engine::game_positions(&moves)[moves.len()];
+        let exact: Analysis = solve_analysis(&position, far_deadline()).unwrap();
+        let searched: Analysis = analyze(
+            &position,
+            SearchLimits {
+                max_depth: 20,
+                time_budget: Duration::from_secs(60),
+            },
+        );
+        assert_eq!(exact.score.signum(), searched.score.signum());
+        assert!(exact.score.abs() > WIN_SCORE / 2 || exact.score == 0);
+    }
+
+    #[test]
+    fn annotates_late_moves() {
+        let moves: Vec<u64> = play_lowest(60);
+        let annotations: Vec<MoveAnnotation> = annotate_endgame(&moves, 8, far_deadline());
+        assert!(!annotations.is_empty());
+        for a in &annotations {
+            assert!(a.best_score >= a.played_score);
+            assert!(a.exact);
+            assert_ne!(a.played_move, PASS);
+        }
+        let positions: Vec<Position> = crate::engine::game_positions(&moves);
+        let first: u64 = annotations[0].move_number;
+        assert!(positions[first as usize - 1].empties() <= 8);
+    }
+
+    #[test]
+    fn gives_up_at_the_deadline() {
+        assert_eq!(solve(&Position::initial(), Instant::now()), None);
+    }
+}
@@ -0,0 +1,450 @@
+use std::time::{Duration, Instant};
+
+use crate::model::{INITIAL_POSITION_BLACK, INITIAL_POSITION_WHITE};
+
+/// Move value used for a pass, the same as in the `moves` table.
+pub const PASS: u64 = u64::MAX;
+/// Offset of the scores of finished games, so that every won position scores
+/// higher than any heuristic evaluation.
+pub const WIN_SCORE: i32 = 10_000;
+
+pub const DEFAULT_ANALYSIS_DEPTH: u32 = 8;
+pub const MAX_ANALYSIS_DEPTH: u32 = 14;
+/// Time budget of an analysis request in milliseconds, shared by all positions.
+pub const DEFAULT_ANALYSIS_TIME_MS: u64 = 2000;
+pub const MAX_ANALYSIS_TIME_MS: u64 = 10000;
+
+const INFINITY: i32 = 1_000_000;
+const NOT_FIRST_FILE: u64 = 0xfefefefefefefefe;
+const NOT_LAST_FILE: u64 = 0x7f7f7f7f7f7f7f7f;
+/// How often the search looks at the clock.
+const NODES_PER_TIME_CHECK: u64 = 1024;
+
+// Square weights by distance from the edge, the table is symmetric so it does not
+// depend on the orientation of the bitboards.
+#[rustfmt::skip]
+const SQUARE_WEIGHTS: [i32; 64] = [
+    100, -20, 10,  5,  5, 10, -20, 100,
+    -20, -50, -2, -2, -2, -2, -50, -20,
+     10,  -2,  1,  1,  1,  1,  -2,  10,
+      5,  -2,  1,  0,  0,  1,  -2,   5,
+      5,  -2,  1,  0,  0,  1,  -2,   5,
+     10,  -2,  1,  1,  1,  1,  -2,  10,
+    -20, -50, -2, -2, -2, -2, -50, -20,
+    100, -20, 10,  5,  5, 10, -20, 100,
+];
+
+#[derive(Debug, Clone, Copy)]
+pub struct SearchLimits {
+    pub max_depth: u32,
+    pub time_budget: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    /// Evaluation from black's point of view. Finished games score
+    /// `WIN_SCORE + disc differential` for the winner.
+    pub score: i32,
+    /// Best move as a single bit, `PASS` if the side to move has to pass and
+    /// `None` if the game is over.
+    pub best_move: Option<u64>,
+    pub principal_variation: Vec<u64>,
+    /// Depth of the last completed iteration.
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub black: u64,
+    pub white: u64,
+    pub black_to_move: bool,
+}
+
+impl Position {
+    pub fn initial() -> Self {
+        Position {
+            black: INITIAL_POSITION_BLACK,
+            white: INITIAL_POSITION_WHITE,
+            black_to_move: true,
+        }
+    }
+
+    /// Discs of the side to move and of its opponent.
+    pub fn player_and_opponent(&self) -> (u64, u64) {
+        if self.black_to_move {
+            (self.black, self.white)
+        } else {
+            (self.white, self.black)
+        }
+    }
+
+    /// Plays `mv` (a single bit or `PASS`) for the side to move.
+    pub fn play(&self, mv: u64) -> Position {
+        let (player, opponent) = self.player_and_opponent();
+        let (player, opponent) = if mv == PASS {
+            (player, opponent)
+        } else {
+            let flipped: u64 = flips(player, opponent, mv);
+            (player | mv | flipped, opponent & !flipped)
+        };
+        if self.black_to_move {
+            Position {
+                black: player,
+                white: opponent,
+                black_to_move: false,
+            }
+        } else {
+            Position {
+                black: opponent,
+                white: player,
+                black_to_move: true,
+            }
+        }
+    }
+
+    pub fn empties(&self) -> u32 {
+        64 - (self.black | self.white).count_ones()
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        let (player, opponent) = self.player_and_opponent();
+        legal_moves(player, opponent) == 0 && legal_moves(opponent, player) == 0
+    }
+}
+
+/// Positions of a game before every move and after the last one.
+pub fn game_positions(moves: &[u64]) -> Vec<Position> {
+    let mut positions: Vec<Position> = vec![Position::initial()];
+    for &mv in moves {
+        let next: Position = positions[positions.len() - 1].play(mv);
+        positions.push(next);
+    }
+    positions
+}
+
+fn shift(bits: u64, direction: usize) -> u64 {
+    match direction {
+        0 => (bits << 1) & NOT_FIRST_FILE,
+        1 => (bits >> 1) & NOT_LAST_FILE,
+        2 => bits << 8,
+        3 => bits >> 8,
+        4 => (bits << 9) & NOT_FIRST_FILE,
+        5 => (bits >> 9) & NOT_LAST_FILE,
+        6 => (bits << 7) & NOT_LAST_FILE,
+        _ => (bits >> 7) & NOT_FIRST_FILE,
+    }
+}
+
+/// Squares `player` may play. The search needs all of them at once, which
+/// `reversi_tools` does not provide, so the engine generates moves itself; the
+/// tests check that it agrees with the `reversi_tools::position` rules the
+/// games are played by.
+pub fn legal_moves(player: u64, opponent: u64) -> u64 {
+    let empty: u64 = !(player | opponent);
+    let mut moves: u64 = 0;
+    for direction in 0..8 {
+        let mut run: u64 = shift(player, direction) & opponent;
+        for _ in 0..5 {
+            run |= shift(run, direction) & opponent;
+        }
+        moves |= shift(run, direction) & empty;
+    }
+    moves
+}
+
+/// Discs of `opponent` flipped by `player` playing `mv`.
+pub fn flips(player: u64, opponent: u64, mv: u64) -> u64 {
+    let mut flipped: u64 = 0;
+    for direction in 0..8 {
+        let mut run: u64 = 0;
+        let mut square: u64 = shift(mv, direction);
+        while square & opponent != 0 {
+            run |= square;
+            square = shift(square, direction);
+        }
+        if square & player != 0 {
+            flipped |= run;
+        }
+    }
+    flipped
+}
+
+/// Score of a finished game won by `diff` discs.
+pub fn differential_score(diff: i32) -> i32 {
+    diff.signum() * WIN_SCORE + diff
+}
+
+fn final_score(player: u64, opponent: u64) -> i32 {
+    differential_score(player.count_ones() as i32 - opponent.count_ones() as i32)
+}
+
+fn evaluate(player: u64, opponent: u64) -> i32 {
+    let mut positional: i32 = 0;
+    for (square, weight) in SQUARE_WEIGHTS.iter().enumerate() {
+        let bit: u64 = 1u64 << square;
+        if player & bit != 0 {
+            positional += weight;
+        } else if opponent & bit != 0 {
+            positional -= weight;
+        }
+    }
+    let mobility: i32 = legal_moves(player, opponent).count_ones() as i32
+        - legal_moves(opponent, player).count_ones() as i32;
+    positional + 10 * mobility
+}
+
+struct Search {
+    deadline: Instant,
+    /// Set while searching the first iteration, which ignores the deadline.
+    must_complete: bool,
+    nodes: u64,
+    aborted: bool,
+    /// Principal variation of the previous iteration, searched first.
+    hint: Vec<u64>,
+}
+
+impl Search {
+    fn negamax(
+        &mut self,
+        player: u64,
+        opponent: u64,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> (i32, Vec<u64>) {
+        self.nodes += 1;
+        if !self.must_complete
+            && self.nodes.is_multiple_of(NODES_PER_TIME_CHECK)
+            && Instant::now() >= self.deadline
+        {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return (0, Vec::new());
+        }
+
+        let moves: u64 = legal_moves(player, opponent);
+        if moves == 0 {
+            if legal_moves(opponent, player) == 0 {
+                return (final_score(player, opponent), Vec::new());
+            }
+            let (score, mut pv) = self.negamax(opponent, player, depth, ply + 1, -beta, -alpha);
+            pv.insert(0, PASS);
+            return (-score, pv);
+        }
+        if depth == 0 {
+            return (evaluate(player, opponent), Vec::new());
+        }
+
+        let mut candidates: Vec<u64> = Vec::new();
+        let mut remaining: u64 = moves;
+        while remaining != 0 {
+            let bit: u64 = remaining & remaining.wrapping_neg();
+            candidates.push(bit);
+            remaining &= remaining - 1;
+        }
+        let hinted: Option<u64> = self.hint.get(ply).copied();
+        candidates.sort_by_key(|&mv| {
+            if Some(mv) == hinted {
+                i32::MIN
+            } else {
+                -SQUARE_WEIGHTS[mv.trailing_zeros() as usize]
+            }
+        });
+
+        let mut best: (i32, Vec<u64>) = (-INFINITY, Vec::new());
+        for mv in candidates {
+            let flipped: u64 = flips(player, opponent, mv);
+            let (score, pv) = self.negamax(
+                opponent & !flipped,
+                player | mv | flipped,
+                depth - 1,
+                ply + 1,
+                -beta,
+                -alpha,
+            );
+            let score: i32 = -score;
+            if score > best.0 {
+                let mut line: Vec<u64> = vec![mv];
+                line.extend(pv);
+                best = (score, line);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+}
+
+/// Iterative deepening alpha-beta search. The first iteration always completes,
+/// later ones are discarded if they run out of time.
+pub fn analyze(position: &Position, limits: SearchLimits) -> Analysis {
+    let (player, opponent) = position.player_and_opponent();
+    let sign: i32 = if position.black_to_move { 1 } else { -1 };
+    if position.is_game_over() {
+        return Analysis {
+            score: sign * final_score(player, opponent),
+            best_move: None,
+            principal_variation: Vec::new(),
+            depth: 0,
+            nodes: 0,
+        };
+    }
+
+    let mut search: Search = Search {
+        deadline: Instant::now() + limits.time_budget,
+        must_complete: true,
+        nodes: 0,
+        aborted: false,
+        hint: Vec::new(),
+    };
+    let mut result: (i32, Vec<u64>, u32) = (0, Vec::new(), 0);
+    // No position has more than 60 empty squares, deeper searches find nothing new.
+    for depth in 1..=limits.max_depth.clamp(1, 60) {
+        let (score, pv) = search.negamax(player, opponent, depth, 0, -INFINITY, INFINITY);
+        if search.aborted {
+            break;
+        }
+        search.hint = pv.clone();
+        search.must_complete = false;
+        result = (score, pv, depth);
+        if score.abs() > WIN_SCORE / 2 || Instant::now() >= search.deadline {
+            break;
+        }
+    }
+
+    Analysis {
+        score: sign * result.0,
+        best_move: result.1.first().copied(),
+        principal_variation: result.1,
+        depth: result.2,
+        nodes: search.nodes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bit(file: u32, rank: u32) -> u64 {
+        1u64 << (rank * 8 + file)
+    }
+
+    fn limits(max_depth: u32) -> SearchLimits {
+        SearchLimits {
+            max_depth,
+            time_budget: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn initial_position_has_four_moves() {
+        let p: Position = Position::initial();
+        assert_eq!(legal_moves(p.black, p.white).count_ones(), 4);
+        assert_eq!(legal_moves(p.white, p.black).count_ones(), 4);
+    }
+
+    #[test]
+    fn moves_flip_enclosed_discs() {
+        let p: Position = Position::initial();
+        let mv: u64 = legal_moves(p.black, p.white) & legal_moves(p.black, p.white).wrapping_neg();
+        let next: Position = p.play(mv);
+        assert_eq!(next.black.count_ones(), 4);
+        assert_eq!(next.white.count_ones(), 1);
+        assert!(!next.black_to_move);
+        assert!(game_positions(&[mv, PASS])[2].black_to_move);
+    }
+
+    #[test]
+    fn takes_the_winning_corner() {
+        // Black can take the corner and flip the whole first rank.
+        let black: u64 = bit(7, 0);
+        let white: u64 = (1..7).fold(0, |acc, f| acc | bit(f, 0)) | bit(3, 3);
+        let position: Position = Position {
+            black,
+            white,
+            black_to_move: true,
+        };
+        let analysis: Analysis = analyze(&position, limits(4));
+        assert_eq!(analysis.best_move, Some(bit(0, 0)));
+        assert!(analysis.score > 0);
+        assert_eq!(analysis.principal_variation[0], bit(0, 0));
+    }
+
+    #[test]
+    fn scores_finished_games_exactly() {
+        let position: Position = Position {
+            black: bit(0, 0) | bit(1, 0),
+            white: bit(7, 7),
+            black_to_move: false,
+        };
+        let analysis: Analysis = analyze(&position, limits(6));
+        assert_eq!(analysis.best_move, None);
+        assert_eq!(analysis.score, WIN_SCORE + 1);
+    }
+
+    #[test]
+    fn agrees_with_the_game_rules() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+        use reversi_tools::position::{apply_move, check_game_status};
+
+        let mut rng: StdRng = StdRng::seed_from_u64(7);
+        for _ in 0..200 {
+            let mut position: Position = Position::initial();
+            while !position.is_game_over() {
+                let (player, opponent) = position.player_and_opponent();
+                let legal: u64 = legal_moves(player, opponent);
+                let white: bool = !position.black_to_move;
+                for square in 0..64 {
+                    let mv: u64 = 1u64 << square;
+                    if (player | opponent) & mv != 0 {
+                        continue;
+                    }
+                    let applied = apply_move(position.white, position.black, mv, white);
+                    assert_eq!(applied.is_ok(), legal & mv != 0);
+                    if let Ok((w, b)) = applied {
+                        let next: Position = position.play(mv);
+                        assert_eq!((w, b), (next.white, next.black));
+                        let mover: u64 = if white { w } else { b };
+                        assert_eq!(flips(player, opponent, mv), opponent & mover);
+                    }
+                }
+                let mv: u64 = if legal == 0 {
+                    PASS
+                } else {
+                    let moves: Vec<u64> = (0..64)
+                        .map(|square| 1u64 << square)
+                        .filter(|m| legal & m != 0)
+                        .collect();
+                    moves[rng.gen_range(0..moves.len())]
+                };
+                position = position.play(mv);
+                let status: u64 =
+                    check_game_status(position.white, position.black, position.black_to_move);
+                assert_eq!(
+                    [u64::MAX - 1, u64::MAX - 2, u64::MAX - 3].contains(&status),
+                    position.is_game_over()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn passes_when_there_is_no_move() {
+        // White has no move, black still has one.
+        let position: Position = Position {
+            black: bit(0, 0),
+            white: bit(1, 0),
+            black_to_move: false,
+        };
+        let analysis: Analysis = analyze(&position, limits(3));
+        assert_eq!(analysis.best_move, Some(PASS));
+        assert_eq!(analysis.principal_variation[1], bit(2, 0));
+        assert_eq!(analysis.score, WIN_SCORE + 3);
+    }
+}
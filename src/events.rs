@@ -0,0 +1,66 @@
+//! Push channel for game updates. Handlers publish an event when a player
+//! joins or moves, and `/games/<id>/events` streams the events of one game to
+//! browsers and bots as Server-Sent Events. Events only reach subscribers of
+//! the server instance that handled the request.
+
+use std::time::Duration;
+
+use rocket::tokio::sync::broadcast;
+
+use crate::model::GameEvent;
+
+/// Events a slow subscriber may fall behind by before it misses some.
+const CAPACITY: usize = 1024;
+
+/// How long clients wait before reconnecting once a stream ends, sent as the
+/// `retry` field when they connect and when the server shuts down.
+pub const RECONNECT_DELAY: Duration = Duration::from_secs(3);
+
+#[derive(Clone)]
+pub struct GameEvents {
+    sender: broadcast::Sender<GameEvent>,
+}
+
+impl GameEvents {
+    pub fn new() -> GameEvents {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        GameEvents { sender }
+    }
+
+    /// Sends the event to the current subscribers, if there are any.
+    pub fn publish(&self, event: GameEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Events of every game published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<GameEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for GameEvents {
+    fn default() -> Self {
+        GameEvents::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn subscribers_get_later_events() {
+        let events: GameEvents = GameEvents::new();
+        events.publish(GameEvent::default());
+        let mut receiver = events.subscribe();
+        let event: GameEvent = GameEvent {
+            game_id: "g".to_string(),
+            status: "white".to_string(),
+            last_move: "c4".to_string(),
+            player_id: "p".to_string(),
+        };
+        events.publish(event.clone());
+        assert_eq!(receiver.recv().await.unwrap(), event);
+        assert!(receiver.try_recv().is_err());
+    }
+}
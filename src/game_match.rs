@@ -0,0 +1,268 @@
+use reversi_tools::position::*;
+
+use crate::model::{
+    Game, GameMatch, MatchGame, MatchGameInfo, MatchInfo, INITIAL_POSITION_BLACK,
+    INITIAL_POSITION_WHITE,
+};
+use crate::service::paired_game;
+
+pub const DEFAULT_MATCH_GAMES: u64 = 2;
+pub const MAX_MATCH_GAMES: u64 = 100;
+
+/// A move of an opening together with the position it leads to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpeningMove {
+    pub move_bit: u64,
+    pub position_black: u64,
+    pub position_white: u64,
+}
+
+/// Splits an opening such as "f5d6c3" or "F5 d6 c3" into squares. Returns `None`
+/// if it contains anything but squares.
+pub fn parse_opening(opening: &str) -> Option<Vec<String>> {
+    let compact: String = opening
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase();
+    if !compact.len().is_multiple_of(2) {
+        return None;
+    }
+    let mut squares: Vec<String> = Vec::new();
+    for square in compact.as_bytes().chunks(2) {
+        if !(b'a'..=b'h').contains(&square[0]) || !(b'1'..=b'8').contains(&square[1]) {
+            return None;
+        }
+        squares.push(String::from_utf8_lossy(square).to_string());
+    }
+    Some(squares)
+}
+
+/// Plays the opening from the initial position, black moving first.
+pub fn play_opening(squares: &[String]) -> Result<Vec<OpeningMove>, String> {
+    let mut position_black: u64 = INITIAL_POSITION_BLACK;
+    let mut position_white: u64 = INITIAL_POSITION_WHITE;
+    let mut moves: Vec<OpeningMove> = Vec::new();
+    for (idx, square) in squares.iter().enumerate() {
+        let move_bit: u64 = move_to_bitmap(square.as_str()).unwrap();
+        let (white, black) = apply_move(position_white, position_black, move_bit, idx % 2 == 1)
+            .map_err(|e| format!("Illegal opening move {}: {}", square, e))?;
+        position_white = white;
+        position_black = black;
+        moves.push(OpeningMove {
+            move_bit,
+            position_black,
+            position_white,
+        });
+    }
+    let status: u64 = check_game_status(position_white, position_black, squares.len() % 2 == 1);
+    if [u64::MAX - 1, u64::MAX - 2, u64::MAX - 3].contains(&status) {
+        return Err("Opening ends the game".to_string());
+    }
+    Ok(moves)
+}
+
+/// Game state after an opening of `moves` moves: black is to move after an even number.
+pub fn state_after_opening(moves: usize) -> u64 {
+    if moves.is_multiple_of(2) {
+        1
+    } else {
+        2
+    }
+}
+
+/// Player A has black in odd games, so every pair of games is played with
+/// swapped colors.
+pub fn player_a_is_black(game_no: u64) -> bool {
+    game_no % 2 == 1
+}
+
+/// Opening of every game of a match. Each pair of games shares an opening;
+/// openings are drawn from the list without repetition until it is exhausted.
+/// `pick(n)` returns a random index below `n`.
+pub fn draw_openings<F>(games: u64, openings: &[String], mut pick: F) -> Vec<String>
+where
+    F: FnMut(usize) -> usize,
+{
+    let mut remaining: Vec<usize> = Vec::new();
+    let mut result: Vec<String> = Vec::new();
+    let mut current: String = String::new();
+    for game_no in 1..=games {
+        if openings.is_empty() {
+            result.push(String::new());
+            continue;
+        }
+        if player_a_is_black(game_no) {
+            if remaining.is_empty() {
+                remaining = (0..openings.len()).collect();
+            }
+            let idx: usize = remaining.swap_remove(pick(remaining.len()));
+            current = openings[idx].clone();
+        }
+        result.push(current.clone());
+    }
+    result
+}
+
+pub fn new_match_game(game_match: &GameMatch, game_no: u64) -> Game {
+    let (black_uuid, white_uuid) = if player_a_is_black(game_no) {
+        (&game_match.player_a_uuid, &game_match.player_b_uuid)
+    } else {
+        (&game_match.player_b_uuid, &game_match.player_a_uuid)
+    };
+    paired_game(black_uuid, white_uuid, &game_match.time_control)
+}
+
+pub fn match_game_result(game: &MatchGame) -> String {
+    match game.game_state {
+        3 => "black_won".to_string(),
+        4 => "white_won".to_string(),
+        5 => "draw".to_string(),
+        _ => "playing".to_string(),
+    }
+}
+
+/// Summary of a match with the aggregate score, a win is worth one point and a
+/// draw half a point.
+pub fn match_info(game_match: &GameMatch, games: &[MatchGame]) -> MatchInfo {
+    let mut score_a: f64 = 0.0;
+    let mut score_b: f64 = 0.0;
+    let mut games_finished: u64 = 0;
+    for game in games {
+        let a_is_black: bool = game.black_uuid == game_match.player_a_uuid;
+        match (game.game_state, a_is_black) {
+            (3, true) | (4, false) => score_a += 1.0,
+            (3, false) | (4, true) => score_b += 1.0,
+            (5, _) => {
+                score_a += 0.5;
+                score_b += 0.5;
+            }
+            _ => continue,
+        }
+        games_finished += 1;
+    }
+    let status: String = if games_finished >= game_match.games {
+        "finished".to_string()
+    } else {
+        "playing".to_string()
+    };
+
+    MatchInfo {
+        match_id: game_match.match_uuid.clone(),
+        player_a: game_match.player_a_uuid.clone(),
+        player_b: game_match.player_b_uuid.clone(),
+        games_total: game_match.games,
+        games_finished,
+        score_a,
+        score_b,
+        status,
+        time_control: game_match.time_control.clone(),
+        games: games
+            .iter()
+            .map(|g| MatchGameInfo {
+                game_no: g.game_no,
+                game_id: g.game_uuid.clone(),
+                black: g.black_uuid.clone(),
+                white: g.white_uuid.clone(),
+                opening: g.opening.clone(),
+                result: match_game_result(g),
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game_match(games: u64) -> GameMatch {
+        GameMatch {
+            match_uuid: "m".to_string(),
+            player_a_uuid: "a".to_string(),
+            player_b_uuid: "b".to_string(),
+            games,
+            time_control: String::new(),
+        }
+    }
+
+    fn played(game_match: &GameMatch, game_no: u64, game_state: u64) -> MatchGame {
+        let game: Game = new_match_game(game_match, game_no);
+        MatchGame {
+            game_no,
+            game_uuid: game.game_uuid,
+            black_uuid: game.black_uuid,
+            white_uuid: game.white_uuid,
+            opening: String::new(),
+            game_state,
+            position_black: game.position_black,
+            position_white: game.position_white,
+        }
+    }
+
+    #[test]
+    fn parses_openings() {
+        assert_eq!(
+            parse_opening("f5d6 C3"),
+            Some(vec!["f5".to_string(), "d6".to_string(), "c3".to_string()])
+        );
+        assert_eq!(parse_opening(""), Some(Vec::new()));
+        assert_eq!(parse_opening("f5d"), None);
+        assert_eq!(parse_opening("i5"), None);
+        assert_eq!(parse_opening("f9"), None);
+    }
+
+    #[test]
+    fn plays_openings_from_the_initial_position() {
+        let squares: Vec<String> = parse_opening("f5d6c3").unwrap();
+        let moves: Vec<OpeningMove> = play_opening(&squares).unwrap();
+        assert_eq!(moves.len(), 3);
+        let last: &OpeningMove = &moves[2];
+        assert_eq!((last.position_black | last.position_white).count_ones(), 7);
+        assert_eq!(state_after_opening(moves.len()), 2);
+        assert!(play_opening(&parse_opening("a1").unwrap()).is_err());
+    }
+
+    #[test]
+    fn colors_alternate_between_games() {
+        let m = game_match(4);
+        let games: Vec<Game> = (1..=4).map(|n| new_match_game(&m, n)).collect();
+        assert_eq!(games[0].black_uuid, "a");
+        assert_eq!(games[1].black_uuid, "b");
+        assert_eq!(games[2].black_uuid, "a");
+        assert_eq!(games[3].white_uuid, "a");
+    }
+
+    #[test]
+    fn game_pairs_share_openings() {
+        let openings: Vec<String> = vec!["f5".to_string(), "f5d6".to_string()];
+        let drawn = draw_openings(6, &openings, |_| 0);
+        assert_eq!(drawn[0], drawn[1]);
+        assert_eq!(drawn[2], drawn[3]);
+        assert_eq!(drawn[4], drawn[5]);
+        // Both openings are used before one is repeated.
+        assert_ne!(drawn[0], drawn[2]);
+        assert_eq!(draw_openings(3, &[], |_| 0), vec![String::new(); 3]);
+    }
+
+    #[test]
+    fn aggregates_the_score() {
+        let m = game_match(4);
+        let games: Vec<MatchGame> = vec![
+            played(&m, 1, 3), // a is black and wins
+            played(&m, 2, 3), // b is black and wins
+            played(&m, 3, 5),
+            played(&m, 4, 1),
+        ];
+        let info: MatchInfo = match_info(&m, &games);
+        assert_eq!(info.score_a, 1.5);
+        assert_eq!(info.score_b, 1.5);
+        assert_eq!(info.games_finished, 3);
+        assert_eq!(info.status, "playing");
+        assert_eq!(info.games[3].result, "playing");
+
+        let games: Vec<MatchGame> = vec![played(&m, 1, 4), played(&m, 2, 4)];
+        let info: MatchInfo = match_info(&game_match(2), &games);
+        assert_eq!((info.score_a, info.score_b), (1.0, 1.0));
+        assert_eq!(info.status, "finished");
+    }
+}
@@ -0,0 +1,132 @@
+//! Liveness and readiness of the server, for orchestrators deciding whether to
+//! restart an instance or send it traffic.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::model::ReadinessCheck;
+use crate::repository::database::Database;
+use crate::repository::db_errors::RepositoryError;
+use crate::repository::migrations::check_schema;
+
+/// How long the database may take to answer a readiness probe.
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether the server is on its way down. Once draining it keeps serving the
+/// requests it gets but reports itself as not ready. Clones share the state.
+#[derive(Clone, Default)]
+pub struct Lifecycle {
+    draining: Arc<AtomicBool>,
+}
+
+impl Lifecycle {
+    pub fn new() -> Lifecycle {
+        Lifecycle::default()
+    }
+
+    pub fn begin_draining(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+}
+
+fn check(name: &str, result: Result<String, String>) -> ReadinessCheck {
+    match result {
+        Ok(message) => ReadinessCheck {
+            name: name.to_string(),
+            ok: true,
+            message,
+        },
+        Err(message) => ReadinessCheck {
+            name: name.to_string(),
+            ok: false,
+            message,
+        },
+    }
+}
+
+/// Fails the check if the database takes longer than `PING_TIMEOUT`.
+async fn within_timeout<T>(
+    call: impl std::future::Future<Output = Result<T, RepositoryError>>,
+) -> Result<T, String> {
+    match tokio::time::timeout(PING_TIMEOUT, call).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err(format!("no answer within {}s", PING_TIMEOUT.as_secs())),
+    }
+}
+
+async fn database_check(db: &Database) -> Result<String, String> {
+    within_timeout(db.ping()).await?;
+    Ok("reachable".to_string())
+}
+
+async fn schema_check(db: &Database) -> Result<String, String> {
+    let target = match db.migration_target() {
+        Some(t) => t,
+        None => return Ok("no schema".to_string()),
+    };
+    within_timeout(check_schema(target)).await?;
+    let applied: Vec<u64> = within_timeout(target.current_versions()).await?;
+    Ok(format!(
+        "version {}",
+        applied.iter().max().copied().unwrap_or_default()
+    ))
+}
+
+/// Runs every readiness check. The server is ready when all of them pass.
+pub async fn readiness_checks(
+    db: &Database,
+    lifecycle: &Lifecycle,
+    annotation_worker_running: bool,
+) -> Vec<ReadinessCheck> {
+    let shutdown: Result<String, String> = if lifecycle.is_draining() {
+        Err("draining".to_string())
+    } else {
+        Ok("serving".to_string())
+    };
+    let annotation_worker: Result<String, String> = if annotation_worker_running {
+        Ok("running".to_string())
+    } else {
+        Err("stopped".to_string())
+    };
+    vec![
+        check("shutdown", shutdown),
+        check("database", database_check(db).await),
+        check("schema", schema_check(db).await),
+        check("annotation_worker", annotation_worker),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn draining_servers_are_not_ready() {
+        let db: Database = Database::connect("sqlite::memory:").unwrap();
+        crate::repository::migrations::migrate(db.migration_target().unwrap())
+            .await
+            .unwrap();
+        let lifecycle: Lifecycle = Lifecycle::new();
+        let checks: Vec<ReadinessCheck> = readiness_checks(&db, &lifecycle, true).await;
+        assert!(checks.iter().all(|c| c.ok), "{:?}", checks);
+        assert_eq!(checks[2].message, "version 8");
+
+        lifecycle.begin_draining();
+        let checks: Vec<ReadinessCheck> = readiness_checks(&db, &lifecycle, true).await;
+        assert!(!checks[0].ok);
+    }
+
+    #[tokio::test]
+    async fn pending_migrations_are_not_ready() {
+        let db: Database = Database::connect("sqlite::memory:").unwrap();
+        let checks: Vec<ReadinessCheck> = readiness_checks(&db, &Lifecycle::new(), false).await;
+        assert!(checks[1].ok);
+        assert!(!checks[2].ok);
+        assert!(!checks[3].ok);
+    }
+}
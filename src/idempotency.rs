@@ -0,0 +1,134 @@
+//! Idempotency keys for moves. A client that cannot tell whether its move
+//! landed sends it again with the same key and gets the response of the first
+//! attempt instead of an error, and the move is not applied twice.
+
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+
+use crate::model::{MoveRecord, MoveResponse};
+use crate::repository::db_errors::RepositoryError;
+use crate::repository::game_repository::GameRepository;
+use crate::service::GameError;
+
+pub use crate::model::IDEMPOTENCY_KEY_HEADER;
+
+/// The `Idempotency-Key` header of the request, if it has one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdempotencyKey(pub Option<String>);
+
+impl IdempotencyKey {
+    /// The header, or else the key sent in the body. Empty keys count as none.
+    pub fn or_field(self, field: &str) -> Option<String> {
+        self.0
+            .or_else(|| Some(field.to_string()))
+            .filter(|key| !key.is_empty())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let key: Option<String> = request
+            .headers()
+            .get_one(IDEMPOTENCY_KEY_HEADER)
+            .map(|key| key.to_string());
+        Outcome::Success(IdempotencyKey(key))
+    }
+}
+
+/// Keys are up to 255 printable ASCII characters.
+pub fn check_key(key: &str) -> Result<(), GameError> {
+    if key.len() <= 255 && key.bytes().all(|b| b.is_ascii_graphic()) {
+        Ok(())
+    } else {
+        Err(GameError::Rejected(
+            "Idempotency key must be 1 to 255 printable ASCII characters".to_string(),
+        ))
+    }
+}
+
+/// Reserves the key for the move before it is played. If the move was sent
+/// with this key less than `ttl_secs` ago, returns the response it got; while
+/// that attempt is still being played the request is refused as a conflict. A
+/// key reused for another move or by another player is rejected.
+pub async fn reserve_key(
+    repo: &(dyn GameRepository + Send + Sync),
+    game_uuid: &str,
+    key: &str,
+    player_uuid: &str,
+    played_move: &str,
+    ttl_secs: u64,
+) -> Result<Option<MoveResponse>, GameError> {
+    // Keys belong to games, so a missing game is reported as such.
+    if repo.get_game(game_uuid).await?.is_none() {
+        return Err(GameError::NotFound);
+    }
+    let reserved: MoveRecord = MoveRecord {
+        game_uuid: game_uuid.to_string(),
+        idempotency_key: key.to_string(),
+        player_uuid: player_uuid.to_string(),
+        played_move: played_move.to_string(),
+        response: String::new(),
+    };
+    if repo.reserve_move_key(&reserved, ttl_secs).await? {
+        return Ok(None);
+    }
+    let record: MoveRecord = match repo.get_move_record(game_uuid, key, ttl_secs).await? {
+        Some(r) => r,
+        // Released by the attempt that held it, or just expired.
+        None => return Err(move_in_progress()),
+    };
+    if record.player_uuid != player_uuid || record.played_move != played_move {
+        return Err(GameError::Rejected(
+            "Idempotency key was used for another move".to_string(),
+        ));
+    }
+    if record.response.is_empty() {
+        return Err(move_in_progress());
+    }
+    let response: MoveResponse = serde_json::from_str(&record.response)
+        .map_err(|e| RepositoryError::Other(format!("Stored move response is invalid: {}", e)))?;
+    Ok(Some(response))
+}
+
+fn move_in_progress() -> GameError {
+    GameError::Conflict("A move with this idempotency key is in progress".to_string())
+}
+
+/// The record of a move with the response it got, stored under its key.
+pub fn move_record(
+    game_uuid: &str,
+    key: &str,
+    player_uuid: &str,
+    played_move: &str,
+    response: &MoveResponse,
+) -> Result<MoveRecord, RepositoryError> {
+    Ok(MoveRecord {
+        game_uuid: game_uuid.to_string(),
+        idempotency_key: key.to_string(),
+        player_uuid: player_uuid.to_string(),
+        played_move: played_move.to_string(),
+        response: serde_json::to_string(response)
+            .map_err(|e| RepositoryError::Other(e.to_string()))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_wins_over_field() {
+        assert_eq!(
+            IdempotencyKey(Some("a".to_string())).or_field("b"),
+            Some("a".to_string())
+        );
+        assert_eq!(IdempotencyKey(None).or_field("b"), Some("b".to_string()));
+        assert_eq!(IdempotencyKey(None).or_field(""), None);
+        assert!(check_key("7f1c-retry").is_ok());
+        assert!(check_key("has space").is_err());
+        assert!(check_key(&"x".repeat(256)).is_err());
+    }
+}
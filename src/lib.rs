@@ -1,6 +1,27 @@
 pub mod repository;
 pub use repository::db_errors::*;
 pub use repository::game_repository::*;
+pub use repository::match_repository::*;
 pub use repository::player_repository::*;
+pub use repository::tournament_repository::*;
 
-pub mod model;
+pub mod bracket;
+pub mod cors;
+pub mod endgame;
+pub mod engine;
+pub mod events;
+pub mod game_match;
+pub mod health;
+pub mod idempotency;
+pub mod matchmaking;
+pub mod metrics;
+pub use reversi_model as model;
+pub mod openapi;
+pub mod opening_book;
+pub mod rate_limit;
+pub mod rating;
+pub mod report;
+pub mod service;
+pub mod settings;
+pub mod telemetry;
+pub mod tournament;
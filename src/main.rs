@@ -1,21 +1,51 @@
 #[macro_use]
 extern crate rocket;
 
+use std::collections::HashMap;
 use std::env;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
-use mysql_async::{Opts, Pool};
-use reversi_tools::position::*;
+use rand::Rng;
+use rocket::http::{ContentType, Status};
+use rocket::response::status;
+use rocket::response::stream::{Event, EventStream};
 use rocket::serde::json::Json;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use rocket::tokio::sync::watch;
+use rocket::tokio::task::JoinHandle;
 use rocket::State;
+use tracing::{instrument, Instrument};
 
-mod repository;
-use repository::game_repository::*;
-use repository::player_repository::*;
-
-mod model;
-use model::*;
-use rand::Rng;
+use reversi_server::bracket::*;
+use reversi_server::cors::*;
+use reversi_server::endgame::*;
+use reversi_server::engine::*;
+use reversi_server::events::*;
+use reversi_server::game_match::*;
+use reversi_server::health::*;
+use reversi_server::idempotency::*;
+use reversi_server::matchmaking::*;
+use reversi_server::metrics::*;
+use reversi_server::model::*;
+use reversi_server::openapi::*;
+use reversi_server::opening_book::*;
+use reversi_server::rate_limit::*;
+use reversi_server::report::*;
+use reversi_server::repository::database::*;
+use reversi_server::repository::db_errors::*;
+use reversi_server::repository::game_repository::*;
+use reversi_server::repository::match_repository::*;
+use reversi_server::repository::metered_repository::*;
+use reversi_server::repository::migrations::*;
+use reversi_server::repository::player_repository::*;
+use reversi_server::repository::tournament_repository::*;
+use reversi_server::service::*;
+use reversi_server::settings::*;
+use reversi_server::telemetry::*;
+use reversi_server::tournament::*;
 
 pub fn generate_uuid() -> String {
     Uuid::new_v4().to_string()
@@ -26,10 +56,320 @@ fn random_upto(n: usize) -> usize {
     rng.gen_range(0..n)
 }
 
-#[get("/players")]
-async fn get_users(pool: &State<Pool>) -> Json<PlayerResponse> {
-    let player_repo = MySqlPlayerRepository::new(pool.inner().clone());
+/// Bookkeeping that has to happen once a game reaches a terminal state.
+async fn game_finished(
+    tournament_repo: &(dyn TournamentRepository + Send + Sync),
+    game_repo: &(dyn GameRepository + Send + Sync),
+    annotation_queue: &AnnotationQueue,
+    game_uuid: &str,
+) {
+    if let Err(e) = on_game_finished(tournament_repo, game_uuid).await {
+        tracing::error!(game_id = game_uuid, error = %e, "Error while advancing tournament");
+    }
+    annotation_queue.queue(game_repo, game_uuid).await;
+}
+
+/// Finished games waiting to be annotated. A single worker annotates them one
+/// after the other so that the engine never takes more than one core.
+struct AnnotationQueue {
+    sender: UnboundedSender<String>,
+    stop: watch::Sender<bool>,
+    worker: std::sync::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl AnnotationQueue {
+    fn start(game_repo: Arc<dyn GameRepository + Send + Sync>) -> AnnotationQueue {
+        let (sender, mut receiver) = unbounded_channel::<String>();
+        let (stop, mut stopped) = watch::channel(false);
+        let worker: JoinHandle<()> = rocket::tokio::spawn(async move {
+            loop {
+                let game_uuid: String = rocket::tokio::select! {
+                    biased;
+                    _ = stopped.changed() => break,
+                    job = receiver.recv() => match job {
+                        Some(g) => g,
+                        None => break,
+                    },
+                };
+                let span: tracing::Span = tracing::info_span!("annotation", game_id = %game_uuid);
+                run_annotation_job(game_repo.as_ref(), &game_uuid)
+                    .instrument(span)
+                    .await;
+            }
+        });
+        AnnotationQueue {
+            sender,
+            stop,
+            worker: std::sync::Mutex::new(Some(worker)),
+        }
+    }
+
+    /// Lets the game being annotated finish, waiting up to `timeout`, and stops
+    /// the worker. Queued games stay queued in the database and are picked up
+    /// again on the next start.
+    async fn stop(&self, timeout: Duration) {
+        let _ = self.stop.send(true);
+        let worker: Option<JoinHandle<()>> = self.worker.lock().unwrap().take();
+        if let Some(worker) = worker {
+            if rocket::tokio::time::timeout(timeout, worker).await.is_err() {
+                tracing::warn!(
+                    "Annotation still running at shutdown, it resumes on the next start"
+                );
+            }
+        }
+    }
+
+    async fn queue(&self, game_repo: &(dyn GameRepository + Send + Sync), game_uuid: &str) {
+        if let Err(e) = game_repo.queue_annotation_job(game_uuid).await {
+            tracing::error!(game_id = game_uuid, error = %e, "Error while queueing annotation");
+            return;
+        }
+        if self.sender.send(game_uuid.to_string()).is_err() {
+            tracing::warn!(
+                game_id = game_uuid,
+                "Annotation worker stopped, game stays queued"
+            );
+        }
+    }
+
+    /// The worker stops when the server shuts down or if it panicked.
+    fn is_running(&self) -> bool {
+        !self.sender.is_closed()
+    }
+
+    /// Picks up the jobs that were queued or running when the server stopped.
+    async fn resume(&self, game_repo: &(dyn GameRepository + Send + Sync)) {
+        match game_repo.unfinished_annotation_jobs().await {
+            Ok(games) => {
+                for game_uuid in games {
+                    let _ = self.sender.send(game_uuid);
+                }
+            }
+            Err(e) => tracing::error!(error = %e, "Error while resuming annotation jobs"),
+        }
+    }
+}
+
+async fn run_annotation_job(game_repo: &(dyn GameRepository + Send + Sync), game_uuid: &str) {
+    let (status, error) = match annotate_finished_game(game_repo, game_uuid).await {
+        Ok(()) => (JOB_DONE, String::new()),
+        Err(e) => {
+            tracing::error!(error = %e, "Error while annotating game");
+            (JOB_FAILED, e)
+        }
+    };
+    if let Err(e) = game_repo
+        .update_annotation_job(game_uuid, status, error.as_str())
+        .await
+    {
+        tracing::error!(error = %e, "Error while updating annotation job");
+    }
+}
+
+/// Evaluates every move of a finished game and stores the annotations.
+async fn annotate_finished_game(
+    game_repo: &(dyn GameRepository + Send + Sync),
+    game_uuid: &str,
+) -> Result<(), String> {
+    game_repo
+        .update_annotation_job(game_uuid, JOB_RUNNING, "")
+        .await
+        .map_err(|e| e.to_string())?;
+    let moves: Vec<u64> = game_repo
+        .get_moves(game_uuid)
+        .await
+        .map_err(|e| e.to_string())?;
+    let annotations: Vec<MoveAnnotation> = rocket::tokio::task::spawn_blocking(move || {
+        annotate_game(
+            &moves,
+            report_limits(),
+            Duration::from_millis(REPORT_SOLVE_TIME_MS),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    game_repo
+        .save_annotations(game_uuid, &annotations)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Rejected by the rate limiter, with the seconds to wait before retrying.
+#[derive(rocket::Responder)]
+#[response(status = 429)]
+struct TooManyRequests {
+    body: Json<RateLimitedResponse>,
+    retry_after: rocket::http::Header<'static>,
+}
+
+#[catch(429)]
+fn too_many_requests(request: &rocket::Request<'_>) -> TooManyRequests {
+    let retry_after: u64 = retry_after(request);
+    let response: RateLimitedResponse = RateLimitedResponse {
+        status: "error".to_string(),
+        error: ResponseError {
+            code: 429,
+            message: format!("Too many requests, retry in {}s", retry_after),
+        },
+    };
+    TooManyRequests {
+        body: Json(response),
+        retry_after: rocket::http::Header::new("Retry-After", retry_after.to_string()),
+    }
+}
+
+#[get("/games/<id>/events")]
+fn game_events(
+    _rate_limit: RateLimited,
+    events: &State<GameEvents>,
+    id: String,
+    mut shutdown: rocket::Shutdown,
+) -> EventStream![] {
+    let mut receiver = events.subscribe();
+    EventStream! {
+        yield Event::empty().with_retry(RECONNECT_DELAY);
+        loop {
+            let event: GameEvent = rocket::tokio::select! {
+                received = receiver.recv() => match received {
+                    Ok(event) => event,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => {
+                        // Some events were dropped, the client has to refetch the game.
+                        yield Event::empty().event("resync");
+                        continue;
+                    }
+                },
+                _ = &mut shutdown => {
+                    // Clients reconnect, to another instance if this one is gone.
+                    yield Event::empty().event("shutdown").with_retry(RECONNECT_DELAY);
+                    break;
+                }
+            };
+            if event.game_id == id {
+                yield Event::json(&event);
+            }
+        }
+    }
+}
+
+/// Answers CORS preflight requests; `CorsFairing` adds the headers.
+#[options("/<_..>")]
+fn preflight() -> Status {
+    Status::NoContent
+}
+
+#[get("/openapi.json")]
+fn openapi_document(
+    _rate_limit: RateLimited,
+    document: &State<ApiDocument>,
+) -> (ContentType, String) {
+    (ContentType::JSON, document.json.clone())
+}
 
+/// Swagger UI for `openapi.json`, loaded from a CDN.
+#[get("/docs")]
+fn swagger_ui(_rate_limit: RateLimited) -> (ContentType, &'static str) {
+    (ContentType::HTML, include_str!("../static/swagger.html"))
+}
+
+#[get("/")]
+fn web_index() -> (ContentType, &'static str) {
+    (ContentType::HTML, include_str!("../static/index.html"))
+}
+
+#[get("/app.js")]
+fn web_script() -> (ContentType, &'static str) {
+    (ContentType::JavaScript, include_str!("../static/app.js"))
+}
+
+#[get("/style.css")]
+fn web_style() -> (ContentType, &'static str) {
+    (ContentType::CSS, include_str!("../static/style.css"))
+}
+
+/// Liveness: the process is up and serving requests.
+#[get("/healthz")]
+async fn healthz() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok".to_string(),
+    })
+}
+
+/// Readiness: answers 503 unless the database is reachable, its schema is the
+/// one this build expects, the background worker runs and the server is not
+/// draining.
+#[get("/readyz")]
+async fn readyz(
+    db: &State<Database>,
+    lifecycle: &State<Lifecycle>,
+    annotation_queue: &State<AnnotationQueue>,
+) -> status::Custom<Json<ReadinessResponse>> {
+    let checks: Vec<ReadinessCheck> =
+        readiness_checks(db.inner(), lifecycle.inner(), annotation_queue.is_running()).await;
+    let ready: bool = checks.iter().all(|c| c.ok);
+    if !ready {
+        tracing::warn!(?checks, "Not ready");
+    }
+    let response: ReadinessResponse = ReadinessResponse {
+        status: if ready { "ready" } else { "not_ready" }.to_string(),
+        checks,
+    };
+    let code: Status = if ready {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+    status::Custom(code, Json(response))
+}
+
+/// Prometheus scrape endpoint. Game counts and pool usage are read when
+/// scraped, everything else is counted as it happens.
+#[get("/metrics")]
+async fn metrics_endpoint(
+    db: &State<Database>,
+    game_repo: &State<Arc<dyn GameRepository + Send + Sync>>,
+    metrics: &State<Arc<Metrics>>,
+) -> (ContentType, String) {
+    match game_repo.count_games_by_state().await {
+        Ok(counts) => {
+            for (state, name) in STATE_NAMES.iter().enumerate() {
+                let count: u64 = counts
+                    .iter()
+                    .find(|(s, _)| *s == state as u64)
+                    .map_or(0, |(_, c)| *c);
+                metrics.games.with_label_values(&[name]).set(count as i64);
+            }
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Error while counting games");
+        }
+    }
+    if let Some(status) = db.pool_status() {
+        for (state, value) in [
+            ("max", Some(status.max)),
+            ("in_use", Some(status.in_use)),
+            ("open", status.open),
+            ("idle", status.idle),
+            ("waiting", Some(status.waiting)),
+        ] {
+            if let Some(value) = value {
+                metrics
+                    .db_pool_connections
+                    .with_label_values(&[state])
+                    .set(value as i64);
+            }
+        }
+    }
+    let content_type: ContentType =
+        ContentType::new("text", "plain").with_params([("version", "0.0.4"), ("charset", "utf-8")]);
+    (content_type, metrics.render())
+}
+
+#[get("/players")]
+async fn get_users(
+    _rate_limit: RateLimited,
+    player_repo: &State<Arc<dyn PlayerRepository + Send + Sync>>,
+) -> Json<PlayerResponse> {
     match player_repo.player_list().await {
         Ok(p) => {
             let response: PlayerResponse = PlayerResponse {
@@ -41,7 +381,7 @@ async fn get_users(pool: &State<Pool>) -> Json<PlayerResponse> {
                 result: p,
             };
 
-            return Json(response);
+            Json(response)
         }
         Err(e) => {
             let response: PlayerResponse = PlayerResponse {
@@ -53,84 +393,102 @@ async fn get_users(pool: &State<Pool>) -> Json<PlayerResponse> {
                 result: Vec::new(),
             };
 
-            return Json(response);
+            Json(response)
         }
     }
 }
 
-#[post("/create_game", format = "json", data = "<request>")]
-async fn create_game(pool: &State<Pool>, request: Json<NewGameRequest>) -> Json<NewGameResponse> {
-    // TODO(1): add player validation
-    println!("Game creation requested by {}", request.player_id.clone());
-    let game_repo = MySqlGameRepository::new(pool.inner().clone());
-
-    let upto: usize = 2;
-
-    //let mut conn = pool.get_conn().await.unwrap();
-    let game_uuid: String = generate_uuid();
-    let mut game: Game = Game {
-        game_uuid: game_uuid.clone(),
-        black_uuid: String::new(),
-        white_uuid: String::new(),
-        position_black: 0x0000000810000000u64,
-        position_white: 0x0000001008000000u64,
-        state: 0,
+fn register_error_response(code: u32, message: String) -> Json<NewPlayerResponse> {
+    Json(NewPlayerResponse {
+        status: "error".to_string(),
+        error: ResponseError { code, message },
+        result: User {
+            player_uuid: String::new(),
+            comment: String::new(),
+        },
+    })
+}
+
+#[post("/players/register", format = "json", data = "<request>")]
+#[instrument(skip_all, fields(request_id = %request_id, player_id))]
+async fn register_player(
+    _rate_limit: RateLimited,
+    player_repo: &State<Arc<dyn PlayerRepository + Send + Sync>>,
+    request_id: RequestId,
+    request: Json<NewPlayerRequest>,
+) -> Json<NewPlayerResponse> {
+    let comment: &str = request.comment.trim();
+    if comment.chars().count() > 1024 {
+        return register_error_response(400, "Comment is longer than 1024 characters".to_string());
+    }
+    let player: User = User {
+        player_uuid: generate_uuid(),
+        comment: comment.to_string(),
     };
-    let color: String;
-    if random_upto(upto) == 1 {
-        color = "white".to_string();
-        game.white_uuid = request.player_id.clone();
-    } else {
-        color = "black".to_string();
-        game.black_uuid = request.player_id.clone();
+    tracing::Span::current().record("player_id", player.player_uuid.as_str());
+    if let Err(e) = player_repo.create_player(&player).await {
+        tracing::warn!(error = %e, "Error registering player");
+        return register_error_response(500, format!("Error while registering player: {}", e));
     }
+    tracing::info!("Player registered");
+    let response: NewPlayerResponse = NewPlayerResponse {
+        status: "ok".to_string(),
+        error: ResponseError {
+            code: 200,
+            message: "".to_string(),
+        },
+        result: player,
+    };
+    Json(response)
+}
 
-    match game_repo.create_game(&game).await {
-        Ok(_) => {}
-        Err(e) => {
-            let response: NewGameResponse = NewGameResponse {
-                status: "error".to_string(),
-                error: ResponseError {
-                    code: 500,
-                    message: format!("{}", e),
-                },
-                result: NewGameResult {
-                    game_id: String::new(),
-                    color: String::new(),
-                },
-            };
-            return Json(response);
-        }
+fn game_error(e: GameError) -> ResponseError {
+    ResponseError {
+        code: e.code(),
+        message: format!("{}", e),
+    }
+}
+
+fn move_error(e: GameError) -> MoveResponse {
+    let result: MoveResult = MoveResult {
+        ok: false,
+        r#continue: true,
+        winner: String::new(),
+    };
+    MoveResponse {
+        status: "error".to_string(),
+        error: game_error(e),
+        result,
     }
+}
 
-    let created_game: Game;
-    match game_repo.get_game(game_uuid.clone().as_str()).await {
-        Ok(g) => match g {
-            Some(gg) => {
-                created_game = gg;
-            }
-            None => {
-                let response: NewGameResponse = NewGameResponse {
-                    status: "error".to_string(),
-                    error: ResponseError {
-                        code: 404,
-                        message: "Game not found".to_string(),
-                    },
-                    result: NewGameResult {
-                        game_id: String::new(),
-                        color: String::new(),
-                    },
-                };
-                return Json(response);
-            }
-        },
+#[post("/create_game", format = "json", data = "<request>")]
+#[instrument(skip_all, fields(request_id = %request_id, player_id = %request.player_id, game_id))]
+async fn create_game(
+    _rate_limit: RateLimited,
+    game_repo: &State<Arc<dyn GameRepository + Send + Sync>>,
+    book: &State<OpeningBook>,
+    settings: &State<Settings>,
+    request_id: RequestId,
+    request: Json<NewGameRequest>,
+) -> Json<NewGameResponse> {
+    // TODO(1): add player validation
+    tracing::info!(opening = %request.opening, "Game creation requested");
+    let service = GameService::new(game_repo.inner().as_ref(), book.inner());
+
+    let created: CreatedGame = match service
+        .create_game(
+            request.player_id.as_str(),
+            request.opening.as_str(),
+            settings.games.default_time_control.as_str(),
+        )
+        .await
+    {
+        Ok(c) => c,
         Err(e) => {
             let response: NewGameResponse = NewGameResponse {
                 status: "error".to_string(),
-                error: ResponseError {
-                    code: 500,
-                    message: format!("{}", e),
-                },
+                error: game_error(e),
                 result: NewGameResult {
                     game_id: String::new(),
                     color: String::new(),
@@ -138,8 +496,17 @@ async fn create_game(pool: &State<Pool>, request: Json<NewGameRequest>) -> Json<
             };
             return Json(response);
         }
-    }
-    println!("New game properties: black_uuid: >{}<, white_uuid: >{}<, black_position: >{}<, white_position: >{}<, state: >{}<", created_game.black_uuid, created_game.white_uuid, created_game.position_black, created_game.position_white, created_game.state);
+    };
+    let created_game: Game = created.game;
+    tracing::Span::current().record("game_id", created_game.game_uuid.as_str());
+    tracing::info!(
+        black_uuid = %created_game.black_uuid,
+        white_uuid = %created_game.white_uuid,
+        position_black = created_game.position_black,
+        position_white = created_game.position_white,
+        state = created_game.state,
+        "Game created"
+    );
     let response: NewGameResponse = NewGameResponse {
         status: "ok".to_string(),
         error: ResponseError {
@@ -147,22 +514,24 @@ async fn create_game(pool: &State<Pool>, request: Json<NewGameRequest>) -> Json<
             message: "".to_string(),
         },
         result: NewGameResult {
-            game_id: game_uuid,
-            color: color,
+            game_id: created_game.game_uuid,
+            color: created.color,
         },
     };
     Json(response)
 }
 
 #[post("/game_list", format = "json", data = "<request>")]
-async fn game_list(pool: &State<Pool>, request: Json<NewGameRequest>) -> Json<GameListResponse> {
+#[instrument(skip_all, fields(request_id = %request_id, player_id = %request.player_id))]
+async fn game_list(
+    _rate_limit: RateLimited,
+    game_repo: &State<Arc<dyn GameRepository + Send + Sync>>,
+    request_id: RequestId,
+    request: Json<NewGameRequest>,
+) -> Json<GameListResponse> {
     // TODO(1): add player validation
-    let game_repo = MySqlGameRepository::new(pool.inner().clone());
-    let games: Vec<Game>;
-    match game_repo.pending_games(request.player_id.clone()).await {
-        Ok(g) => {
-            games = g;
-        }
+    let games: Vec<Game> = match game_repo.pending_games(request.player_id.clone()).await {
+        Ok(g) => g,
         Err(e) => {
             let result: Vec<AvailableGame> = Vec::new();
             let response: GameListResponse = GameListResponse {
@@ -171,15 +540,15 @@ async fn game_list(pool: &State<Pool>, request: Json<NewGameRequest>) -> Json<Ga
                     code: 500,
                     message: format!("{}", e),
                 },
-                result: result,
+                result,
             };
             return Json(response);
         }
-    }
+    };
 
     let mut result: Vec<AvailableGame> = Vec::new();
     for game in games {
-        if game.white_uuid != "".to_string() {
+        if !game.white_uuid.is_empty() {
             result.push(AvailableGame {
                 game_id: game.game_uuid,
                 first_player: game.white_uuid,
@@ -198,534 +567,1710 @@ async fn game_list(pool: &State<Pool>, request: Json<NewGameRequest>) -> Json<Ga
             code: 200,
             message: "".to_string(),
         },
-        result: result,
+        result,
     };
     Json(response)
 }
 
 #[post("/game_status", format = "json", data = "<request>")]
-async fn game_status(pool: &State<Pool>, request: Json<GameRequest>) -> Json<GameStatusResponse> {
+#[instrument(skip_all, fields(request_id = %request_id, player_id = %request.player_id, game_id = %request.game_id))]
+async fn game_status(
+    _rate_limit: RateLimited,
+    game_repo: &State<Arc<dyn GameRepository + Send + Sync>>,
+    book: &State<OpeningBook>,
+    request_id: RequestId,
+    request: Json<GameRequest>,
+) -> Json<GameStatusResponse> {
     // TODO(1): add player validation
-    //let mut conn = pool.get_conn().await.unwrap();
-    let statuses: Vec<String> = vec![
-        "pending".to_string(),
-        "black".to_string(),
-        "white".to_string(),
-        "black_won".to_string(),
-        "white_won".to_string(),
-        "draw".to_string(),
-    ];
-    let game_repo = MySqlGameRepository::new(pool.inner().clone());
-
-    let last_move: String; // = get_last_move(pool, request.game_id.clone()).await;
-    match game_repo.get_last_move(request.game_id.as_str()).await {
-        Ok(m) => {
-            if m == 0 {
-                //println!("No moves yet!");
-                last_move = String::new();
-            } else if m == u64::MAX {
-                last_move = "pass".to_string();
-            } else {
-                last_move = move_to_algebraic(m).unwrap();
-            }
-        }
-        Err(e) => {
-            println!("Game status error: {}", e);
+    let service = GameService::new(game_repo.inner().as_ref(), book.inner());
+
+    match service.game_status(request.game_id.as_str()).await {
+        Ok(status) => {
             let response: GameStatusResponse = GameStatusResponse {
                 status: "ok".to_string(),
                 error: ResponseError {
-                    code: 500,
-                    message: format!("{}", e),
+                    code: 200,
+                    message: "".to_string(),
                 },
+                result: GameStatusResult {
+                    status: status.status,
+                    last_move: status.last_move,
+                    opening: status.opening,
+                },
+            };
+            Json(response)
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "Game status error");
+            let response: GameStatusResponse = GameStatusResponse {
+                status: "error".to_string(),
+                error: game_error(e),
                 result: GameStatusResult {
                     status: String::new(),
                     last_move: String::new(),
+                    opening: String::new(),
                 },
             };
-            return Json(response);
+            Json(response)
         }
     }
+}
 
-    let game: Game;
-
+#[post("/game_history", format = "json", data = "<request>")]
+#[instrument(skip_all, fields(request_id = %request_id, player_id = %request.player_id, game_id = %request.game_id))]
+async fn game_history(
+    _rate_limit: RateLimited,
+    game_repo: &State<Arc<dyn GameRepository + Send + Sync>>,
+    book: &State<OpeningBook>,
+    request_id: RequestId,
+    request: Json<GameRequest>,
+) -> Json<GameHistoryResponse> {
     match game_repo.get_game(request.game_id.as_str()).await {
-        Ok(g) => match g {
-            Some(gg) => {
-                game = gg;
-            }
-            None => {
-                let result: GameStatusResult = GameStatusResult {
-                    status: String::new(),
-                    last_move: String::new(),
-                };
-                let response: GameStatusResponse = GameStatusResponse {
-                    status: "error".to_string(),
-                    error: ResponseError {
-                        code: 404,
-                        message: format!("Game UUID not found"),
-                    },
-                    result: result,
-                };
-                return Json(response);
-            }
-        },
-        Err(e) => {
-            let result: GameStatusResult = GameStatusResult {
-                status: String::new(),
-                last_move: String::new(),
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            let response: GameHistoryResponse = GameHistoryResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 404,
+                    message: "Game UUID not found".to_string(),
+                },
+                result: GameHistoryResult {
+                    moves: Vec::new(),
+                    opening: String::new(),
+                    annotations: Vec::new(),
+                },
             };
-            let response: GameStatusResponse = GameStatusResponse {
+            return Json(response);
+        }
+        Err(e) => {
+            let response: GameHistoryResponse = GameHistoryResponse {
                 status: "error".to_string(),
                 error: ResponseError {
                     code: 500,
                     message: format!("{}", e),
                 },
-                result: result,
+                result: GameHistoryResult {
+                    moves: Vec::new(),
+                    opening: String::new(),
+                    annotations: Vec::new(),
+                },
             };
             return Json(response);
         }
     }
-    let result: GameStatusResult = GameStatusResult {
-        status: statuses[game.state as usize].clone(),
-        last_move: last_move,
-    };
-
-    let response: GameStatusResponse = GameStatusResponse {
-        status: "ok".to_string(),
-        error: ResponseError {
-            code: 200,
-            message: "".to_string(),
-        },
-        result: result,
-    };
-    Json(response)
-}
 
-#[post("/join", format = "json", data = "<request>")]
-async fn game_join(pool: &State<Pool>, request: Json<GameRequest>) -> Json<GameJoinResponse> {
-    // TODO(1): add player validation
-    // TODO(3): make sure the joining player is different from the game creator
-    // TODO(4): make sure the game is in pending state
-    let game_repo = MySqlGameRepository::new(pool.inner().clone());
-    let mut game: Game;
-    match game_repo.get_game(request.game_id.as_str()).await {
-        Ok(g) => match g {
-            Some(gg) => {
-                game = gg;
-            }
-            None => {
-                let result: GameJoinResult = GameJoinResult {
-                    result: false,
-                    color: String::new(),
-                };
-                let response: GameJoinResponse = GameJoinResponse {
+    let annotations: Vec<MoveAnnotation> =
+        match game_repo.get_annotations(request.game_id.as_str()).await {
+            Ok(a) => a,
+            Err(e) => {
+                let response: GameHistoryResponse = GameHistoryResponse {
                     status: "error".to_string(),
                     error: ResponseError {
-                        code: 404,
-                        message: format!("Game UUID not found"),
+                        code: 500,
+                        message: format!("{}", e),
+                    },
+                    result: GameHistoryResult {
+                        moves: Vec::new(),
+                        opening: String::new(),
+                        annotations: Vec::new(),
                     },
-                    result: result,
                 };
                 return Json(response);
             }
-        },
-        Err(e) => {
-            let result: GameJoinResult = GameJoinResult {
-                result: false,
-                color: String::new(),
-            };
-            let response: GameJoinResponse = GameJoinResponse {
-                status: "error".to_string(),
+        };
+
+    match game_repo.get_moves(request.game_id.as_str()).await {
+        Ok(moves) => {
+            let moves: Vec<String> = algebraic_moves(&moves);
+            let opening: String = book.name_for(&moves).unwrap_or_default().to_string();
+            let annotations: Vec<MoveAnnotationInfo> = annotations
+                .iter()
+                .map(|a| MoveAnnotationInfo {
+                    move_number: a.move_number,
+                    played_move: algebraic_moves(&[a.played_move]).concat(),
+                    best_move: algebraic_moves(&[a.best_move]).concat(),
+                    best_score: a.best_score,
+                    played_score: a.played_score,
+                    loss: move_loss(a),
+                    exact: a.exact,
+                })
+                .collect();
+            let response: GameHistoryResponse = GameHistoryResponse {
+                status: "ok".to_string(),
                 error: ResponseError {
-                    code: 500,
-                    message: format!("{}", e),
+                    code: 200,
+                    message: String::new(),
+                },
+                result: GameHistoryResult {
+                    moves,
+                    opening,
+                    annotations,
                 },
-                result: result,
             };
-            return Json(response);
+            Json(response)
         }
-    }
-    let color: String;
-    if game.black_uuid == "".to_string() {
-        color = "black".to_string();
-        game.black_uuid = request.player_id.clone();
-    } else {
-        color = "white".to_string();
-        game.white_uuid = request.player_id.clone();
-    }
-    game.state = 1;
-    match game_repo.update_game(&game).await {
-        Ok(_) => {}
         Err(e) => {
-            let result: GameJoinResult = GameJoinResult {
-                result: false,
-                color: String::new(),
-            };
-            let response: GameJoinResponse = GameJoinResponse {
+            let response: GameHistoryResponse = GameHistoryResponse {
                 status: "error".to_string(),
                 error: ResponseError {
                     code: 500,
                     message: format!("{}", e),
                 },
-                result: result,
+                result: GameHistoryResult {
+                    moves: Vec::new(),
+                    opening: String::new(),
+                    annotations: Vec::new(),
+                },
             };
-            return Json(response);
+            Json(response)
         }
     }
-    let result: GameJoinResult = GameJoinResult {
-        result: true,
-        color: color,
-    };
-    let response: GameJoinResponse = GameJoinResponse {
-        status: "ok".to_string(),
-        error: ResponseError {
-            code: 200,
-            message: "".to_string(),
-        },
-        result: result,
+}
+
+fn analyze_error_response(code: u32, message: String) -> Json<AnalyzeResponse> {
+    let response: AnalyzeResponse = AnalyzeResponse {
+        status: "error".to_string(),
+        error: ResponseError { code, message },
+        result: AnalyzeResult::default(),
     };
     Json(response)
 }
 
-#[post("/move", format = "json", data = "<request>")]
-async fn game_move(pool: &State<Pool>, request: Json<MoveRequest>) -> Json<MoveResponse> {
-    let game_repo = MySqlGameRepository::new(pool.inner().clone());
-
-    let mut game: Game;
+#[post("/analyze", format = "json", data = "<request>")]
+#[instrument(skip_all, fields(request_id = %request_id, game_id = %request.game_id))]
+async fn analyze_game(
+    _rate_limit: RateLimited,
+    game_repo: &State<Arc<dyn GameRepository + Send + Sync>>,
+    request_id: RequestId,
+    request: Json<AnalyzeRequest>,
+) -> Json<AnalyzeResponse> {
     match game_repo.get_game(request.game_id.as_str()).await {
-        Ok(g) => match g {
-            Some(gg) => {
-                game = gg;
-            }
-            None => {
-                let result: MoveResult = MoveResult {
-                    ok: false,
-                    r#continue: true,
-                    winner: String::new(),
-                };
-                let response: MoveResponse = MoveResponse {
-                    status: "error".to_string(),
-                    error: ResponseError {
-                        code: 404,
-                        message: format!("Game UUID not found"),
-                    },
-                    result: result,
-                };
+        Ok(Some(_)) => {}
+        Ok(None) => return analyze_error_response(404, "Game UUID not found".to_string()),
+        Err(e) => return analyze_error_response(500, format!("{}", e)),
+    }
+    let moves: Vec<u64> = match game_repo.get_moves(request.game_id.as_str()).await {
+        Ok(m) => m,
+        Err(e) => return analyze_error_response(500, format!("{}", e)),
+    };
+
+    let positions: Vec<Position> = game_positions(&moves);
+    let plies: Vec<u64> = match request.ply {
+        Some(ply) if ply as usize >= positions.len() => {
+            return analyze_error_response(400, format!("The game has only {} moves", moves.len()));
+        }
+        Some(ply) => vec![ply],
+        None => (0..positions.len() as u64).collect(),
+    };
+    let max_depth: u32 = match request.depth {
+        0 => DEFAULT_ANALYSIS_DEPTH,
+        d => d.min(MAX_ANALYSIS_DEPTH),
+    };
+    let time_ms: u64 = match request.time_ms {
+        0 => DEFAULT_ANALYSIS_TIME_MS,
+        t => t.min(MAX_ANALYSIS_TIME_MS),
+    };
+    let solve_empties: u32 = request
+        .solve_empties
+        .unwrap_or(DEFAULT_SOLVE_EMPTIES)
+        .min(MAX_SOLVE_EMPTIES);
+    let limits: SearchLimits = SearchLimits {
+        max_depth,
+        time_budget: Duration::from_millis(time_ms / plies.len() as u64),
+    };
+
+    // The search is CPU bound, keep it off the async workers.
+    let selected: Vec<(u64, Position)> = plies
+        .iter()
+        .map(|&ply| (ply, positions[ply as usize]))
+        .collect();
+    // Late positions are solved exactly, the heuristic search takes over if the
+    // solver runs out of time.
+    let analyses: Vec<(u64, Position, Analysis, bool)> =
+        match rocket::tokio::task::spawn_blocking(move || {
+            selected
+                .into_iter()
+                .map(|(ply, position)| {
+                    let solved: Option<Analysis> = if position.empties() <= solve_empties {
+                        solve_analysis(&position, Instant::now() + limits.time_budget)
+                    } else {
+                        None
+                    };
+                    match solved {
+                        Some(analysis) => (ply, position, analysis, true),
+                        None => (ply, position, analyze(&position, limits), false),
+                    }
+                })
+                .collect::<Vec<(u64, Position, Analysis, bool)>>()
+        })
+        .await
+        {
+            Ok(a) => a,
+            Err(e) => return analyze_error_response(500, format!("Analysis failed: {}", e)),
+        };
+
+    let result: Vec<PositionAnalysis> = analyses
+        .into_iter()
+        .map(|(ply, position, analysis, exact)| PositionAnalysis {
+            ply,
+            to_move: match (analysis.best_move, position.black_to_move) {
+                (None, _) => String::new(),
+                (Some(_), true) => "black".to_string(),
+                (Some(_), false) => "white".to_string(),
+            },
+            move_played: algebraic_moves(moves.get(ply as usize).copied().as_slice()).concat(),
+            score: analysis.score,
+            best_move: algebraic_moves(analysis.best_move.as_slice()).concat(),
+            principal_variation: algebraic_moves(&analysis.principal_variation),
+            depth: analysis.depth,
+            exact,
+        })
+        .collect();
+
+    let response: AnalyzeResponse = AnalyzeResponse {
+        status: "ok".to_string(),
+        error: ResponseError {
+            code: 200,
+            message: String::new(),
+        },
+        result: AnalyzeResult {
+            game_id: request.game_id.clone(),
+            positions: result,
+        },
+    };
+    Json(response)
+}
+
+fn report_error_response(code: u32, message: String) -> Json<GameReportResponse> {
+    let response: GameReportResponse = GameReportResponse {
+        status: "error".to_string(),
+        error: ResponseError { code, message },
+        result: GameReportResult::default(),
+    };
+    Json(response)
+}
+
+#[post("/game_report", format = "json", data = "<request>")]
+#[instrument(skip_all, fields(request_id = %request_id, player_id = %request.player_id, game_id = %request.game_id))]
+async fn game_report(
+    _rate_limit: RateLimited,
+    game_repo: &State<Arc<dyn GameRepository + Send + Sync>>,
+    request_id: RequestId,
+    request: Json<GameRequest>,
+) -> Json<GameReportResponse> {
+    let game: Game = match game_repo.get_game(request.game_id.as_str()).await {
+        Ok(Some(g)) => g,
+        Ok(None) => return report_error_response(404, "Game UUID not found".to_string()),
+        Err(e) => return report_error_response(500, format!("{}", e)),
+    };
+    let job: Option<AnnotationJob> =
+        match game_repo.get_annotation_job(request.game_id.as_str()).await {
+            Ok(j) => j,
+            Err(e) => return report_error_response(500, format!("{}", e)),
+        };
+
+    let mut result: GameReportResult = GameReportResult {
+        game_id: request.game_id.clone(),
+        status: "none".to_string(),
+        outcome: if game.state >= 3 {
+            STATE_NAMES[game.state as usize].to_string()
+        } else {
+            String::new()
+        },
+        black_discs: game.position_black.count_ones() as u64,
+        white_discs: game.position_white.count_ones() as u64,
+        ..Default::default()
+    };
+    match job {
+        Some(job) if job.status == JOB_DONE => {
+            let moves: Vec<u64> = match game_repo.get_moves(request.game_id.as_str()).await {
+                Ok(m) => m,
+                Err(e) => return report_error_response(500, format!("{}", e)),
+            };
+            let annotations: Vec<MoveAnnotation> =
+                match game_repo.get_annotations(request.game_id.as_str()).await {
+                    Ok(a) => a,
+                    Err(e) => return report_error_response(500, format!("{}", e)),
+                };
+            result.moves = move_reports(&moves, &annotations);
+            result.black = player_accuracy(&result.moves, "black");
+            result.white = player_accuracy(&result.moves, "white");
+            result.status = job.status;
+        }
+        Some(job) if job.status == JOB_FAILED => {
+            return report_error_response(500, format!("Annotation failed: {}", job.error));
+        }
+        Some(job) => {
+            result.status = job.status;
+        }
+        None => {}
+    }
+
+    let response: GameReportResponse = GameReportResponse {
+        status: "ok".to_string(),
+        error: ResponseError {
+            code: 200,
+            message: String::new(),
+        },
+        result,
+    };
+    Json(response)
+}
+
+#[post("/join", format = "json", data = "<request>")]
+#[instrument(skip_all, fields(request_id = %request_id, player_id = %request.player_id, game_id = %request.game_id))]
+async fn game_join(
+    _rate_limit: RateLimited,
+    game_repo: &State<Arc<dyn GameRepository + Send + Sync>>,
+    book: &State<OpeningBook>,
+    events: &State<GameEvents>,
+    request_id: RequestId,
+    request: Json<GameRequest>,
+) -> Json<GameJoinResponse> {
+    let service = GameService::new(game_repo.inner().as_ref(), book.inner());
+
+    match service
+        .join_game(request.game_id.as_str(), request.player_id.as_str())
+        .await
+    {
+        Ok(joined) => {
+            events.publish(GameEvent {
+                game_id: joined.game.game_uuid.clone(),
+                status: STATE_NAMES[joined.game.state as usize].to_string(),
+                last_move: String::new(),
+                player_id: request.player_id.clone(),
+            });
+            let response: GameJoinResponse = GameJoinResponse {
+                status: "ok".to_string(),
+                error: ResponseError {
+                    code: 200,
+                    message: "".to_string(),
+                },
+                result: GameJoinResult {
+                    result: true,
+                    color: joined.color,
+                },
+            };
+            Json(response)
+        }
+        Err(e) => {
+            let response: GameJoinResponse = GameJoinResponse {
+                status: "error".to_string(),
+                error: game_error(e),
+                result: GameJoinResult {
+                    result: false,
+                    color: String::new(),
+                },
+            };
+            Json(response)
+        }
+    }
+}
+
+#[post("/move", format = "json", data = "<request>")]
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip_all, fields(request_id = %request_id, player_id = %request.player_id, game_id = %request.game_id, played = %request.r#move))]
+async fn game_move(
+    _rate_limit: RateLimited,
+    game_repo: &State<Arc<dyn GameRepository + Send + Sync>>,
+    tournament_repo: &State<Arc<dyn TournamentRepository + Send + Sync>>,
+    book: &State<OpeningBook>,
+    annotation_queue: &State<AnnotationQueue>,
+    metrics: &State<Arc<Metrics>>,
+    settings: &State<Settings>,
+    events: &State<GameEvents>,
+    request_id: RequestId,
+    idempotency_key: IdempotencyKey,
+    request: Json<MoveRequest>,
+) -> Json<MoveResponse> {
+    let service = GameService::new(game_repo.inner().as_ref(), book.inner());
+    let ttl_secs: u64 = settings.games.idempotency_ttl_secs;
+
+    let key: Option<String> = idempotency_key.or_field(request.idempotency_key.as_str());
+    if let Some(key) = &key {
+        let stored: Result<Option<MoveResponse>, GameError> = match check_key(key) {
+            Ok(()) => {
+                reserve_key(
+                    game_repo.inner().as_ref(),
+                    request.game_id.as_str(),
+                    key,
+                    request.player_id.as_str(),
+                    request.r#move.as_str(),
+                    ttl_secs,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        match stored {
+            Ok(Some(response)) => {
+                tracing::info!("Replaying the response of an earlier attempt");
                 return Json(response);
             }
-        },
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!(error = %e, "Error checking the idempotency key");
+                return Json(move_error(e));
+            }
+        }
+    }
+
+    let outcome: MoveOutcome = match service
+        .make_move(
+            request.game_id.as_str(),
+            request.player_id.as_str(),
+            request.r#move.as_str(),
+            key.as_deref(),
+        )
+        .await
+    {
+        Ok(o) => o,
         Err(e) => {
-            let result: MoveResult = MoveResult {
-                ok: false,
-                r#continue: true,
-                winner: String::new(),
+            tracing::warn!(error = %e, "Error applying move");
+            // The move was not played, so a retry with the key may play it.
+            if let Some(key) = &key {
+                if let Err(e) = game_repo
+                    .release_move_key(request.game_id.as_str(), key)
+                    .await
+                {
+                    tracing::warn!(error = %e, "Could not release the idempotency key");
+                }
+            }
+            return Json(move_error(e));
+        }
+    };
+    if request.r#move != "resign" {
+        metrics.moves.inc();
+    }
+    events.publish(GameEvent {
+        game_id: outcome.game.game_uuid.clone(),
+        status: STATE_NAMES[outcome.game.state as usize].to_string(),
+        last_move: request.r#move.clone(),
+        player_id: request.player_id.clone(),
+    });
+    if outcome.finished {
+        metrics
+            .games_finished
+            .with_label_values(&[STATE_NAMES[outcome.game.state as usize]])
+            .inc();
+        game_finished(
+            tournament_repo.inner().as_ref(),
+            game_repo.inner().as_ref(),
+            annotation_queue.inner(),
+            outcome.game.game_uuid.as_str(),
+        )
+        .await;
+    }
+    Json(outcome.response())
+}
+
+#[post("/matchmaking/enqueue", format = "json", data = "<request>")]
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip_all, fields(request_id = %request_id, player_id = %request.player_id))]
+async fn matchmaking_enqueue(
+    _rate_limit: RateLimited,
+    game_repo: &State<Arc<dyn GameRepository + Send + Sync>>,
+    player_repo: &State<Arc<dyn PlayerRepository + Send + Sync>>,
+    book: &State<OpeningBook>,
+    queue: &State<MatchmakingQueue>,
+    settings: &State<Settings>,
+    request_id: RequestId,
+    request: Json<MatchmakingRequest>,
+) -> Json<MatchmakingResponse> {
+    let rating: u32 = match player_repo.get_rating(request.player_id.as_str()).await {
+        Ok(Some(r)) => r,
+        Ok(None) => {
+            let response: MatchmakingResponse = MatchmakingResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 404,
+                    message: "Player UUID not found".to_string(),
+                },
+                result: MatchmakingResult {
+                    matched: false,
+                    game_id: String::new(),
+                    color: String::new(),
+                },
             };
-            let response: MoveResponse = MoveResponse {
+            return Json(response);
+        }
+        Err(e) => {
+            let response: MatchmakingResponse = MatchmakingResponse {
                 status: "error".to_string(),
                 error: ResponseError {
                     code: 500,
                     message: format!("{}", e),
                 },
-                result: result,
+                result: MatchmakingResult {
+                    matched: false,
+                    game_id: String::new(),
+                    color: String::new(),
+                },
             };
             return Json(response);
         }
+    };
+
+    let (player, opponent) = match queue.enqueue(
+        request.player_id.as_str(),
+        rating,
+        request.time_control.as_str(),
+    ) {
+        EnqueueOutcome::Waiting => {
+            let response: MatchmakingResponse = MatchmakingResponse {
+                status: "ok".to_string(),
+                error: ResponseError {
+                    code: 200,
+                    message: String::new(),
+                },
+                result: MatchmakingResult {
+                    matched: false,
+                    game_id: String::new(),
+                    color: String::new(),
+                },
+            };
+            return Json(response);
+        }
+        EnqueueOutcome::Matched(matched) => {
+            let response: MatchmakingResponse = MatchmakingResponse {
+                status: "ok".to_string(),
+                error: ResponseError {
+                    code: 200,
+                    message: String::new(),
+                },
+                result: MatchmakingResult {
+                    matched: true,
+                    game_id: matched.game_uuid,
+                    color: matched.color,
+                },
+            };
+            return Json(response);
+        }
+        EnqueueOutcome::Paired { player, opponent } => (player, opponent),
+    };
+
+    let mut time_control: String =
+        agreed_time_control(&player.time_control, &opponent.time_control);
+    if time_control.is_empty() {
+        time_control = settings.games.default_time_control.clone();
     }
-    let mut curr_player: String = "black".to_string();
-    if game.white_uuid == request.player_id {
-        curr_player = "white".to_string();
-    }
-    let mut next_player: String = "white".to_string();
-    if curr_player == "white".to_string() {
-        next_player = "black".to_string();
+    let color: String;
+    let opponent_color: String;
+    let (black_uuid, white_uuid) = if random_upto(2) == 1 {
+        color = "white".to_string();
+        opponent_color = "black".to_string();
+        (&opponent.player_uuid, &player.player_uuid)
+    } else {
+        color = "black".to_string();
+        opponent_color = "white".to_string();
+        (&player.player_uuid, &opponent.player_uuid)
+    };
+
+    let service = GameService::new(game_repo.inner().as_ref(), book.inner());
+    let game: Game = match service
+        .create_paired_game(black_uuid, white_uuid, time_control.as_str())
+        .await
+    {
+        Ok(g) => g,
+        Err(e) => {
+            queue.requeue(opponent);
+            queue.requeue(player);
+            let response: MatchmakingResponse = MatchmakingResponse {
+                status: "error".to_string(),
+                error: game_error(e),
+                result: MatchmakingResult {
+                    matched: false,
+                    game_id: String::new(),
+                    color: String::new(),
+                },
+            };
+            return Json(response);
+        }
+    };
+
+    queue.complete_match(
+        opponent.player_uuid.as_str(),
+        MatchedGame {
+            game_uuid: game.game_uuid.clone(),
+            color: opponent_color,
+        },
+    );
+
+    let response: MatchmakingResponse = MatchmakingResponse {
+        status: "ok".to_string(),
+        error: ResponseError {
+            code: 200,
+            message: String::new(),
+        },
+        result: MatchmakingResult {
+            matched: true,
+            game_id: game.game_uuid,
+            color,
+        },
+    };
+    Json(response)
+}
+
+#[post("/matchmaking/dequeue", format = "json", data = "<request>")]
+#[instrument(skip_all, fields(request_id = %request_id, player_id = %request.player_id))]
+async fn matchmaking_dequeue(
+    _rate_limit: RateLimited,
+    queue: &State<MatchmakingQueue>,
+    request_id: RequestId,
+    request: Json<NewGameRequest>,
+) -> Json<DequeueResponse> {
+    let response: DequeueResponse = DequeueResponse {
+        status: "ok".to_string(),
+        error: ResponseError {
+            code: 200,
+            message: String::new(),
+        },
+        result: DequeueResult {
+            result: queue.dequeue(request.player_id.as_str()),
+        },
+    };
+    Json(response)
+}
+
+#[get("/leaderboard?<page>&<per_page>&<time_control>")]
+#[instrument(skip_all, fields(request_id = %request_id))]
+async fn leaderboard(
+    _rate_limit: RateLimited,
+    game_repo: &State<Arc<dyn GameRepository + Send + Sync>>,
+    request_id: RequestId,
+    page: Option<u64>,
+    per_page: Option<u64>,
+    time_control: Option<String>,
+) -> Json<LeaderboardResponse> {
+    let page: u64 = page.unwrap_or(1).max(1);
+    let per_page: u64 = per_page.unwrap_or(20).clamp(1, 100);
+    let time_control: String = time_control.unwrap_or_default();
+
+    match game_repo
+        .leaderboard((page - 1) * per_page, per_page, time_control.as_str())
+        .await
+    {
+        Ok(entries) => {
+            let response: LeaderboardResponse = LeaderboardResponse {
+                status: "ok".to_string(),
+                error: ResponseError {
+                    code: 200,
+                    message: String::new(),
+                },
+                result: LeaderboardResult {
+                    page,
+                    per_page,
+                    entries,
+                },
+            };
+            Json(response)
+        }
+        Err(e) => {
+            let response: LeaderboardResponse = LeaderboardResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 500,
+                    message: format!("{}", e),
+                },
+                result: LeaderboardResult {
+                    page,
+                    per_page,
+                    entries: Vec::new(),
+                },
+            };
+            Json(response)
+        }
     }
+}
 
-    if request.r#move == "resign".to_string() {
-        if curr_player == "white".to_string() {
-            game.state = 3;
-        } else {
-            game.state = 4;
+#[get("/players/<id>/stats")]
+#[instrument(skip_all, fields(request_id = %request_id, player_id = %id))]
+async fn player_stats(
+    _rate_limit: RateLimited,
+    game_repo: &State<Arc<dyn GameRepository + Send + Sync>>,
+    request_id: RequestId,
+    id: &str,
+) -> Json<PlayerStatsResponse> {
+    match game_repo.player_stats(id).await {
+        Ok(Some(stats)) => {
+            let response: PlayerStatsResponse = PlayerStatsResponse {
+                status: "ok".to_string(),
+                error: ResponseError {
+                    code: 200,
+                    message: String::new(),
+                },
+                result: stats,
+            };
+            Json(response)
         }
-        match game_repo.update_game(&game).await {
-            Ok(_) => {}
-            Err(e) => {
-                let result: MoveResult = MoveResult {
-                    ok: false,
-                    r#continue: true,
-                    winner: String::new(),
-                };
-                let response: MoveResponse = MoveResponse {
-                    status: "error".to_string(),
-                    error: ResponseError {
-                        code: 500,
-                        message: format!("{}", e),
-                    },
-                    result: result,
-                };
-                return Json(response);
-            }
+        Ok(None) => {
+            let response: PlayerStatsResponse = PlayerStatsResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 404,
+                    message: "Player UUID not found".to_string(),
+                },
+                result: PlayerStats::default(),
+            };
+            Json(response)
         }
-        let result: MoveResult = MoveResult {
-            ok: true,
-            r#continue: false,
-            winner: next_player.clone(),
+        Err(e) => {
+            let response: PlayerStatsResponse = PlayerStatsResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 500,
+                    message: format!("{}", e),
+                },
+                result: PlayerStats::default(),
+            };
+            Json(response)
+        }
+    }
+}
+
+fn tournament_error_code(e: &TournamentError) -> u32 {
+    match e {
+        TournamentError::NotFound => 404,
+        TournamentError::Repository(_) => 500,
+        _ => 400,
+    }
+}
+
+#[post("/tournaments/create", format = "json", data = "<request>")]
+#[instrument(skip_all, fields(request_id = %request_id, tournament_id))]
+async fn tournament_create(
+    _rate_limit: RateLimited,
+    tournament_repo: &State<Arc<dyn TournamentRepository + Send + Sync>>,
+    request_id: RequestId,
+    request: Json<NewTournamentRequest>,
+) -> Json<NewTournamentResponse> {
+    let format: TournamentFormat;
+    if let Some(f) = TournamentFormat::parse(request.format.as_str()) {
+        format = f;
+    } else {
+        let response: NewTournamentResponse = NewTournamentResponse {
+            status: "error".to_string(),
+            error: ResponseError {
+                code: 400,
+                message: format!("Unknown tournament format: {}", request.format),
+            },
+            result: NewTournamentResult {
+                tournament_id: String::new(),
+            },
         };
-        let response: MoveResponse = MoveResponse {
-            status: "ok".to_string(),
+        return Json(response);
+    }
+
+    let tiebreak: Tiebreak;
+    if request.tiebreak.is_empty() {
+        tiebreak = Tiebreak::Armageddon;
+    } else if let Some(t) = Tiebreak::parse(request.tiebreak.as_str()) {
+        tiebreak = t;
+    } else {
+        let response: NewTournamentResponse = NewTournamentResponse {
+            status: "error".to_string(),
             error: ResponseError {
-                code: 200,
-                message: String::new(),
+                code: 400,
+                message: format!("Unknown tiebreak rule: {}", request.tiebreak),
+            },
+            result: NewTournamentResult {
+                tournament_id: String::new(),
             },
-            result: result,
         };
         return Json(response);
     }
 
-    if request.r#move == "pass".to_string() {
-        game.state = 3 - game.state;
-        let mut cont: bool = true;
-        let mut winner: String = String::new();
-        let game_status =
-            check_game_status(game.position_white, game.position_black, game.state == 2);
-
-        if game_status == (u64::MAX - 2) {
-            game.state = 4;
-            cont = false;
-            winner = "white".to_string();
-        } else if game_status == (u64::MAX - 1) {
-            game.state = 3;
-            cont = false;
-            winner = "black".to_string();
-        } else if game_status == (u64::MAX - 3) {
-            game.state = 5;
-            cont = false;
-            winner = "draw".to_string();
-        }
-        let max_move: u64;
-        match game_repo.get_max_move_no(request.game_id.as_str()).await {
-            Ok(m) => {
-                max_move = m;
-            }
-            Err(e) => {
-                let result: MoveResult = MoveResult {
-                    ok: false,
-                    r#continue: true,
-                    winner: String::new(),
-                };
-                let response: MoveResponse = MoveResponse {
-                    status: "ok".to_string(),
-                    error: ResponseError {
-                        code: 500,
-                        message: format!("{}", e),
-                    },
-                    result: result,
-                };
-                return Json(response);
-            }
+    let tournament: Tournament = Tournament {
+        tournament_uuid: generate_uuid(),
+        name: request.name.clone(),
+        format: format.as_str().to_string(),
+        rounds: request.rounds,
+        current_round: 0,
+        state: 0,
+        time_control: request.time_control.clone(),
+        best_of: request.best_of.max(1),
+        tiebreak: tiebreak.as_str().to_string(),
+    };
+    tracing::Span::current().record("tournament_id", tournament.tournament_uuid.as_str());
+    match tournament_repo.create_tournament(&tournament).await {
+        Ok(_) => {
+            let response: NewTournamentResponse = NewTournamentResponse {
+                status: "ok".to_string(),
+                error: ResponseError {
+                    code: 200,
+                    message: String::new(),
+                },
+                result: NewTournamentResult {
+                    tournament_id: tournament.tournament_uuid,
+                },
+            };
+            Json(response)
         }
-
-        match game_repo
-            .update_game_with_move(&game, u64::MAX, max_move + 1)
-            .await
-        {
-            Ok(_) => {
-                let result: MoveResult = MoveResult {
-                    ok: true,
-                    r#continue: cont,
-                    winner: winner,
-                };
-                let response: MoveResponse = MoveResponse {
-                    status: "ok".to_string(),
-                    error: ResponseError {
-                        code: 200,
-                        message: String::new(),
-                    },
-                    result: result,
-                };
-                return Json(response);
-            }
-            Err(e) => {
-                let result: MoveResult = MoveResult {
-                    ok: false,
-                    r#continue: true,
-                    winner: String::new(),
-                };
-                let response: MoveResponse = MoveResponse {
-                    status: "error".to_string(),
-                    error: ResponseError {
-                        code: 500,
-                        message: format!("{}", e),
-                    },
-                    result: result,
-                };
-                return Json(response);
-            }
+        Err(e) => {
+            let response: NewTournamentResponse = NewTournamentResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 500,
+                    message: format!("{}", e),
+                },
+                result: NewTournamentResult {
+                    tournament_id: String::new(),
+                },
+            };
+            Json(response)
         }
     }
+}
 
-    match apply_move(
-        game.position_white,
-        game.position_black,
-        move_to_bitmap(&request.r#move.clone().as_str()).unwrap(),
-        curr_player == "white".to_string(),
-    ) {
-        Ok((new_white, new_black)) => {
-            //let mut next_state: u64 = 2;
-            let mut cont: bool = true;
-            /*if curr_player == "white".to_string() {
-                next_state = 1;
-            }*/
-            game.position_white = new_white;
-            game.position_black = new_black;
-            game.state = 3 - game.state;
-            //let game_status: &str = check_game_status(game.position_white, game.position_black);
-            let game_status = check_game_status(
-                game.position_white,
-                game.position_black,
-                curr_player == "white".to_string(),
-            );
-            if game_status == (u64::MAX - 2) {
-                game.state = 4;
-                cont = false;
-            } else if game_status == (u64::MAX - 1) {
-                game.state = 3;
-                cont = false;
-            } else if game_status == (u64::MAX - 3) {
-                game.state = 5;
-                cont = false;
-            }
-            let max_move: u64;
-            //match get_max_move_no(pool, request.game_id.clone()).await {
-            match game_repo.get_max_move_no(request.game_id.as_str()).await {
-                Ok(m) => {
-                    max_move = m;
-                }
-                Err(e) => {
-                    println!("Error while getting last move number: {}", e);
-                    let result: MoveResult = MoveResult {
-                        ok: false,
-                        r#continue: true,
-                        winner: String::new(),
-                    };
-                    let response: MoveResponse = MoveResponse {
-                        status: "error".to_string(),
+#[post("/tournaments/register", format = "json", data = "<request>")]
+#[instrument(skip_all, fields(request_id = %request_id, tournament_id = %request.tournament_id, player_id = %request.player_id))]
+async fn tournament_register(
+    _rate_limit: RateLimited,
+    tournament_repo: &State<Arc<dyn TournamentRepository + Send + Sync>>,
+    player_repo: &State<Arc<dyn PlayerRepository + Send + Sync>>,
+    request_id: RequestId,
+    request: Json<TournamentRegisterRequest>,
+) -> Json<TournamentRegisterResponse> {
+    let error: ResponseError;
+    match player_repo.get_rating(request.player_id.as_str()).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            let response: TournamentRegisterResponse = TournamentRegisterResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 404,
+                    message: "Player UUID not found".to_string(),
+                },
+                result: TournamentRegisterResult { result: false },
+            };
+            return Json(response);
+        }
+        Err(e) => {
+            let response: TournamentRegisterResponse = TournamentRegisterResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 500,
+                    message: format!("{}", e),
+                },
+                result: TournamentRegisterResult { result: false },
+            };
+            return Json(response);
+        }
+    }
+    match tournament_repo
+        .get_tournament(request.tournament_id.as_str())
+        .await
+    {
+        Ok(Some(t)) if t.state == 0 => {
+            match tournament_repo
+                .register_player(request.tournament_id.as_str(), request.player_id.as_str())
+                .await
+            {
+                Ok(registered) => {
+                    let response: TournamentRegisterResponse = TournamentRegisterResponse {
+                        status: "ok".to_string(),
                         error: ResponseError {
-                            code: 500,
-                            message: format!("{}", e),
+                            code: 200,
+                            message: String::new(),
                         },
-                        result: result,
+                        result: TournamentRegisterResult { result: registered },
                     };
                     return Json(response);
                 }
+                Err(e) => {
+                    error = ResponseError {
+                        code: 500,
+                        message: format!("{}", e),
+                    };
+                }
+            }
+        }
+        Ok(Some(_)) => {
+            error = ResponseError {
+                code: 400,
+                message: "Registration is closed".to_string(),
+            };
+        }
+        Ok(None) => {
+            error = ResponseError {
+                code: 404,
+                message: "Tournament UUID not found".to_string(),
+            };
+        }
+        Err(e) => {
+            error = ResponseError {
+                code: 500,
+                message: format!("{}", e),
+            };
+        }
+    }
+    let response: TournamentRegisterResponse = TournamentRegisterResponse {
+        status: "error".to_string(),
+        error,
+        result: TournamentRegisterResult { result: false },
+    };
+    Json(response)
+}
+
+#[post("/tournaments/start", format = "json", data = "<request>")]
+#[instrument(skip_all, fields(request_id = %request_id, tournament_id = %request.tournament_id))]
+async fn tournament_start(
+    _rate_limit: RateLimited,
+    tournament_repo: &State<Arc<dyn TournamentRepository + Send + Sync>>,
+    request_id: RequestId,
+    request: Json<TournamentStartRequest>,
+) -> Json<TournamentResponse> {
+    if let Ok(Some(t)) = tournament_repo
+        .get_tournament(request.tournament_id.as_str())
+        .await
+    {
+        if t.state != 0 {
+            let response: TournamentResponse = TournamentResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 400,
+                    message: "Tournament has already started".to_string(),
+                },
+                result: TournamentInfo::default(),
+            };
+            return Json(response);
+        }
+    }
+    if let Err(e) = advance_tournament(
+        tournament_repo.inner().as_ref(),
+        request.tournament_id.as_str(),
+    )
+    .await
+    {
+        let response: TournamentResponse = TournamentResponse {
+            status: "error".to_string(),
+            error: ResponseError {
+                code: tournament_error_code(&e),
+                message: format!("{}", e),
+            },
+            result: TournamentInfo::default(),
+        };
+        return Json(response);
+    }
+    tournament_response(
+        tournament_repo.inner().as_ref(),
+        request.tournament_id.as_str(),
+    )
+    .await
+}
+
+#[get("/tournaments/<id>")]
+#[instrument(skip_all, fields(request_id = %request_id, tournament_id = %id))]
+async fn tournament_details(
+    _rate_limit: RateLimited,
+    tournament_repo: &State<Arc<dyn TournamentRepository + Send + Sync>>,
+    request_id: RequestId,
+    id: &str,
+) -> Json<TournamentResponse> {
+    tournament_response(tournament_repo.inner().as_ref(), id).await
+}
+
+/// The tournament with its players and pairings, as returned by the details
+/// and start endpoints.
+async fn tournament_response(
+    tournament_repo: &(dyn TournamentRepository + Send + Sync),
+    id: &str,
+) -> Json<TournamentResponse> {
+    let tournament: Tournament = match tournament_repo.get_tournament(id).await {
+        Ok(Some(t)) => t,
+        Ok(None) => {
+            let response: TournamentResponse = TournamentResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 404,
+                    message: "Tournament UUID not found".to_string(),
+                },
+                result: TournamentInfo::default(),
+            };
+            return Json(response);
+        }
+        Err(e) => {
+            let response: TournamentResponse = TournamentResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 500,
+                    message: format!("{}", e),
+                },
+                result: TournamentInfo::default(),
+            };
+            return Json(response);
+        }
+    };
+    let players = tournament_repo.get_players(id).await;
+    let pairings = tournament_repo.get_pairings(id).await;
+    match (players, pairings) {
+        (Ok(players), Ok(pairings)) => {
+            let response: TournamentResponse = TournamentResponse {
+                status: "ok".to_string(),
+                error: ResponseError {
+                    code: 200,
+                    message: String::new(),
+                },
+                result: TournamentInfo {
+                    tournament_id: tournament.tournament_uuid,
+                    name: tournament.name,
+                    format: tournament.format,
+                    rounds: tournament.rounds,
+                    current_round: tournament.current_round,
+                    status: tournament_status_name(tournament.state),
+                    time_control: tournament.time_control,
+                    players,
+                    pairings: pairings
+                        .iter()
+                        .map(|p| TournamentPairingInfo {
+                            round: p.round,
+                            match_id: p.bracket_match.clone(),
+                            black: p.black_uuid.clone(),
+                            white: p.white_uuid.clone(),
+                            game_id: p.game_uuid.clone(),
+                            result: pairing_result(p),
+                        })
+                        .collect(),
+                },
+            };
+            Json(response)
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            let response: TournamentResponse = TournamentResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 500,
+                    message: format!("{}", e),
+                },
+                result: TournamentInfo::default(),
+            };
+            Json(response)
+        }
+    }
+}
+
+#[get("/tournaments/<id>/standings")]
+#[instrument(skip_all, fields(request_id = %request_id, tournament_id = %id))]
+async fn tournament_standings(
+    _rate_limit: RateLimited,
+    tournament_repo: &State<Arc<dyn TournamentRepository + Send + Sync>>,
+    request_id: RequestId,
+    id: &str,
+) -> Json<StandingsResponse> {
+    match tournament_repo.get_tournament(id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            let response: StandingsResponse = StandingsResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 404,
+                    message: "Tournament UUID not found".to_string(),
+                },
+                result: Vec::new(),
+            };
+            return Json(response);
+        }
+        Err(e) => {
+            let response: StandingsResponse = StandingsResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 500,
+                    message: format!("{}", e),
+                },
+                result: Vec::new(),
+            };
+            return Json(response);
+        }
+    }
+    let players = tournament_repo.get_players(id).await;
+    let pairings = tournament_repo.get_pairings(id).await;
+    match (players, pairings) {
+        (Ok(players), Ok(pairings)) => {
+            let response: StandingsResponse = StandingsResponse {
+                status: "ok".to_string(),
+                error: ResponseError {
+                    code: 200,
+                    message: String::new(),
+                },
+                result: compute_standings(&players, &pairings),
+            };
+            Json(response)
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            let response: StandingsResponse = StandingsResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 500,
+                    message: format!("{}", e),
+                },
+                result: Vec::new(),
+            };
+            Json(response)
+        }
+    }
+}
+
+#[get("/tournaments/<id>/bracket")]
+#[instrument(skip_all, fields(request_id = %request_id, tournament_id = %id))]
+async fn tournament_bracket_details(
+    _rate_limit: RateLimited,
+    tournament_repo: &State<Arc<dyn TournamentRepository + Send + Sync>>,
+    request_id: RequestId,
+    id: &str,
+) -> Json<BracketResponse> {
+    let tournament: Tournament = match tournament_repo.get_tournament(id).await {
+        Ok(Some(t)) => t,
+        Ok(None) => {
+            let response: BracketResponse = BracketResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 404,
+                    message: "Tournament UUID not found".to_string(),
+                },
+                result: BracketInfo::default(),
+            };
+            return Json(response);
+        }
+        Err(e) => {
+            let response: BracketResponse = BracketResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 500,
+                    message: format!("{}", e),
+                },
+                result: BracketInfo::default(),
+            };
+            return Json(response);
+        }
+    };
+    let players = tournament_repo.get_players(id).await;
+    let pairings = tournament_repo.get_pairings(id).await;
+    let (players, pairings) = match (players, pairings) {
+        (Ok(players), Ok(pairings)) => (players, pairings),
+        (Err(e), _) | (_, Err(e)) => {
+            let response: BracketResponse = BracketResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 500,
+                    message: format!("{}", e),
+                },
+                result: BracketInfo::default(),
+            };
+            return Json(response);
+        }
+    };
+
+    match tournament_bracket(&tournament, &players, &pairings) {
+        Ok(Some(bracket)) => {
+            let response: BracketResponse = BracketResponse {
+                status: "ok".to_string(),
+                error: ResponseError {
+                    code: 200,
+                    message: String::new(),
+                },
+                result: BracketInfo {
+                    tournament_id: tournament.tournament_uuid.clone(),
+                    format: tournament.format.clone(),
+                    best_of: tournament.best_of,
+                    tiebreak: tournament.tiebreak.clone(),
+                    status: tournament_status_name(tournament.state),
+                    champion: bracket.champion.unwrap_or_default(),
+                    matches: bracket.matches,
+                },
+            };
+            Json(response)
+        }
+        Ok(None) => {
+            let response: BracketResponse = BracketResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: 400,
+                    message: "Tournament is not an elimination tournament".to_string(),
+                },
+                result: BracketInfo::default(),
+            };
+            Json(response)
+        }
+        Err(e) => {
+            let response: BracketResponse = BracketResponse {
+                status: "error".to_string(),
+                error: ResponseError {
+                    code: tournament_error_code(&e),
+                    message: format!("{}", e),
+                },
+                result: BracketInfo::default(),
+            };
+            Json(response)
+        }
+    }
+}
+
+fn match_error_response(code: u32, message: String) -> Json<MatchResponse> {
+    let response: MatchResponse = MatchResponse {
+        status: "error".to_string(),
+        error: ResponseError { code, message },
+        result: MatchInfo::default(),
+    };
+    Json(response)
+}
+
+#[post("/matches/create", format = "json", data = "<request>")]
+#[instrument(skip_all, fields(request_id = %request_id, match_id, player_a = %request.player_a, player_b = %request.player_b))]
+async fn match_create(
+    _rate_limit: RateLimited,
+    match_repo: &State<Arc<dyn MatchRepository + Send + Sync>>,
+    game_repo: &State<Arc<dyn GameRepository + Send + Sync>>,
+    player_repo: &State<Arc<dyn PlayerRepository + Send + Sync>>,
+    book: &State<OpeningBook>,
+    request_id: RequestId,
+    request: Json<NewMatchRequest>,
+) -> Json<MatchResponse> {
+    if request.player_a.is_empty() || request.player_a == request.player_b {
+        return match_error_response(400, "A match needs two different players".to_string());
+    }
+    let games: u64 = if request.games == 0 {
+        DEFAULT_MATCH_GAMES
+    } else {
+        request.games
+    };
+    if games > MAX_MATCH_GAMES {
+        return match_error_response(
+            400,
+            format!("A match can have at most {} games", MAX_MATCH_GAMES),
+        );
+    }
+
+    // Openings are validated up front so that no games are created for a bad request.
+    let mut openings: Vec<String> = Vec::new();
+    let mut opening_moves: HashMap<String, Vec<OpeningMove>> = HashMap::new();
+    for opening in request.openings.iter() {
+        let squares: Vec<String> = match book.resolve(opening.as_str()) {
+            Some(s) => s,
+            None => return match_error_response(400, format!("Invalid opening: {}", opening)),
+        };
+        match play_opening(&squares) {
+            Ok(moves) => {
+                let normalized: String = squares.concat();
+                openings.push(normalized.clone());
+                opening_moves.insert(normalized, moves);
             }
-            match game_repo
-                .update_game_with_move(
-                    &game,
-                    move_to_bitmap(&request.r#move.clone().as_str()).unwrap(),
-                    max_move + 1,
-                )
-                .await
-            {
-                Ok(_) => {
-                    let result: MoveResult = MoveResult {
-                        ok: true,
-                        r#continue: cont,
-                        winner: String::new(),
-                    };
-                    let response: MoveResponse = MoveResponse {
-                        status: "ok".to_string(),
-                        error: ResponseError {
-                            code: 200,
-                            message: String::new(),
-                        },
-                        result: result,
-                    };
-                    return Json(response);
-                }
-                Err(e) => {
-                    println!("Error while updating move: {}", e);
-                    let result: MoveResult = MoveResult {
-                        ok: false,
-                        r#continue: true,
-                        winner: String::new(),
-                    };
-                    let response: MoveResponse = MoveResponse {
-                        status: "error".to_string(),
-                        error: ResponseError {
-                            code: 500,
-                            message: format!("{}", e),
-                        },
-                        result: result,
-                    };
-                    return Json(response);
-                }
+            Err(e) => return match_error_response(400, e),
+        }
+    }
+
+    for player in [&request.player_a, &request.player_b] {
+        match player_repo.get_rating(player.as_str()).await {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                return match_error_response(404, format!("Player UUID not found: {}", player))
             }
+            Err(e) => return match_error_response(500, format!("{}", e)),
         }
-        Err(e) => {
-            println!("Error applying move {}: {}", request.r#move, e);
-            let result: MoveResult = MoveResult {
-                ok: false,
-                r#continue: true,
-                winner: String::new(),
+    }
+
+    let game_match: GameMatch = GameMatch {
+        match_uuid: generate_uuid(),
+        player_a_uuid: request.player_a.clone(),
+        player_b_uuid: request.player_b.clone(),
+        games,
+        time_control: request.time_control.clone(),
+    };
+    tracing::Span::current().record("match_id", game_match.match_uuid.as_str());
+    let drawn: Vec<String> = draw_openings(games, &openings, random_upto);
+    let mut match_games: Vec<NewMatchGame> = Vec::new();
+    for (game_no, opening) in (1..=games).zip(drawn) {
+        match_games.push(NewMatchGame {
+            game_no,
+            game: new_match_game(&game_match, game_no),
+            moves: opening_moves.get(&opening).cloned().unwrap_or_default(),
+            opening,
+        });
+    }
+    let service = GameService::new(game_repo.inner().as_ref(), book.inner());
+    if let Err(e) = service.store_match(&game_match, match_games).await {
+        return match_error_response(e.code(), format!("{}", e));
+    }
+
+    match match_repo
+        .get_match_games(game_match.match_uuid.as_str())
+        .await
+    {
+        Ok(match_games) => {
+            let response: MatchResponse = MatchResponse {
+                status: "ok".to_string(),
+                error: ResponseError {
+                    code: 200,
+                    message: String::new(),
+                },
+                result: match_info(&game_match, &match_games),
             };
-            let response: MoveResponse = MoveResponse {
-                status: "error".to_string(),
+            Json(response)
+        }
+        Err(e) => match_error_response(500, format!("{}", e)),
+    }
+}
+
+#[get("/matches/<id>")]
+#[instrument(skip_all, fields(request_id = %request_id, match_id = %id))]
+async fn match_details(
+    _rate_limit: RateLimited,
+    match_repo: &State<Arc<dyn MatchRepository + Send + Sync>>,
+    request_id: RequestId,
+    id: &str,
+) -> Json<MatchResponse> {
+    let game_match: GameMatch = match match_repo.get_match(id).await {
+        Ok(Some(m)) => m,
+        Ok(None) => return match_error_response(404, "Match UUID not found".to_string()),
+        Err(e) => return match_error_response(500, format!("{}", e)),
+    };
+    match match_repo.get_match_games(id).await {
+        Ok(match_games) => {
+            let response: MatchResponse = MatchResponse {
+                status: "ok".to_string(),
                 error: ResponseError {
-                    code: 400,
-                    message: e.to_string(),
+                    code: 200,
+                    message: String::new(),
                 },
-                result: result,
+                result: match_info(&game_match, &match_games),
             };
-            return Json(response);
+            Json(response)
+        }
+        Err(e) => match_error_response(500, format!("{}", e)),
+    }
+}
+
+/// Runs the `migrate` or `rollback` command against the database.
+async fn run_schema_command(db: &Database, command: &str) -> Result<(), RepositoryError> {
+    let target: &(dyn MigrationTarget + Send + Sync) = match db.migration_target() {
+        Some(t) => t,
+        None => {
+            println!("This storage backend has no schema");
+            return Ok(());
+        }
+    };
+    match command {
+        "migrate" => {
+            let applied: Vec<&Migration> = migrate(target).await?;
+            if applied.is_empty() {
+                println!("The schema is up to date");
+            }
+            for migration in applied {
+                println!("Applied {}", migration.name);
+            }
+        }
+        "rollback" => {
+            let reverted: &Migration = rollback(target).await?;
+            println!("Rolled back {}", reverted.name);
+        }
+        _ => {
+            return Err(RepositoryError::Other(format!(
+                "Unknown command {}, expected migrate or rollback",
+                command
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// Brings the schema up to date before serving, or only checks it without
+/// `auto_migrate`. A schema newer than this build is always refused.
+async fn prepare_schema(db: &Database, auto_migrate: bool) -> Result<(), RepositoryError> {
+    let target: &(dyn MigrationTarget + Send + Sync) = match db.migration_target() {
+        Some(t) => t,
+        None => return Ok(()),
+    };
+    if !auto_migrate {
+        return check_schema(target).await;
+    }
+    for migration in migrate(target).await? {
+        tracing::info!(migration = migration.name, "Applied migration");
+    }
+    Ok(())
+}
+
+/// Assembles the server around a storage backend. The repositories are managed
+/// as trait objects, so handlers work the same with every backend.
+async fn build_rocket(
+    figment: rocket::figment::Figment,
+    settings: Settings,
+    db: Database,
+    opening_book: OpeningBook,
+) -> rocket::Rocket<rocket::Build> {
+    let metrics: Arc<Metrics> = Arc::new(Metrics::new());
+    let game_repo: Arc<dyn GameRepository + Send + Sync> = Arc::new(MeteredGameRepository::new(
+        Arc::from(db.game_repository()),
+        metrics.clone(),
+    ));
+    let player_repo: Arc<dyn PlayerRepository + Send + Sync> = Arc::new(
+        MeteredPlayerRepository::new(Arc::from(db.player_repository()), metrics.clone()),
+    );
+    let tournament_repo: Arc<dyn TournamentRepository + Send + Sync> = Arc::new(
+        MeteredTournamentRepository::new(Arc::from(db.tournament_repository()), metrics.clone()),
+    );
+    let match_repo: Arc<dyn MatchRepository + Send + Sync> = Arc::new(MeteredMatchRepository::new(
+        Arc::from(db.match_repository()),
+        metrics.clone(),
+    ));
+
+    let annotation_queue: AnnotationQueue = AnnotationQueue::start(game_repo.clone());
+    annotation_queue.resume(game_repo.as_ref()).await;
+
+    let mut routes: Vec<rocket::Route> = routes![
+        get_users,
+        register_player,
+        create_game,
+        game_list,
+        game_status,
+        game_history,
+        analyze_game,
+        game_report,
+        game_join,
+        game_move,
+        game_events,
+        leaderboard,
+        player_stats
+    ];
+    if settings.features.matchmaking {
+        routes.extend(routes![matchmaking_enqueue, matchmaking_dequeue]);
+    }
+    if settings.features.tournaments {
+        routes.extend(routes![
+            tournament_create,
+            tournament_register,
+            tournament_start,
+            tournament_details,
+            tournament_standings,
+            tournament_bracket_details
+        ]);
+    }
+    if settings.features.matches {
+        routes.extend(routes![match_create, match_details]);
+    }
+    if settings.features.api_docs {
+        routes.extend(routes![openapi_document, swagger_ui]);
+    }
+    if settings.cors.is_enabled() {
+        routes.extend(routes![preflight]);
+    }
+    let api_document: ApiDocument = ApiDocument {
+        json: build_document("/reversi/v1", &routes).to_string(),
+    };
+    let mut root_routes: Vec<rocket::Route> = routes![metrics_endpoint, healthz, readyz];
+    if settings.features.web_ui {
+        root_routes.extend(routes![web_index, web_script, web_style]);
+    }
+
+    rocket::custom(figment)
+        .attach(RequestIdFairing)
+        .attach(MetricsFairing {
+            metrics: metrics.clone(),
+        })
+        .attach(RateLimitFairing)
+        .attach(CorsFairing {
+            config: settings.cors.clone(),
+            prefix: "/reversi/v1",
+        })
+        .manage(RateLimiter::new(settings.rate_limit.clone()))
+        .manage(metrics)
+        .manage(Lifecycle::new())
+        .attach(rocket::fairing::AdHoc::on_shutdown(
+            "Graceful shutdown",
+            |rocket| {
+                Box::pin(async move {
+                    if let Some(lifecycle) = rocket.state::<Lifecycle>() {
+                        lifecycle.begin_draining();
+                    }
+                    if let Some(queue) = rocket.state::<AnnotationQueue>() {
+                        let grace: u64 = rocket.config().shutdown.grace as u64;
+                        queue.stop(Duration::from_secs(grace)).await;
+                    }
+                })
+            },
+        ))
+        .manage(db)
+        .manage(game_repo)
+        .manage(player_repo)
+        .manage(tournament_repo)
+        .manage(match_repo)
+        .manage(annotation_queue)
+        .manage(MatchmakingQueue::new(settings.matchmaking.clone()))
+        .manage(opening_book)
+        .manage(GameEvents::new())
+        .manage(api_document)
+        .manage(settings)
+        .mount("/reversi/v1", routes)
+        .mount("/", root_routes)
+        .register("/", catchers![too_many_requests])
+}
+
+/// Waits for SIGTERM or Ctrl-C.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use rocket::tokio::signal::unix::{signal, SignalKind};
+        let mut term = signal(SignalKind::terminate()).expect("Cannot listen for SIGTERM");
+        rocket::tokio::select! {
+            _ = term.recv() => {}
+            _ = rocket::tokio::signal::ctrl_c() => {}
         }
     }
+    #[cfg(not(unix))]
+    {
+        let _ = rocket::tokio::signal::ctrl_c().await;
+    }
+}
+
+/// On the first signal the server reports itself as not ready for `drain`,
+/// so that load balancers stop sending requests, and then shuts down
+/// gracefully. A second signal skips the rest of the drain period.
+async fn drain_on_signal(lifecycle: Lifecycle, shutdown: rocket::Shutdown, drain: Duration) {
+    shutdown_signal().await;
+    tracing::info!(drain_secs = drain.as_secs(), "Shutdown requested, draining");
+    lifecycle.begin_draining();
+    rocket::tokio::select! {
+        _ = rocket::tokio::time::sleep(drain) => {}
+        _ = shutdown_signal() => {}
+    }
+    shutdown.notify();
 }
 
 #[rocket::main]
 async fn main() {
     dotenv::dotenv().ok(); // Optional: Load from .env file
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let settings: Settings = match Settings::load() {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Invalid settings: {}", e);
+            std::process::exit(1);
+        }
+    };
+    init_tracing(&settings.log);
+    tracing::info!(
+        settings = %serde_json::to_string(&settings.redacted()).unwrap_or_default(),
+        "Effective settings"
+    );
 
-    let port: u16 = std::env::var("PORT")
-        .ok()
-        .and_then(|val| val.parse().ok())
-        .unwrap_or(8000);
-    let figment = rocket::Config::figment().merge(("port", port));
-    env_logger::init();
+    // Signals are handled by `drain_on_signal`, Rocket only does the rest.
+    let figment = rocket::Config::figment()
+        .merge(("port", settings.port))
+        .merge(("shutdown.ctrlc", false))
+        .merge(("shutdown.signals", Vec::<String>::new()))
+        .merge(("shutdown.grace", settings.shutdown.grace_secs))
+        .merge(("shutdown.mercy", settings.shutdown.mercy_secs));
+    let drain: Duration = Duration::from_secs(settings.shutdown.drain_secs as u64);
 
-    let opts = Opts::from_url(&database_url).expect("Invalid DATABASE_URL");
+    let command: Option<String> = env::args().nth(1);
+    if let Some(command) = command {
+        let db: Database = Database::connect(&settings.database.url).expect("Invalid DATABASE_URL");
+        if let Err(e) = run_schema_command(&db, command.as_str()).await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let pool: PoolSize = PoolSize {
+        min: settings.database.min_connections,
+        max: settings.database.max_connections,
+    };
+    let db: Database =
+        Database::connect_with(&settings.database.url, pool).expect("Invalid DATABASE_URL");
+    if let Err(e) = prepare_schema(&db, settings.database.auto_migrate).await {
+        tracing::error!(error = %e, "Refusing to start");
+        std::process::exit(1);
+    }
 
-    let pool = Pool::new(opts);
+    let opening_book: OpeningBook = match &settings.opening_book {
+        Some(path) => OpeningBook::load(path).expect("Invalid OPENING_BOOK"),
+        None => OpeningBook::builtin(),
+    };
+    tracing::info!(
+        openings = opening_book.openings().len(),
+        "Loaded book openings"
+    );
 
-    rocket::custom(figment)
-        .manage(pool)
-        .mount(
-            "/reversi/v1",
-            routes![
-                get_users,
-                create_game,
-                game_list,
-                game_status,
-                game_join,
-                game_move
-            ],
-        )
-        .launch()
+    let rocket = match build_rocket(figment, settings, db.clone(), opening_book)
         .await
-        .unwrap();
+        .ignite()
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to start");
+            std::process::exit(1);
+        }
+    };
+    let lifecycle: Lifecycle = rocket.state::<Lifecycle>().unwrap().clone();
+    rocket::tokio::spawn(drain_on_signal(lifecycle, rocket.shutdown(), drain));
+
+    match rocket.launch().await {
+        Ok(_) => tracing::info!("Server stopped"),
+        Err(e) => tracing::error!(error = %e, "Server stopped before all requests finished"),
+    }
+    if let Err(e) = db.close().await {
+        tracing::error!(error = %e, "Error while closing the database");
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use dotenv::dotenv;
-    use mysql_async::{params, Conn, OptsBuilder, Pool, Row};
-    use rocket::{http::Status, local::asynchronous::Client, State};
+    use rocket::{http::Status, local::asynchronous::Client};
     use serde_json::json;
-    use std::env;
-    use tokio::runtime::Runtime;
     use uuid::Uuid;
 
+    const CREATOR: &str = "11111111-1111-4111-8111-111111111111";
+    const OPPONENT: &str = "22222222-2222-4222-8222-222222222222";
+
+    /// A server on a fresh in-memory backend that knows the two test players.
+    async fn client() -> Client {
+        client_with(Settings::default()).await
+    }
+
+    async fn client_with(settings: Settings) -> Client {
+        let db: Database =
+            Database::connect(&format!("memory://?players={},{}", CREATOR, OPPONENT))
+                .expect("Invalid DATABASE_URL");
+        Client::tracked(
+            build_rocket(
+                rocket::Config::figment(),
+                settings,
+                db,
+                OpeningBook::builtin(),
+            )
+            .await,
+        )
+        .await
+        .expect("Failed to create Rocket client")
+    }
+
+    /// Creates and joins a game, returns its id and the player who has black.
+    async fn start_game(client: &Client) -> (String, &'static str) {
+        let response = client
+            .post("/reversi/v1/create_game")
+            .json(&json!({ "player_id": CREATOR }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<NewGameResponse>().await.unwrap();
+        assert_eq!(body.status, "ok");
+        let black: &'static str = if body.result.color == "black" {
+            CREATOR
+        } else {
+            OPPONENT
+        };
+        let game_id: String = body.result.game_id;
+
+        let response = client
+            .post("/reversi/v1/join")
+            .json(&json!({ "player_id": OPPONENT, "game_id": game_id }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<GameJoinResponse>().await.unwrap();
+        assert!(body.result.result);
+        assert_ne!(body.result.color == "black", black == CREATOR);
+        (game_id, black)
+    }
+
     #[tokio::test]
     async fn test_generate_uuid() {
         let uuid = generate_uuid(); // Use the function directly, no `super` needed.
@@ -735,28 +2280,719 @@ mod tests {
         );
     }
 
+    #[tokio::test]
     async fn test_get_users() {
-        dotenv().ok();
-        let database_url = env::var("DATABASE_URL").unwrap();
-        let opts = Opts::from_url(&database_url).expect("Invalid DATABASE_URL"); // Correctly parse the URL
-        let pool = Pool::new(opts);
-
-        let client = Client::tracked(rocket::build().manage(pool).mount(
-            "/reversi/v1",
-            routes![get_users, create_game, game_list, game_status, game_join],
-        ))
-        .await
-        .expect("Failed to create Rocket client");
-
+        let client = client().await;
         let response = client.get("/reversi/v1/players").dispatch().await;
         assert_eq!(response.status(), Status::Ok);
 
         let body = response.into_json::<PlayerResponse>().await.unwrap();
         assert_eq!(body.status, "ok");
+        let players: Vec<&str> = body.result.iter().map(|u| u.player_uuid.as_str()).collect();
+        assert_eq!(players, vec![CREATOR, OPPONENT]);
+    }
+
+    #[tokio::test]
+    async fn test_players_register() {
+        let client = client().await;
+        let response = client
+            .post("/reversi/v1/players/register")
+            .json(&json!({ "comment": " Alice " }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<NewPlayerResponse>().await.unwrap();
+        assert_eq!(body.status, "ok", "{}", body.error.message);
+        assert_eq!(body.result.comment, "Alice");
+        let player: String = body.result.player_uuid;
+        assert!(Uuid::parse_str(&player).is_ok());
+
+        // The new player can play right away.
+        let response = client
+            .post("/reversi/v1/create_game")
+            .json(&json!({ "player_id": player }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<NewGameResponse>().await.unwrap();
+        assert_eq!(body.status, "ok", "{}", body.error.message);
+
+        let response = client.get("/reversi/v1/players").dispatch().await;
+        let body = response.into_json::<PlayerResponse>().await.unwrap();
+        assert!(body
+            .result
+            .iter()
+            .any(|u| u.player_uuid == player && u.comment == "Alice"));
+
+        let response = client
+            .post("/reversi/v1/players/register")
+            .json(&json!({ "comment": "x".repeat(1025) }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<NewPlayerResponse>().await.unwrap();
+        assert_eq!(body.error.code, 400);
+    }
+
+    #[tokio::test]
+    async fn test_pending_games_are_offered_to_others() {
+        let client = client().await;
+        let response = client
+            .post("/reversi/v1/create_game")
+            .json(&json!({ "player_id": CREATOR }))
+            .dispatch()
+            .await;
+        let game_id: String = response
+            .into_json::<NewGameResponse>()
+            .await
+            .unwrap()
+            .result
+            .game_id;
+
+        for (player, offered) in [(OPPONENT, true), (CREATOR, false)] {
+            let response = client
+                .post("/reversi/v1/game_list")
+                .json(&json!({ "player_id": player }))
+                .dispatch()
+                .await;
+            let body = response.into_json::<GameListResponse>().await.unwrap();
+            assert_eq!(body.result.iter().any(|g| g.game_id == game_id), offered);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_moves_are_stored() {
+        let client = client().await;
+        let (game_id, black) = start_game(&client).await;
+
+        let response = client
+            .post("/reversi/v1/move")
+            .json(&json!({ "player_id": black, "game_id": game_id, "move": "c4" }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<MoveResponse>().await.unwrap();
+        assert!(body.result.ok, "{}", body.error.message);
+
+        let response = client
+            .post("/reversi/v1/game_status")
+            .json(&json!({ "player_id": OPPONENT, "game_id": game_id }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<GameStatusResponse>().await.unwrap();
+        assert_eq!(body.result.last_move, "c4");
+
+        let response = client
+            .post("/reversi/v1/game_history")
+            .json(&json!({ "player_id": OPPONENT, "game_id": game_id }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<GameHistoryResponse>().await.unwrap();
+        assert_eq!(body.result.moves, vec!["c4"]);
+    }
+
+    #[tokio::test]
+    async fn test_moves_with_idempotency_keys() {
+        let client = client().await;
+        let (game_id, black) = start_game(&client).await;
+
+        for _ in 0..2 {
+            let response = client
+                .post("/reversi/v1/move")
+                .header(rocket::http::Header::new(IDEMPOTENCY_KEY_HEADER, "retry-1"))
+                .json(&json!({ "player_id": black, "game_id": game_id, "move": "c4" }))
+                .dispatch()
+                .await;
+            let body = response.into_json::<MoveResponse>().await.unwrap();
+            assert!(body.result.ok, "{}", body.error.message);
+            assert!(body.result.r#continue);
+        }
+        let response = client
+            .post("/reversi/v1/game_history")
+            .json(&json!({ "player_id": OPPONENT, "game_id": game_id }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<GameHistoryResponse>().await.unwrap();
+        assert_eq!(body.result.moves, vec!["c4"]);
+
+        // The key belongs to c4 now.
+        let response = client
+            .post("/reversi/v1/move")
+            .json(&json!({
+                "player_id": black,
+                "game_id": game_id,
+                "move": "d3",
+                "idempotency_key": "retry-1",
+            }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<MoveResponse>().await.unwrap();
+        assert_eq!(body.error.code, 400);
+        assert!(!body.result.ok);
+
+        // Without a key a repeated move is refused.
+        let response = client
+            .post("/reversi/v1/move")
+            .json(&json!({ "player_id": black, "game_id": game_id, "move": "c4" }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<MoveResponse>().await.unwrap();
+        assert!(!body.result.ok);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_moves_with_the_same_key() {
+        let client = client().await;
+        for _ in 0..10 {
+            let (game_id, black) = start_game(&client).await;
+            let send = || {
+                client
+                    .post("/reversi/v1/move")
+                    .header(rocket::http::Header::new(IDEMPOTENCY_KEY_HEADER, "retry-1"))
+                    .json(&json!({ "player_id": black, "game_id": game_id, "move": "c4" }))
+                    .dispatch()
+            };
+            let (first, second) = tokio::join!(send(), send());
+            let mut played: usize = 0;
+            for response in [first, second] {
+                let body = response.into_json::<MoveResponse>().await.unwrap();
+                if body.result.ok {
+                    played += 1;
+                } else {
+                    // The duplicate came while the first attempt was playing.
+                    assert_eq!(body.error.code, 409, "{}", body.error.message);
+                }
+            }
+            assert!(played >= 1);
+
+            let response = client
+                .post("/reversi/v1/game_history")
+                .json(&json!({ "player_id": OPPONENT, "game_id": game_id }))
+                .dispatch()
+                .await;
+            let body = response.into_json::<GameHistoryResponse>().await.unwrap();
+            assert_eq!(body.result.moves, vec!["c4"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_game_events_are_pushed() {
+        let client = client().await;
+        let (game_id, black) = start_game(&client).await;
+
+        let mut stream = client
+            .get(format!("/reversi/v1/games/{}/events", game_id))
+            .dispatch()
+            .await;
+        assert_eq!(stream.content_type(), Some(ContentType::EventStream));
+        let response = client
+            .post("/reversi/v1/move")
+            .json(&json!({ "player_id": black, "game_id": game_id, "move": "c4" }))
+            .dispatch()
+            .await;
         assert!(
-            body.result.is_empty() || !body.result.is_empty(),
-            "Result list should be present"
+            response
+                .into_json::<MoveResponse>()
+                .await
+                .unwrap()
+                .result
+                .ok
+        );
+
+        let mut received: Vec<u8> = Vec::new();
+        let mut events: Vec<String> = read_events(&mut stream, &mut received, 2).await;
+        let retry: String = format!("retry:{}", RECONNECT_DELAY.as_millis());
+        assert_eq!(events.remove(0), format!("{}\ndata:", retry));
+        let data: &str = events[0].strip_prefix("data:").unwrap();
+        let event: GameEvent = serde_json::from_str(data).unwrap();
+        assert_eq!(event.game_id, game_id);
+        assert_eq!(event.last_move, "c4");
+        assert_eq!(event.player_id, black);
+        assert_eq!(event.status, "white");
+
+        client.rocket().shutdown().notify();
+        let events: Vec<String> = read_events(&mut stream, &mut received, 1).await;
+        assert!(events[0].starts_with(&format!("{}\nevent:shutdown", retry)));
+    }
+
+    /// Reads the next `count` events of a stream, skipping heartbeats. Rocket
+    /// sends a heartbeat as a comment line, which may land between the lines
+    /// of an event.
+    async fn read_events(
+        stream: &mut rocket::local::asynchronous::LocalResponse<'_>,
+        received: &mut Vec<u8>,
+        count: usize,
+    ) -> Vec<String> {
+        let mut events: Vec<String> = Vec::new();
+        let mut chunk: [u8; 256] = [0; 256];
+        while events.len() < count {
+            if let Some(end) = received.windows(2).position(|w| w == b"\n\n") {
+                let event: String = String::from_utf8(received[..end].to_vec())
+                    .unwrap()
+                    .lines()
+                    .filter(|line| !line.starts_with(':'))
+                    .collect::<Vec<&str>>()
+                    .join("\n");
+                received.drain(..end + 2);
+                if !event.is_empty() {
+                    events.push(event);
+                }
+                continue;
+            }
+            let n: usize = rocket::tokio::io::AsyncReadExt::read(stream, &mut chunk)
+                .await
+                .unwrap();
+            received.extend_from_slice(&chunk[..n]);
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn test_cors_and_web_ui() {
+        let mut settings: Settings = Settings::default();
+        settings.cors.allowed_origins = vec!["https://play.example.com".to_string()];
+        let client = client_with(settings).await;
+
+        let response = client
+            .options("/reversi/v1/move")
+            .header(rocket::http::Header::new(
+                "Origin",
+                "https://play.example.com",
+            ))
+            .header(rocket::http::Header::new(
+                "Access-Control-Request-Method",
+                "POST",
+            ))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NoContent);
+        let headers = response.headers();
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Origin"),
+            Some("https://play.example.com")
+        );
+        assert!(headers
+            .get_one("Access-Control-Allow-Headers")
+            .unwrap()
+            .contains("Idempotency-Key"));
+
+        let response = client
+            .get("/reversi/v1/players")
+            .header(rocket::http::Header::new(
+                "Origin",
+                "https://evil.example.com",
+            ))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            None
+        );
+
+        let response = client.get("/").dispatch().await;
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+        assert!(response.into_string().await.unwrap().contains("/app.js"));
+        let response = client.get("/app.js").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[tokio::test]
+    async fn test_openapi_document_matches_routes() {
+        let mut settings: Settings = Settings::default();
+        settings.cors.allowed_origins = vec!["*".to_string()];
+        let client = client_with(settings).await;
+
+        let response = client.get("/reversi/v1/openapi.json").dispatch().await;
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        let document = response.into_json::<serde_json::Value>().await.unwrap();
+        assert_eq!(document["servers"][0]["url"], "/reversi/v1");
+
+        let mut documented: usize = 0;
+        for route in client.rocket().routes() {
+            if route.uri.base() != "/reversi/v1" || route.method == rocket::http::Method::Options {
+                continue;
+            }
+            let (path, _) = openapi_path(route.uri.unmounted_origin.path().as_str());
+            let operation = &document["paths"][&path][route.method.as_str().to_lowercase()];
+            assert_eq!(
+                operation["operationId"].as_str(),
+                route.name.as_deref(),
+                "{} {} is not documented",
+                route.method,
+                path
+            );
+            for name in query_params(route) {
+                assert!(operation["parameters"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .any(|p| p["name"] == name.as_str()));
+            }
+            documented += 1;
+        }
+        // Every entry of OPERATIONS belongs to a live route.
+        assert_eq!(documented, OPERATIONS.len());
+        let operations: usize = document["paths"]
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|item| item.as_object().unwrap().len())
+            .sum();
+        assert_eq!(operations, documented);
+
+        let status = &document["paths"]["/game_status"]["post"]["responses"]["200"]["content"]
+            ["application/json"]["schema"];
+        assert_eq!(status["$ref"], "#/components/schemas/GameStatusResponse");
+        let description = document["components"]["schemas"]["GameStatusResult"]["properties"]
+            ["status"]["description"]
+            .as_str()
+            .unwrap();
+        for state in STATE_NAMES {
+            assert!(description.contains(state), "{} is not documented", state);
+        }
+
+        let response = client.get("/reversi/v1/docs").dispatch().await;
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+        assert!(response
+            .into_string()
+            .await
+            .unwrap()
+            .contains("openapi.json"));
+    }
+
+    #[tokio::test]
+    async fn test_game_report_includes_the_outcome() {
+        let client = client().await;
+        let (game_id, black) = start_game(&client).await;
+        let response = client
+            .post("/reversi/v1/game_report")
+            .json(&json!({ "player_id": CREATOR, "game_id": game_id }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<GameReportResponse>().await.unwrap();
+        assert_eq!(body.result.outcome, "");
+        assert_eq!((body.result.black_discs, body.result.white_discs), (2, 2));
+
+        let response = client
+            .post("/reversi/v1/move")
+            .json(&json!({ "player_id": black, "game_id": game_id, "move": "resign" }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<MoveResponse>().await.unwrap();
+        assert!(body.result.ok, "{}", body.error.message);
+
+        let response = client
+            .post("/reversi/v1/game_report")
+            .json(&json!({ "player_id": CREATOR, "game_id": game_id }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<GameReportResponse>().await.unwrap();
+        assert_eq!(body.status, "ok");
+        assert_eq!(body.result.outcome, "white_won");
+        assert_eq!((body.result.black_discs, body.result.white_discs), (2, 2));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_games_and_players() {
+        let client = client().await;
+        let response = client
+            .post("/reversi/v1/join")
+            .json(&json!({ "player_id": OPPONENT, "game_id": generate_uuid() }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<GameJoinResponse>().await.unwrap();
+        assert_eq!(body.status, "error");
+        assert!(!body.result.result);
+
+        let response = client
+            .get(format!("/reversi/v1/players/{}/stats", generate_uuid()))
+            .dispatch()
+            .await;
+        let body = response.into_json::<PlayerStatsResponse>().await.unwrap();
+        assert_eq!(body.error.code, 404);
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let client = client().await;
+        let (game_id, black) = start_game(&client).await;
+        client
+            .post("/reversi/v1/move")
+            .json(&json!({ "player_id": black, "game_id": game_id, "move": "c4" }))
+            .dispatch()
+            .await;
+
+        let response = client.get("/metrics").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let body: String = response.into_string().await.unwrap();
+        assert!(body.contains("reversi_moves_total 1"));
+        assert!(body.contains("reversi_games{state=\"white\"} 1"));
+        assert!(body.contains(
+            "reversi_http_requests_total{method=\"POST\",route=\"/reversi/v1/move\",status=\"200\"} 1"
+        ));
+        assert!(body.contains("reversi_http_request_duration_seconds_bucket"));
+    }
+
+    #[tokio::test]
+    async fn test_request_ids_are_echoed() {
+        let client = client().await;
+        let response = client
+            .get("/reversi/v1/players")
+            .header(rocket::http::Header::new(REQUEST_ID_HEADER, "trace-42"))
+            .dispatch()
+            .await;
+        assert_eq!(
+            response.headers().get_one(REQUEST_ID_HEADER),
+            Some("trace-42")
         );
+
+        let response = client.get("/reversi/v1/players").dispatch().await;
+        let generated: &str = response.headers().get_one(REQUEST_ID_HEADER).unwrap();
+        assert!(Uuid::parse_str(generated).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_health_and_readiness() {
+        let client = client().await;
+        let response = client.get("/healthz").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client.get("/readyz").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let body = response.into_json::<ReadinessResponse>().await.unwrap();
+        assert_eq!(body.status, "ready");
+
+        client
+            .rocket()
+            .state::<Lifecycle>()
+            .unwrap()
+            .begin_draining();
+        let response = client.get("/readyz").dispatch().await;
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        let body = response.into_json::<ReadinessResponse>().await.unwrap();
+        assert!(body
+            .checks
+            .iter()
+            .any(|c| c.name == "shutdown" && !c.ok && c.message == "draining"));
+        let response = client.get("/healthz").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_features_are_not_mounted() {
+        let mut settings: Settings = Settings::default();
+        settings.features.matchmaking = false;
+        settings.features.matches = false;
+        settings.games.default_time_control = "blitz".to_string();
+        let client = client_with(settings).await;
+
+        let response = client
+            .post("/reversi/v1/matchmaking/enqueue")
+            .json(&json!({ "player_id": CREATOR }))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotFound);
+        let response = client
+            .get(format!("/reversi/v1/matches/{}", Uuid::new_v4()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotFound);
+        let response = client.get("/reversi/v1/openapi.json").dispatch().await;
+        let document = response.into_json::<serde_json::Value>().await.unwrap();
+        assert!(document["paths"]["/matches/create"].is_null());
+        assert!(document["paths"]["/create_game"]["post"].is_object());
+
+        let response = client
+            .post("/reversi/v1/create_game")
+            .json(&json!({ "player_id": CREATOR }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<NewGameResponse>().await.unwrap();
+        assert_eq!(body.status, "ok");
+        let game = client
+            .rocket()
+            .state::<Arc<dyn GameRepository + Send + Sync>>()
+            .unwrap()
+            .get_game(&body.result.game_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(game.time_control, "blitz");
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let mut settings: Settings = Settings::default();
+        settings.rate_limit.write = Limit {
+            per_second: 0.01,
+            burst: 2,
+        };
+        let client = client_with(settings).await;
+        let first: std::net::SocketAddr = "10.0.0.1:40000".parse().unwrap();
+        for _ in 0..2 {
+            let response = client
+                .post("/reversi/v1/create_game")
+                .remote(first)
+                .json(&json!({ "player_id": CREATOR }))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+        }
+        // Requests naming the player from another address have a budget of their own.
+        let response = client
+            .post("/reversi/v1/create_game")
+            .remote("10.0.0.2:40000".parse().unwrap())
+            .json(&json!({ "player_id": CREATOR }))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let response = client
+            .post("/reversi/v1/create_game")
+            .remote(first)
+            .json(&json!({ "player_id": CREATOR }))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::TooManyRequests);
+        assert_eq!(response.headers().get_one("Retry-After"), Some("100"));
+        let body = response.into_json::<RateLimitedResponse>().await.unwrap();
+        assert_eq!(body.error.code, 429);
+
+        // Reads have their own budget.
+        let response = client
+            .post("/reversi/v1/game_list")
+            .remote(first)
+            .json(&json!({ "player_id": CREATOR }))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let response = client.get("/metrics").dispatch().await;
+        let text: String = response.into_string().await.unwrap();
+        assert!(text.contains("reversi_rate_limited_total{route=\"create_game\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn test_annotation_worker_stops() {
+        let db: Database = Database::connect("memory://").unwrap();
+        let queue: AnnotationQueue = AnnotationQueue::start(Arc::from(db.game_repository()));
+        assert!(queue.is_running());
+        queue.stop(Duration::from_secs(1)).await;
+        assert!(!queue.is_running());
+    }
+
+    #[tokio::test]
+    async fn test_tournament_runs_to_the_end() {
+        let client = client().await;
+        let response = client
+            .post("/reversi/v1/tournaments/create")
+            .json(&json!({ "name": "Weekly", "format": "round_robin" }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<NewTournamentResponse>().await.unwrap();
+        assert_eq!(body.status, "ok", "{}", body.error.message);
+        let tournament_id: String = body.result.tournament_id;
+
+        for player in [CREATOR, OPPONENT, CREATOR] {
+            client
+                .post("/reversi/v1/tournaments/register")
+                .json(&json!({ "tournament_id": tournament_id, "player_id": player }))
+                .dispatch()
+                .await;
+        }
+        let response = client
+            .post("/reversi/v1/tournaments/register")
+            .json(&json!({ "tournament_id": tournament_id, "player_id": generate_uuid() }))
+            .dispatch()
+            .await;
+        let body = response
+            .into_json::<TournamentRegisterResponse>()
+            .await
+            .unwrap();
+        assert_eq!(body.error.code, 404);
+        assert!(!body.result.result);
+        let response = client
+            .post("/reversi/v1/tournaments/start")
+            .json(&json!({ "tournament_id": tournament_id }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<TournamentResponse>().await.unwrap();
+        assert_eq!(body.status, "ok", "{}", body.error.message);
+        assert_eq!(body.result.status, "running");
+        assert_eq!(body.result.players.len(), 2);
+        assert_eq!(body.result.pairings.len(), 1);
+        let pairing: &TournamentPairingInfo = &body.result.pairings[0];
+        assert_eq!(pairing.result, "playing");
+
+        // Finishing the only game finishes the tournament.
+        let response = client
+            .post("/reversi/v1/move")
+            .json(&json!({ "player_id": pairing.black, "game_id": pairing.game_id, "move": "resign" }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<MoveResponse>().await.unwrap();
+        assert!(body.result.ok, "{}", body.error.message);
+
+        let response = client
+            .get(format!("/reversi/v1/tournaments/{}", tournament_id))
+            .dispatch()
+            .await;
+        let body = response.into_json::<TournamentResponse>().await.unwrap();
+        assert_eq!(body.result.status, "finished");
+        assert_eq!(body.result.pairings[0].result, "white_won");
+        let winner: String = body.result.pairings[0].white.clone();
+
+        let response = client
+            .get(format!(
+                "/reversi/v1/tournaments/{}/standings",
+                tournament_id
+            ))
+            .dispatch()
+            .await;
+        let body = response.into_json::<StandingsResponse>().await.unwrap();
+        assert_eq!(body.result[0].player_id, winner);
+        assert_eq!(body.result[0].wins, 1);
+    }
+
+    #[tokio::test]
+    async fn test_match_games_start_from_the_opening() {
+        let client = client().await;
+        let response = client
+            .post("/reversi/v1/matches/create")
+            .json(&json!({
+                "player_a": CREATOR,
+                "player_b": OPPONENT,
+                "games": 2,
+                "openings": ["f5d6c3"],
+            }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<MatchResponse>().await.unwrap();
+        assert_eq!(body.status, "ok", "{}", body.error.message);
+        assert_eq!(body.result.games.len(), 2);
+        assert!(body.result.games.iter().all(|g| g.opening == "f5d6c3"));
+        assert_eq!(body.result.status, "playing");
+        let match_id: String = body.result.match_id;
+        let game: &MatchGameInfo = &body.result.games[0];
+        assert_ne!(game.black, body.result.games[1].black);
+
+        // The opening moves are stored and white is to move.
+        let response = client
+            .post("/reversi/v1/game_history")
+            .json(&json!({ "player_id": CREATOR, "game_id": game.game_id }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<GameHistoryResponse>().await.unwrap();
+        assert_eq!(body.result.moves, vec!["f5", "d6", "c3"]);
+        let response = client
+            .post("/reversi/v1/move")
+            .json(&json!({ "player_id": game.white, "game_id": game.game_id, "move": "resign" }))
+            .dispatch()
+            .await;
+        let body = response.into_json::<MoveResponse>().await.unwrap();
+        assert!(body.result.ok, "{}", body.error.message);
+
+        let response = client
+            .get(format!("/reversi/v1/matches/{}", match_id))
+            .dispatch()
+            .await;
+        let body = response.into_json::<MatchResponse>().await.unwrap();
+        assert_eq!(body.result.games_finished, 1);
+        assert_eq!(body.result.games[0].result, "black_won");
     }
-    // TODO(8): add more unit tests.
 }
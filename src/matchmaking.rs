@@ -0,0 +1,435 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchmakingConfig {
+    /// Maximum rating difference accepted right after enqueueing.
+    pub base_window: u32,
+    /// How much the accepted rating difference grows per second of waiting.
+    pub widen_per_second: u32,
+    /// Upper bound for the accepted rating difference.
+    pub max_window: u32,
+    /// Seconds a waiting player stays in the queue without polling again.
+    pub waiting_ttl_secs: u64,
+    /// Seconds a created game is kept for a paired player who has not polled again.
+    pub matched_ttl_secs: u64,
+}
+
+impl Default for MatchmakingConfig {
+    fn default() -> Self {
+        MatchmakingConfig {
+            base_window: 100,
+            widen_per_second: 10,
+            max_window: 800,
+            waiting_ttl_secs: 30,
+            matched_ttl_secs: 600,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueEntry {
+    pub player_uuid: String,
+    pub rating: u32,
+    /// Preferred time control, an empty string means "any".
+    pub time_control: String,
+    pub enqueued_at: Instant,
+    /// Last enqueue of the player, entries not polled within the waiting TTL
+    /// are dropped.
+    pub polled_at: Instant,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchedGame {
+    pub game_uuid: String,
+    pub color: String,
+}
+
+#[derive(Debug)]
+pub enum EnqueueOutcome {
+    /// No suitable opponent yet, the player stays in the queue.
+    Waiting,
+    /// An earlier enqueue of the player has already been paired and the game created.
+    Matched(MatchedGame),
+    /// The player has been paired with `opponent`; both are removed from the queue
+    /// and reserved until the caller creates the game and reports it via `complete_match`,
+    /// or puts them back with `requeue`.
+    Paired {
+        player: QueueEntry,
+        opponent: QueueEntry,
+    },
+}
+
+struct QueueState {
+    waiting: Vec<QueueEntry>,
+    /// Games created for paired players, with the time they were recorded.
+    matched: HashMap<String, (MatchedGame, Instant)>,
+    /// Paired players whose game is still being created, with their opponent and
+    /// the time they were paired. Their polls are answered with `Waiting`.
+    reserved: HashMap<String, (String, Instant)>,
+}
+
+pub struct MatchmakingQueue {
+    config: MatchmakingConfig,
+    state: Mutex<QueueState>,
+}
+
+impl MatchmakingQueue {
+    pub fn new(config: MatchmakingConfig) -> Self {
+        MatchmakingQueue {
+            config,
+            state: Mutex::new(QueueState {
+                waiting: Vec::new(),
+                matched: HashMap::new(),
+                reserved: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn enqueue(&self, player_uuid: &str, rating: u32, time_control: &str) -> EnqueueOutcome {
+        self.enqueue_at(player_uuid, rating, time_control, Instant::now())
+    }
+
+    fn enqueue_at(
+        &self,
+        player_uuid: &str,
+        rating: u32,
+        time_control: &str,
+        now: Instant,
+    ) -> EnqueueOutcome {
+        let mut state = self.state.lock().unwrap();
+        self.expire(&mut state, now);
+        if let Some((game, _)) = state.matched.remove(player_uuid) {
+            return EnqueueOutcome::Matched(game);
+        }
+        if state.reserved.contains_key(player_uuid) {
+            return EnqueueOutcome::Waiting;
+        }
+
+        // Re-enqueueing keeps the original timestamp so that polling does not reset
+        // the widening of the rating window.
+        let entry: QueueEntry = match state
+            .waiting
+            .iter()
+            .position(|e| e.player_uuid == player_uuid)
+        {
+            Some(idx) => {
+                let mut e = state.waiting.remove(idx);
+                e.rating = rating;
+                e.time_control = time_control.to_string();
+                e.polled_at = now;
+                e
+            }
+            None => QueueEntry {
+                player_uuid: player_uuid.to_string(),
+                rating,
+                time_control: time_control.to_string(),
+                enqueued_at: now,
+                polled_at: now,
+            },
+        };
+
+        let mut best: Option<(usize, u32)> = None;
+        for (idx, candidate) in state.waiting.iter().enumerate() {
+            if !time_controls_compatible(&entry.time_control, &candidate.time_control) {
+                continue;
+            }
+            let diff = entry.rating.abs_diff(candidate.rating);
+            let window = self.window(&entry, now).max(self.window(candidate, now));
+            if diff > window {
+                continue;
+            }
+            // The queue is kept in arrival order, so on equal distance the player
+            // who has been waiting the longest wins.
+            match best {
+                Some((_, best_diff)) if best_diff <= diff => {}
+                _ => best = Some((idx, diff)),
+            }
+        }
+
+        match best {
+            Some((idx, _)) => {
+                let opponent = state.waiting.remove(idx);
+                state.reserved.insert(
+                    entry.player_uuid.clone(),
+                    (opponent.player_uuid.clone(), now),
+                );
+                state.reserved.insert(
+                    opponent.player_uuid.clone(),
+                    (entry.player_uuid.clone(), now),
+                );
+                EnqueueOutcome::Paired {
+                    player: entry,
+                    opponent,
+                }
+            }
+            None => {
+                state.waiting.push(entry);
+                EnqueueOutcome::Waiting
+            }
+        }
+    }
+
+    /// Records the game created for a paired player, it is handed out on their next enqueue.
+    /// Releases the reservation of the player and their opponent.
+    /// Games not picked up within `matched_ttl_secs` are dropped.
+    pub fn complete_match(&self, player_uuid: &str, game: MatchedGame) {
+        self.complete_match_at(player_uuid, game, Instant::now())
+    }
+
+    fn complete_match_at(&self, player_uuid: &str, game: MatchedGame, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        self.expire(&mut state, now);
+        release(&mut state, player_uuid);
+        state.matched.insert(player_uuid.to_string(), (game, now));
+    }
+
+    /// Puts a paired entry back into the queue, e.g. when the game could not be created.
+    pub fn requeue(&self, entry: QueueEntry) {
+        let mut state = self.state.lock().unwrap();
+        release(&mut state, entry.player_uuid.as_str());
+        if !state
+            .waiting
+            .iter()
+            .any(|e| e.player_uuid == entry.player_uuid)
+        {
+            state.waiting.push(entry);
+            state.waiting.sort_by_key(|e| e.enqueued_at);
+        }
+    }
+
+    /// Removes the player from the queue, returns false if they were not waiting.
+    pub fn dequeue(&self, player_uuid: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let before = state.waiting.len();
+        state.waiting.retain(|e| e.player_uuid != player_uuid);
+        before != state.waiting.len()
+    }
+
+    /// Drops the players who stopped polling, the games nobody picked up and the
+    /// reservations of pairings that were never completed.
+    fn expire(&self, state: &mut QueueState, now: Instant) {
+        let waiting_ttl: Duration = Duration::from_secs(self.config.waiting_ttl_secs);
+        let matched_ttl: Duration = Duration::from_secs(self.config.matched_ttl_secs);
+        state
+            .waiting
+            .retain(|e| now.saturating_duration_since(e.polled_at) < waiting_ttl);
+        state
+            .matched
+            .retain(|_, (_, matched_at)| now.saturating_duration_since(*matched_at) < matched_ttl);
+        state
+            .reserved
+            .retain(|_, (_, paired_at)| now.saturating_duration_since(*paired_at) < waiting_ttl);
+    }
+
+    fn window(&self, entry: &QueueEntry, now: Instant) -> u32 {
+        let waited = now.saturating_duration_since(entry.enqueued_at).as_secs();
+        let widened = (waited as u32).saturating_mul(self.config.widen_per_second);
+        self.config
+            .base_window
+            .saturating_add(widened)
+            .min(self.config.max_window)
+    }
+}
+
+/// Removes the reservation of the player and of the opponent they were paired with.
+fn release(state: &mut QueueState, player_uuid: &str) {
+    if let Some((opponent, _)) = state.reserved.remove(player_uuid) {
+        state.reserved.remove(&opponent);
+    }
+}
+
+pub fn time_controls_compatible(a: &str, b: &str) -> bool {
+    a.is_empty() || b.is_empty() || a == b
+}
+
+/// Time control for a game between two compatible preferences.
+pub fn agreed_time_control(a: &str, b: &str) -> String {
+    if a.is_empty() {
+        b.to_string()
+    } else {
+        a.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue() -> MatchmakingQueue {
+        MatchmakingQueue::new(MatchmakingConfig::default())
+    }
+
+    #[test]
+    fn pairs_players_within_window() {
+        let q = queue();
+        let now = Instant::now();
+        assert!(matches!(
+            q.enqueue_at("a", 1500, "", now),
+            EnqueueOutcome::Waiting
+        ));
+        match q.enqueue_at("b", 1550, "", now) {
+            EnqueueOutcome::Paired { player, opponent } => {
+                assert_eq!(player.player_uuid, "b");
+                assert_eq!(opponent.player_uuid, "a");
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        assert!(!q.dequeue("a"));
+        assert!(!q.dequeue("b"));
+    }
+
+    #[test]
+    fn window_widens_with_waiting_time() {
+        let q = queue();
+        let now = Instant::now();
+        q.enqueue_at("a", 1500, "", now);
+        assert!(matches!(
+            q.enqueue_at("b", 1800, "", now),
+            EnqueueOutcome::Waiting
+        ));
+        let later = now + Duration::from_secs(25);
+        assert!(matches!(
+            q.enqueue_at("b", 1800, "", later),
+            EnqueueOutcome::Paired { .. }
+        ));
+    }
+
+    #[test]
+    fn prefers_closest_rating() {
+        let q = queue();
+        let now = Instant::now();
+        q.enqueue_at("far", 1420, "", now);
+        q.enqueue_at("near", 1600, "", now);
+        match q.enqueue_at("me", 1560, "", now) {
+            EnqueueOutcome::Paired { opponent, .. } => assert_eq!(opponent.player_uuid, "near"),
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn respects_time_control_preference() {
+        let q = queue();
+        let now = Instant::now();
+        q.enqueue_at("a", 1500, "blitz", now);
+        assert!(matches!(
+            q.enqueue_at("b", 1500, "rapid", now),
+            EnqueueOutcome::Waiting
+        ));
+        match q.enqueue_at("c", 1500, "", now) {
+            EnqueueOutcome::Paired { player, opponent } => {
+                assert_eq!(opponent.player_uuid, "a");
+                assert_eq!(
+                    agreed_time_control(&player.time_control, &opponent.time_control),
+                    "blitz"
+                );
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn matched_game_is_handed_out_once() {
+        let q = queue();
+        let game = MatchedGame {
+            game_uuid: "g".to_string(),
+            color: "white".to_string(),
+        };
+        q.complete_match("a", game.clone());
+        match q.enqueue("a", 1500, "") {
+            EnqueueOutcome::Matched(g) => assert_eq!(g, game),
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        assert!(matches!(q.enqueue("a", 1500, ""), EnqueueOutcome::Waiting));
+        assert!(q.dequeue("a"));
+        assert!(!q.dequeue("a"));
+    }
+
+    #[test]
+    fn unclaimed_matched_game_expires() {
+        let q = queue();
+        let now = Instant::now();
+        let game = MatchedGame {
+            game_uuid: "g".to_string(),
+            color: "black".to_string(),
+        };
+        q.complete_match_at("a", game.clone(), now);
+        q.complete_match_at("b", game, now);
+        assert!(matches!(
+            q.enqueue_at("b", 1500, "", now + Duration::from_secs(60)),
+            EnqueueOutcome::Matched(_)
+        ));
+        assert!(matches!(
+            q.enqueue_at("a", 1500, "", now + Duration::from_secs(600)),
+            EnqueueOutcome::Waiting
+        ));
+        assert!(q.state.lock().unwrap().matched.is_empty());
+    }
+
+    #[test]
+    fn paired_players_wait_until_the_game_is_recorded() {
+        let q = queue();
+        let now = Instant::now();
+        q.enqueue_at("a", 1500, "", now);
+        assert!(matches!(
+            q.enqueue_at("b", 1500, "", now),
+            EnqueueOutcome::Paired { .. }
+        ));
+        // "a" polls while the game is being created.
+        assert!(matches!(
+            q.enqueue_at("a", 1500, "", now),
+            EnqueueOutcome::Waiting
+        ));
+        assert!(!q.dequeue("a"));
+        let game = MatchedGame {
+            game_uuid: "g".to_string(),
+            color: "black".to_string(),
+        };
+        q.complete_match_at("a", game.clone(), now);
+        match q.enqueue_at("a", 1500, "", now) {
+            EnqueueOutcome::Matched(g) => assert_eq!(g, game),
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        assert!(q.state.lock().unwrap().reserved.is_empty());
+    }
+
+    #[test]
+    fn requeued_players_are_released() {
+        let q = queue();
+        let now = Instant::now();
+        q.enqueue_at("a", 1500, "", now);
+        let (player, opponent) = match q.enqueue_at("b", 1500, "", now) {
+            EnqueueOutcome::Paired { player, opponent } => (player, opponent),
+            other => panic!("unexpected outcome: {:?}", other),
+        };
+        q.requeue(opponent);
+        q.requeue(player);
+        assert!(q.state.lock().unwrap().reserved.is_empty());
+        assert!(matches!(
+            q.enqueue_at("a", 1500, "", now),
+            EnqueueOutcome::Paired { .. }
+        ));
+    }
+
+    #[test]
+    fn players_who_stop_polling_leave_the_queue() {
+        let q = queue();
+        let now = Instant::now();
+        q.enqueue_at("gone", 1500, "", now);
+        q.enqueue_at("polling", 1850, "", now);
+        assert!(matches!(
+            q.enqueue_at("polling", 1850, "", now + Duration::from_secs(20)),
+            EnqueueOutcome::Waiting
+        ));
+        // By now the windows would pair them, but "gone" has not polled for 30 seconds.
+        assert!(matches!(
+            q.enqueue_at("polling", 1850, "", now + Duration::from_secs(40)),
+            EnqueueOutcome::Waiting
+        ));
+        assert!(!q.dequeue("gone"));
+        assert!(q.dequeue("polling"));
+    }
+}
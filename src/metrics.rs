@@ -0,0 +1,209 @@
+//! Prometheus metrics of the server. Requests are measured by `MetricsFairing`,
+//! repository calls by the metered repositories, and game events by the
+//! handlers that cause them.
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Request, Response};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::repository::db_errors::RepositoryError;
+
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_request_duration: HistogramVec,
+    pub games: IntGaugeVec,
+    pub moves: IntCounter,
+    pub games_finished: IntCounterVec,
+    pub db_pool_connections: IntGaugeVec,
+    pub repository_calls_in_flight: IntGauge,
+    pub repository_errors: IntCounterVec,
+    pub rate_limited: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let registry: Registry = Registry::new();
+        let metrics: Metrics = Metrics {
+            http_requests: IntCounterVec::new(
+                Opts::new("reversi_http_requests_total", "HTTP requests by route"),
+                &["route", "method", "status"],
+            )
+            .unwrap(),
+            http_request_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "reversi_http_request_duration_seconds",
+                    "HTTP request latency by route",
+                ),
+                &["route", "method"],
+            )
+            .unwrap(),
+            games: IntGaugeVec::new(
+                Opts::new("reversi_games", "Games by state, updated when scraped"),
+                &["state"],
+            )
+            .unwrap(),
+            moves: IntCounter::new("reversi_moves_total", "Moves and passes played").unwrap(),
+            games_finished: IntCounterVec::new(
+                Opts::new("reversi_games_finished_total", "Games finished by result"),
+                &["result"],
+            )
+            .unwrap(),
+            db_pool_connections: IntGaugeVec::new(
+                Opts::new(
+                    "reversi_db_pool_connections",
+                    "Database pool connections, for backends that report them",
+                ),
+                &["state"],
+            )
+            .unwrap(),
+            repository_calls_in_flight: IntGauge::new(
+                "reversi_repository_calls_in_flight",
+                "Repository calls waiting for the database",
+            )
+            .unwrap(),
+            repository_errors: IntCounterVec::new(
+                Opts::new(
+                    "reversi_repository_errors_total",
+                    "Failed repository calls by error",
+                ),
+                &["error"],
+            )
+            .unwrap(),
+            rate_limited: IntCounterVec::new(
+                Opts::new(
+                    "reversi_rate_limited_total",
+                    "Requests rejected by the rate limiter by route",
+                ),
+                &["route"],
+            )
+            .unwrap(),
+            registry,
+        };
+        metrics
+            .registry
+            .register(Box::new(metrics.http_requests.clone()))
+            .unwrap();
+        metrics
+            .registry
+            .register(Box::new(metrics.http_request_duration.clone()))
+            .unwrap();
+        metrics
+            .registry
+            .register(Box::new(metrics.games.clone()))
+            .unwrap();
+        metrics
+            .registry
+            .register(Box::new(metrics.moves.clone()))
+            .unwrap();
+        metrics
+            .registry
+            .register(Box::new(metrics.games_finished.clone()))
+            .unwrap();
+        metrics
+            .registry
+            .register(Box::new(metrics.db_pool_connections.clone()))
+            .unwrap();
+        metrics
+            .registry
+            .register(Box::new(metrics.repository_calls_in_flight.clone()))
+            .unwrap();
+        metrics
+            .registry
+            .register(Box::new(metrics.repository_errors.clone()))
+            .unwrap();
+        metrics
+            .registry
+            .register(Box::new(metrics.rate_limited.clone()))
+            .unwrap();
+        metrics
+    }
+
+    pub fn record_repository_error(&self, error: &RepositoryError) {
+        let variant: &str = match error {
+            RepositoryError::DatabaseError(_) => "DatabaseError",
+            RepositoryError::Unsupported(_) => "Unsupported",
+            RepositoryError::SchemaError(_) => "SchemaError",
+            RepositoryError::Other(_) => "Other",
+        };
+        self.repository_errors.with_label_values(&[variant]).inc();
+    }
+
+    /// All metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer: Vec<u8> = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+/// When the request came in, kept in the request's local cache. Shared with
+/// the request log of `RequestIdFairing`.
+pub(crate) struct RequestStart(pub(crate) Instant);
+
+/// Counts requests and measures their latency per route. Routes are labelled
+/// with their URI pattern, so ids in paths do not create new series.
+pub struct MetricsFairing {
+    pub metrics: Arc<Metrics>,
+}
+
+#[rocket::async_trait]
+impl Fairing for MetricsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Prometheus metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let start: &RequestStart = request.local_cache(|| RequestStart(Instant::now()));
+        let route: String = match request.route() {
+            Some(r) => r.uri.path().to_string(),
+            None => "unmatched".to_string(),
+        };
+        let method: String = request.method().to_string();
+        let status: String = response.status().code.to_string();
+        self.metrics
+            .http_requests
+            .with_label_values(&[route.as_str(), method.as_str(), status.as_str()])
+            .inc();
+        self.metrics
+            .http_request_duration
+            .with_label_values(&[route.as_str(), method.as_str()])
+            .observe(start.0.elapsed().as_secs_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_text_format() {
+        let metrics = Metrics::new();
+        metrics.moves.inc();
+        metrics.record_repository_error(&RepositoryError::DatabaseError("gone".to_string()));
+        let text: String = metrics.render();
+        assert!(text.contains("# TYPE reversi_moves_total counter"));
+        assert!(text.contains("reversi_moves_total 1"));
+        assert!(text.contains("reversi_repository_errors_total{error=\"DatabaseError\"} 1"));
+    }
+}
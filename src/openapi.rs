@@ -0,0 +1,461 @@
+//! OpenAPI 3 description of the API, served at `/reversi/v1/openapi.json`.
+//! The schemas are derived from the model types and the paths from the routes
+//! that are actually mounted, so features that are turned off are left out.
+//! Every handler below `/reversi/v1` needs an entry in `OPERATIONS`; the tests
+//! check the document against the live routes.
+
+use rocket::http::Method;
+use rocket::Route;
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{InstanceType, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+
+use crate::model::*;
+
+/// Adds the schema of a type to the generator and returns a reference to it.
+pub type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+fn schema<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    gen.subschema_for::<T>()
+}
+
+/// Any JSON object.
+fn object(_: &mut SchemaGenerator) -> Schema {
+    Schema::Object(SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        ..SchemaObject::default()
+    })
+}
+
+/// The document of the mounted routes, rendered once at startup.
+pub struct ApiDocument {
+    pub json: String,
+}
+
+/// What a route responds with.
+pub enum Body {
+    Json(SchemaFn),
+    /// Server-Sent Events with JSON data of the given schema.
+    EventStream(SchemaFn),
+    Html,
+}
+
+/// Documentation of one handler.
+pub struct Operation {
+    /// Name of the handler function.
+    pub handler: &'static str,
+    pub summary: &'static str,
+    pub tag: &'static str,
+    pub request: Option<SchemaFn>,
+    pub response: Body,
+    /// Query parameters in the order of the route, all of them optional.
+    pub query: &'static [(&'static str, SchemaFn)],
+}
+
+pub const OPERATIONS: &[Operation] = &[
+    Operation {
+        handler: "get_users",
+        summary: "List the players",
+        tag: "players",
+        request: None,
+        response: Body::Json(schema::<PlayerResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "register_player",
+        summary: "Register a player and get their id",
+        tag: "players",
+        request: Some(schema::<NewPlayerRequest>),
+        response: Body::Json(schema::<NewPlayerResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "create_game",
+        summary: "Create a game and take a random color",
+        tag: "games",
+        request: Some(schema::<NewGameRequest>),
+        response: Body::Json(schema::<NewGameResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "game_list",
+        summary: "List the games waiting for an opponent",
+        tag: "games",
+        request: Some(schema::<NewGameRequest>),
+        response: Body::Json(schema::<GameListResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "game_status",
+        summary: "Get the state and the last move of a game",
+        tag: "games",
+        request: Some(schema::<GameRequest>),
+        response: Body::Json(schema::<GameStatusResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "game_history",
+        summary: "Get all moves of a game",
+        tag: "games",
+        request: Some(schema::<GameRequest>),
+        response: Body::Json(schema::<GameHistoryResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "analyze_game",
+        summary: "Evaluate the positions of a game with the engine",
+        tag: "analysis",
+        request: Some(schema::<AnalyzeRequest>),
+        response: Body::Json(schema::<AnalyzeResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "game_report",
+        summary: "Get the move labels and accuracy of a finished game",
+        tag: "analysis",
+        request: Some(schema::<GameRequest>),
+        response: Body::Json(schema::<GameReportResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "game_join",
+        summary: "Join a game as the second player",
+        tag: "games",
+        request: Some(schema::<GameRequest>),
+        response: Body::Json(schema::<GameJoinResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "game_move",
+        summary: "Play a move, pass or resign; send an Idempotency-Key header to retry safely",
+        tag: "games",
+        request: Some(schema::<MoveRequest>),
+        response: Body::Json(schema::<MoveResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "game_events",
+        summary: "Stream the joins and moves of a game",
+        tag: "games",
+        request: None,
+        response: Body::EventStream(schema::<GameEvent>),
+        query: &[],
+    },
+    Operation {
+        handler: "leaderboard",
+        summary: "Rank the players by rating",
+        tag: "players",
+        request: None,
+        response: Body::Json(schema::<LeaderboardResponse>),
+        query: &[
+            ("page", schema::<u64>),
+            ("per_page", schema::<u64>),
+            ("time_control", schema::<String>),
+        ],
+    },
+    Operation {
+        handler: "player_stats",
+        summary: "Get the results of a player by color",
+        tag: "players",
+        request: None,
+        response: Body::Json(schema::<PlayerStatsResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "matchmaking_enqueue",
+        summary: "Wait for an opponent or get the game the player was paired into",
+        tag: "matchmaking",
+        request: Some(schema::<MatchmakingRequest>),
+        response: Body::Json(schema::<MatchmakingResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "matchmaking_dequeue",
+        summary: "Leave the matchmaking queue",
+        tag: "matchmaking",
+        request: Some(schema::<NewGameRequest>),
+        response: Body::Json(schema::<DequeueResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "tournament_create",
+        summary: "Create a tournament open for registration",
+        tag: "tournaments",
+        request: Some(schema::<NewTournamentRequest>),
+        response: Body::Json(schema::<NewTournamentResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "tournament_register",
+        summary: "Register a player for a tournament",
+        tag: "tournaments",
+        request: Some(schema::<TournamentRegisterRequest>),
+        response: Body::Json(schema::<TournamentRegisterResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "tournament_start",
+        summary: "Close registration and pair the first round",
+        tag: "tournaments",
+        request: Some(schema::<TournamentStartRequest>),
+        response: Body::Json(schema::<TournamentResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "tournament_details",
+        summary: "Get a tournament with its players and pairings",
+        tag: "tournaments",
+        request: None,
+        response: Body::Json(schema::<TournamentResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "tournament_standings",
+        summary: "Get the standings of a tournament",
+        tag: "tournaments",
+        request: None,
+        response: Body::Json(schema::<StandingsResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "tournament_bracket_details",
+        summary: "Get the bracket of an elimination tournament",
+        tag: "tournaments",
+        request: None,
+        response: Body::Json(schema::<BracketResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "match_create",
+        summary: "Create all games of a match between two players",
+        tag: "matches",
+        request: Some(schema::<NewMatchRequest>),
+        response: Body::Json(schema::<MatchResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "match_details",
+        summary: "Get the games and the score of a match",
+        tag: "matches",
+        request: None,
+        response: Body::Json(schema::<MatchResponse>),
+        query: &[],
+    },
+    Operation {
+        handler: "openapi_document",
+        summary: "This document",
+        tag: "docs",
+        request: None,
+        response: Body::Json(object),
+        query: &[],
+    },
+    Operation {
+        handler: "swagger_ui",
+        summary: "Swagger UI for this document",
+        tag: "docs",
+        request: None,
+        response: Body::Html,
+        query: &[],
+    },
+];
+
+pub fn operation(handler: &str) -> Option<&'static Operation> {
+    OPERATIONS.iter().find(|o| o.handler == handler)
+}
+
+/// Converts a Rocket path such as `/players/<id>/stats` to the OpenAPI form
+/// `/players/{id}/stats` and returns it with the names of its parameters.
+pub fn openapi_path(path: &str) -> (String, Vec<String>) {
+    let mut params: Vec<String> = Vec::new();
+    let segments: Vec<String> = path
+        .split('/')
+        .map(
+            |segment| match segment.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+                Some(name) => {
+                    let name: &str = name.trim_end_matches("..");
+                    params.push(name.to_string());
+                    format!("{{{}}}", name)
+                }
+                None => segment.to_string(),
+            },
+        )
+        .collect();
+    (segments.join("/"), params)
+}
+
+/// Names of the dynamic query parameters of a route, e.g. `page` for
+/// `?<page>&<per_page>`.
+pub fn query_params(route: &Route) -> Vec<String> {
+    match route.uri.unmounted_origin.query() {
+        Some(query) => query
+            .as_str()
+            .split('&')
+            .filter_map(|p| p.strip_prefix('<').and_then(|p| p.strip_suffix('>')))
+            .map(|p| p.to_string())
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+fn to_value(schema: Schema) -> Value {
+    serde_json::to_value(schema).unwrap_or(Value::Null)
+}
+
+fn json_content(schema: Value) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+/// Builds the document for the routes mounted at `base`. CORS preflight
+/// routes are not operations and routes without an entry in `OPERATIONS` are
+/// skipped.
+pub fn build_document(base: &str, routes: &[Route]) -> Value {
+    let mut gen: SchemaGenerator = SchemaSettings::openapi3().into_generator();
+    let rate_limited: Value = to_value(schema::<RateLimitedResponse>(&mut gen));
+    let mut paths: Map<String, Value> = Map::new();
+
+    for route in routes {
+        if route.method == Method::Options {
+            continue;
+        }
+        let op: &Operation = match route.name.as_deref().and_then(operation) {
+            Some(op) => op,
+            None => continue,
+        };
+        let (path, path_params) = openapi_path(route.uri.unmounted_origin.path().as_str());
+
+        let mut parameters: Vec<Value> = Vec::new();
+        for name in path_params {
+            parameters.push(json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": { "type": "string" },
+            }));
+        }
+        for name in query_params(route) {
+            let schema: Value = match op.query.iter().find(|(n, _)| *n == name) {
+                Some((_, f)) => to_value(f(&mut gen)),
+                None => json!({ "type": "string" }),
+            };
+            parameters.push(json!({
+                "name": name,
+                "in": "query",
+                "required": false,
+                "schema": schema,
+            }));
+        }
+
+        let ok: Value = match op.response {
+            Body::Json(f) => json!({
+                "description": "The result, or an error with `status` set to \"error\"",
+                "content": json_content(to_value(f(&mut gen))),
+            }),
+            Body::EventStream(f) => json!({
+                "description": "Server-Sent Events whose data is the JSON schema below",
+                "content": { "text/event-stream": { "schema": to_value(f(&mut gen)) } },
+            }),
+            Body::Html => json!({
+                "description": "HTML page",
+                "content": { "text/html": { "schema": { "type": "string" } } },
+            }),
+        };
+        let mut operation: Map<String, Value> = Map::new();
+        operation.insert("operationId".to_string(), json!(op.handler));
+        operation.insert("summary".to_string(), json!(op.summary));
+        operation.insert("tags".to_string(), json!([op.tag]));
+        if !parameters.is_empty() {
+            operation.insert("parameters".to_string(), Value::Array(parameters));
+        }
+        if let Some(f) = op.request {
+            operation.insert(
+                "requestBody".to_string(),
+                json!({ "required": true, "content": json_content(to_value(f(&mut gen))) }),
+            );
+        }
+        operation.insert(
+            "responses".to_string(),
+            json!({
+                "200": ok,
+                "429": {
+                    "description": "Rate limited",
+                    "headers": {
+                        "Retry-After": {
+                            "description": "Seconds to wait before retrying",
+                            "schema": { "type": "integer" },
+                        },
+                    },
+                    "content": json_content(rate_limited.clone()),
+                },
+            }),
+        );
+
+        let item: &mut Value = paths
+            .entry(path)
+            .or_insert_with(|| Value::Object(Map::new()));
+        item[route.method.as_str().to_lowercase()] = Value::Object(operation);
+    }
+
+    let schemas: Map<String, Value> = gen
+        .take_definitions()
+        .into_iter()
+        .map(|(name, schema)| (name, to_value(schema)))
+        .collect();
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Reversi API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "url": base }],
+        "paths": paths,
+        "components": { "schemas": schemas },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(method: Method, uri: &str, name: &'static str) -> Route {
+        let mut route: Route = Route::new(method, uri, rocket::route::dummy_handler);
+        route.name = Some(name.into());
+        route
+    }
+
+    #[test]
+    fn converts_paths() {
+        assert_eq!(
+            openapi_path("/players/<id>/stats"),
+            ("/players/{id}/stats".to_string(), vec!["id".to_string()])
+        );
+        assert_eq!(openapi_path("/move"), ("/move".to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn documents_routes_with_model_schemas() {
+        let routes: Vec<Route> = vec![
+            route(Method::Post, "/move", "game_move"),
+            route(Method::Get, "/leaderboard?<page>&<per_page>", "leaderboard"),
+            route(Method::Options, "/<_..>", "preflight"),
+            route(Method::Get, "/undocumented", "undocumented"),
+        ];
+        let doc: Value = build_document("/reversi/v1", &routes);
+
+        let paths: &Map<String, Value> = doc["paths"].as_object().unwrap();
+        assert_eq!(paths.len(), 2);
+        let body: &Value =
+            &doc["paths"]["/move"]["post"]["requestBody"]["content"]["application/json"]["schema"];
+        assert_eq!(body["$ref"], "#/components/schemas/MoveRequest");
+        let request: &Value = &doc["components"]["schemas"]["MoveRequest"];
+        assert!(request["properties"]["move"].is_object());
+        assert_eq!(request["required"], json!(["game_id", "move", "player_id"]));
+
+        let params: &Vec<Value> = doc["paths"]["/leaderboard"]["get"]["parameters"]
+            .as_array()
+            .unwrap();
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0]["name"], "page");
+        assert_eq!(params[0]["schema"]["type"], "integer");
+    }
+}
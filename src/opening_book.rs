@@ -0,0 +1,184 @@
+use reversi_tools::position::*;
+use std::fs;
+
+use crate::game_match::parse_opening;
+
+const BUILTIN_BOOK: &str = include_str!("../data/openings.txt");
+
+#[derive(Debug, thiserror::Error)]
+pub enum OpeningBookError {
+    #[error("Cannot read opening book {0}: {1}")]
+    Io(String, String),
+    #[error("Invalid opening book line {0}: {1}")]
+    InvalidLine(usize, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookOpening {
+    pub name: String,
+    /// Moves from black's f5 start.
+    pub moves: Vec<String>,
+}
+
+/// Named openings, loaded from a text file with one opening per line: the moves
+/// followed by the name, e.g. "f5d6c3d3c4 Tiger". Empty lines and lines starting
+/// with '#' are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct OpeningBook {
+    openings: Vec<BookOpening>,
+}
+
+impl OpeningBook {
+    pub fn parse(text: &str) -> Result<OpeningBook, OpeningBookError> {
+        let mut openings: Vec<BookOpening> = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (moves, name) = match line.split_once(char::is_whitespace) {
+                Some((moves, name)) => (moves, name.trim()),
+                None => return Err(OpeningBookError::InvalidLine(idx + 1, line.to_string())),
+            };
+            match parse_opening(moves) {
+                Some(moves) if !moves.is_empty() => openings.push(BookOpening {
+                    name: name.to_string(),
+                    moves: normalize(&moves),
+                }),
+                _ => return Err(OpeningBookError::InvalidLine(idx + 1, line.to_string())),
+            }
+        }
+        Ok(OpeningBook { openings })
+    }
+
+    pub fn load(path: &str) -> Result<OpeningBook, OpeningBookError> {
+        let text: String = fs::read_to_string(path)
+            .map_err(|e| OpeningBookError::Io(path.to_string(), e.to_string()))?;
+        OpeningBook::parse(&text)
+    }
+
+    /// The book shipped with the server.
+    pub fn builtin() -> OpeningBook {
+        OpeningBook::parse(BUILTIN_BOOK).expect("built-in opening book is valid")
+    }
+
+    pub fn openings(&self) -> &[BookOpening] {
+        &self.openings
+    }
+
+    /// Name of the longest book opening the game started with.
+    pub fn name_for(&self, moves: &[String]) -> Option<&str> {
+        let moves: Vec<String> = normalize(moves);
+        self.openings
+            .iter()
+            .filter(|o| moves.starts_with(&o.moves))
+            .max_by_key(|o| o.moves.len())
+            .map(|o| o.name.as_str())
+    }
+
+    /// Moves of an opening given either by its name (case-insensitive) or as a
+    /// move sequence such as "f5d6c3".
+    pub fn resolve(&self, opening: &str) -> Option<Vec<String>> {
+        if let Some(o) = self
+            .openings
+            .iter()
+            .find(|o| o.name.eq_ignore_ascii_case(opening.trim()))
+        {
+            return Some(o.moves.clone());
+        }
+        parse_opening(opening)
+    }
+}
+
+/// Algebraic notation of stored move positions, "pass" for a pass.
+pub fn algebraic_moves(moves: &[u64]) -> Vec<String> {
+    moves
+        .iter()
+        .map(|&m| {
+            if m == u64::MAX {
+                "pass".to_string()
+            } else {
+                move_to_algebraic(m).unwrap().to_lowercase()
+            }
+        })
+        .collect()
+}
+
+/// Maps the moves onto the orientation in which black opens with f5. The four
+/// legal first moves are equivalent under the symmetries of the initial position.
+pub fn normalize(moves: &[String]) -> Vec<String> {
+    let transform: fn(u8, u8) -> (u8, u8) = match moves.first().map(|m| m.as_str()) {
+        Some("e6") => |c, r| (r, c),
+        Some("d3") => |c, r| (7 - r, 7 - c),
+        Some("c4") => |c, r| (7 - c, 7 - r),
+        _ => |c, r| (c, r),
+    };
+    moves
+        .iter()
+        .map(|m| match m.as_bytes() {
+            [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+                let (c, r) = transform(file - b'a', rank - b'1');
+                format!("{}{}", (b'a' + c) as char, (b'1' + r) as char)
+            }
+            _ => m.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_match::play_opening;
+
+    fn moves(s: &str) -> Vec<String> {
+        parse_opening(s).unwrap()
+    }
+
+    #[test]
+    fn builtin_book_is_playable() {
+        let book: OpeningBook = OpeningBook::builtin();
+        assert!(!book.openings().is_empty());
+        for opening in book.openings() {
+            assert!(play_opening(&opening.moves).is_ok(), "{}", opening.name);
+        }
+    }
+
+    #[test]
+    fn names_the_longest_matching_opening() {
+        let book: OpeningBook = OpeningBook::builtin();
+        assert_eq!(book.name_for(&moves("f5d6c3d3c4f4")), Some("Tiger"));
+        assert_eq!(book.name_for(&moves("f5d6c3")), Some("Perpendicular"));
+        assert_eq!(book.name_for(&moves("f5")), None);
+        assert_eq!(book.name_for(&[]), None);
+    }
+
+    #[test]
+    fn recognizes_symmetric_starts() {
+        let book: OpeningBook = OpeningBook::builtin();
+        // The Tiger played from each of the four legal first moves.
+        for line in ["f5d6c3d3c4", "e6f4c3c4d3", "d3c5f6f5e6", "c4e3f6e6f5"] {
+            assert_eq!(book.name_for(&moves(line)), Some("Tiger"), "{}", line);
+        }
+    }
+
+    #[test]
+    fn resolves_names_and_move_prefixes() {
+        let book: OpeningBook = OpeningBook::builtin();
+        assert_eq!(book.resolve("tiger"), Some(moves("f5d6c3d3c4")));
+        assert_eq!(book.resolve("f5 f6"), Some(moves("f5f6")));
+        assert_eq!(book.resolve("Unknown"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        assert!(OpeningBook::parse("# comment\n\nf5d6 Perpendicular\n").is_ok());
+        assert!(matches!(
+            OpeningBook::parse("f5d6\n"),
+            Err(OpeningBookError::InvalidLine(1, _))
+        ));
+        assert!(matches!(
+            OpeningBook::parse("f5\nz9 Nonsense\n"),
+            Err(OpeningBookError::InvalidLine(_, _))
+        ));
+    }
+}
@@ -0,0 +1,377 @@
+//! Token-bucket rate limiting. Every client IP and every player has a bucket
+//! for reads and one for writes, and a request takes a token from the bucket
+//! of its IP and, if it names one, of its player. The API has no
+//! authentication, so the player is the `player_id` of the JSON body, which
+//! `RateLimitFairing` reads before routing, and anybody can claim to be any
+//! player. Player buckets are therefore kept per IP, so that requests naming
+//! a player cannot use up that player's budget on other addresses. Handlers
+//! opt in with the `RateLimited` guard.
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::metrics::Metrics;
+
+/// Routes below `/reversi/v1` that change state and use the write budget.
+pub const WRITE_ROUTES: &[&str] = &[
+    "create_game",
+    "join",
+    "move",
+    "matchmaking/enqueue",
+    "matchmaking/dequeue",
+    "tournaments/create",
+    "tournaments/register",
+    "tournaments/start",
+    "matches/create",
+    "players/register",
+];
+
+/// Bodies longer than this are not searched for a player id.
+const MAX_PEEK: usize = 1024;
+
+/// Beyond this many buckets the full ones are dropped; a new bucket starts
+/// full, so nothing is lost.
+const MAX_BUCKETS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limit {
+    /// Tokens added per second.
+    pub per_second: f64,
+    /// Size of the bucket, i.e. how many requests may arrive at once.
+    pub burst: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub read: Limit,
+    pub write: Limit,
+    /// Limits of single routes, by their path below `/reversi/v1` such as
+    /// `move` or `tournaments/<id>`. Such a route gets buckets of its own.
+    pub routes: BTreeMap<String, Limit>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            enabled: true,
+            read: Limit {
+                per_second: 20.0,
+                burst: 60,
+            },
+            write: Limit {
+                per_second: 5.0,
+                burst: 20,
+            },
+            routes: BTreeMap::new(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// The name of the budget a route draws from and its limit.
+    pub fn limit_for(&self, route: &str) -> (String, Limit) {
+        if let Some(limit) = self.routes.get(route) {
+            return (format!("route:{}", route), *limit);
+        }
+        if WRITE_ROUTES.contains(&route) {
+            ("write".to_string(), self.write)
+        } else {
+            ("read".to_string(), self.read)
+        }
+    }
+
+    /// Every limit that is not positive.
+    pub fn problems(&self) -> Vec<String> {
+        let mut limits: Vec<(String, Limit)> = vec![
+            ("rate_limit.read".to_string(), self.read),
+            ("rate_limit.write".to_string(), self.write),
+        ];
+        for (route, limit) in &self.routes {
+            limits.push((format!("rate_limit.routes.\"{}\"", route), *limit));
+        }
+        limits
+            .into_iter()
+            .filter(|(_, limit)| !(limit.per_second > 0.0 && limit.burst > 0))
+            .map(|(name, _)| format!("{} needs a positive per_second and burst", name))
+            .collect()
+    }
+}
+
+/// Whom a bucket belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Client {
+    Ip(IpAddr),
+    /// A player id as claimed by requests from one address.
+    Player(String, IpAddr),
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct BucketKey {
+    client: Client,
+    budget: String,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    limit: Limit,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed: f64 = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst as f64);
+        self.updated = now;
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.limit.burst as f64
+    }
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<BucketKey, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Takes a token from the bucket of every client for the route, or from
+    /// none of them if one is empty. Then returns how long until all of them
+    /// have a token again.
+    pub fn acquire(&self, clients: &[Client], route: &str, now: Instant) -> Result<(), Duration> {
+        let (budget, limit) = self.config.limit_for(route);
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > MAX_BUCKETS {
+            buckets.retain(|_, bucket| {
+                bucket.refill(now);
+                !bucket.is_full()
+            });
+        }
+        let mut wait: f64 = 0.0;
+        for client in clients {
+            let bucket: &mut Bucket = buckets
+                .entry(BucketKey {
+                    client: client.clone(),
+                    budget: budget.clone(),
+                })
+                .or_insert(Bucket {
+                    tokens: limit.burst as f64,
+                    updated: now,
+                    limit,
+                });
+            bucket.refill(now);
+            if bucket.tokens < 1.0 {
+                wait = wait.max((1.0 - bucket.tokens) / limit.per_second);
+            }
+        }
+        if wait > 0.0 {
+            return Err(Duration::from_secs_f64(wait));
+        }
+        for client in clients {
+            let key: BucketKey = BucketKey {
+                client: client.clone(),
+                budget: budget.clone(),
+            };
+            if let Some(bucket) = buckets.get_mut(&key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `player_id` of the request body, if it has one.
+struct Player(Option<String>);
+
+/// Seconds a rejected client should wait, for the `Retry-After` header.
+struct RetryAfter(u64);
+
+#[derive(Deserialize)]
+struct PlayerField {
+    player_id: Option<String>,
+}
+
+/// The seconds a rejected request should wait before retrying, at least 1.
+pub fn retry_after(request: &Request<'_>) -> u64 {
+    request.local_cache(|| RetryAfter(1)).0
+}
+
+/// Reads the player id from JSON bodies before they reach the handlers.
+pub struct RateLimitFairing;
+
+#[rocket::async_trait]
+impl Fairing for RateLimitFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate limit player ids",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, data: &mut Data<'_>) {
+        if !request.content_type().is_some_and(|ct| ct.is_json()) {
+            return;
+        }
+        let body: &[u8] = data.peek(MAX_PEEK).await;
+        let player: Option<String> = serde_json::from_slice::<PlayerField>(body)
+            .ok()
+            .and_then(|field| field.player_id);
+        request.local_cache(|| Player(player));
+    }
+}
+
+/// Lets a request through while its client has tokens left and fails it with
+/// 429 otherwise.
+pub struct RateLimited;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RateLimited {
+    type Error = Duration;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let limiter: &RateLimiter = match request.rocket().state::<RateLimiter>() {
+            Some(l) if l.is_enabled() => l,
+            _ => return Outcome::Success(RateLimited),
+        };
+        let route: &str = request
+            .route()
+            .map(|r| {
+                r.uri
+                    .unmounted_origin
+                    .path()
+                    .as_str()
+                    .trim_start_matches('/')
+            })
+            .unwrap_or_default();
+        let mut clients: Vec<Client> = Vec::new();
+        if let Some(ip) = request.client_ip() {
+            clients.push(Client::Ip(ip));
+            if let Some(player) = &request.local_cache(|| Player(None)).0 {
+                clients.push(Client::Player(player.clone(), ip));
+            }
+        }
+        match limiter.acquire(&clients, route, Instant::now()) {
+            Ok(()) => Outcome::Success(RateLimited),
+            Err(wait) => {
+                let secs: u64 = wait.as_secs_f64().ceil().max(1.0) as u64;
+                request.local_cache(|| RetryAfter(secs));
+                if let Some(metrics) = request.rocket().state::<Arc<Metrics>>() {
+                    metrics.rate_limited.with_label_values(&[route]).inc();
+                }
+                tracing::warn!(route, retry_after = secs, "Rate limited");
+                Outcome::Error((Status::TooManyRequests, wait))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> RateLimiter {
+        let mut routes: BTreeMap<String, Limit> = BTreeMap::new();
+        routes.insert(
+            "game_status".to_string(),
+            Limit {
+                per_second: 1.0,
+                burst: 1,
+            },
+        );
+        RateLimiter::new(RateLimitConfig {
+            write: Limit {
+                per_second: 2.0,
+                burst: 2,
+            },
+            routes,
+            ..RateLimitConfig::default()
+        })
+    }
+
+    #[test]
+    fn buckets_empty_and_refill() {
+        let limiter: RateLimiter = limiter();
+        let ip: [Client; 1] = [Client::Ip("10.0.0.1".parse().unwrap())];
+        let start: Instant = Instant::now();
+        assert!(limiter.acquire(&ip, "move", start).is_ok());
+        assert!(limiter.acquire(&ip, "move", start).is_ok());
+        let wait: Duration = limiter.acquire(&ip, "move", start).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+        // Reads have a budget of their own.
+        assert!(limiter.acquire(&ip, "game_list", start).is_ok());
+        let later: Instant = start + Duration::from_millis(500);
+        assert!(limiter.acquire(&ip, "move", later).is_ok());
+        assert!(limiter.acquire(&ip, "move", later).is_err());
+    }
+
+    #[test]
+    fn every_client_needs_a_token() {
+        let limiter: RateLimiter = limiter();
+        let start: Instant = Instant::now();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let player: Client = Client::Player("p".to_string(), ip);
+        let other: Client = Client::Player("q".to_string(), ip);
+        assert!(limiter
+            .acquire(std::slice::from_ref(&player), "game_status", start)
+            .is_ok());
+        // The player's bucket is empty.
+        assert!(limiter
+            .acquire(&[Client::Ip(ip), player], "game_status", start)
+            .is_err());
+        // A rejected request took no token from the address.
+        assert!(limiter
+            .acquire(&[Client::Ip(ip), other.clone()], "game_status", start)
+            .is_ok());
+        assert!(limiter.acquire(&[other], "game_status", start).is_err());
+    }
+
+    #[test]
+    fn other_addresses_cannot_drain_a_player() {
+        let limiter: RateLimiter = limiter();
+        let start: Instant = Instant::now();
+        let own: IpAddr = "10.0.0.1".parse().unwrap();
+        let attacker: IpAddr = "10.0.0.2".parse().unwrap();
+        let clients = |ip: IpAddr| [Client::Ip(ip), Client::Player("p".to_string(), ip)];
+        assert!(limiter.acquire(&clients(attacker), "move", start).is_ok());
+        assert!(limiter.acquire(&clients(attacker), "move", start).is_ok());
+        assert!(limiter.acquire(&clients(attacker), "move", start).is_err());
+        // The player still has a full budget from their own address.
+        assert!(limiter.acquire(&clients(own), "move", start).is_ok());
+        assert!(limiter.acquire(&clients(own), "move", start).is_ok());
+    }
+
+    #[test]
+    fn rejects_limits_that_never_refill() {
+        let mut config: RateLimitConfig = RateLimitConfig::default();
+        assert!(config.problems().is_empty());
+        config.routes.insert(
+            "move".to_string(),
+            Limit {
+                per_second: 0.0,
+                burst: 5,
+            },
+        );
+        assert_eq!(config.problems().len(), 1);
+    }
+}
@@ -0,0 +1,48 @@
+//! Elo ratings. The storage backends update both players' ratings in the
+//! same transaction as the update that finishes a game, so every game is
+//! rated exactly once.
+
+/// Rating of a new player.
+pub const DEFAULT_RATING: u32 = 1500;
+/// Maximum rating change per game.
+const K_FACTOR: f64 = 32.0;
+
+/// Whether a game stored in state `previous` ends with the update to `state`.
+pub fn finishes(previous: u64, state: u64) -> bool {
+    previous < 3 && (3..=5).contains(&state)
+}
+
+/// The ratings of black and white after a game that ended in `state`: 3 when
+/// black won, 4 when white won, 5 for a draw.
+pub fn updated_ratings(black: u32, white: u32, state: u64) -> (u32, u32) {
+    let score: f64 = match state {
+        3 => 1.0,
+        4 => 0.0,
+        _ => 0.5,
+    };
+    let expected: f64 = 1.0 / (1.0 + 10f64.powf((white as f64 - black as f64) / 400.0));
+    let change: i64 = (K_FACTOR * (score - expected)).round() as i64;
+    (
+        (black as i64 + change).max(0) as u32,
+        (white as i64 - change).max(0) as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_finished_games() {
+        assert_eq!(updated_ratings(1500, 1500, 3), (1516, 1484));
+        assert_eq!(updated_ratings(1500, 1500, 4), (1484, 1516));
+        assert_eq!(updated_ratings(1500, 1500, 5), (1500, 1500));
+        // An upset moves the ratings more than an expected result.
+        assert_eq!(updated_ratings(1400, 1800, 3), (1429, 1771));
+        assert_eq!(updated_ratings(1800, 1400, 3), (1803, 1397));
+
+        assert!(finishes(2, 3));
+        assert!(!finishes(3, 3));
+        assert!(!finishes(1, 2));
+    }
+}
@@ -0,0 +1,277 @@
+use std::time::{Duration, Instant};
+
+use crate::endgame::{annotate_endgame, DEFAULT_SOLVE_EMPTIES};
+use crate::engine::{analyze, game_positions, Analysis, Position, SearchLimits, PASS};
+use crate::model::{MoveAnnotation, MoveReport, PlayerAccuracy};
+use crate::opening_book::algebraic_moves;
+
+pub const JOB_RUNNING: &str = "running";
+pub const JOB_DONE: &str = "done";
+pub const JOB_FAILED: &str = "failed";
+
+/// Search limits for every position of an annotated game.
+pub const REPORT_DEPTH: u32 = 6;
+pub const REPORT_TIME_PER_POSITION_MS: u64 = 100;
+/// Time the endgame solver may spend on an annotated game.
+pub const REPORT_SOLVE_TIME_MS: u64 = 5000;
+
+/// Engine score drops that make a move an inaccuracy or a blunder.
+pub const INACCURACY_LOSS: i32 = 60;
+pub const BLUNDER_LOSS: i32 = 200;
+/// The same for solved moves, in discs of the final result.
+pub const EXACT_INACCURACY_LOSS: i32 = 4;
+pub const EXACT_BLUNDER_LOSS: i32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveLabel {
+    Best,
+    Good,
+    Inaccuracy,
+    Blunder,
+}
+
+impl MoveLabel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MoveLabel::Best => "best",
+            MoveLabel::Good => "good",
+            MoveLabel::Inaccuracy => "inaccuracy",
+            MoveLabel::Blunder => "blunder",
+        }
+    }
+}
+
+/// How much worse the played move is than the best one. Heuristic scores of
+/// neighbouring positions can disagree, so the loss never goes below zero.
+pub fn move_loss(annotation: &MoveAnnotation) -> i32 {
+    (annotation.best_score - annotation.played_score).max(0)
+}
+
+pub fn move_label(annotation: &MoveAnnotation) -> MoveLabel {
+    let loss: i32 = move_loss(annotation);
+    if annotation.played_move == annotation.best_move || loss == 0 {
+        return MoveLabel::Best;
+    }
+    let (inaccuracy, blunder) = if annotation.exact {
+        // Throwing away the win or the draw is a blunder however few discs it costs.
+        if annotation.played_score.signum() < annotation.best_score.signum() {
+            return MoveLabel::Blunder;
+        }
+        (EXACT_INACCURACY_LOSS, EXACT_BLUNDER_LOSS)
+    } else {
+        (INACCURACY_LOSS, BLUNDER_LOSS)
+    };
+    if loss >= blunder {
+        MoveLabel::Blunder
+    } else if loss >= inaccuracy {
+        MoveLabel::Inaccuracy
+    } else {
+        MoveLabel::Good
+    }
+}
+
+/// Evaluates every position of the game and annotates each move with the
+/// engine's choice. Moves late enough for the endgame solver then get exact
+/// scores, as far as it gets within `solve_time`. Passes are forced and skipped.
+pub fn annotate_game(
+    moves: &[u64],
+    limits: SearchLimits,
+    solve_time: Duration,
+) -> Vec<MoveAnnotation> {
+    let positions: Vec<Position> = game_positions(moves);
+    let evaluations: Vec<Analysis> = positions.iter().map(|p| analyze(p, limits)).collect();
+
+    let mut annotations: Vec<MoveAnnotation> = Vec::new();
+    for (idx, &mv) in moves.iter().enumerate() {
+        if mv == PASS {
+            continue;
+        }
+        let sign: i32 = if positions[idx].black_to_move { 1 } else { -1 };
+        let best_score: i32 = sign * evaluations[idx].score;
+        let best_move: u64 = evaluations[idx].best_move.unwrap_or(mv);
+        // The engine's own choice keeps its score, the search of the next position
+        // sees one move less far.
+        let played_score: i32 = if mv == best_move {
+            best_score
+        } else {
+            sign * evaluations[idx + 1].score
+        };
+        annotations.push(MoveAnnotation {
+            move_number: idx as u64 + 1,
+            played_move: mv,
+            best_move,
+            best_score,
+            played_score,
+            exact: false,
+        });
+    }
+
+    for solved in annotate_endgame(moves, DEFAULT_SOLVE_EMPTIES, Instant::now() + solve_time) {
+        if let Some(a) = annotations
+            .iter_mut()
+            .find(|a| a.move_number == solved.move_number)
+        {
+            *a = solved;
+        }
+    }
+    annotations
+}
+
+/// Limits used for the background annotation of finished games.
+pub fn report_limits() -> SearchLimits {
+    SearchLimits {
+        max_depth: REPORT_DEPTH,
+        time_budget: Duration::from_millis(REPORT_TIME_PER_POSITION_MS),
+    }
+}
+
+/// Per-move report of an annotated game.
+pub fn move_reports(moves: &[u64], annotations: &[MoveAnnotation]) -> Vec<MoveReport> {
+    let positions: Vec<Position> = game_positions(moves);
+    annotations
+        .iter()
+        .filter(|a| a.move_number >= 1 && a.move_number as usize <= moves.len())
+        .map(|a| MoveReport {
+            move_number: a.move_number,
+            player: if positions[a.move_number as usize - 1].black_to_move {
+                "black".to_string()
+            } else {
+                "white".to_string()
+            },
+            played_move: algebraic_moves(&[a.played_move]).concat(),
+            best_move: algebraic_moves(&[a.best_move]).concat(),
+            evaluation: a.played_score,
+            loss: move_loss(a),
+            label: move_label(a).as_str().to_string(),
+            exact: a.exact,
+        })
+        .collect()
+}
+
+/// Accuracy of one player over the annotated moves, 100 for a player
+/// without annotated moves.
+pub fn player_accuracy(reports: &[MoveReport], player: &str) -> PlayerAccuracy {
+    let mut accuracy: PlayerAccuracy = PlayerAccuracy::default();
+    for report in reports.iter().filter(|r| r.player == player) {
+        accuracy.moves += 1;
+        match report.label.as_str() {
+            "best" => accuracy.best_moves += 1,
+            "inaccuracy" => accuracy.inaccuracies += 1,
+            "blunder" => accuracy.blunders += 1,
+            _ => {}
+        }
+    }
+    accuracy.accuracy = if accuracy.moves == 0 {
+        100.0
+    } else {
+        let sound: u64 = accuracy.moves - accuracy.inaccuracies - accuracy.blunders;
+        (1000.0 * sound as f64 / accuracy.moves as f64).round() / 10.0
+    };
+    accuracy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::legal_moves;
+
+    fn annotation(best_score: i32, played_score: i32, exact: bool) -> MoveAnnotation {
+        MoveAnnotation {
+            move_number: 1,
+            played_move: 1,
+            best_move: 2,
+            best_score,
+            played_score,
+            exact,
+        }
+    }
+
+    /// Moves of a game in which both sides always take their lowest legal move.
+    fn lowest_move_game() -> Vec<u64> {
+        let mut moves: Vec<u64> = Vec::new();
+        let mut position: Position = Position::initial();
+        while !position.is_game_over() {
+            let (player, opponent) = position.player_and_opponent();
+            let legal: u64 = legal_moves(player, opponent);
+            let mv: u64 = if legal == 0 {
+                PASS
+            } else {
+                legal & legal.wrapping_neg()
+            };
+            moves.push(mv);
+            position = position.play(mv);
+        }
+        moves
+    }
+
+    #[test]
+    fn labels_moves_by_evaluation_drop() {
+        assert_eq!(move_label(&annotation(100, 80, false)), MoveLabel::Good);
+        assert_eq!(
+            move_label(&annotation(100, 0, false)),
+            MoveLabel::Inaccuracy
+        );
+        assert_eq!(
+            move_label(&annotation(100, -150, false)),
+            MoveLabel::Blunder
+        );
+        assert_eq!(move_label(&annotation(10, 30, false)), MoveLabel::Best);
+
+        assert_eq!(move_label(&annotation(10, 8, true)), MoveLabel::Good);
+        assert_eq!(move_label(&annotation(10, 4, true)), MoveLabel::Inaccuracy);
+        assert_eq!(move_label(&annotation(2, 0, true)), MoveLabel::Blunder);
+        assert_eq!(move_label(&annotation(-2, -20, true)), MoveLabel::Blunder);
+
+        let mut best: MoveAnnotation = annotation(100, -500, false);
+        best.played_move = best.best_move;
+        assert_eq!(move_label(&best), MoveLabel::Best);
+    }
+
+    #[test]
+    fn computes_player_accuracy() {
+        let report = |player: &str, label: &str| MoveReport {
+            move_number: 1,
+            player: player.to_string(),
+            played_move: String::new(),
+            best_move: String::new(),
+            evaluation: 0,
+            loss: 0,
+            label: label.to_string(),
+            exact: false,
+        };
+        let reports: Vec<MoveReport> = vec![
+            report("black", "best"),
+            report("black", "good"),
+            report("black", "blunder"),
+            report("white", "inaccuracy"),
+        ];
+        let black: PlayerAccuracy = player_accuracy(&reports, "black");
+        assert_eq!((black.moves, black.best_moves, black.blunders), (3, 1, 1));
+        assert_eq!(black.accuracy, 66.7);
+        assert_eq!(player_accuracy(&reports, "white").accuracy, 0.0);
+        assert_eq!(player_accuracy(&[], "white").accuracy, 100.0);
+    }
+
+    #[test]
+    fn annotates_every_move() {
+        let moves: Vec<u64> = lowest_move_game();
+        let limits: SearchLimits = SearchLimits {
+            max_depth: 2,
+            time_budget: Duration::from_secs(10),
+        };
+        let annotations: Vec<MoveAnnotation> =
+            annotate_game(&moves, limits, Duration::from_secs(60));
+        let played: usize = moves.iter().filter(|&&m| m != PASS).count();
+        assert_eq!(annotations.len(), played);
+        assert!(!annotations[0].exact);
+        assert!(annotations[annotations.len() - 1].exact);
+
+        let reports: Vec<MoveReport> = move_reports(&moves, &annotations);
+        assert_eq!(reports.len(), played);
+        assert_eq!(reports[0].player, "black");
+        assert_eq!(reports[0].move_number, 1);
+        let black: PlayerAccuracy = player_accuracy(&reports, "black");
+        let white: PlayerAccuracy = player_accuracy(&reports, "white");
+        assert_eq!((black.moves + white.moves) as usize, played);
+    }
+}
@@ -0,0 +1,546 @@
+//! Behaviour every storage backend has to share. Each backend's tests create
+//! two players with the default rating and run these checks against them,
+//! the player checks first since finishing a game changes the ratings.
+
+use crate::game_match::OpeningMove;
+use crate::model::{
+    Game, GameMatch, MatchGame, MoveAnnotation, MoveRecord, Tournament, TournamentPairing, User,
+    INITIAL_POSITION_BLACK, INITIAL_POSITION_WHITE,
+};
+use crate::repository::game_repository::GameRepository;
+use crate::repository::match_repository::{MatchRepository, NewMatchGame};
+use crate::repository::player_repository::PlayerRepository;
+use crate::repository::tournament_repository::TournamentRepository;
+use uuid::Uuid;
+
+fn new_game(black_uuid: &str) -> Game {
+    Game {
+        game_uuid: Uuid::new_v4().to_string(),
+        black_uuid: black_uuid.to_string(),
+        white_uuid: String::new(),
+        position_black: INITIAL_POSITION_BLACK,
+        position_white: INITIAL_POSITION_WHITE,
+        state: 0,
+        time_control: "blitz".to_string(),
+    }
+}
+
+/// Pending games counted by `count_games_by_state`. The database may be shared
+/// with other tests, so only differences are checked.
+async fn pending_count(repo: &(dyn GameRepository + Send + Sync)) -> u64 {
+    let counts: Vec<(u64, u64)> = repo.count_games_by_state().await.unwrap();
+    assert!(counts.windows(2).all(|w| w[0].0 < w[1].0));
+    counts
+        .iter()
+        .find(|(state, _)| *state == 0)
+        .map_or(0, |(_, count)| *count)
+}
+
+pub async fn check_game_repository(repo: &(dyn GameRepository + Send + Sync), players: [&str; 2]) {
+    let [black, white] = players;
+
+    // A pending game is offered to everybody but its creator.
+    let pending_before: u64 = pending_count(repo).await;
+    let mut game: Game = new_game(black);
+    repo.create_game(&game).await.unwrap();
+    assert_eq!(pending_count(repo).await, pending_before + 1);
+    assert_eq!(
+        repo.get_game(&game.game_uuid).await.unwrap(),
+        Some(game.clone())
+    );
+    let offered: Vec<Game> = repo.pending_games(white.to_string()).await.unwrap();
+    assert!(offered.iter().any(|g| g.game_uuid == game.game_uuid));
+    let own: Vec<Game> = repo.pending_games(black.to_string()).await.unwrap();
+    assert!(!own.iter().any(|g| g.game_uuid == game.game_uuid));
+    assert_eq!(
+        repo.get_game(&Uuid::new_v4().to_string()).await.unwrap(),
+        None
+    );
+
+    game.white_uuid = white.to_string();
+    game.state = 1;
+    repo.update_game(&game, None).await.unwrap();
+    assert_eq!(
+        repo.get_game(&game.game_uuid).await.unwrap(),
+        Some(game.clone())
+    );
+    assert_eq!(repo.get_max_move_no(&game.game_uuid).await.unwrap(), 0);
+    assert_eq!(repo.get_last_move(&game.game_uuid).await.unwrap(), 0);
+
+    // Moves and positions use all 64 bits, a pass is stored as u64::MAX.
+    game.position_black = 1u64 << 63 | 0x0000_0018_1000_0000;
+    game.position_white = 1;
+    game.state = 2;
+    repo.update_game_with_move(&game, 1u64 << 63, 1, None)
+        .await
+        .unwrap();
+    game.state = 1;
+    repo.update_game_with_move(&game, u64::MAX, 2, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        repo.get_game(&game.game_uuid).await.unwrap(),
+        Some(game.clone())
+    );
+    assert_eq!(
+        repo.get_moves(&game.game_uuid).await.unwrap(),
+        vec![1u64 << 63, u64::MAX]
+    );
+    assert_eq!(repo.get_max_move_no(&game.game_uuid).await.unwrap(), 2);
+    assert_eq!(repo.get_last_move(&game.game_uuid).await.unwrap(), u64::MAX);
+
+    // The game update and the move are stored together or not at all.
+    let missing: Game = new_game(black);
+    assert!(repo
+        .update_game_with_move(&missing, 1, 1, None)
+        .await
+        .is_err());
+    assert!(repo.get_moves(&missing.game_uuid).await.unwrap().is_empty());
+
+    // Black wins with more discs. The game is rated once, however often the
+    // finished game is stored.
+    game.state = 3;
+    repo.update_game(&game, None).await.unwrap();
+    repo.update_game(&game, None).await.unwrap();
+    let stats = repo.player_stats(black).await.unwrap().unwrap();
+    assert_eq!(stats.wins_black, 1);
+    assert_eq!(stats.rating, 1516);
+    assert!(stats.average_disc_differential > 0.0);
+    let stats = repo.player_stats(white).await.unwrap().unwrap();
+    assert_eq!(stats.losses_white, 1);
+    assert_eq!(stats.rating, 1484);
+    assert_eq!(
+        repo.player_stats(&Uuid::new_v4().to_string())
+            .await
+            .unwrap(),
+        None
+    );
+
+    let leaderboard = repo.leaderboard(0, 1000, "blitz").await.unwrap();
+    let entry = leaderboard.iter().find(|e| e.player_id == black).unwrap();
+    assert_eq!(entry.wins, 1);
+    assert_eq!(
+        entry.rank as usize,
+        leaderboard
+            .iter()
+            .position(|e| e.player_id == black)
+            .unwrap()
+            + 1
+    );
+    let entry = leaderboard.iter().find(|e| e.player_id == white).unwrap();
+    assert_eq!((entry.wins, entry.losses), (0, 1));
+    let other = repo.leaderboard(0, 1000, "no-such-control").await.unwrap();
+    assert!(!other.iter().any(|e| e.player_id == black));
+
+    // Annotations replace earlier ones of the same move.
+    let annotation = |move_number: u64, best_score: i32| MoveAnnotation {
+        move_number,
+        played_move: 1u64 << 63,
+        best_move: 1,
+        best_score,
+        played_score: -3,
+        exact: true,
+    };
+    repo.save_annotations(&game.game_uuid, &[annotation(2, 5), annotation(1, 4)])
+        .await
+        .unwrap();
+    repo.save_annotations(&game.game_uuid, &[annotation(2, 7)])
+        .await
+        .unwrap();
+    assert_eq!(
+        repo.get_annotations(&game.game_uuid).await.unwrap(),
+        vec![annotation(1, 4), annotation(2, 7)]
+    );
+
+    assert_eq!(
+        repo.get_annotation_job(&game.game_uuid).await.unwrap(),
+        None
+    );
+    repo.queue_annotation_job(&game.game_uuid).await.unwrap();
+    assert!(repo
+        .unfinished_annotation_jobs()
+        .await
+        .unwrap()
+        .contains(&game.game_uuid));
+    repo.update_annotation_job(&game.game_uuid, "failed", "out of time")
+        .await
+        .unwrap();
+    let job = repo
+        .get_annotation_job(&game.game_uuid)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        (job.status.as_str(), job.error.as_str()),
+        ("failed", "out of time")
+    );
+    assert!(!repo
+        .unfinished_annotation_jobs()
+        .await
+        .unwrap()
+        .contains(&game.game_uuid));
+    repo.queue_annotation_job(&game.game_uuid).await.unwrap();
+    let job = repo
+        .get_annotation_job(&game.game_uuid)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!((job.status.as_str(), job.error.as_str()), ("queued", ""));
+
+    // A key is reserved once, and gets its response with the move.
+    let key: String = Uuid::new_v4().to_string();
+    let reserved: MoveRecord = MoveRecord {
+        game_uuid: game.game_uuid.clone(),
+        idempotency_key: key.clone(),
+        player_uuid: black.to_string(),
+        played_move: "c4".to_string(),
+        response: String::new(),
+    };
+    assert_eq!(
+        repo.get_move_record(&game.game_uuid, &key, 3600)
+            .await
+            .unwrap(),
+        None
+    );
+    assert!(repo.reserve_move_key(&reserved, 3600).await.unwrap());
+    assert!(!repo.reserve_move_key(&reserved, 3600).await.unwrap());
+    assert_eq!(
+        repo.get_move_record(&game.game_uuid, &key, 3600)
+            .await
+            .unwrap(),
+        Some(reserved.clone())
+    );
+    let record: MoveRecord = MoveRecord {
+        response: r#"{"status":"ok"}"#.to_string(),
+        ..reserved.clone()
+    };
+    repo.update_game(&game, Some(&record)).await.unwrap();
+    repo.release_move_key(&game.game_uuid, &key).await.unwrap();
+    assert_eq!(
+        repo.get_move_record(&game.game_uuid, &key, 3600)
+            .await
+            .unwrap(),
+        Some(record)
+    );
+    assert_eq!(
+        repo.get_move_record(&game.game_uuid, "other", 3600)
+            .await
+            .unwrap(),
+        None
+    );
+
+    // A key whose move was not played can be reserved again.
+    let failed: MoveRecord = MoveRecord {
+        idempotency_key: Uuid::new_v4().to_string(),
+        ..reserved
+    };
+    assert!(repo.reserve_move_key(&failed, 3600).await.unwrap());
+    repo.release_move_key(&game.game_uuid, &failed.idempotency_key)
+        .await
+        .unwrap();
+    assert!(repo.reserve_move_key(&failed, 3600).await.unwrap());
+}
+
+pub async fn check_player_repository(
+    repo: &(dyn PlayerRepository + Send + Sync),
+    players: [&str; 2],
+) {
+    let listed: Vec<String> = repo
+        .player_list()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|u| u.player_uuid)
+        .collect();
+    for player in players {
+        assert!(listed.iter().any(|p| p == player));
+        assert_eq!(repo.get_rating(player).await.unwrap(), Some(1500));
+    }
+    assert_eq!(
+        repo.get_rating(&Uuid::new_v4().to_string()).await.unwrap(),
+        None
+    );
+
+    // New players start with the default rating; a player id is taken once.
+    let player: User = User {
+        player_uuid: Uuid::new_v4().to_string(),
+        comment: "Registered".to_string(),
+    };
+    repo.create_player(&player).await.unwrap();
+    assert!(repo.create_player(&player).await.is_err());
+    assert_eq!(
+        repo.get_rating(&player.player_uuid).await.unwrap(),
+        Some(1500)
+    );
+    assert!(repo
+        .player_list()
+        .await
+        .unwrap()
+        .into_iter()
+        .any(|u| u.player_uuid == player.player_uuid && u.comment == "Registered"));
+}
+
+fn paired_game(black_uuid: &str, white_uuid: &str) -> Game {
+    Game {
+        white_uuid: white_uuid.to_string(),
+        state: 1,
+        ..new_game(black_uuid)
+    }
+}
+
+fn pairing(round: u64, game: &Game, bracket_match: &str, game_no: u64) -> TournamentPairing {
+    TournamentPairing {
+        round,
+        black_uuid: game.black_uuid.clone(),
+        white_uuid: game.white_uuid.clone(),
+        game_uuid: game.game_uuid.clone(),
+        game_state: game.state,
+        position_black: game.position_black,
+        position_white: game.position_white,
+        bracket_match: bracket_match.to_string(),
+        game_no,
+    }
+}
+
+pub async fn check_tournament_repository(
+    repo: &(dyn TournamentRepository + Send + Sync),
+    game_repo: &(dyn GameRepository + Send + Sync),
+    players: [&str; 2],
+) {
+    let [black, white] = players;
+    let mut tournament: Tournament = Tournament {
+        tournament_uuid: Uuid::new_v4().to_string(),
+        name: "Conformance".to_string(),
+        format: "round_robin".to_string(),
+        rounds: 0,
+        current_round: 0,
+        state: 0,
+        time_control: "blitz".to_string(),
+        best_of: 1,
+        tiebreak: "armageddon".to_string(),
+    };
+    repo.create_tournament(&tournament).await.unwrap();
+    assert_eq!(
+        repo.get_tournament(&tournament.tournament_uuid)
+            .await
+            .unwrap(),
+        Some(tournament.clone())
+    );
+    assert_eq!(
+        repo.get_tournament(&Uuid::new_v4().to_string())
+            .await
+            .unwrap(),
+        None
+    );
+
+    // Players are registered once.
+    assert!(repo
+        .register_player(&tournament.tournament_uuid, black)
+        .await
+        .unwrap());
+    assert!(repo
+        .register_player(&tournament.tournament_uuid, white)
+        .await
+        .unwrap());
+    assert!(!repo
+        .register_player(&tournament.tournament_uuid, black)
+        .await
+        .unwrap());
+    let mut registered: Vec<String> = repo.get_players(&tournament.tournament_uuid).await.unwrap();
+    registered.sort();
+    let mut expected: Vec<String> = vec![black.to_string(), white.to_string()];
+    expected.sort();
+    assert_eq!(registered, expected);
+
+    // A round starts once, together with its games.
+    tournament.rounds = 2;
+    let game: Game = paired_game(black, white);
+    let pairings: Vec<TournamentPairing> = vec![pairing(1, &game, "", 0)];
+    assert!(repo
+        .start_round(&tournament, 1, std::slice::from_ref(&game), &pairings)
+        .await
+        .unwrap());
+    assert!(!repo
+        .start_round(&tournament, 1, &[paired_game(black, white)], &pairings)
+        .await
+        .unwrap());
+    tournament.current_round = 1;
+    tournament.state = 1;
+    assert_eq!(
+        repo.get_tournament(&tournament.tournament_uuid)
+            .await
+            .unwrap(),
+        Some(tournament.clone())
+    );
+    assert_eq!(
+        game_repo.get_game(&game.game_uuid).await.unwrap(),
+        Some(game.clone())
+    );
+    assert_eq!(
+        repo.tournament_for_game(&game.game_uuid).await.unwrap(),
+        Some(tournament.tournament_uuid.clone())
+    );
+    assert_eq!(
+        repo.tournament_for_game(&Uuid::new_v4().to_string())
+            .await
+            .unwrap(),
+        None
+    );
+
+    // Pairings report the current state of their games.
+    let mut finished: Game = game.clone();
+    finished.state = 5;
+    game_repo.update_game(&finished, None).await.unwrap();
+    let stored: Vec<TournamentPairing> = repo
+        .get_pairings(&tournament.tournament_uuid)
+        .await
+        .unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].game_state, 5);
+    assert_eq!(stored[0].white_uuid, white);
+    assert_eq!(stored[0].bracket_match, "");
+
+    // A bracket game is only added once.
+    tournament.current_round = 2;
+    let bracket: Game = paired_game(white, black);
+    assert!(repo
+        .add_games(
+            &tournament,
+            std::slice::from_ref(&bracket),
+            &[pairing(2, &bracket, "F", 1)]
+        )
+        .await
+        .unwrap());
+    let duplicate: Game = paired_game(white, black);
+    assert!(!repo
+        .add_games(
+            &tournament,
+            std::slice::from_ref(&duplicate),
+            &[pairing(2, &duplicate, "F", 1)]
+        )
+        .await
+        .unwrap());
+    assert_eq!(
+        game_repo.get_game(&duplicate.game_uuid).await.unwrap(),
+        None
+    );
+    let stored: Vec<TournamentPairing> = repo
+        .get_pairings(&tournament.tournament_uuid)
+        .await
+        .unwrap();
+    assert_eq!(stored.len(), 2);
+    assert_eq!(stored[1].bracket_match, "F");
+    assert_eq!(stored[1].game_no, 1);
+    assert_eq!(
+        repo.get_tournament(&tournament.tournament_uuid)
+            .await
+            .unwrap()
+            .unwrap()
+            .current_round,
+        2
+    );
+
+    tournament.state = 2;
+    repo.update_tournament(&tournament).await.unwrap();
+    assert_eq!(
+        repo.get_tournament(&tournament.tournament_uuid)
+            .await
+            .unwrap(),
+        Some(tournament)
+    );
+}
+
+pub async fn check_match_repository(
+    repo: &(dyn MatchRepository + Send + Sync),
+    game_repo: &(dyn GameRepository + Send + Sync),
+    players: [&str; 2],
+) {
+    let [black, white] = players;
+    let game_match: GameMatch = GameMatch {
+        match_uuid: Uuid::new_v4().to_string(),
+        player_a_uuid: black.to_string(),
+        player_b_uuid: white.to_string(),
+        games: 2,
+        time_control: "blitz".to_string(),
+    };
+    // The second game starts after a forced opening move.
+    let opening: OpeningMove = OpeningMove {
+        move_bit: 1 << 37,
+        position_black: 0x0000_0038_1000_0000,
+        position_white: 0x0000_0000_0800_0000,
+    };
+    let first: Game = paired_game(black, white);
+    let second: Game = Game {
+        position_black: opening.position_black,
+        position_white: opening.position_white,
+        state: 2,
+        ..paired_game(white, black)
+    };
+    let games: Vec<NewMatchGame> = vec![
+        NewMatchGame {
+            game_no: 1,
+            game: first.clone(),
+            opening: String::new(),
+            moves: Vec::new(),
+        },
+        NewMatchGame {
+            game_no: 2,
+            game: second.clone(),
+            opening: "f5".to_string(),
+            moves: vec![opening.clone()],
+        },
+    ];
+    game_repo.create_match(&game_match, &games).await.unwrap();
+
+    assert_eq!(
+        repo.get_match(&game_match.match_uuid).await.unwrap(),
+        Some(game_match.clone())
+    );
+    assert_eq!(
+        repo.get_match(&Uuid::new_v4().to_string()).await.unwrap(),
+        None
+    );
+    let stored: Vec<MatchGame> = repo.get_match_games(&game_match.match_uuid).await.unwrap();
+    let numbers: Vec<u64> = stored.iter().map(|g| g.game_no).collect();
+    assert_eq!(numbers, vec![1, 2]);
+    assert_eq!(stored[1].game_uuid, second.game_uuid);
+    assert_eq!(stored[1].black_uuid, white);
+    assert_eq!(stored[1].opening, "f5");
+    assert_eq!(stored[1].game_state, 2);
+    assert_eq!(stored[1].position_black, opening.position_black);
+    assert_eq!(
+        game_repo.get_moves(&second.game_uuid).await.unwrap(),
+        vec![opening.move_bit]
+    );
+    assert!(game_repo
+        .get_moves(&first.game_uuid)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // A match is stored with all of its games or not at all.
+    let broken: GameMatch = GameMatch {
+        match_uuid: Uuid::new_v4().to_string(),
+        ..game_match.clone()
+    };
+    let clash: Vec<NewMatchGame> = vec![
+        NewMatchGame {
+            game_no: 1,
+            game: paired_game(black, white),
+            opening: String::new(),
+            moves: Vec::new(),
+        },
+        NewMatchGame {
+            game_no: 2,
+            game: first.clone(),
+            opening: String::new(),
+            moves: Vec::new(),
+        },
+    ];
+    assert!(game_repo.create_match(&broken, &clash).await.is_err());
+    assert_eq!(repo.get_match(&broken.match_uuid).await.unwrap(), None);
+    assert_eq!(
+        game_repo.get_game(&clash[0].game.game_uuid).await.unwrap(),
+        None
+    );
+}
@@ -0,0 +1,189 @@
+use mysql_async::{prelude::*, Opts, OptsBuilder, PoolConstraints, PoolOpts};
+
+use crate::repository::db_errors::*;
+use crate::repository::game_repository::*;
+use crate::repository::match_repository::*;
+use crate::repository::memory_repository::*;
+use crate::repository::migrations::*;
+use crate::repository::mysql_pool::*;
+use crate::repository::player_repository::*;
+use crate::repository::postgres_repository::*;
+use crate::repository::sqlite_repository::*;
+use crate::repository::tournament_repository::*;
+
+/// Limits of the connection pool, the driver's defaults where `None`. SQLite
+/// and `memory://` have no pool and ignore them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PoolSize {
+    pub min: Option<usize>,
+    pub max: Option<usize>,
+}
+
+/// Connections of a database pool.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoolStatus {
+    pub max: usize,
+    /// Connections checked out of the pool.
+    pub in_use: usize,
+    /// Open and idle connections, `None` where the driver does not report them.
+    pub open: Option<usize>,
+    pub idle: Option<usize>,
+    /// Callers waiting for a connection.
+    pub waiting: usize,
+}
+
+/// Storage backend, chosen by the scheme of `DATABASE_URL`.
+#[derive(Clone)]
+pub enum Database {
+    MySql(MySqlPool),
+    Sqlite(SqliteDatabase),
+    Postgres(PostgresDatabase),
+    Memory(MemoryDatabase),
+}
+
+impl Database {
+    /// Connects to `mysql://...`, `postgres://...`, `sqlite://<path>`, `sqlite::memory:`
+    /// or `memory://`.
+    pub fn connect(database_url: &str) -> Result<Database, RepositoryError> {
+        Database::connect_with(database_url, PoolSize::default())
+    }
+
+    pub fn connect_with(database_url: &str, pool: PoolSize) -> Result<Database, RepositoryError> {
+        if database_url.starts_with("mysql://") {
+            let opts: Opts = Opts::from_url(database_url)
+                .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+            let default: PoolConstraints = opts.pool_opts().constraints();
+            let max: usize = pool.max.unwrap_or(default.max());
+            let min: usize = pool.min.unwrap_or(default.min().min(max));
+            let constraints: PoolConstraints = PoolConstraints::new(min, max).ok_or_else(|| {
+                RepositoryError::Other(format!("invalid pool size {}..{}", min, max))
+            })?;
+            let pool_opts: PoolOpts = opts.pool_opts().clone().with_constraints(constraints);
+            let opts: Opts = OptsBuilder::from_opts(opts).pool_opts(pool_opts).into();
+            return Ok(Database::MySql(MySqlPool::new(opts)));
+        }
+        if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+            return Ok(Database::Postgres(PostgresDatabase::connect(
+                database_url,
+                pool.max,
+            )?));
+        }
+        if database_url.starts_with("memory://") {
+            return Ok(Database::Memory(MemoryDatabase::open(database_url)?));
+        }
+        if let Some(path) = database_url
+            .strip_prefix("sqlite://")
+            .or_else(|| database_url.strip_prefix("sqlite:"))
+        {
+            return Ok(Database::Sqlite(SqliteDatabase::open(path)?));
+        }
+        Err(RepositoryError::Unsupported(format!(
+            "unknown database URL scheme in {}",
+            database_url
+        )))
+    }
+
+    /// Where the schema migrations go, `None` for `memory://` which has no schema.
+    pub fn migration_target(&self) -> Option<&(dyn MigrationTarget + Send + Sync)> {
+        match self {
+            Database::MySql(pool) => Some(pool),
+            Database::Sqlite(db) => Some(db),
+            Database::Postgres(db) => Some(db),
+            Database::Memory(_) => None,
+        }
+    }
+
+    /// Checks that the backend answers.
+    pub async fn ping(&self) -> Result<(), RepositoryError> {
+        match self {
+            Database::MySql(pool) => {
+                let mut conn = pool
+                    .get_conn()
+                    .await
+                    .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+                conn.ping()
+                    .await
+                    .map_err(|e| RepositoryError::DatabaseError(e.to_string()))
+            }
+            Database::Sqlite(db) => db.ping().await,
+            Database::Postgres(db) => db.ping().await,
+            Database::Memory(_) => Ok(()),
+        }
+    }
+
+    /// Closes the pool once the connections in use are returned. Called after
+    /// the server has stopped.
+    pub async fn close(self) -> Result<(), RepositoryError> {
+        match self {
+            Database::MySql(pool) => pool
+                .disconnect()
+                .await
+                .map_err(|e| RepositoryError::DatabaseError(e.to_string())),
+            Database::Postgres(db) => {
+                db.close();
+                Ok(())
+            }
+            Database::Sqlite(_) | Database::Memory(_) => Ok(()),
+        }
+    }
+
+    /// Usage of the connection pool, SQLite and memory have none.
+    pub fn pool_status(&self) -> Option<PoolStatus> {
+        match self {
+            Database::MySql(pool) => Some(PoolStatus {
+                max: pool.max(),
+                in_use: pool.in_use(),
+                open: None,
+                idle: None,
+                waiting: pool.waiting(),
+            }),
+            Database::Postgres(db) => {
+                let (max, open, idle, waiting) = db.pool_status();
+                Some(PoolStatus {
+                    max,
+                    in_use: open.saturating_sub(idle),
+                    open: Some(open),
+                    idle: Some(idle),
+                    waiting,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn game_repository(&self) -> Box<dyn GameRepository + Send + Sync> {
+        match self {
+            Database::MySql(pool) => Box::new(MySqlGameRepository::new(pool.clone())),
+            Database::Sqlite(db) => Box::new(SqliteGameRepository::new(db.clone())),
+            Database::Postgres(db) => Box::new(PostgresGameRepository::new(db.clone())),
+            Database::Memory(db) => Box::new(MemoryGameRepository::new(db.clone())),
+        }
+    }
+
+    pub fn player_repository(&self) -> Box<dyn PlayerRepository + Send + Sync> {
+        match self {
+            Database::MySql(pool) => Box::new(MySqlPlayerRepository::new(pool.clone())),
+            Database::Sqlite(db) => Box::new(SqlitePlayerRepository::new(db.clone())),
+            Database::Postgres(db) => Box::new(PostgresPlayerRepository::new(db.clone())),
+            Database::Memory(db) => Box::new(MemoryPlayerRepository::new(db.clone())),
+        }
+    }
+
+    pub fn tournament_repository(&self) -> Box<dyn TournamentRepository + Send + Sync> {
+        match self {
+            Database::MySql(pool) => Box::new(MySqlTournamentRepository::new(pool.clone())),
+            Database::Sqlite(db) => Box::new(SqliteTournamentRepository::new(db.clone())),
+            Database::Postgres(db) => Box::new(PostgresTournamentRepository::new(db.clone())),
+            Database::Memory(db) => Box::new(MemoryTournamentRepository::new(db.clone())),
+        }
+    }
+
+    pub fn match_repository(&self) -> Box<dyn MatchRepository + Send + Sync> {
+        match self {
+            Database::MySql(pool) => Box::new(MySqlMatchRepository::new(pool.clone())),
+            Database::Sqlite(db) => Box::new(SqliteMatchRepository::new(db.clone())),
+            Database::Postgres(db) => Box::new(PostgresMatchRepository::new(db.clone())),
+            Database::Memory(db) => Box::new(MemoryMatchRepository::new(db.clone())),
+        }
+    }
+}
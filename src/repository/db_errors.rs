@@ -2,6 +2,10 @@
 pub enum RepositoryError {
     #[error("Database error: {0}")]
     DatabaseError(String),
+    #[error("Not supported by this storage backend: {0}")]
+    Unsupported(String),
+    #[error("Schema error: {0}")]
+    SchemaError(String),
     #[error("Other error: {0}")]
     Other(String),
 }
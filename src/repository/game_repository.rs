@@ -1,29 +1,269 @@
-use crate::model::Game;
+use crate::model::{
+    AnnotationJob, Game, GameMatch, LeaderboardEntry, MoveAnnotation, MoveRecord, PlayerStats,
+};
 use async_trait::async_trait;
-use mysql_async::{params, prelude::*, Pool, TxOpts};
-use std::collections::HashMap;
-use std::sync::RwLock;
+use mysql_async::{params, prelude::*, Transaction, TxOpts};
 
+use crate::rating::{finishes, updated_ratings};
 use crate::repository::db_errors::*;
+use crate::repository::match_repository::NewMatchGame;
+use crate::repository::mysql_pool::MySqlPool;
 
 #[async_trait]
 pub trait GameRepository {
     async fn pending_games(&self, player_uuid: String) -> Result<Vec<Game>, RepositoryError>;
+    /// Number of games in each state, as `(state, count)` pairs ordered by state.
+    /// States without games are left out.
+    async fn count_games_by_state(&self) -> Result<Vec<(u64, u64)>, RepositoryError>;
     async fn get_game(&self, game_uuid: &str) -> Result<Option<Game>, RepositoryError>;
     async fn get_max_move_no(&self, game_uuid: &str) -> Result<u64, RepositoryError>;
     async fn get_last_move(&self, game_uuid: &str) -> Result<u64, RepositoryError>;
+    /// Move positions of the game in move order, `u64::MAX` stands for a pass.
+    async fn get_moves(&self, game_uuid: &str) -> Result<Vec<u64>, RepositoryError>;
     async fn create_game(&self, game: &Game) -> Result<(), RepositoryError>;
-    async fn update_game(&self, game: &Game) -> Result<(), RepositoryError>;
+    /// Updates the game. Given the record of the move that changed it, also
+    /// fills in the response of its reserved idempotency key, in the same
+    /// transaction.
+    async fn update_game(
+        &self,
+        game: &Game,
+        record: Option<&MoveRecord>,
+    ) -> Result<(), RepositoryError>;
+    /// Updates the game and records the move, like `update_game`.
     async fn update_game_with_move(
         &self,
         game: &Game,
         move_bit: u64,
         move_no: u64,
+        record: Option<&MoveRecord>,
+    ) -> Result<(), RepositoryError>;
+    /// Creates a match with its games, their opening moves and the links
+    /// between them in one transaction. Each game gets the rows `create_game`
+    /// and `update_game_with_move` would write for it.
+    async fn create_match(
+        &self,
+        game_match: &GameMatch,
+        games: &[NewMatchGame],
+    ) -> Result<(), RepositoryError>;
+    /// Stores move annotations, replacing earlier annotations of the same moves.
+    async fn save_annotations(
+        &self,
+        game_uuid: &str,
+        annotations: &[MoveAnnotation],
     ) -> Result<(), RepositoryError>;
+    async fn get_annotations(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Vec<MoveAnnotation>, RepositoryError>;
+    /// Queues the annotation of a game, requeueing it if it was annotated before.
+    async fn queue_annotation_job(&self, game_uuid: &str) -> Result<(), RepositoryError>;
+    async fn update_annotation_job(
+        &self,
+        game_uuid: &str,
+        status: &str,
+        error: &str,
+    ) -> Result<(), RepositoryError>;
+    async fn get_annotation_job(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Option<AnnotationJob>, RepositoryError>;
+    /// Games whose annotation is queued or was interrupted while running.
+    async fn unfinished_annotation_jobs(&self) -> Result<Vec<String>, RepositoryError>;
+    /// The move stored under an idempotency key, unless it is older than
+    /// `ttl_secs`. The response is empty while the move is being played.
+    async fn get_move_record(
+        &self,
+        game_uuid: &str,
+        idempotency_key: &str,
+        ttl_secs: u64,
+    ) -> Result<Option<MoveRecord>, RepositoryError>;
+    /// Reserves an idempotency key for a move before it is played, storing the
+    /// record without its response, and drops the records older than
+    /// `ttl_secs`. Returns false if the key is taken.
+    async fn reserve_move_key(
+        &self,
+        record: &MoveRecord,
+        ttl_secs: u64,
+    ) -> Result<bool, RepositoryError>;
+    /// Frees a reserved key whose move could not be played. Keys with a
+    /// response stay.
+    async fn release_move_key(
+        &self,
+        game_uuid: &str,
+        idempotency_key: &str,
+    ) -> Result<(), RepositoryError>;
+    async fn player_stats(&self, player_uuid: &str)
+        -> Result<Option<PlayerStats>, RepositoryError>;
+    async fn leaderboard(
+        &self,
+        offset: u64,
+        limit: u64,
+        time_control: &str,
+    ) -> Result<Vec<LeaderboardEntry>, RepositoryError>;
+}
+
+/// Inserts the game row, on its own or within a transaction.
+pub(crate) async fn insert_game<Q: Queryable + Send>(
+    conn: &mut Q,
+    game: &Game,
+) -> Result<(), RepositoryError> {
+    conn.exec_drop(
+        r#"
+        INSERT INTO games (
+            game_uuid,
+            black_uuid,
+            white_uuid,
+            position_black,
+            position_white,
+            state,
+            time_control,
+            start_date
+        )
+        VALUES (
+            UUID_TO_BIN(:game_uuid),
+            IF(:black_uuid = '', NULL, UUID_TO_BIN(:black_uuid)),
+            IF(:white_uuid = '', NULL, UUID_TO_BIN(:white_uuid)),
+            :position_black,
+            :position_white,
+            :state,
+            :time_control,
+            NOW()
+        )
+        "#,
+        params! {
+            "game_uuid" => &game.game_uuid,
+            "black_uuid" => &game.black_uuid,
+            "white_uuid" => &game.white_uuid,
+            "position_black" => game.position_black,
+            "position_white" => game.position_white,
+            "state" => game.state,
+            "time_control" => &game.time_control,
+        },
+    )
+    .await
+    .map_err(|e| RepositoryError::DatabaseError(e.to_string()))
+}
+
+/// Records a move of the game, the positions are the ones after the move.
+async fn insert_move(
+    tx: &mut Transaction<'_>,
+    game_uuid: &str,
+    move_bit: u64,
+    move_no: u64,
+    position_black: u64,
+    position_white: u64,
+) -> Result<(), RepositoryError> {
+    tx.exec_drop(
+        r#"
+        INSERT INTO moves (
+            game_uuid,
+            move_number,
+            move_position,
+            position_black,
+            position_white,
+            move_date
+        ) VALUES (UUID_TO_BIN(:game_uuid), :move_number, :next_move, :position_black, :position_white, NOW())
+        "#,
+        params! {
+            "game_uuid" => game_uuid,
+            "next_move" => move_bit,
+            "move_number" => move_no,
+            "position_black" => position_black,
+            "position_white" => position_white,
+        },
+    )
+    .await
+    .map_err(|e| RepositoryError::DatabaseError(e.to_string()))
+}
+
+/// Fills in the response of the move's reserved idempotency key within `tx`.
+async fn complete_move_key(
+    tx: &mut Transaction<'_>,
+    record: &MoveRecord,
+) -> Result<(), RepositoryError> {
+    tx.exec_drop(
+        r#"
+        UPDATE move_idempotency_keys
+        SET response = :response
+        WHERE game_uuid = UUID_TO_BIN(:game_uuid)
+            AND idempotency_key = :idempotency_key
+        "#,
+        params! {
+            "game_uuid" => &record.game_uuid,
+            "idempotency_key" => &record.idempotency_key,
+            "response" => &record.response,
+        },
+    )
+    .await
+    .map_err(|e| RepositoryError::DatabaseError(e.to_string()))
+}
+
+/// Updates the game within `tx`. The update that finishes a rated game also
+/// updates the ratings of both players.
+async fn update_game_in(tx: &mut Transaction<'_>, game: &Game) -> Result<(), RepositoryError> {
+    let previous: Option<u64> = tx
+        .exec_first(
+            "SELECT state FROM games WHERE game_uuid = UUID_TO_BIN(:game_uuid) FOR UPDATE",
+            params! { "game_uuid" => &game.game_uuid },
+        )
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+    tx.exec_drop(
+        r#"
+        UPDATE games
+        SET
+            black_uuid = IF(:black_uuid = '', NULL, UUID_TO_BIN(:black_uuid)),
+            white_uuid = IF(:white_uuid = '', NULL, UUID_TO_BIN(:white_uuid)),
+            position_black = :position_black,
+            position_white = :position_white,
+            state = :state,
+            end_date = NOW()
+        WHERE game_uuid = UUID_TO_BIN(:game_uuid)
+        "#,
+        params! {
+            "game_uuid" => &game.game_uuid,
+            "black_uuid" => &game.black_uuid,
+            "white_uuid" => &game.white_uuid,
+            "position_black" => game.position_black,
+            "position_white" => game.position_white,
+            "state" => game.state,
+        },
+    )
+    .await
+    .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+    let finished: bool = previous.is_some_and(|p| finishes(p, game.state));
+    if !finished || game.black_uuid.is_empty() || game.white_uuid.is_empty() {
+        return Ok(());
+    }
+    let mut ratings: Vec<u32> = Vec::new();
+    for player in [&game.black_uuid, &game.white_uuid] {
+        let rating: Option<u32> = tx
+            .exec_first(
+                "SELECT rating FROM players WHERE player_uuid = UUID_TO_BIN(:player_uuid) FOR UPDATE",
+                params! { "player_uuid" => player },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        match rating {
+            Some(r) => ratings.push(r),
+            None => return Ok(()),
+        }
+    }
+    let (black, white) = updated_ratings(ratings[0], ratings[1], game.state);
+    for (player, rating) in [(&game.black_uuid, black), (&game.white_uuid, white)] {
+        tx.exec_drop(
+            "UPDATE players SET rating = :rating WHERE player_uuid = UUID_TO_BIN(:player_uuid)",
+            params! { "rating" => rating, "player_uuid" => player },
+        )
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+    }
+    Ok(())
 }
 
 pub struct MySqlGameRepository {
-    pool: Pool,
+    pool: MySqlPool,
 }
 
 #[async_trait]
@@ -44,7 +284,8 @@ impl GameRepository for MySqlGameRepository {
                 IFNULL(BIN_TO_UUID(white_uuid), '') AS white_uuid,
                 position_black,
                 position_white,
-                state
+                state,
+                time_control
             FROM games
             WHERE game_uuid = UUID_TO_BIN(:game_uuid)
             "#,
@@ -55,13 +296,22 @@ impl GameRepository for MySqlGameRepository {
             .await
             .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?
             .map(
-                |(game_uuid, black_uuid, white_uuid, position_black, position_white, state)| Game {
+                |(
                     game_uuid,
                     black_uuid,
                     white_uuid,
                     position_black,
                     position_white,
                     state,
+                    time_control,
+                )| Game {
+                    game_uuid,
+                    black_uuid,
+                    white_uuid,
+                    position_black,
+                    position_white,
+                    state,
+                    time_control,
                 },
             );
 
@@ -119,50 +369,296 @@ impl GameRepository for MySqlGameRepository {
         }
     }
 
+    async fn get_moves(&self, game_uuid: &str) -> Result<Vec<u64>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let moves: Vec<u64> = conn
+            .exec_map(
+                r#"
+                SELECT move_position
+                FROM moves
+                WHERE game_uuid = UUID_TO_BIN(:game_uuid)
+                ORDER BY move_number ASC
+                "#,
+                params! {
+                    "game_uuid" => game_uuid,
+                },
+                |move_position: u64| move_position,
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(moves)
+    }
+
     async fn create_game(&self, game: &Game) -> Result<(), RepositoryError> {
         let mut conn = self
             .pool
             .get_conn()
             .await
             .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        insert_game(&mut *conn, game).await
+    }
 
-        conn.exec_drop(
+    async fn update_game(
+        &self,
+        game: &Game,
+        record: Option<&MoveRecord>,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        let mut tx = conn
+            .start_transaction(TxOpts::default())
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        update_game_in(&mut tx, game).await?;
+        if let Some(record) = record {
+            complete_move_key(&mut tx, record).await?;
+        }
+        tx.commit()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn update_game_with_move(
+        &self,
+        game: &Game,
+        move_bit: u64,
+        move_no: u64,
+        record: Option<&MoveRecord>,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        let mut tx = conn
+            .start_transaction(TxOpts::default())
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        update_game_in(&mut tx, game).await?;
+        insert_move(
+            &mut tx,
+            &game.game_uuid,
+            move_bit,
+            move_no,
+            game.position_black,
+            game.position_white,
+        )
+        .await?;
+        if let Some(record) = record {
+            complete_move_key(&mut tx, record).await?;
+        }
+        tx.commit()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn create_match(
+        &self,
+        game_match: &GameMatch,
+        games: &[NewMatchGame],
+    ) -> Result<(), RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        let mut tx = conn
+            .start_transaction(TxOpts::default())
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        tx.exec_drop(
             r#"
-            INSERT INTO games (
-                game_uuid,
-                black_uuid,
-                white_uuid,
-                position_black,
-                position_white,
-                state,
+            INSERT INTO matches (
+                match_uuid,
+                player_a_uuid,
+                player_b_uuid,
+                games,
+                time_control,
                 start_date
             )
             VALUES (
-                UUID_TO_BIN(:game_uuid),
-                IF(:black_uuid = '', NULL, UUID_TO_BIN(:black_uuid)),
-                IF(:white_uuid = '', NULL, UUID_TO_BIN(:white_uuid)),
-                :position_black,
-                :position_white,
-                :state,
+                UUID_TO_BIN(:match_uuid),
+                UUID_TO_BIN(:player_a_uuid),
+                UUID_TO_BIN(:player_b_uuid),
+                :games,
+                :time_control,
                 NOW()
             )
             "#,
             params! {
-                "game_uuid" => &game.game_uuid,
-                "black_uuid" => &game.black_uuid,
-                "white_uuid" => &game.white_uuid,
-                "position_black" => game.position_black,
-                "position_white" => game.position_white,
-                "state" => game.state,
+                "match_uuid" => &game_match.match_uuid,
+                "player_a_uuid" => &game_match.player_a_uuid,
+                "player_b_uuid" => &game_match.player_b_uuid,
+                "games" => game_match.games,
+                "time_control" => &game_match.time_control,
             },
         )
         .await
         .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
 
+        for new_game in games {
+            insert_game(&mut tx, &new_game.game).await?;
+            for (idx, m) in new_game.moves.iter().enumerate() {
+                insert_move(
+                    &mut tx,
+                    &new_game.game.game_uuid,
+                    m.move_bit,
+                    idx as u64 + 1,
+                    m.position_black,
+                    m.position_white,
+                )
+                .await?;
+            }
+            tx.exec_drop(
+                r#"
+                INSERT INTO match_games (
+                    match_uuid,
+                    game_no,
+                    game_uuid,
+                    opening
+                )
+                VALUES (
+                    UUID_TO_BIN(:match_uuid),
+                    :game_no,
+                    UUID_TO_BIN(:game_uuid),
+                    :opening
+                )
+                "#,
+                params! {
+                    "match_uuid" => &game_match.match_uuid,
+                    "game_no" => new_game.game_no,
+                    "game_uuid" => &new_game.game.game_uuid,
+                    "opening" => &new_game.opening,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
         Ok(())
     }
 
-    async fn update_game(&self, game: &Game) -> Result<(), RepositoryError> {
+    async fn save_annotations(
+        &self,
+        game_uuid: &str,
+        annotations: &[MoveAnnotation],
+    ) -> Result<(), RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        let mut tx = conn
+            .start_transaction(TxOpts::default())
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        for annotation in annotations {
+            tx.exec_drop(
+                r#"
+                REPLACE INTO move_annotations (
+                    game_uuid,
+                    move_number,
+                    played_move,
+                    best_move,
+                    best_score,
+                    played_score,
+                    exact
+                )
+                VALUES (
+                    UUID_TO_BIN(:game_uuid),
+                    :move_number,
+                    :played_move,
+                    :best_move,
+                    :best_score,
+                    :played_score,
+                    :exact
+                )
+                "#,
+                params! {
+                    "game_uuid" => game_uuid,
+                    "move_number" => annotation.move_number,
+                    "played_move" => annotation.played_move,
+                    "best_move" => annotation.best_move,
+                    "best_score" => annotation.best_score,
+                    "played_score" => annotation.played_score,
+                    "exact" => annotation.exact,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn get_annotations(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Vec<MoveAnnotation>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let annotations: Vec<MoveAnnotation> = conn
+            .exec_map(
+                r#"
+                SELECT
+                    move_number,
+                    played_move,
+                    best_move,
+                    best_score,
+                    played_score,
+                    exact
+                FROM move_annotations
+                WHERE game_uuid = UUID_TO_BIN(:game_uuid)
+                ORDER BY move_number ASC
+                "#,
+                params! {
+                    "game_uuid" => game_uuid,
+                },
+                |(move_number, played_move, best_move, best_score, played_score, exact)| {
+                    MoveAnnotation {
+                        move_number,
+                        played_move,
+                        best_move,
+                        best_score,
+                        played_score,
+                        exact,
+                    }
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(annotations)
+    }
+
+    async fn queue_annotation_job(&self, game_uuid: &str) -> Result<(), RepositoryError> {
         let mut conn = self
             .pool
             .get_conn()
@@ -171,23 +667,16 @@ impl GameRepository for MySqlGameRepository {
 
         conn.exec_drop(
             r#"
-            UPDATE games
-            SET
-                black_uuid = UUID_TO_BIN(:black_uuid),
-                white_uuid = UUID_TO_BIN(:white_uuid),
-                position_black = :position_black,
-                position_white = :position_white,
-                state = :state,
-                end_date = NOW()
-            WHERE game_uuid = UUID_TO_BIN(:game_uuid)
+            INSERT INTO annotation_jobs (game_uuid, status, queued_at)
+            VALUES (UUID_TO_BIN(:game_uuid), 'queued', NOW())
+            ON DUPLICATE KEY UPDATE
+                status = 'queued',
+                error = NULL,
+                queued_at = NOW(),
+                finished_at = NULL
             "#,
             params! {
-                "game_uuid" => &game.game_uuid,
-                "black_uuid" => &game.black_uuid,
-                "white_uuid" => &game.white_uuid,
-                "position_black" => game.position_black,
-                "position_white" => game.position_white,
-                "state" => game.state,
+                "game_uuid" => game_uuid,
             },
         )
         .await
@@ -196,72 +685,240 @@ impl GameRepository for MySqlGameRepository {
         Ok(())
     }
 
-    async fn update_game_with_move(
+    async fn update_annotation_job(
         &self,
-        game: &Game,
-        move_bit: u64,
-        move_no: u64,
+        game_uuid: &str,
+        status: &str,
+        error: &str,
     ) -> Result<(), RepositoryError> {
         let mut conn = self
             .pool
             .get_conn()
             .await
             .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
-        let mut tx = conn
-            .start_transaction(TxOpts::default())
-            .await
-            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
-        tx.exec_drop(
+
+        conn.exec_drop(
             r#"
-            UPDATE games
+            UPDATE annotation_jobs
             SET
-                black_uuid = UUID_TO_BIN(:black_uuid),
-                white_uuid = UUID_TO_BIN(:white_uuid),
-                position_black = :position_black,
-                position_white = :position_white,
-                state = :state,
-                end_date = NOW()
+                status = :status,
+                error = IF(:error = '', NULL, :error),
+                finished_at = IF(:status IN ('done', 'failed'), NOW(), NULL)
             WHERE game_uuid = UUID_TO_BIN(:game_uuid)
             "#,
             params! {
-                "game_uuid" => &game.game_uuid,
-                "black_uuid" => &game.black_uuid,
-                "white_uuid" => &game.white_uuid,
-                "position_black" => game.position_black,
-                "position_white" => game.position_white,
-                "state" => game.state,
+                "game_uuid" => game_uuid,
+                "status" => status,
+                "error" => error,
             },
         )
         .await
         .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
 
-        tx.exec_drop(
+        Ok(())
+    }
+
+    async fn get_annotation_job(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Option<AnnotationJob>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let job: Option<AnnotationJob> = conn
+            .exec_first(
+                r#"
+                SELECT
+                    BIN_TO_UUID(game_uuid) AS game_uuid,
+                    status,
+                    COALESCE(error, '') AS error
+                FROM annotation_jobs
+                WHERE game_uuid = UUID_TO_BIN(:game_uuid)
+                "#,
+                params! {
+                    "game_uuid" => game_uuid,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?
+            .map(|(game_uuid, status, error)| AnnotationJob {
+                game_uuid,
+                status,
+                error,
+            });
+
+        Ok(job)
+    }
+
+    async fn unfinished_annotation_jobs(&self) -> Result<Vec<String>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let games: Vec<String> = conn
+            .query(
+                r#"
+                SELECT BIN_TO_UUID(game_uuid)
+                FROM annotation_jobs
+                WHERE status IN ('queued', 'running')
+                ORDER BY queued_at ASC
+                "#,
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(games)
+    }
+
+    async fn get_move_record(
+        &self,
+        game_uuid: &str,
+        idempotency_key: &str,
+        ttl_secs: u64,
+    ) -> Result<Option<MoveRecord>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let record: Option<MoveRecord> = conn
+            .exec_first(
+                r#"
+                SELECT
+                    BIN_TO_UUID(game_uuid) AS game_uuid,
+                    idempotency_key,
+                    BIN_TO_UUID(player_uuid) AS player_uuid,
+                    played_move,
+                    response
+                FROM move_idempotency_keys
+                WHERE game_uuid = UUID_TO_BIN(:game_uuid)
+                    AND idempotency_key = :idempotency_key
+                    AND created_at >= NOW() - INTERVAL :ttl_secs SECOND
+                "#,
+                params! {
+                    "game_uuid" => game_uuid,
+                    "idempotency_key" => idempotency_key,
+                    "ttl_secs" => ttl_secs,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?
+            .map(
+                |(game_uuid, idempotency_key, player_uuid, played_move, response)| MoveRecord {
+                    game_uuid,
+                    idempotency_key,
+                    player_uuid,
+                    played_move,
+                    response,
+                },
+            );
+
+        Ok(record)
+    }
+
+    async fn reserve_move_key(
+        &self,
+        record: &MoveRecord,
+        ttl_secs: u64,
+    ) -> Result<bool, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        conn.exec_drop(
+            "DELETE FROM move_idempotency_keys WHERE created_at < NOW() - INTERVAL :ttl_secs SECOND",
+            params! {
+                "ttl_secs" => ttl_secs,
+            },
+        )
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        // The primary key lets only one of concurrent attempts insert the row.
+        conn.exec_drop(
             r#"
-            INSERT INTO moves (
+            INSERT INTO move_idempotency_keys (
                 game_uuid,
-                move_number,
-                move_position,
-                position_black,
-                position_white,
-                move_date
-            ) VALUES (UUID_TO_BIN(:game_uuid), :move_number, :next_move, :position_black, :position_white, NOW())
+                idempotency_key,
+                player_uuid,
+                played_move,
+                response,
+                created_at
+            )
+            VALUES (
+                UUID_TO_BIN(:game_uuid),
+                :idempotency_key,
+                UUID_TO_BIN(:player_uuid),
+                :played_move,
+                '',
+                NOW()
+            )
+            ON DUPLICATE KEY UPDATE game_uuid = game_uuid
             "#,
             params! {
-                "game_uuid" => &game.game_uuid,
-                "next_move" => move_bit,
-                "move_number" => move_no,
-                "position_black" => game.position_black,
-                "position_white" => game.position_white,
+                "game_uuid" => &record.game_uuid,
+                "idempotency_key" => &record.idempotency_key,
+                "player_uuid" => &record.player_uuid,
+                "played_move" => &record.played_move,
             },
-            ).await.map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        )
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
 
-        tx.commit()
+        Ok(conn.affected_rows() == 1)
+    }
+
+    async fn release_move_key(
+        &self,
+        game_uuid: &str,
+        idempotency_key: &str,
+    ) -> Result<(), RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
             .await
             .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
 
+        conn.exec_drop(
+            r#"
+            DELETE FROM move_idempotency_keys
+            WHERE game_uuid = UUID_TO_BIN(:game_uuid)
+                AND idempotency_key = :idempotency_key
+                AND response = ''
+            "#,
+            params! {
+                "game_uuid" => game_uuid,
+                "idempotency_key" => idempotency_key,
+            },
+        )
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
         Ok(())
     }
 
+    async fn count_games_by_state(&self) -> Result<Vec<(u64, u64)>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let counts: Vec<(u64, u64)> = conn
+            .query("SELECT state, COUNT(*) FROM games GROUP BY state ORDER BY state")
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(counts)
+    }
+
     async fn pending_games(&self, player_uuid: String) -> Result<Vec<Game>, RepositoryError> {
         let mut conn = self
             .pool
@@ -276,18 +933,28 @@ impl GameRepository for MySqlGameRepository {
                 IFNULL(BIN_TO_UUID(white_uuid), '') AS white_uuid,
                 position_black,
                 position_white,
-                state
+                state,
+                time_control
             FROM games
             WHERE state = 0 AND IFNULL(bin_to_uuid(black_uuid), bin_to_uuid(white_uuid)) <> ?
             ORDER BY start_date ASC",
                 (player_uuid.clone(),),
-                |(game_uuid, black_uuid, white_uuid, position_black, position_white, state)| Game {
+                |(
                     game_uuid,
                     black_uuid,
                     white_uuid,
                     position_black,
                     position_white,
                     state,
+                    time_control,
+                )| Game {
+                    game_uuid,
+                    black_uuid,
+                    white_uuid,
+                    position_black,
+                    position_white,
+                    state,
+                    time_control,
                 },
             )
             .await
@@ -295,100 +962,214 @@ impl GameRepository for MySqlGameRepository {
 
         Ok(games)
     }
-}
 
-impl MySqlGameRepository {
-    pub fn new(pool: Pool) -> Self {
-        MySqlGameRepository { pool }
-    }
-}
+    async fn player_stats(
+        &self,
+        player_uuid: &str,
+    ) -> Result<Option<PlayerStats>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
 
-pub struct MockGameRepository {
-    games: RwLock<HashMap<String, Game>>,
-    moves: RwLock<HashMap<String, Vec<u64>>>,
-}
+        let rating: Option<u32> = conn
+            .exec_first(
+                "SELECT rating FROM players WHERE player_uuid = UUID_TO_BIN(:player_uuid)",
+                params! {
+                    "player_uuid" => player_uuid,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        let rating: u32 = match rating {
+            Some(r) => r,
+            None => return Ok(None),
+        };
 
-impl MockGameRepository {
-    pub fn new() -> Self {
-        Self {
-            games: RwLock::new(HashMap::new()),
-            moves: RwLock::new(HashMap::new()),
+        let row: Option<(u64, u64, u64, u64, u64, u64, u64, f64)> = conn
+            .exec_first(
+                r#"
+            SELECT
+                COUNT(*) AS games_played,
+                CAST(IFNULL(SUM(black_uuid = UUID_TO_BIN(:player_uuid) AND state = 3), 0) AS UNSIGNED) AS wins_black,
+                CAST(IFNULL(SUM(black_uuid = UUID_TO_BIN(:player_uuid) AND state = 4), 0) AS UNSIGNED) AS losses_black,
+                CAST(IFNULL(SUM(black_uuid = UUID_TO_BIN(:player_uuid) AND state = 5), 0) AS UNSIGNED) AS draws_black,
+                CAST(IFNULL(SUM(white_uuid = UUID_TO_BIN(:player_uuid) AND state = 4), 0) AS UNSIGNED) AS wins_white,
+                CAST(IFNULL(SUM(white_uuid = UUID_TO_BIN(:player_uuid) AND state = 3), 0) AS UNSIGNED) AS losses_white,
+                CAST(IFNULL(SUM(white_uuid = UUID_TO_BIN(:player_uuid) AND state = 5), 0) AS UNSIGNED) AS draws_white,
+                CAST(IFNULL(AVG(
+                    IF(
+                        black_uuid = UUID_TO_BIN(:player_uuid),
+                        CAST(BIT_COUNT(position_black) AS SIGNED) - CAST(BIT_COUNT(position_white) AS SIGNED),
+                        CAST(BIT_COUNT(position_white) AS SIGNED) - CAST(BIT_COUNT(position_black) AS SIGNED)
+                    )
+                ), 0) AS DOUBLE) AS average_disc_differential
+            FROM games
+            WHERE state IN (3, 4, 5)
+                AND (black_uuid = UUID_TO_BIN(:player_uuid) OR white_uuid = UUID_TO_BIN(:player_uuid))
+            "#,
+                params! {
+                    "player_uuid" => player_uuid,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let mut stats: PlayerStats = PlayerStats {
+            player_id: player_uuid.to_string(),
+            rating,
+            ..Default::default()
+        };
+        if let Some((
+            games_played,
+            wins_black,
+            losses_black,
+            draws_black,
+            wins_white,
+            losses_white,
+            draws_white,
+            average_disc_differential,
+        )) = row
+        {
+            stats.games_played = games_played;
+            stats.wins_black = wins_black;
+            stats.losses_black = losses_black;
+            stats.draws_black = draws_black;
+            stats.wins_white = wins_white;
+            stats.losses_white = losses_white;
+            stats.draws_white = draws_white;
+            stats.average_disc_differential = average_disc_differential;
         }
-    }
 
-    pub fn insert_game(&self, game_uuid: &str, game: Game) {
-        self.games
-            .write()
-            .unwrap()
-            .insert(game_uuid.to_string(), game);
+        Ok(Some(stats))
     }
 
-    pub fn insert_move(&self, game_uuid: &str, move_number: u64) {
-        self.moves
-            .write()
-            .unwrap()
-            .entry(game_uuid.to_string())
-            .or_default()
-            .push(move_number);
-    }
-}
+    async fn leaderboard(
+        &self,
+        offset: u64,
+        limit: u64,
+        time_control: &str,
+    ) -> Result<Vec<LeaderboardEntry>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
 
-#[async_trait]
-impl GameRepository for MockGameRepository {
-    async fn get_game(&self, game_uuid: &str) -> Result<Option<Game>, RepositoryError> {
-        let guard = self.games.read().unwrap();
-        Ok(guard.get(game_uuid).cloned())
-    }
+        let rows: Vec<(String, u32, u64, u64, u64, u64)> = conn
+            .exec(
+                r#"
+            SELECT
+                BIN_TO_UUID(p.player_uuid) AS player_uuid,
+                p.rating,
+                COUNT(*) AS games_played,
+                CAST(SUM(
+                    (g.black_uuid = p.player_uuid AND g.state = 3)
+                    OR (g.white_uuid = p.player_uuid AND g.state = 4)
+                ) AS UNSIGNED) AS wins,
+                CAST(SUM(
+                    (g.black_uuid = p.player_uuid AND g.state = 4)
+                    OR (g.white_uuid = p.player_uuid AND g.state = 3)
+                ) AS UNSIGNED) AS losses,
+                CAST(SUM(g.state = 5) AS UNSIGNED) AS draws
+            FROM players p
+            JOIN games g
+                ON (g.black_uuid = p.player_uuid OR g.white_uuid = p.player_uuid)
+                AND g.state IN (3, 4, 5)
+                AND (:time_control = '' OR g.time_control = :time_control)
+            GROUP BY p.player_uuid, p.rating
+            ORDER BY p.rating DESC, wins DESC, games_played DESC, player_uuid ASC
+            LIMIT :limit OFFSET :offset
+            "#,
+                params! {
+                    "time_control" => time_control,
+                    "limit" => limit,
+                    "offset" => offset,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
 
-    async fn get_max_move_no(&self, game_uuid: &str) -> Result<u64, RepositoryError> {
-        let guard = self.moves.read().unwrap();
-        let max_move = guard
-            .get(game_uuid)
-            .and_then(|m| m.iter().max())
-            .cloned()
-            .unwrap_or(0);
-        Ok(max_move)
-    }
+        let entries: Vec<LeaderboardEntry> = rows
+            .into_iter()
+            .enumerate()
+            .map(
+                |(idx, (player_id, rating, games_played, wins, losses, draws))| LeaderboardEntry {
+                    rank: offset + idx as u64 + 1,
+                    player_id,
+                    rating,
+                    games_played,
+                    wins,
+                    losses,
+                    draws,
+                },
+            )
+            .collect();
 
-    async fn get_last_move(&self, game_uuid: &str) -> Result<u64, RepositoryError> {
-        let guard = self.moves.read().unwrap();
-        match guard.get(game_uuid) {
-            Some(m) => {
-                let l = m.len();
-                if l == 0 {
-                    return Err(RepositoryError::Other(
-                        "Failed to get last move".to_string(),
-                    ));
-                }
-                return Ok(m[l - 1]);
-            }
-            None => {
-                return Err(RepositoryError::Other(
-                    "Failed to get last move".to_string(),
-                ));
-            }
-        }
+        Ok(entries)
     }
+}
 
-    async fn create_game(&self, _game: &Game) -> Result<(), RepositoryError> {
-        Ok(())
+impl MySqlGameRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        MySqlGameRepository { pool }
     }
+}
 
-    async fn update_game(&self, _game: &Game) -> Result<(), RepositoryError> {
-        Ok(())
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    async fn update_game_with_move(
-        &self,
-        _game: &Game,
-        _move_bit: u64,
-        _move_no: u64,
-    ) -> Result<(), RepositoryError> {
-        Ok(())
-    }
+    /// Runs against the database in `REVERSI_TEST_MYSQL_URL`, which needs the
+    /// schema in `sql/`. Skipped without the variable.
+    #[tokio::test]
+    async fn mysql_conforms_to_the_repository_contract() {
+        let url: String = match std::env::var("REVERSI_TEST_MYSQL_URL") {
+            Ok(url) => url,
+            Err(_) => {
+                println!("REVERSI_TEST_MYSQL_URL is not set, skipping");
+                return;
+            }
+        };
+        let pool: MySqlPool = MySqlPool::new(mysql_async::Opts::from_url(url.as_str()).unwrap());
+        let players: [String; 2] = [
+            uuid::Uuid::new_v4().to_string(),
+            uuid::Uuid::new_v4().to_string(),
+        ];
+        let mut conn = pool.get_conn().await.unwrap();
+        for player in &players {
+            conn.exec_drop(
+                "INSERT INTO players (player_uuid, comment) VALUES (UUID_TO_BIN(:player_uuid), 'test')",
+                params! { "player_uuid" => player },
+            )
+            .await
+            .unwrap();
+        }
+        drop(conn);
 
-    async fn pending_games(&self, _player_uuid: String) -> Result<Vec<Game>, RepositoryError> {
-        let games: Vec<Game> = Vec::new();
-        Ok(games)
+        let players: [&str; 2] = [players[0].as_str(), players[1].as_str()];
+        crate::repository::conformance::check_player_repository(
+            &crate::repository::player_repository::MySqlPlayerRepository::new(pool.clone()),
+            players,
+        )
+        .await;
+        crate::repository::conformance::check_game_repository(
+            &MySqlGameRepository::new(pool.clone()),
+            players,
+        )
+        .await;
+        crate::repository::conformance::check_tournament_repository(
+            &crate::repository::tournament_repository::MySqlTournamentRepository::new(pool.clone()),
+            &MySqlGameRepository::new(pool.clone()),
+            players,
+        )
+        .await;
+        crate::repository::conformance::check_match_repository(
+            &crate::repository::match_repository::MySqlMatchRepository::new(pool.clone()),
+            &MySqlGameRepository::new(pool.clone()),
+            players,
+        )
+        .await;
     }
 }
@@ -0,0 +1,129 @@
+use crate::game_match::OpeningMove;
+use crate::model::{Game, GameMatch, MatchGame};
+use async_trait::async_trait;
+use mysql_async::{params, prelude::*};
+
+use crate::repository::db_errors::*;
+use crate::repository::mysql_pool::MySqlPool;
+
+/// A game of a new match, stored as it stands after its opening moves.
+#[derive(Debug, Clone)]
+pub struct NewMatchGame {
+    pub game_no: u64,
+    pub game: Game,
+    pub opening: String,
+    pub moves: Vec<OpeningMove>,
+}
+
+/// Reads matches. They are created with their games through
+/// `GameRepository::create_match`.
+#[async_trait]
+pub trait MatchRepository {
+    async fn get_match(&self, match_uuid: &str) -> Result<Option<GameMatch>, RepositoryError>;
+    /// Games of the match ordered by game number.
+    async fn get_match_games(&self, match_uuid: &str) -> Result<Vec<MatchGame>, RepositoryError>;
+}
+
+pub struct MySqlMatchRepository {
+    pool: MySqlPool,
+}
+
+#[async_trait]
+impl MatchRepository for MySqlMatchRepository {
+    async fn get_match(&self, match_uuid: &str) -> Result<Option<GameMatch>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let game_match: Option<GameMatch> = conn
+            .exec_first(
+                r#"
+            SELECT
+                BIN_TO_UUID(match_uuid) AS match_uuid,
+                BIN_TO_UUID(player_a_uuid) AS player_a_uuid,
+                BIN_TO_UUID(player_b_uuid) AS player_b_uuid,
+                games,
+                time_control
+            FROM matches
+            WHERE match_uuid = UUID_TO_BIN(:match_uuid)
+            "#,
+                params! {
+                    "match_uuid" => match_uuid,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?
+            .map(
+                |(match_uuid, player_a_uuid, player_b_uuid, games, time_control)| GameMatch {
+                    match_uuid,
+                    player_a_uuid,
+                    player_b_uuid,
+                    games,
+                    time_control,
+                },
+            );
+
+        Ok(game_match)
+    }
+
+    async fn get_match_games(&self, match_uuid: &str) -> Result<Vec<MatchGame>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let games: Vec<MatchGame> = conn
+            .exec_map(
+                r#"
+            SELECT
+                m.game_no,
+                BIN_TO_UUID(m.game_uuid) AS game_uuid,
+                BIN_TO_UUID(g.black_uuid) AS black_uuid,
+                BIN_TO_UUID(g.white_uuid) AS white_uuid,
+                m.opening,
+                g.state,
+                g.position_black,
+                g.position_white
+            FROM match_games m
+            JOIN games g ON g.game_uuid = m.game_uuid
+            WHERE m.match_uuid = UUID_TO_BIN(:match_uuid)
+            ORDER BY m.game_no ASC
+            "#,
+                params! {
+                    "match_uuid" => match_uuid,
+                },
+                |(
+                    game_no,
+                    game_uuid,
+                    black_uuid,
+                    white_uuid,
+                    opening,
+                    game_state,
+                    position_black,
+                    position_white,
+                )| MatchGame {
+                    game_no,
+                    game_uuid,
+                    black_uuid,
+                    white_uuid,
+                    opening,
+                    game_state,
+                    position_black,
+                    position_white,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(games)
+    }
+}
+
+impl MySqlMatchRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        MySqlMatchRepository { pool }
+    }
+}
@@ -0,0 +1,989 @@
+use crate::model::{
+    AnnotationJob, Game, GameMatch, LeaderboardEntry, MatchGame, MoveAnnotation, MoveRecord,
+    PlayerStats, Tournament, TournamentPairing, User,
+};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::rating::{finishes, updated_ratings, DEFAULT_RATING};
+use crate::repository::db_errors::*;
+use crate::repository::game_repository::GameRepository;
+use crate::repository::match_repository::{MatchRepository, NewMatchGame};
+use crate::repository::player_repository::PlayerRepository;
+use crate::repository::tournament_repository::TournamentRepository;
+
+struct StoredPlayer {
+    comment: String,
+    rating: u32,
+}
+
+struct StoredGame {
+    game: Game,
+    /// Creation order, stands in for `start_date`.
+    created: u64,
+    /// Moves as (move number, move position).
+    moves: Vec<(u64, u64)>,
+    annotations: Vec<MoveAnnotation>,
+}
+
+struct StoredJob {
+    job: AnnotationJob,
+    /// Queueing order, stands in for `queued_at`.
+    queued: u64,
+}
+
+struct StoredMoveRecord {
+    record: MoveRecord,
+    created: Instant,
+}
+
+struct StoredMatchGame {
+    game_no: u64,
+    game_uuid: String,
+    opening: String,
+}
+
+#[derive(Default)]
+struct MemoryStore {
+    players: HashMap<String, StoredPlayer>,
+    player_order: Vec<String>,
+    games: HashMap<String, StoredGame>,
+    annotation_jobs: HashMap<String, StoredJob>,
+    /// Keyed by game and idempotency key.
+    move_records: HashMap<(String, String), StoredMoveRecord>,
+    tournaments: HashMap<String, Tournament>,
+    /// Players of each tournament in registration order.
+    tournament_players: HashMap<String, Vec<String>>,
+    /// Pairings of all tournaments with their tournament, in creation order.
+    /// Game states and positions are read from `games`.
+    pairings: Vec<(String, TournamentPairing)>,
+    matches: HashMap<String, GameMatch>,
+    match_games: HashMap<String, Vec<StoredMatchGame>>,
+    sequence: u64,
+}
+
+impl MemoryStore {
+    fn next_sequence(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+
+    /// Mirrors the foreign keys from games to players.
+    fn check_players(&self, game: &Game) -> Result<(), RepositoryError> {
+        for player in [&game.black_uuid, &game.white_uuid] {
+            if !player.is_empty() && !self.players.contains_key(player) {
+                return Err(RepositoryError::DatabaseError(format!(
+                    "Unknown player {}",
+                    player
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Mirrors the constraints on a new game.
+    fn check_new_game(&self, game: &Game) -> Result<(), RepositoryError> {
+        self.check_players(game)?;
+        if self.games.contains_key(&game.game_uuid) {
+            return Err(RepositoryError::DatabaseError(format!(
+                "Duplicate game {}",
+                game.game_uuid
+            )));
+        }
+        Ok(())
+    }
+
+    fn insert_game(&mut self, game: &Game) -> Result<(), RepositoryError> {
+        self.check_new_game(game)?;
+        let created: u64 = self.next_sequence();
+        self.games.insert(
+            game.game_uuid.clone(),
+            StoredGame {
+                game: game.clone(),
+                created,
+                moves: Vec::new(),
+                annotations: Vec::new(),
+            },
+        );
+        Ok(())
+    }
+
+    fn tournament_mut(
+        &mut self,
+        tournament_uuid: &str,
+    ) -> Result<&mut Tournament, RepositoryError> {
+        self.tournaments.get_mut(tournament_uuid).ok_or_else(|| {
+            RepositoryError::DatabaseError(format!("Unknown tournament {}", tournament_uuid))
+        })
+    }
+
+    fn game_mut(&mut self, game_uuid: &str) -> Result<&mut StoredGame, RepositoryError> {
+        self.games
+            .get_mut(game_uuid)
+            .ok_or_else(|| RepositoryError::DatabaseError(format!("Unknown game {}", game_uuid)))
+    }
+
+    /// Fills in the response of the move's reserved idempotency key.
+    fn complete_move_key(&mut self, record: Option<&MoveRecord>) {
+        let record: &MoveRecord = match record {
+            Some(r) => r,
+            None => return,
+        };
+        let key: (String, String) = (record.game_uuid.clone(), record.idempotency_key.clone());
+        if let Some(stored) = self.move_records.get_mut(&key) {
+            stored.record.response = record.response.clone();
+        }
+    }
+
+    /// The update that finishes a rated game also updates the ratings of both
+    /// players.
+    fn update_game(&mut self, game: &Game) -> Result<(), RepositoryError> {
+        self.check_players(game)?;
+        let previous: u64 = match self.games.get_mut(&game.game_uuid) {
+            Some(stored) => std::mem::replace(&mut stored.game, game.clone()).state,
+            None => return Ok(()),
+        };
+        if !finishes(previous, game.state)
+            || game.black_uuid.is_empty()
+            || game.white_uuid.is_empty()
+        {
+            return Ok(());
+        }
+        let black: u32 = self.players[&game.black_uuid].rating;
+        let white: u32 = self.players[&game.white_uuid].rating;
+        let (black, white) = updated_ratings(black, white, game.state);
+        if let Some(player) = self.players.get_mut(&game.black_uuid) {
+            player.rating = black;
+        }
+        if let Some(player) = self.players.get_mut(&game.white_uuid) {
+            player.rating = white;
+        }
+        Ok(())
+    }
+}
+
+/// Storage kept in the memory of the server process, shared by the
+/// repositories and lost when the server stops.
+#[derive(Clone, Default)]
+pub struct MemoryDatabase {
+    store: Arc<RwLock<MemoryStore>>,
+}
+
+impl MemoryDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses `memory://`, optionally followed by `?players=<uuid>,<uuid>,...`
+    /// to register players up front.
+    pub fn open(database_url: &str) -> Result<MemoryDatabase, RepositoryError> {
+        let db: MemoryDatabase = MemoryDatabase::new();
+        let rest: &str = database_url.strip_prefix("memory://").unwrap_or("");
+        let query: &str = match rest.split_once('?') {
+            Some((_, query)) => query,
+            None => return Ok(db),
+        };
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            match pair.split_once('=') {
+                Some(("players", players)) => {
+                    for player in players.split(',').filter(|p| !p.is_empty()) {
+                        db.add_player(player, "");
+                    }
+                }
+                _ => {
+                    return Err(RepositoryError::Unsupported(format!(
+                        "unknown memory:// parameter {}",
+                        pair
+                    )))
+                }
+            }
+        }
+        Ok(db)
+    }
+
+    /// Registers a player with the default rating, like a row inserted into
+    /// the players table by hand.
+    pub fn add_player(&self, player_uuid: &str, comment: &str) {
+        let mut store = self.store.write().unwrap();
+        if store.players.contains_key(player_uuid) {
+            return;
+        }
+        store.players.insert(
+            player_uuid.to_string(),
+            StoredPlayer {
+                comment: comment.to_string(),
+                rating: DEFAULT_RATING,
+            },
+        );
+        store.player_order.push(player_uuid.to_string());
+    }
+}
+
+pub struct MemoryGameRepository {
+    db: MemoryDatabase,
+}
+
+#[async_trait]
+impl GameRepository for MemoryGameRepository {
+    async fn pending_games(&self, player_uuid: String) -> Result<Vec<Game>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        let mut pending: Vec<&StoredGame> = store
+            .games
+            .values()
+            .filter(|g| g.game.state == 0)
+            .filter(|g| {
+                let creator: &str = if g.game.black_uuid.is_empty() {
+                    &g.game.white_uuid
+                } else {
+                    &g.game.black_uuid
+                };
+                creator != player_uuid
+            })
+            .collect();
+        pending.sort_by_key(|g| g.created);
+        Ok(pending.into_iter().map(|g| g.game.clone()).collect())
+    }
+
+    async fn count_games_by_state(&self) -> Result<Vec<(u64, u64)>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        let mut counts: Vec<(u64, u64)> = Vec::new();
+        for g in store.games.values() {
+            match counts.iter_mut().find(|(state, _)| *state == g.game.state) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((g.game.state, 1)),
+            }
+        }
+        counts.sort();
+        Ok(counts)
+    }
+
+    async fn get_move_record(
+        &self,
+        game_uuid: &str,
+        idempotency_key: &str,
+        ttl_secs: u64,
+    ) -> Result<Option<MoveRecord>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        let key: (String, String) = (game_uuid.to_string(), idempotency_key.to_string());
+        Ok(store
+            .move_records
+            .get(&key)
+            .filter(|r| r.created.elapsed() <= Duration::from_secs(ttl_secs))
+            .map(|r| r.record.clone()))
+    }
+
+    async fn reserve_move_key(
+        &self,
+        record: &MoveRecord,
+        ttl_secs: u64,
+    ) -> Result<bool, RepositoryError> {
+        let mut store = self.db.store.write().unwrap();
+        store.game_mut(&record.game_uuid)?;
+        store
+            .move_records
+            .retain(|_, r| r.created.elapsed() <= Duration::from_secs(ttl_secs));
+        let key: (String, String) = (record.game_uuid.clone(), record.idempotency_key.clone());
+        if store.move_records.contains_key(&key) {
+            return Ok(false);
+        }
+        store.move_records.insert(
+            key,
+            StoredMoveRecord {
+                record: MoveRecord {
+                    response: String::new(),
+                    ..record.clone()
+                },
+                created: Instant::now(),
+            },
+        );
+        Ok(true)
+    }
+
+    async fn release_move_key(
+        &self,
+        game_uuid: &str,
+        idempotency_key: &str,
+    ) -> Result<(), RepositoryError> {
+        let mut store = self.db.store.write().unwrap();
+        let key: (String, String) = (game_uuid.to_string(), idempotency_key.to_string());
+        if store
+            .move_records
+            .get(&key)
+            .is_some_and(|r| r.record.response.is_empty())
+        {
+            store.move_records.remove(&key);
+        }
+        Ok(())
+    }
+
+    async fn get_game(&self, game_uuid: &str) -> Result<Option<Game>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        Ok(store.games.get(game_uuid).map(|g| g.game.clone()))
+    }
+
+    async fn get_max_move_no(&self, game_uuid: &str) -> Result<u64, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        Ok(store
+            .games
+            .get(game_uuid)
+            .and_then(|g| g.moves.iter().map(|(number, _)| *number).max())
+            .unwrap_or(0))
+    }
+
+    async fn get_last_move(&self, game_uuid: &str) -> Result<u64, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        Ok(store
+            .games
+            .get(game_uuid)
+            .and_then(|g| g.moves.iter().max_by_key(|(number, _)| *number))
+            .map(|(_, position)| *position)
+            .unwrap_or(0))
+    }
+
+    async fn get_moves(&self, game_uuid: &str) -> Result<Vec<u64>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        let mut moves: Vec<(u64, u64)> = store
+            .games
+            .get(game_uuid)
+            .map(|g| g.moves.clone())
+            .unwrap_or_default();
+        moves.sort_by_key(|(number, _)| *number);
+        Ok(moves.into_iter().map(|(_, position)| position).collect())
+    }
+
+    async fn create_game(&self, game: &Game) -> Result<(), RepositoryError> {
+        let mut store = self.db.store.write().unwrap();
+        store.insert_game(game)
+    }
+
+    async fn update_game(
+        &self,
+        game: &Game,
+        record: Option<&MoveRecord>,
+    ) -> Result<(), RepositoryError> {
+        let mut store = self.db.store.write().unwrap();
+        store.update_game(game)?;
+        store.complete_move_key(record);
+        Ok(())
+    }
+
+    async fn update_game_with_move(
+        &self,
+        game: &Game,
+        move_bit: u64,
+        move_no: u64,
+        record: Option<&MoveRecord>,
+    ) -> Result<(), RepositoryError> {
+        // All changes happen under one lock, checked before anything is written.
+        let mut store = self.db.store.write().unwrap();
+        store.check_players(game)?;
+        store.game_mut(&game.game_uuid)?;
+        store.update_game(game)?;
+        store
+            .game_mut(&game.game_uuid)?
+            .moves
+            .push((move_no, move_bit));
+        store.complete_move_key(record);
+        Ok(())
+    }
+
+    async fn create_match(
+        &self,
+        game_match: &GameMatch,
+        games: &[NewMatchGame],
+    ) -> Result<(), RepositoryError> {
+        // Everything is checked under the lock before anything is written.
+        let mut store = self.db.store.write().unwrap();
+        if store.matches.contains_key(&game_match.match_uuid) {
+            return Err(RepositoryError::DatabaseError(format!(
+                "Duplicate match {}",
+                game_match.match_uuid
+            )));
+        }
+        for player in [&game_match.player_a_uuid, &game_match.player_b_uuid] {
+            if !store.players.contains_key(player) {
+                return Err(RepositoryError::DatabaseError(format!(
+                    "Unknown player {}",
+                    player
+                )));
+            }
+        }
+        for new_game in games {
+            store.check_new_game(&new_game.game)?;
+        }
+
+        store
+            .matches
+            .insert(game_match.match_uuid.clone(), game_match.clone());
+        let mut match_games: Vec<StoredMatchGame> = Vec::new();
+        for new_game in games {
+            store.insert_game(&new_game.game)?;
+            let moves: &mut Vec<(u64, u64)> = &mut store.game_mut(&new_game.game.game_uuid)?.moves;
+            for (idx, m) in new_game.moves.iter().enumerate() {
+                moves.push((idx as u64 + 1, m.move_bit));
+            }
+            match_games.push(StoredMatchGame {
+                game_no: new_game.game_no,
+                game_uuid: new_game.game.game_uuid.clone(),
+                opening: new_game.opening.clone(),
+            });
+        }
+        store
+            .match_games
+            .insert(game_match.match_uuid.clone(), match_games);
+        Ok(())
+    }
+
+    async fn save_annotations(
+        &self,
+        game_uuid: &str,
+        annotations: &[MoveAnnotation],
+    ) -> Result<(), RepositoryError> {
+        let mut store = self.db.store.write().unwrap();
+        let stored: &mut Vec<MoveAnnotation> = &mut store.game_mut(game_uuid)?.annotations;
+        for annotation in annotations {
+            stored.retain(|a| a.move_number != annotation.move_number);
+            stored.push(annotation.clone());
+        }
+        stored.sort_by_key(|a| a.move_number);
+        Ok(())
+    }
+
+    async fn get_annotations(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Vec<MoveAnnotation>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        Ok(store
+            .games
+            .get(game_uuid)
+            .map(|g| g.annotations.clone())
+            .unwrap_or_default())
+    }
+
+    async fn queue_annotation_job(&self, game_uuid: &str) -> Result<(), RepositoryError> {
+        let mut store = self.db.store.write().unwrap();
+        store.game_mut(game_uuid)?;
+        let queued: u64 = store.next_sequence();
+        store.annotation_jobs.insert(
+            game_uuid.to_string(),
+            StoredJob {
+                job: AnnotationJob {
+                    game_uuid: game_uuid.to_string(),
+                    status: "queued".to_string(),
+                    error: String::new(),
+                },
+                queued,
+            },
+        );
+        Ok(())
+    }
+
+    async fn update_annotation_job(
+        &self,
+        game_uuid: &str,
+        status: &str,
+        error: &str,
+    ) -> Result<(), RepositoryError> {
+        let mut store = self.db.store.write().unwrap();
+        if let Some(stored) = store.annotation_jobs.get_mut(game_uuid) {
+            stored.job.status = status.to_string();
+            stored.job.error = error.to_string();
+        }
+        Ok(())
+    }
+
+    async fn get_annotation_job(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Option<AnnotationJob>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        Ok(store.annotation_jobs.get(game_uuid).map(|j| j.job.clone()))
+    }
+
+    async fn unfinished_annotation_jobs(&self) -> Result<Vec<String>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        let mut unfinished: Vec<&StoredJob> = store
+            .annotation_jobs
+            .values()
+            .filter(|j| j.job.status == "queued" || j.job.status == "running")
+            .collect();
+        unfinished.sort_by_key(|j| j.queued);
+        Ok(unfinished
+            .into_iter()
+            .map(|j| j.job.game_uuid.clone())
+            .collect())
+    }
+
+    async fn player_stats(
+        &self,
+        player_uuid: &str,
+    ) -> Result<Option<PlayerStats>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        let player: &StoredPlayer = match store.players.get(player_uuid) {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+        let mut stats: PlayerStats = PlayerStats {
+            player_id: player_uuid.to_string(),
+            rating: player.rating,
+            ..Default::default()
+        };
+        for stored in store.games.values() {
+            stats.record_game(&stored.game);
+        }
+        Ok(Some(stats))
+    }
+
+    async fn leaderboard(
+        &self,
+        offset: u64,
+        limit: u64,
+        time_control: &str,
+    ) -> Result<Vec<LeaderboardEntry>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        let mut ranked: Vec<PlayerStats> = Vec::new();
+        for (player_uuid, player) in &store.players {
+            let mut stats: PlayerStats = PlayerStats {
+                player_id: player_uuid.clone(),
+                rating: player.rating,
+                ..Default::default()
+            };
+            for stored in store.games.values() {
+                if time_control.is_empty() || stored.game.time_control == time_control {
+                    stats.record_game(&stored.game);
+                }
+            }
+            if stats.games_played > 0 {
+                ranked.push(stats);
+            }
+        }
+        ranked.sort_by(|a, b| {
+            b.rating
+                .cmp(&a.rating)
+                .then(b.wins().cmp(&a.wins()))
+                .then(b.games_played.cmp(&a.games_played))
+                .then(a.player_id.cmp(&b.player_id))
+        });
+        Ok(ranked
+            .into_iter()
+            .enumerate()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .map(|(idx, s)| LeaderboardEntry {
+                rank: idx as u64 + 1,
+                wins: s.wins(),
+                losses: s.losses(),
+                draws: s.draws(),
+                player_id: s.player_id,
+                rating: s.rating,
+                games_played: s.games_played,
+            })
+            .collect())
+    }
+}
+
+impl MemoryGameRepository {
+    pub fn new(db: MemoryDatabase) -> Self {
+        MemoryGameRepository { db }
+    }
+}
+
+pub struct MemoryPlayerRepository {
+    db: MemoryDatabase,
+}
+
+#[async_trait]
+impl PlayerRepository for MemoryPlayerRepository {
+    async fn player_list(&self) -> Result<Vec<User>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        Ok(store
+            .player_order
+            .iter()
+            .map(|player_uuid| User {
+                player_uuid: player_uuid.clone(),
+                comment: store.players[player_uuid].comment.clone(),
+            })
+            .collect())
+    }
+
+    async fn get_rating(&self, player_uuid: &str) -> Result<Option<u32>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        Ok(store.players.get(player_uuid).map(|p| p.rating))
+    }
+
+    async fn create_player(&self, player: &User) -> Result<(), RepositoryError> {
+        let mut store = self.db.store.write().unwrap();
+        // Mirrors the primary key of the players table.
+        if store.players.contains_key(&player.player_uuid) {
+            return Err(RepositoryError::DatabaseError(format!(
+                "Duplicate player {}",
+                player.player_uuid
+            )));
+        }
+        store.players.insert(
+            player.player_uuid.clone(),
+            StoredPlayer {
+                comment: player.comment.clone(),
+                rating: DEFAULT_RATING,
+            },
+        );
+        store.player_order.push(player.player_uuid.clone());
+        Ok(())
+    }
+}
+
+impl MemoryPlayerRepository {
+    pub fn new(db: MemoryDatabase) -> Self {
+        MemoryPlayerRepository { db }
+    }
+}
+
+pub struct MemoryTournamentRepository {
+    db: MemoryDatabase,
+}
+
+#[async_trait]
+impl TournamentRepository for MemoryTournamentRepository {
+    async fn create_tournament(&self, tournament: &Tournament) -> Result<(), RepositoryError> {
+        let mut store = self.db.store.write().unwrap();
+        if store.tournaments.contains_key(&tournament.tournament_uuid) {
+            return Err(RepositoryError::DatabaseError(format!(
+                "Duplicate tournament {}",
+                tournament.tournament_uuid
+            )));
+        }
+        store
+            .tournaments
+            .insert(tournament.tournament_uuid.clone(), tournament.clone());
+        Ok(())
+    }
+
+    async fn get_tournament(
+        &self,
+        tournament_uuid: &str,
+    ) -> Result<Option<Tournament>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        Ok(store.tournaments.get(tournament_uuid).cloned())
+    }
+
+    async fn update_tournament(&self, tournament: &Tournament) -> Result<(), RepositoryError> {
+        let mut store = self.db.store.write().unwrap();
+        if let Some(stored) = store.tournaments.get_mut(&tournament.tournament_uuid) {
+            stored.rounds = tournament.rounds;
+            stored.current_round = tournament.current_round;
+            stored.state = tournament.state;
+        }
+        Ok(())
+    }
+
+    async fn register_player(
+        &self,
+        tournament_uuid: &str,
+        player_uuid: &str,
+    ) -> Result<bool, RepositoryError> {
+        let mut store = self.db.store.write().unwrap();
+        store.tournament_mut(tournament_uuid)?;
+        if !store.players.contains_key(player_uuid) {
+            return Err(RepositoryError::DatabaseError(format!(
+                "Unknown player {}",
+                player_uuid
+            )));
+        }
+        let players: &mut Vec<String> = store
+            .tournament_players
+            .entry(tournament_uuid.to_string())
+            .or_default();
+        if players.iter().any(|p| p == player_uuid) {
+            return Ok(false);
+        }
+        players.push(player_uuid.to_string());
+        Ok(true)
+    }
+
+    async fn get_players(&self, tournament_uuid: &str) -> Result<Vec<String>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        Ok(store
+            .tournament_players
+            .get(tournament_uuid)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn get_pairings(
+        &self,
+        tournament_uuid: &str,
+    ) -> Result<Vec<TournamentPairing>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        let mut pairings: Vec<TournamentPairing> = Vec::new();
+        for (_, pairing) in store.pairings.iter().filter(|(t, _)| t == tournament_uuid) {
+            let mut pairing: TournamentPairing = pairing.clone();
+            if let Some(stored) = store.games.get(&pairing.game_uuid) {
+                pairing.game_state = stored.game.state;
+                pairing.position_black = stored.game.position_black;
+                pairing.position_white = stored.game.position_white;
+            }
+            pairings.push(pairing);
+        }
+        pairings.sort_by_key(|p| p.round);
+        Ok(pairings)
+    }
+
+    async fn tournament_for_game(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Option<String>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        Ok(store
+            .pairings
+            .iter()
+            .find(|(_, p)| !game_uuid.is_empty() && p.game_uuid == game_uuid)
+            .map(|(t, _)| t.clone()))
+    }
+
+    async fn start_round(
+        &self,
+        tournament: &Tournament,
+        round: u64,
+        games: &[Game],
+        pairings: &[TournamentPairing],
+    ) -> Result<bool, RepositoryError> {
+        let mut store = self.db.store.write().unwrap();
+        if store
+            .tournament_mut(&tournament.tournament_uuid)?
+            .current_round
+            != tournament.current_round
+        {
+            return Ok(false);
+        }
+        for game in games {
+            store.check_new_game(game)?;
+        }
+
+        let stored: &mut Tournament = store.tournament_mut(&tournament.tournament_uuid)?;
+        stored.rounds = tournament.rounds;
+        stored.current_round = round;
+        stored.state = 1;
+        for game in games {
+            store.insert_game(game)?;
+        }
+        for pairing in pairings {
+            store
+                .pairings
+                .push((tournament.tournament_uuid.clone(), pairing.clone()));
+        }
+        Ok(true)
+    }
+
+    async fn add_games(
+        &self,
+        tournament: &Tournament,
+        games: &[Game],
+        pairings: &[TournamentPairing],
+    ) -> Result<bool, RepositoryError> {
+        let mut store = self.db.store.write().unwrap();
+        store.tournament_mut(&tournament.tournament_uuid)?;
+        // Bracket games that already exist are skipped, like the unique
+        // (tournament, bracket match, game number) index does in SQL.
+        let mut added: Vec<(&Game, &TournamentPairing)> = Vec::new();
+        for (game, pairing) in games.iter().zip(pairings) {
+            let exists: bool = store.pairings.iter().any(|(t, p)| {
+                *t == tournament.tournament_uuid
+                    && p.bracket_match == pairing.bracket_match
+                    && p.game_no == pairing.game_no
+            });
+            if !exists {
+                store.check_new_game(game)?;
+                added.push((game, pairing));
+            }
+        }
+        if added.is_empty() {
+            return Ok(false);
+        }
+
+        let stored: &mut Tournament = store.tournament_mut(&tournament.tournament_uuid)?;
+        stored.rounds = tournament.rounds;
+        stored.current_round = stored.current_round.max(tournament.current_round);
+        stored.state = 1;
+        for (game, pairing) in added {
+            store.insert_game(game)?;
+            store
+                .pairings
+                .push((tournament.tournament_uuid.clone(), pairing.clone()));
+        }
+        Ok(true)
+    }
+}
+
+impl MemoryTournamentRepository {
+    pub fn new(db: MemoryDatabase) -> Self {
+        MemoryTournamentRepository { db }
+    }
+}
+
+pub struct MemoryMatchRepository {
+    db: MemoryDatabase,
+}
+
+#[async_trait]
+impl MatchRepository for MemoryMatchRepository {
+    async fn get_match(&self, match_uuid: &str) -> Result<Option<GameMatch>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        Ok(store.matches.get(match_uuid).cloned())
+    }
+
+    async fn get_match_games(&self, match_uuid: &str) -> Result<Vec<MatchGame>, RepositoryError> {
+        let store = self.db.store.read().unwrap();
+        let mut games: Vec<MatchGame> = Vec::new();
+        for match_game in store.match_games.get(match_uuid).into_iter().flatten() {
+            let game: &Game = match store.games.get(&match_game.game_uuid) {
+                Some(stored) => &stored.game,
+                None => continue,
+            };
+            games.push(MatchGame {
+                game_no: match_game.game_no,
+                game_uuid: game.game_uuid.clone(),
+                black_uuid: game.black_uuid.clone(),
+                white_uuid: game.white_uuid.clone(),
+                opening: match_game.opening.clone(),
+                game_state: game.state,
+                position_black: game.position_black,
+                position_white: game.position_white,
+            });
+        }
+        games.sort_by_key(|g| g.game_no);
+        Ok(games)
+    }
+}
+
+impl MemoryMatchRepository {
+    pub fn new(db: MemoryDatabase) -> Self {
+        MemoryMatchRepository { db }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::conformance::*;
+    use uuid::Uuid;
+
+    fn finished_game(uuid: &str, black: &str, white: &str, state: u64, time_control: &str) -> Game {
+        Game {
+            game_uuid: uuid.to_string(),
+            black_uuid: black.to_string(),
+            white_uuid: white.to_string(),
+            // 40 black discs against 24 white ones.
+            position_black: 0x000000ffffffffff,
+            position_white: 0xffffff0000000000,
+            state,
+            time_control: time_control.to_string(),
+        }
+    }
+
+    fn database_with_players(players: &[&str]) -> MemoryDatabase {
+        let db: MemoryDatabase = MemoryDatabase::new();
+        for player in players {
+            db.add_player(player, "");
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn conforms_to_the_repository_contract() {
+        let players: [String; 2] = [Uuid::new_v4().to_string(), Uuid::new_v4().to_string()];
+        let players: [&str; 2] = [players[0].as_str(), players[1].as_str()];
+        let db: MemoryDatabase = database_with_players(&players);
+        check_player_repository(&MemoryPlayerRepository::new(db.clone()), players).await;
+        check_game_repository(&MemoryGameRepository::new(db.clone()), players).await;
+        check_tournament_repository(
+            &MemoryTournamentRepository::new(db.clone()),
+            &MemoryGameRepository::new(db.clone()),
+            players,
+        )
+        .await;
+        check_match_repository(
+            &MemoryMatchRepository::new(db.clone()),
+            &MemoryGameRepository::new(db),
+            players,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn player_stats_counts_results_by_color() {
+        let repo = MemoryGameRepository::new(database_with_players(&["a", "b"]));
+        repo.create_game(&finished_game("g1", "a", "b", 3, ""))
+            .await
+            .unwrap();
+        repo.create_game(&finished_game("g2", "b", "a", 3, ""))
+            .await
+            .unwrap();
+        repo.create_game(&finished_game("g3", "b", "a", 5, ""))
+            .await
+            .unwrap();
+        repo.create_game(&finished_game("g4", "a", "b", 1, ""))
+            .await
+            .unwrap();
+
+        let stats = repo.player_stats("a").await.unwrap().unwrap();
+        assert_eq!(stats.games_played, 3);
+        assert_eq!(stats.wins_black, 1);
+        assert_eq!(stats.losses_white, 1);
+        assert_eq!(stats.draws_white, 1);
+        assert_eq!(stats.wins(), 1);
+        assert!((stats.average_disc_differential - (16.0 - 16.0 - 16.0) / 3.0).abs() < 1e-9);
+
+        assert!(repo.player_stats("nobody").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn leaderboard_filters_and_paginates() {
+        let repo = MemoryGameRepository::new(database_with_players(&["a", "b", "c"]));
+        repo.create_game(&finished_game("g1", "a", "b", 3, "blitz"))
+            .await
+            .unwrap();
+        repo.create_game(&finished_game("g2", "a", "c", 3, "blitz"))
+            .await
+            .unwrap();
+        repo.create_game(&finished_game("g3", "c", "b", 3, "rapid"))
+            .await
+            .unwrap();
+
+        let all = repo.leaderboard(0, 10, "").await.unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].player_id, "a");
+        assert_eq!(all[0].wins, 2);
+
+        let blitz = repo.leaderboard(0, 10, "blitz").await.unwrap();
+        assert_eq!(blitz.len(), 3);
+        assert_eq!(blitz[2].player_id, "c");
+        assert_eq!(blitz[2].losses, 1);
+
+        let second_page = repo.leaderboard(1, 1, "").await.unwrap();
+        assert_eq!(second_page.len(), 1);
+        assert_eq!(second_page[0].rank, 2);
+    }
+
+    #[tokio::test]
+    async fn games_need_known_players() {
+        let repo = MemoryGameRepository::new(database_with_players(&["a"]));
+        assert!(repo
+            .create_game(&finished_game("g1", "a", "stranger", 1, ""))
+            .await
+            .is_err());
+        assert_eq!(repo.get_game("g1").await.unwrap(), None);
+    }
+
+    #[test]
+    fn url_registers_players() {
+        let db = MemoryDatabase::open("memory://?players=a,b").unwrap();
+        let store = db.store.read().unwrap();
+        assert_eq!(store.player_order, vec!["a", "b"]);
+        drop(store);
+        assert!(MemoryDatabase::open("memory://").is_ok());
+        assert!(MemoryDatabase::open("memory://?size=1").is_err());
+    }
+}
@@ -0,0 +1,500 @@
+//! Repositories that count the calls and errors of another repository in the
+//! server's metrics and time them in tracing spans. They wrap whatever backend
+//! is configured.
+
+use crate::metrics::Metrics;
+use crate::model::{
+    AnnotationJob, Game, GameMatch, LeaderboardEntry, MatchGame, MoveAnnotation, MoveRecord,
+    PlayerStats, Tournament, TournamentPairing, User,
+};
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::Instrument;
+
+use crate::repository::db_errors::*;
+use crate::repository::game_repository::GameRepository;
+use crate::repository::match_repository::{MatchRepository, NewMatchGame};
+use crate::repository::player_repository::PlayerRepository;
+use crate::repository::tournament_repository::TournamentRepository;
+
+/// Runs a repository call in a `repository` span, keeping it in the in-flight
+/// gauge while it waits and counting its error, if any.
+async fn observe<T>(
+    metrics: &Metrics,
+    call: &'static str,
+    future: impl Future<Output = Result<T, RepositoryError>>,
+) -> Result<T, RepositoryError> {
+    let span: tracing::Span = tracing::debug_span!("repository", call);
+    let start: Instant = Instant::now();
+    metrics.repository_calls_in_flight.inc();
+    let result: Result<T, RepositoryError> = future.instrument(span.clone()).await;
+    metrics.repository_calls_in_flight.dec();
+    let elapsed_ms: f64 = start.elapsed().as_secs_f64() * 1000.0;
+    span.in_scope(|| match &result {
+        Ok(_) => tracing::debug!(elapsed_ms, "repository call finished"),
+        Err(e) => {
+            metrics.record_repository_error(e);
+            tracing::warn!(elapsed_ms, error = %e, "repository call failed");
+        }
+    });
+    result
+}
+
+pub struct MeteredGameRepository {
+    inner: Arc<dyn GameRepository + Send + Sync>,
+    metrics: Arc<Metrics>,
+}
+
+impl MeteredGameRepository {
+    pub fn new(inner: Arc<dyn GameRepository + Send + Sync>, metrics: Arc<Metrics>) -> Self {
+        MeteredGameRepository { inner, metrics }
+    }
+}
+
+#[async_trait]
+impl GameRepository for MeteredGameRepository {
+    async fn pending_games(&self, player_uuid: String) -> Result<Vec<Game>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "pending_games",
+            self.inner.pending_games(player_uuid),
+        )
+        .await
+    }
+
+    async fn count_games_by_state(&self) -> Result<Vec<(u64, u64)>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "count_games_by_state",
+            self.inner.count_games_by_state(),
+        )
+        .await
+    }
+
+    async fn get_game(&self, game_uuid: &str) -> Result<Option<Game>, RepositoryError> {
+        observe(&self.metrics, "get_game", self.inner.get_game(game_uuid)).await
+    }
+
+    async fn get_max_move_no(&self, game_uuid: &str) -> Result<u64, RepositoryError> {
+        observe(
+            &self.metrics,
+            "get_max_move_no",
+            self.inner.get_max_move_no(game_uuid),
+        )
+        .await
+    }
+
+    async fn get_last_move(&self, game_uuid: &str) -> Result<u64, RepositoryError> {
+        observe(
+            &self.metrics,
+            "get_last_move",
+            self.inner.get_last_move(game_uuid),
+        )
+        .await
+    }
+
+    async fn get_moves(&self, game_uuid: &str) -> Result<Vec<u64>, RepositoryError> {
+        observe(&self.metrics, "get_moves", self.inner.get_moves(game_uuid)).await
+    }
+
+    async fn create_game(&self, game: &Game) -> Result<(), RepositoryError> {
+        observe(&self.metrics, "create_game", self.inner.create_game(game)).await
+    }
+
+    async fn update_game(
+        &self,
+        game: &Game,
+        record: Option<&MoveRecord>,
+    ) -> Result<(), RepositoryError> {
+        observe(
+            &self.metrics,
+            "update_game",
+            self.inner.update_game(game, record),
+        )
+        .await
+    }
+
+    async fn update_game_with_move(
+        &self,
+        game: &Game,
+        move_bit: u64,
+        move_no: u64,
+        record: Option<&MoveRecord>,
+    ) -> Result<(), RepositoryError> {
+        observe(
+            &self.metrics,
+            "update_game_with_move",
+            self.inner
+                .update_game_with_move(game, move_bit, move_no, record),
+        )
+        .await
+    }
+
+    async fn create_match(
+        &self,
+        game_match: &GameMatch,
+        games: &[NewMatchGame],
+    ) -> Result<(), RepositoryError> {
+        observe(
+            &self.metrics,
+            "create_match",
+            self.inner.create_match(game_match, games),
+        )
+        .await
+    }
+
+    async fn save_annotations(
+        &self,
+        game_uuid: &str,
+        annotations: &[MoveAnnotation],
+    ) -> Result<(), RepositoryError> {
+        observe(
+            &self.metrics,
+            "save_annotations",
+            self.inner.save_annotations(game_uuid, annotations),
+        )
+        .await
+    }
+
+    async fn get_annotations(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Vec<MoveAnnotation>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "get_annotations",
+            self.inner.get_annotations(game_uuid),
+        )
+        .await
+    }
+
+    async fn queue_annotation_job(&self, game_uuid: &str) -> Result<(), RepositoryError> {
+        observe(
+            &self.metrics,
+            "queue_annotation_job",
+            self.inner.queue_annotation_job(game_uuid),
+        )
+        .await
+    }
+
+    async fn update_annotation_job(
+        &self,
+        game_uuid: &str,
+        status: &str,
+        error: &str,
+    ) -> Result<(), RepositoryError> {
+        observe(
+            &self.metrics,
+            "update_annotation_job",
+            self.inner.update_annotation_job(game_uuid, status, error),
+        )
+        .await
+    }
+
+    async fn get_annotation_job(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Option<AnnotationJob>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "get_annotation_job",
+            self.inner.get_annotation_job(game_uuid),
+        )
+        .await
+    }
+
+    async fn unfinished_annotation_jobs(&self) -> Result<Vec<String>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "unfinished_annotation_jobs",
+            self.inner.unfinished_annotation_jobs(),
+        )
+        .await
+    }
+
+    async fn get_move_record(
+        &self,
+        game_uuid: &str,
+        idempotency_key: &str,
+        ttl_secs: u64,
+    ) -> Result<Option<MoveRecord>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "get_move_record",
+            self.inner
+                .get_move_record(game_uuid, idempotency_key, ttl_secs),
+        )
+        .await
+    }
+
+    async fn reserve_move_key(
+        &self,
+        record: &MoveRecord,
+        ttl_secs: u64,
+    ) -> Result<bool, RepositoryError> {
+        observe(
+            &self.metrics,
+            "reserve_move_key",
+            self.inner.reserve_move_key(record, ttl_secs),
+        )
+        .await
+    }
+
+    async fn release_move_key(
+        &self,
+        game_uuid: &str,
+        idempotency_key: &str,
+    ) -> Result<(), RepositoryError> {
+        observe(
+            &self.metrics,
+            "release_move_key",
+            self.inner.release_move_key(game_uuid, idempotency_key),
+        )
+        .await
+    }
+
+    async fn player_stats(
+        &self,
+        player_uuid: &str,
+    ) -> Result<Option<PlayerStats>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "player_stats",
+            self.inner.player_stats(player_uuid),
+        )
+        .await
+    }
+
+    async fn leaderboard(
+        &self,
+        offset: u64,
+        limit: u64,
+        time_control: &str,
+    ) -> Result<Vec<LeaderboardEntry>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "leaderboard",
+            self.inner.leaderboard(offset, limit, time_control),
+        )
+        .await
+    }
+}
+
+pub struct MeteredPlayerRepository {
+    inner: Arc<dyn PlayerRepository + Send + Sync>,
+    metrics: Arc<Metrics>,
+}
+
+impl MeteredPlayerRepository {
+    pub fn new(inner: Arc<dyn PlayerRepository + Send + Sync>, metrics: Arc<Metrics>) -> Self {
+        MeteredPlayerRepository { inner, metrics }
+    }
+}
+
+#[async_trait]
+impl PlayerRepository for MeteredPlayerRepository {
+    async fn player_list(&self) -> Result<Vec<User>, RepositoryError> {
+        observe(&self.metrics, "player_list", self.inner.player_list()).await
+    }
+
+    async fn get_rating(&self, player_uuid: &str) -> Result<Option<u32>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "get_rating",
+            self.inner.get_rating(player_uuid),
+        )
+        .await
+    }
+
+    async fn create_player(&self, player: &User) -> Result<(), RepositoryError> {
+        observe(
+            &self.metrics,
+            "create_player",
+            self.inner.create_player(player),
+        )
+        .await
+    }
+}
+
+pub struct MeteredTournamentRepository {
+    inner: Arc<dyn TournamentRepository + Send + Sync>,
+    metrics: Arc<Metrics>,
+}
+
+impl MeteredTournamentRepository {
+    pub fn new(inner: Arc<dyn TournamentRepository + Send + Sync>, metrics: Arc<Metrics>) -> Self {
+        MeteredTournamentRepository { inner, metrics }
+    }
+}
+
+#[async_trait]
+impl TournamentRepository for MeteredTournamentRepository {
+    async fn create_tournament(&self, tournament: &Tournament) -> Result<(), RepositoryError> {
+        observe(
+            &self.metrics,
+            "create_tournament",
+            self.inner.create_tournament(tournament),
+        )
+        .await
+    }
+
+    async fn get_tournament(
+        &self,
+        tournament_uuid: &str,
+    ) -> Result<Option<Tournament>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "get_tournament",
+            self.inner.get_tournament(tournament_uuid),
+        )
+        .await
+    }
+
+    async fn update_tournament(&self, tournament: &Tournament) -> Result<(), RepositoryError> {
+        observe(
+            &self.metrics,
+            "update_tournament",
+            self.inner.update_tournament(tournament),
+        )
+        .await
+    }
+
+    async fn register_player(
+        &self,
+        tournament_uuid: &str,
+        player_uuid: &str,
+    ) -> Result<bool, RepositoryError> {
+        observe(
+            &self.metrics,
+            "register_player",
+            self.inner.register_player(tournament_uuid, player_uuid),
+        )
+        .await
+    }
+
+    async fn get_players(&self, tournament_uuid: &str) -> Result<Vec<String>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "get_players",
+            self.inner.get_players(tournament_uuid),
+        )
+        .await
+    }
+
+    async fn get_pairings(
+        &self,
+        tournament_uuid: &str,
+    ) -> Result<Vec<TournamentPairing>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "get_pairings",
+            self.inner.get_pairings(tournament_uuid),
+        )
+        .await
+    }
+
+    async fn tournament_for_game(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Option<String>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "tournament_for_game",
+            self.inner.tournament_for_game(game_uuid),
+        )
+        .await
+    }
+
+    async fn start_round(
+        &self,
+        tournament: &Tournament,
+        round: u64,
+        games: &[Game],
+        pairings: &[TournamentPairing],
+    ) -> Result<bool, RepositoryError> {
+        observe(
+            &self.metrics,
+            "start_round",
+            self.inner.start_round(tournament, round, games, pairings),
+        )
+        .await
+    }
+
+    async fn add_games(
+        &self,
+        tournament: &Tournament,
+        games: &[Game],
+        pairings: &[TournamentPairing],
+    ) -> Result<bool, RepositoryError> {
+        observe(
+            &self.metrics,
+            "add_games",
+            self.inner.add_games(tournament, games, pairings),
+        )
+        .await
+    }
+}
+
+pub struct MeteredMatchRepository {
+    inner: Arc<dyn MatchRepository + Send + Sync>,
+    metrics: Arc<Metrics>,
+}
+
+impl MeteredMatchRepository {
+    pub fn new(inner: Arc<dyn MatchRepository + Send + Sync>, metrics: Arc<Metrics>) -> Self {
+        MeteredMatchRepository { inner, metrics }
+    }
+}
+
+#[async_trait]
+impl MatchRepository for MeteredMatchRepository {
+    async fn get_match(&self, match_uuid: &str) -> Result<Option<GameMatch>, RepositoryError> {
+        observe(&self.metrics, "get_match", self.inner.get_match(match_uuid)).await
+    }
+
+    async fn get_match_games(&self, match_uuid: &str) -> Result<Vec<MatchGame>, RepositoryError> {
+        observe(
+            &self.metrics,
+            "get_match_games",
+            self.inner.get_match_games(match_uuid),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory_repository::*;
+
+    #[tokio::test]
+    async fn counts_errors_by_variant() {
+        let metrics: Arc<Metrics> = Arc::new(Metrics::new());
+        let repo = MeteredGameRepository::new(
+            Arc::new(MemoryGameRepository::new(MemoryDatabase::new())),
+            metrics.clone(),
+        );
+        let game: Game = Game {
+            game_uuid: uuid::Uuid::new_v4().to_string(),
+            black_uuid: uuid::Uuid::new_v4().to_string(),
+            white_uuid: String::new(),
+            position_black: 0,
+            position_white: 0,
+            state: 0,
+            time_control: String::new(),
+        };
+        // The player is unknown, so the game is rejected.
+        assert!(repo.create_game(&game).await.is_err());
+        assert_eq!(repo.count_games_by_state().await.unwrap(), vec![]);
+        assert_eq!(metrics.repository_calls_in_flight.get(), 0);
+        assert_eq!(
+            metrics
+                .repository_errors
+                .with_label_values(&["DatabaseError"])
+                .get(),
+            1
+        );
+    }
+}
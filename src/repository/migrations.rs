@@ -0,0 +1,468 @@
+//! Versioned schema changes. Every backend keeps its own copy of the files in
+//! `sql/`, numbered alike, and records the versions a database has seen in the
+//! `schema_migrations` table. Databases set up by hand before that table
+//! existed are adopted from the tables, columns and indexes they have.
+
+use async_trait::async_trait;
+use mysql_async::{params, prelude::*, Conn};
+
+use crate::repository::db_errors::*;
+use crate::repository::mysql_pool::MySqlPool;
+
+pub struct Migration {
+    pub version: u64,
+    pub name: &'static str,
+    pub up: &'static str,
+    /// Contents of the matching `rollback_*` file, if there is one.
+    pub down: Option<&'static str>,
+    /// The table, `table.column` or `table.index` the migration creates last.
+    /// A database that has it has seen the migration, see `adopted_versions`.
+    pub creates: &'static str,
+}
+
+macro_rules! migration {
+    ($dir:literal, $version:literal, $name:literal, $creates:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            creates: $creates,
+            up: include_str!(concat!("../../sql/", $dir, $name, ".sql")),
+            down: Some(include_str!(concat!(
+                "../../sql/",
+                $dir,
+                "rollback_",
+                $name,
+                ".sql"
+            ))),
+        }
+    };
+}
+
+pub const MYSQL_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 0,
+        name: "0000_initial",
+        up: include_str!("../../sql/0000_initial.sql"),
+        down: None,
+        creates: "moves",
+    },
+    migration!("", 1, "0001_dates", "moves.move_date"),
+    migration!("", 2, "0002_matchmaking", "games.time_control"),
+    migration!("", 3, "0003_tournaments", "tournament_pairings"),
+    migration!("", 4, "0004_brackets", "tournament_pairings.bracket_game"),
+    migration!("", 5, "0005_matches", "match_games"),
+    migration!("", 6, "0006_annotations", "move_annotations"),
+    migration!("", 7, "0007_annotation_jobs", "annotation_jobs"),
+    migration!("", 8, "0008_idempotency_keys", "move_idempotency_keys"),
+];
+
+pub const POSTGRES_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 0,
+        name: "0000_initial",
+        up: include_str!("../../sql/postgres/0000_initial.sql"),
+        down: None,
+        creates: "moves.moves_game_uuid",
+    },
+    migration!("postgres/", 1, "0001_dates", "moves.move_date"),
+    migration!("postgres/", 2, "0002_matchmaking", "games.time_control"),
+    migration!(
+        "postgres/",
+        3,
+        "0003_tournaments",
+        "tournament_pairings.tournament_pairings_game_uuid"
+    ),
+    migration!(
+        "postgres/",
+        4,
+        "0004_brackets",
+        "tournament_pairings.tournament_pairings_bracket_game"
+    ),
+    migration!(
+        "postgres/",
+        5,
+        "0005_matches",
+        "match_games.match_games_game_uuid"
+    ),
+    migration!("postgres/", 6, "0006_annotations", "move_annotations"),
+    migration!(
+        "postgres/",
+        7,
+        "0007_annotation_jobs",
+        "annotation_jobs.annotation_jobs_status"
+    ),
+    migration!(
+        "postgres/",
+        8,
+        "0008_idempotency_keys",
+        "move_idempotency_keys.move_idempotency_keys_created_at"
+    ),
+];
+
+pub const SQLITE_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 0,
+        name: "0000_initial",
+        up: include_str!("../../sql/sqlite/0000_initial.sql"),
+        down: None,
+        creates: "moves.moves_game_uuid",
+    },
+    migration!("sqlite/", 1, "0001_dates", "moves.move_date"),
+    migration!("sqlite/", 2, "0002_matchmaking", "games.time_control"),
+    migration!(
+        "sqlite/",
+        3,
+        "0003_tournaments",
+        "tournament_pairings.tournament_pairings_game_uuid"
+    ),
+    migration!(
+        "sqlite/",
+        4,
+        "0004_brackets",
+        "tournament_pairings.tournament_pairings_bracket_game"
+    ),
+    migration!(
+        "sqlite/",
+        5,
+        "0005_matches",
+        "match_games.match_games_game_uuid"
+    ),
+    migration!("sqlite/", 6, "0006_annotations", "move_annotations"),
+    migration!(
+        "sqlite/",
+        7,
+        "0007_annotation_jobs",
+        "annotation_jobs.annotation_jobs_status"
+    ),
+    migration!(
+        "sqlite/",
+        8,
+        "0008_idempotency_keys",
+        "move_idempotency_keys.move_idempotency_keys_created_at"
+    ),
+];
+
+/// A database the migrations can be applied to.
+#[async_trait]
+pub trait MigrationTarget {
+    fn migrations(&self) -> &'static [Migration];
+    /// Versions recorded in `schema_migrations`, which is created if missing.
+    async fn applied_versions(&self) -> Result<Vec<u64>, RepositoryError>;
+    /// Versions recorded in `schema_migrations` without changing the
+    /// database; none if the table does not exist yet.
+    async fn current_versions(&self) -> Result<Vec<u64>, RepositoryError>;
+    /// Runs the migration and records its version.
+    async fn apply(&self, migration: &Migration) -> Result<(), RepositoryError>;
+    /// Runs the rollback of the migration and forgets its version.
+    async fn revert(&self, migration: &Migration) -> Result<(), RepositoryError>;
+}
+
+/// Migrations not applied yet, in order. Fails if the database has seen a
+/// version this build does not know, i.e. its schema is newer.
+pub fn pending_migrations(
+    migrations: &'static [Migration],
+    applied: &[u64],
+) -> Result<Vec<&'static Migration>, RepositoryError> {
+    if let Some(unknown) = applied
+        .iter()
+        .find(|v| !migrations.iter().any(|m| m.version == **v))
+    {
+        return Err(RepositoryError::SchemaError(format!(
+            "the database has migration {:04} applied, which this server does not know; upgrade the server",
+            unknown
+        )));
+    }
+    Ok(migrations
+        .iter()
+        .filter(|m| !applied.contains(&m.version))
+        .collect())
+}
+
+/// Versions of a database set up by hand before `schema_migrations` existed:
+/// those of the migrations whose last table, column or index it has. `columns`
+/// are the `(table, column or index)` pairs of the database. A database without
+/// `games` is new and has none.
+pub fn adopted_versions(migrations: &[Migration], columns: &[(String, String)]) -> Vec<u64> {
+    if !columns.iter().any(|(table, _)| table == "games") {
+        return Vec::new();
+    }
+    migrations
+        .iter()
+        .filter(|m| match m.creates.split_once('.') {
+            Some((table, column)) => columns.iter().any(|(t, c)| t == table && c == column),
+            None => columns.iter().any(|(t, _)| t == m.creates),
+        })
+        .map(|m| m.version)
+        .collect()
+}
+
+/// Applies the pending migrations and returns them.
+pub async fn migrate(
+    target: &(dyn MigrationTarget + Send + Sync),
+) -> Result<Vec<&'static Migration>, RepositoryError> {
+    let applied: Vec<u64> = target.applied_versions().await?;
+    let pending: Vec<&'static Migration> = pending_migrations(target.migrations(), &applied)?;
+    for migration in &pending {
+        target.apply(migration).await?;
+    }
+    Ok(pending)
+}
+
+/// Reverts the latest applied migration and returns it.
+pub async fn rollback(
+    target: &(dyn MigrationTarget + Send + Sync),
+) -> Result<&'static Migration, RepositoryError> {
+    let applied: Vec<u64> = target.applied_versions().await?;
+    pending_migrations(target.migrations(), &applied)?;
+    let migration: &'static Migration = match target
+        .migrations()
+        .iter()
+        .rev()
+        .find(|m| applied.contains(&m.version))
+    {
+        Some(m) => m,
+        None => {
+            return Err(RepositoryError::SchemaError(
+                "no migration has been applied".to_string(),
+            ))
+        }
+    };
+    if migration.down.is_none() {
+        return Err(RepositoryError::SchemaError(format!(
+            "{} has no rollback",
+            migration.name
+        )));
+    }
+    target.revert(migration).await?;
+    Ok(migration)
+}
+
+/// Checks that the schema is exactly the one this build expects. Only reads
+/// from the database.
+pub async fn check_schema(
+    target: &(dyn MigrationTarget + Send + Sync),
+) -> Result<(), RepositoryError> {
+    let applied: Vec<u64> = target.current_versions().await?;
+    let pending: Vec<&'static Migration> = pending_migrations(target.migrations(), &applied)?;
+    if let Some(migration) = pending.first() {
+        return Err(RepositoryError::SchemaError(format!(
+            "{} is not applied; run the migrate command",
+            migration.name
+        )));
+    }
+    Ok(())
+}
+
+/// Tables, columns and indexes of the current MySQL database, as
+/// `(table, column or index)`.
+async fn mysql_columns(conn: &mut Conn) -> Result<Vec<(String, String)>, RepositoryError> {
+    conn.query(
+        r#"
+        SELECT table_name, column_name FROM information_schema.columns
+        WHERE table_schema = DATABASE()
+        UNION
+        SELECT table_name, index_name FROM information_schema.statistics
+        WHERE table_schema = DATABASE()
+        "#,
+    )
+    .await
+    .map_err(|e| RepositoryError::DatabaseError(e.to_string()))
+}
+
+/// MySQL cannot roll back schema changes, so a failing migration may leave
+/// part of its changes behind without being recorded.
+#[async_trait]
+impl MigrationTarget for MySqlPool {
+    fn migrations(&self) -> &'static [Migration] {
+        MYSQL_MIGRATIONS
+    }
+
+    async fn applied_versions(&self) -> Result<Vec<u64>, RepositoryError> {
+        let mut conn = self
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        let columns: Vec<(String, String)> = mysql_columns(&mut conn).await?;
+        if !columns
+            .iter()
+            .any(|(table, _)| table == "schema_migrations")
+        {
+            conn.query_drop(
+                r#"
+                CREATE TABLE IF NOT EXISTS schema_migrations (
+                    version BIGINT UNSIGNED NOT NULL PRIMARY KEY,
+                    name VARCHAR(255) NOT NULL,
+                    applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+                ) ENGINE=InnoDB
+                "#,
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+            let adopted: Vec<u64> = adopted_versions(MYSQL_MIGRATIONS, &columns);
+            for migration in MYSQL_MIGRATIONS
+                .iter()
+                .filter(|m| adopted.contains(&m.version))
+            {
+                conn.exec_drop(
+                    "INSERT IGNORE INTO schema_migrations (version, name) VALUES (:version, :name)",
+                    params! {
+                        "version" => migration.version,
+                        "name" => migration.name,
+                    },
+                )
+                .await
+                .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+            }
+        }
+        let versions: Vec<u64> = conn
+            .query("SELECT version FROM schema_migrations ORDER BY version")
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(versions)
+    }
+
+    async fn current_versions(&self) -> Result<Vec<u64>, RepositoryError> {
+        let mut conn = self
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        let columns: Vec<(String, String)> = mysql_columns(&mut conn).await?;
+        if !columns
+            .iter()
+            .any(|(table, _)| table == "schema_migrations")
+        {
+            return Ok(adopted_versions(MYSQL_MIGRATIONS, &columns));
+        }
+        let versions: Vec<u64> = conn
+            .query("SELECT version FROM schema_migrations ORDER BY version")
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(versions)
+    }
+
+    async fn apply(&self, migration: &Migration) -> Result<(), RepositoryError> {
+        let mut conn = self
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        conn.query_drop(migration.up)
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        conn.exec_drop(
+            "INSERT INTO schema_migrations (version, name) VALUES (:version, :name)",
+            params! {
+                "version" => migration.version,
+                "name" => migration.name,
+            },
+        )
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn revert(&self, migration: &Migration) -> Result<(), RepositoryError> {
+        let mut conn = self
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        conn.query_drop(migration.down.unwrap_or_default())
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        conn.exec_drop(
+            "DELETE FROM schema_migrations WHERE version = :version",
+            params! {
+                "version" => migration.version,
+            },
+        )
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_ordered_and_unique() {
+        for migrations in [MYSQL_MIGRATIONS, POSTGRES_MIGRATIONS, SQLITE_MIGRATIONS] {
+            assert!(migrations.windows(2).all(|w| w[0].version < w[1].version));
+            assert!(migrations[1..].iter().all(|m| m.down.is_some()));
+        }
+    }
+
+    #[test]
+    fn pending_skips_applied_versions() {
+        let pending = pending_migrations(POSTGRES_MIGRATIONS, &[0, 1]).unwrap();
+        let versions: Vec<u64> = pending.iter().map(|m| m.version).collect();
+        assert_eq!(versions, vec![2, 3, 4, 5, 6, 7, 8]);
+        assert!(
+            pending_migrations(POSTGRES_MIGRATIONS, &[0, 1, 2, 3, 4, 5, 6, 7, 8])
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn adopts_schemas_applied_by_hand() {
+        let columns = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(t, c)| (t.to_string(), c.to_string()))
+                .collect()
+        };
+        // The baseline schema: 0000_initial and 0001_dates.
+        let baseline: Vec<(String, String)> = columns(&[
+            ("players", "player_uuid"),
+            ("games", "game_uuid"),
+            ("games", "start_date"),
+            ("moves", "move_id"),
+            ("moves", "move_date"),
+        ]);
+        assert_eq!(adopted_versions(MYSQL_MIGRATIONS, &baseline), vec![0, 1]);
+        let mut later: Vec<(String, String)> = baseline.clone();
+        later.extend(columns(&[
+            ("games", "time_control"),
+            ("tournament_pairings", "pairing_id"),
+            ("move_annotations", "game_uuid"),
+        ]));
+        assert_eq!(
+            adopted_versions(MYSQL_MIGRATIONS, &later),
+            vec![0, 1, 2, 3, 6]
+        );
+        // 0004 adds its columns before the unique index, a database that only
+        // got part of it has not seen it.
+        later.push(("tournament_pairings".to_string(), "game_no".to_string()));
+        assert_eq!(
+            adopted_versions(MYSQL_MIGRATIONS, &later),
+            vec![0, 1, 2, 3, 6]
+        );
+        later.push((
+            "tournament_pairings".to_string(),
+            "bracket_game".to_string(),
+        ));
+        assert_eq!(
+            adopted_versions(MYSQL_MIGRATIONS, &later),
+            vec![0, 1, 2, 3, 4, 6]
+        );
+        // The PostgreSQL 0000_initial ends with an index on moves.
+        assert_eq!(adopted_versions(POSTGRES_MIGRATIONS, &baseline), vec![1]);
+        let mut postgres: Vec<(String, String)> = baseline.clone();
+        postgres.push(("moves".to_string(), "moves_game_uuid".to_string()));
+        assert_eq!(adopted_versions(POSTGRES_MIGRATIONS, &postgres), vec![0, 1]);
+        assert!(
+            adopted_versions(MYSQL_MIGRATIONS, &columns(&[("players", "player_uuid")])).is_empty()
+        );
+    }
+
+    #[test]
+    fn refuses_a_newer_schema() {
+        assert!(pending_migrations(SQLITE_MIGRATIONS, &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]).is_err());
+        assert!(pending_migrations(POSTGRES_MIGRATIONS, &[0, 9]).is_err());
+    }
+}
@@ -1,3 +1,15 @@
+pub mod database;
 pub mod db_errors;
 pub mod game_repository;
+pub mod match_repository;
+pub mod memory_repository;
+pub mod metered_repository;
+pub mod migrations;
+pub mod mysql_pool;
 pub mod player_repository;
+pub mod postgres_repository;
+pub mod sqlite_repository;
+pub mod tournament_repository;
+
+#[cfg(test)]
+mod conformance;
@@ -0,0 +1,130 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use mysql_async::{Conn, Opts, Pool};
+
+/// The MySQL connection pool. mysql_async does not report the state of its
+/// pool, so the connections checked out of it and the callers waiting for one
+/// are counted here.
+#[derive(Clone)]
+pub struct MySqlPool {
+    pool: Pool,
+    max: usize,
+    counts: Arc<Counts>,
+}
+
+#[derive(Default)]
+struct Counts {
+    in_use: AtomicUsize,
+    waiting: AtomicUsize,
+}
+
+/// Decrements a count when dropped, so that cancelled calls are not counted.
+struct CountGuard {
+    counts: Arc<Counts>,
+    waiting: bool,
+}
+
+impl Drop for CountGuard {
+    fn drop(&mut self) {
+        let count: &AtomicUsize = if self.waiting {
+            &self.counts.waiting
+        } else {
+            &self.counts.in_use
+        };
+        count.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl CountGuard {
+    fn new(counts: &Arc<Counts>, waiting: bool) -> CountGuard {
+        if waiting {
+            counts.waiting.fetch_add(1, Ordering::Relaxed);
+        } else {
+            counts.in_use.fetch_add(1, Ordering::Relaxed);
+        }
+        CountGuard {
+            counts: counts.clone(),
+            waiting,
+        }
+    }
+}
+
+/// A connection checked out of a `MySqlPool`, returned to it when dropped.
+pub struct PooledConn {
+    conn: Conn,
+    _in_use: CountGuard,
+}
+
+impl Deref for PooledConn {
+    type Target = Conn;
+
+    fn deref(&self) -> &Conn {
+        &self.conn
+    }
+}
+
+impl DerefMut for PooledConn {
+    fn deref_mut(&mut self) -> &mut Conn {
+        &mut self.conn
+    }
+}
+
+impl MySqlPool {
+    pub fn new(opts: Opts) -> MySqlPool {
+        let max: usize = opts.pool_opts().constraints().max();
+        MySqlPool {
+            pool: Pool::new(opts),
+            max,
+            counts: Arc::new(Counts::default()),
+        }
+    }
+
+    pub async fn get_conn(&self) -> Result<PooledConn, mysql_async::Error> {
+        let waiting: CountGuard = CountGuard::new(&self.counts, true);
+        let conn: Conn = self.pool.get_conn().await?;
+        drop(waiting);
+        Ok(PooledConn {
+            conn,
+            _in_use: CountGuard::new(&self.counts, false),
+        })
+    }
+
+    /// Configured maximum number of connections.
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Connections checked out of the pool.
+    pub fn in_use(&self) -> usize {
+        self.counts.in_use.load(Ordering::Relaxed)
+    }
+
+    /// Callers waiting for a connection.
+    pub fn waiting(&self) -> usize {
+        self.counts.waiting.load(Ordering::Relaxed)
+    }
+
+    /// Closes the pool once the connections in use are returned.
+    pub async fn disconnect(self) -> Result<(), mysql_async::Error> {
+        self.pool.disconnect().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn counts_connections_of_the_pool() {
+        let opts: Opts =
+            Opts::from_url("mysql://root@127.0.0.1:1/reversi?pool_min=1&pool_max=7").unwrap();
+        let pool: MySqlPool = MySqlPool::new(opts);
+        assert_eq!(pool.max(), 7);
+
+        // Nothing listens on port 1, the failed attempt is not counted.
+        assert!(pool.get_conn().await.is_err());
+        assert_eq!((pool.in_use(), pool.waiting()), (0, 0));
+    }
+}
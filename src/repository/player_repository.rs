@@ -1,16 +1,20 @@
 use crate::model::User;
 use async_trait::async_trait;
-use mysql_async::{prelude::*, Pool};
+use mysql_async::{params, prelude::*};
 
 use crate::repository::db_errors::*;
+use crate::repository::mysql_pool::MySqlPool;
 
 #[async_trait]
 pub trait PlayerRepository {
     async fn player_list(&self) -> Result<Vec<User>, RepositoryError>;
+    async fn get_rating(&self, player_uuid: &str) -> Result<Option<u32>, RepositoryError>;
+    /// Registers a player with the default rating.
+    async fn create_player(&self, player: &User) -> Result<(), RepositoryError>;
 }
 
 pub struct MySqlPlayerRepository {
-    pool: Pool,
+    pool: MySqlPool,
 }
 
 #[async_trait]
@@ -34,10 +38,48 @@ impl PlayerRepository for MySqlPlayerRepository {
 
         Ok(users)
     }
+
+    async fn get_rating(&self, player_uuid: &str) -> Result<Option<u32>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        let rating: Option<u32> = conn
+            .exec_first(
+                "SELECT rating FROM players WHERE player_uuid = UUID_TO_BIN(:player_uuid)",
+                params! {
+                    "player_uuid" => player_uuid,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(rating)
+    }
+
+    async fn create_player(&self, player: &User) -> Result<(), RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        conn.exec_drop(
+            "INSERT INTO players (player_uuid, comment) VALUES (UUID_TO_BIN(:player_uuid), :comment)",
+            params! {
+                "player_uuid" => &player.player_uuid,
+                "comment" => &player.comment,
+            },
+        )
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
 }
 
 impl MySqlPlayerRepository {
-    pub fn new(pool: Pool) -> Self {
+    pub fn new(pool: MySqlPool) -> Self {
         MySqlPlayerRepository { pool }
     }
 }
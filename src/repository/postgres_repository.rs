@@ -0,0 +1,1599 @@
+use crate::model::{
+    AnnotationJob, Game, GameMatch, LeaderboardEntry, MatchGame, MoveAnnotation, MoveRecord,
+    PlayerStats, Tournament, TournamentPairing, User,
+};
+use async_trait::async_trait;
+use deadpool_postgres::{GenericClient, Manager, Object, Pool};
+use tokio_postgres::{NoTls, Row};
+use uuid::Uuid;
+
+use crate::rating::{finishes, updated_ratings};
+use crate::repository::db_errors::*;
+use crate::repository::game_repository::GameRepository;
+use crate::repository::match_repository::{MatchRepository, NewMatchGame};
+use crate::repository::migrations::*;
+use crate::repository::player_repository::PlayerRepository;
+use crate::repository::tournament_repository::TournamentRepository;
+
+const GAME_COLUMNS: &str = r#"
+    game_uuid,
+    black_uuid,
+    white_uuid,
+    position_black,
+    position_white,
+    state,
+    time_control
+"#;
+
+const UPDATE_GAME: &str = r#"
+    UPDATE games
+    SET
+        black_uuid = $2,
+        white_uuid = $3,
+        position_black = $4,
+        position_white = $5,
+        state = $6,
+        end_date = NOW()
+    WHERE game_uuid = $1
+"#;
+
+/// Connection pool of a PostgreSQL database, shared by the repositories.
+#[derive(Clone)]
+pub struct PostgresDatabase {
+    pool: Pool,
+}
+
+impl PostgresDatabase {
+    /// Connects with at most `max_connections`, deadpool's default if `None`.
+    pub fn connect(
+        database_url: &str,
+        max_connections: Option<usize>,
+    ) -> Result<PostgresDatabase, RepositoryError> {
+        let config: tokio_postgres::Config = database_url
+            .parse()
+            .map_err(|e: tokio_postgres::Error| RepositoryError::DatabaseError(e.to_string()))?;
+        PostgresDatabase::from_config(config, max_connections)
+    }
+
+    pub fn from_config(
+        config: tokio_postgres::Config,
+        max_connections: Option<usize>,
+    ) -> Result<PostgresDatabase, RepositoryError> {
+        let manager: Manager = Manager::new(config, NoTls);
+        let mut builder = Pool::builder(manager);
+        if let Some(max) = max_connections {
+            builder = builder.max_size(max);
+        }
+        let pool: Pool = builder
+            .build()
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        Ok(PostgresDatabase { pool })
+    }
+
+    /// Connections of the pool: (maximum, open, idle, callers waiting).
+    pub fn pool_status(&self) -> (usize, usize, usize, usize) {
+        let status = self.pool.status();
+        (
+            status.max_size,
+            status.size,
+            status.available,
+            status.waiting,
+        )
+    }
+
+    pub async fn ping(&self) -> Result<(), RepositoryError> {
+        self.client()
+            .await?
+            .simple_query("SELECT 1")
+            .await
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Closes the pool; idle connections are dropped right away, the others as
+    /// they are returned.
+    pub fn close(&self) {
+        self.pool.close();
+    }
+
+    /// Tables, columns and indexes of the current schema, as
+    /// `(table, column or index)`.
+    async fn columns(&self, client: &Object) -> Result<Vec<(String, String)>, RepositoryError> {
+        let rows: Vec<Row> = client
+            .query(
+                r#"
+                SELECT table_name::TEXT, column_name::TEXT FROM information_schema.columns
+                WHERE table_schema = current_schema()
+                UNION
+                SELECT tablename::TEXT, indexname::TEXT FROM pg_indexes
+                WHERE schemaname = current_schema()
+                "#,
+                &[],
+            )
+            .await
+            .map_err(db_error)?;
+        Ok(rows.iter().map(|r| (r.get(0), r.get(1))).collect())
+    }
+
+    async fn client(&self) -> Result<Object, RepositoryError> {
+        self.pool
+            .get()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))
+    }
+}
+
+fn db_error(e: tokio_postgres::Error) -> RepositoryError {
+    match e.as_db_error() {
+        Some(db_error) => RepositoryError::DatabaseError(db_error.to_string()),
+        None => RepositoryError::DatabaseError(e.to_string()),
+    }
+}
+
+fn parse_uuid(uuid: &str) -> Result<Uuid, RepositoryError> {
+    Uuid::parse_str(uuid)
+        .map_err(|e| RepositoryError::DatabaseError(format!("Invalid UUID {}: {}", uuid, e)))
+}
+
+/// An empty string stands for a missing player.
+fn parse_optional_uuid(uuid: &str) -> Result<Option<Uuid>, RepositoryError> {
+    if uuid.is_empty() {
+        return Ok(None);
+    }
+    parse_uuid(uuid).map(Some)
+}
+
+fn uuid_string(uuid: Option<Uuid>) -> String {
+    uuid.map(|u| u.to_string()).unwrap_or_default()
+}
+
+// BIGINT is signed, bitboards and the pass marker are stored with the same bits.
+fn to_db(value: u64) -> i64 {
+    value as i64
+}
+
+fn from_db(value: i64) -> u64 {
+    value as u64
+}
+
+fn game_from_row(row: &Row) -> Game {
+    Game {
+        game_uuid: row.get::<_, Uuid>(0).to_string(),
+        black_uuid: uuid_string(row.get(1)),
+        white_uuid: uuid_string(row.get(2)),
+        position_black: from_db(row.get(3)),
+        position_white: from_db(row.get(4)),
+        state: from_db(row.get(5)),
+        time_control: row.get(6),
+    }
+}
+
+async fn insert_game<C: GenericClient>(client: &C, game: &Game) -> Result<u64, RepositoryError> {
+    client
+        .execute(
+            r#"
+            INSERT INTO games (
+                game_uuid,
+                black_uuid,
+                white_uuid,
+                position_black,
+                position_white,
+                state,
+                time_control,
+                start_date
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, NOW())
+            "#,
+            &[
+                &parse_uuid(&game.game_uuid)?,
+                &parse_optional_uuid(&game.black_uuid)?,
+                &parse_optional_uuid(&game.white_uuid)?,
+                &to_db(game.position_black),
+                &to_db(game.position_white),
+                &to_db(game.state),
+                &game.time_control,
+            ],
+        )
+        .await
+        .map_err(db_error)
+}
+
+/// Records a move of the game, the positions are the ones after the move.
+async fn insert_move<C: GenericClient>(
+    client: &C,
+    game_uuid: &str,
+    move_bit: u64,
+    move_no: u64,
+    position_black: u64,
+    position_white: u64,
+) -> Result<u64, RepositoryError> {
+    client
+        .execute(
+            r#"
+            INSERT INTO moves (
+                game_uuid,
+                move_number,
+                move_position,
+                position_black,
+                position_white,
+                move_date
+            ) VALUES ($1, $2, $3, $4, $5, NOW())
+            "#,
+            &[
+                &parse_uuid(game_uuid)?,
+                &to_db(move_no),
+                &to_db(move_bit),
+                &to_db(position_black),
+                &to_db(position_white),
+            ],
+        )
+        .await
+        .map_err(db_error)
+}
+
+/// Fills in the response of the move's reserved idempotency key.
+async fn complete_move_key<C: GenericClient>(
+    client: &C,
+    record: &MoveRecord,
+) -> Result<u64, RepositoryError> {
+    client
+        .execute(
+            r#"
+            UPDATE move_idempotency_keys
+            SET response = $3
+            WHERE game_uuid = $1 AND idempotency_key = $2
+            "#,
+            &[
+                &parse_uuid(&record.game_uuid)?,
+                &record.idempotency_key,
+                &record.response,
+            ],
+        )
+        .await
+        .map_err(db_error)
+}
+
+#[async_trait]
+impl MigrationTarget for PostgresDatabase {
+    fn migrations(&self) -> &'static [Migration] {
+        POSTGRES_MIGRATIONS
+    }
+
+    async fn applied_versions(&self) -> Result<Vec<u64>, RepositoryError> {
+        let mut client = self.client().await?;
+        let columns: Vec<(String, String)> = self.columns(&client).await?;
+        if !columns
+            .iter()
+            .any(|(table, _)| table == "schema_migrations")
+        {
+            let adopted: Vec<u64> = adopted_versions(POSTGRES_MIGRATIONS, &columns);
+            let tx = client.transaction().await.map_err(db_error)?;
+            tx.batch_execute(
+                r#"
+                CREATE TABLE IF NOT EXISTS schema_migrations (
+                    version BIGINT NOT NULL PRIMARY KEY,
+                    name VARCHAR(255) NOT NULL,
+                    applied_at TIMESTAMP NOT NULL DEFAULT NOW()
+                )
+                "#,
+            )
+            .await
+            .map_err(db_error)?;
+            for migration in POSTGRES_MIGRATIONS
+                .iter()
+                .filter(|m| adopted.contains(&m.version))
+            {
+                tx.execute(
+                    "INSERT INTO schema_migrations (version, name) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                    &[&to_db(migration.version), &migration.name],
+                )
+                .await
+                .map_err(db_error)?;
+            }
+            tx.commit().await.map_err(db_error)?;
+        }
+        let rows: Vec<Row> = client
+            .query(
+                "SELECT version FROM schema_migrations ORDER BY version",
+                &[],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows.iter().map(|r| from_db(r.get(0))).collect())
+    }
+
+    async fn current_versions(&self) -> Result<Vec<u64>, RepositoryError> {
+        let client = self.client().await?;
+        let columns: Vec<(String, String)> = self.columns(&client).await?;
+        if !columns
+            .iter()
+            .any(|(table, _)| table == "schema_migrations")
+        {
+            return Ok(adopted_versions(POSTGRES_MIGRATIONS, &columns));
+        }
+        let rows: Vec<Row> = client
+            .query(
+                "SELECT version FROM schema_migrations ORDER BY version",
+                &[],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows.iter().map(|r| from_db(r.get(0))).collect())
+    }
+
+    async fn apply(&self, migration: &Migration) -> Result<(), RepositoryError> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await.map_err(db_error)?;
+        tx.batch_execute(migration.up).await.map_err(db_error)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name) VALUES ($1, $2)",
+            &[&to_db(migration.version), &migration.name],
+        )
+        .await
+        .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn revert(&self, migration: &Migration) -> Result<(), RepositoryError> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await.map_err(db_error)?;
+        tx.batch_execute(migration.down.unwrap_or_default())
+            .await
+            .map_err(db_error)?;
+        tx.execute(
+            "DELETE FROM schema_migrations WHERE version = $1",
+            &[&to_db(migration.version)],
+        )
+        .await
+        .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+pub struct PostgresGameRepository {
+    db: PostgresDatabase,
+}
+
+impl PostgresGameRepository {
+    /// Updates the game within a transaction. The update that finishes a
+    /// rated game also updates the ratings of both players.
+    async fn update_game_on(
+        tx: &deadpool_postgres::Transaction<'_>,
+        game: &Game,
+    ) -> Result<u64, RepositoryError> {
+        let game_uuid: Uuid = parse_uuid(&game.game_uuid)?;
+        let previous: Option<i64> = tx
+            .query_opt(
+                "SELECT state FROM games WHERE game_uuid = $1 FOR UPDATE",
+                &[&game_uuid],
+            )
+            .await
+            .map_err(db_error)?
+            .map(|row| row.get(0));
+        let updated: u64 = tx
+            .execute(
+                UPDATE_GAME,
+                &[
+                    &game_uuid,
+                    &parse_optional_uuid(&game.black_uuid)?,
+                    &parse_optional_uuid(&game.white_uuid)?,
+                    &to_db(game.position_black),
+                    &to_db(game.position_white),
+                    &to_db(game.state),
+                ],
+            )
+            .await
+            .map_err(db_error)?;
+
+        let finished: bool = previous.is_some_and(|p| finishes(from_db(p), game.state));
+        if !finished || game.black_uuid.is_empty() || game.white_uuid.is_empty() {
+            return Ok(updated);
+        }
+        let players: [Uuid; 2] = [parse_uuid(&game.black_uuid)?, parse_uuid(&game.white_uuid)?];
+        let mut ratings: Vec<u32> = Vec::new();
+        for player in &players {
+            let rating: Option<i32> = tx
+                .query_opt(
+                    "SELECT rating FROM players WHERE player_uuid = $1 FOR UPDATE",
+                    &[player],
+                )
+                .await
+                .map_err(db_error)?
+                .map(|row| row.get(0));
+            match rating {
+                Some(r) => ratings.push(r.max(0) as u32),
+                None => return Ok(updated),
+            }
+        }
+        let (black, white) = updated_ratings(ratings[0], ratings[1], game.state);
+        for (player, rating) in players.iter().zip([black, white]) {
+            tx.execute(
+                "UPDATE players SET rating = $2 WHERE player_uuid = $1",
+                &[player, &(rating as i32)],
+            )
+            .await
+            .map_err(db_error)?;
+        }
+        Ok(updated)
+    }
+}
+
+#[async_trait]
+impl GameRepository for PostgresGameRepository {
+    async fn pending_games(&self, player_uuid: String) -> Result<Vec<Game>, RepositoryError> {
+        let client = self.db.client().await?;
+        let rows: Vec<Row> = client
+            .query(
+                &format!(
+                    r#"
+                    SELECT {}
+                    FROM games
+                    WHERE state = 0 AND COALESCE(black_uuid, white_uuid)::text <> $1
+                    ORDER BY start_date ASC
+                    "#,
+                    GAME_COLUMNS
+                ),
+                &[&player_uuid],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows.iter().map(game_from_row).collect())
+    }
+
+    async fn get_move_record(
+        &self,
+        game_uuid: &str,
+        idempotency_key: &str,
+        ttl_secs: u64,
+    ) -> Result<Option<MoveRecord>, RepositoryError> {
+        let client = self.db.client().await?;
+        let row: Option<Row> = client
+            .query_opt(
+                r#"
+                SELECT game_uuid, idempotency_key, player_uuid, played_move, response
+                FROM move_idempotency_keys
+                WHERE game_uuid = $1
+                    AND idempotency_key = $2
+                    AND created_at >= NOW() - make_interval(secs => $3)
+                "#,
+                &[
+                    &parse_uuid(game_uuid)?,
+                    &idempotency_key,
+                    &(ttl_secs as f64),
+                ],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(row.map(|r| MoveRecord {
+            game_uuid: r.get::<_, Uuid>(0).to_string(),
+            idempotency_key: r.get(1),
+            player_uuid: r.get::<_, Uuid>(2).to_string(),
+            played_move: r.get(3),
+            response: r.get(4),
+        }))
+    }
+
+    async fn reserve_move_key(
+        &self,
+        record: &MoveRecord,
+        ttl_secs: u64,
+    ) -> Result<bool, RepositoryError> {
+        let client = self.db.client().await?;
+        client
+            .execute(
+                "DELETE FROM move_idempotency_keys WHERE created_at < NOW() - make_interval(secs => $1)",
+                &[&(ttl_secs as f64)],
+            )
+            .await
+            .map_err(db_error)?;
+        let inserted: u64 = client
+            .execute(
+                r#"
+                INSERT INTO move_idempotency_keys (
+                    game_uuid,
+                    idempotency_key,
+                    player_uuid,
+                    played_move,
+                    response,
+                    created_at
+                )
+                VALUES ($1, $2, $3, $4, '', NOW())
+                ON CONFLICT (game_uuid, idempotency_key) DO NOTHING
+                "#,
+                &[
+                    &parse_uuid(&record.game_uuid)?,
+                    &record.idempotency_key,
+                    &parse_uuid(&record.player_uuid)?,
+                    &record.played_move,
+                ],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(inserted == 1)
+    }
+
+    async fn release_move_key(
+        &self,
+        game_uuid: &str,
+        idempotency_key: &str,
+    ) -> Result<(), RepositoryError> {
+        let client = self.db.client().await?;
+        client
+            .execute(
+                r#"
+                DELETE FROM move_idempotency_keys
+                WHERE game_uuid = $1 AND idempotency_key = $2 AND response = ''
+                "#,
+                &[&parse_uuid(game_uuid)?, &idempotency_key],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn count_games_by_state(&self) -> Result<Vec<(u64, u64)>, RepositoryError> {
+        let client = self.db.client().await?;
+        let rows: Vec<Row> = client
+            .query(
+                "SELECT state, COUNT(*) FROM games GROUP BY state ORDER BY state",
+                &[],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows
+            .iter()
+            .map(|r| (from_db(r.get(0)), from_db(r.get(1))))
+            .collect())
+    }
+
+    async fn get_game(&self, game_uuid: &str) -> Result<Option<Game>, RepositoryError> {
+        let client = self.db.client().await?;
+        let row: Option<Row> = client
+            .query_opt(
+                &format!("SELECT {} FROM games WHERE game_uuid = $1", GAME_COLUMNS),
+                &[&parse_uuid(game_uuid)?],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(row.as_ref().map(game_from_row))
+    }
+
+    async fn get_max_move_no(&self, game_uuid: &str) -> Result<u64, RepositoryError> {
+        let client = self.db.client().await?;
+        let row: Row = client
+            .query_one(
+                "SELECT MAX(move_number) FROM moves WHERE game_uuid = $1",
+                &[&parse_uuid(game_uuid)?],
+            )
+            .await
+            .map_err(db_error)?;
+        let max_move: Option<i64> = row.get(0);
+
+        Ok(max_move.map(from_db).unwrap_or(0))
+    }
+
+    async fn get_last_move(&self, game_uuid: &str) -> Result<u64, RepositoryError> {
+        let client = self.db.client().await?;
+        let row: Option<Row> = client
+            .query_opt(
+                r#"
+                SELECT move_position
+                FROM moves
+                WHERE game_uuid = $1
+                ORDER BY move_number DESC
+                LIMIT 1
+                "#,
+                &[&parse_uuid(game_uuid)?],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(row.map(|r| from_db(r.get(0))).unwrap_or(0))
+    }
+
+    async fn get_moves(&self, game_uuid: &str) -> Result<Vec<u64>, RepositoryError> {
+        let client = self.db.client().await?;
+        let rows: Vec<Row> = client
+            .query(
+                r#"
+                SELECT move_position
+                FROM moves
+                WHERE game_uuid = $1
+                ORDER BY move_number ASC
+                "#,
+                &[&parse_uuid(game_uuid)?],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows.iter().map(|r| from_db(r.get(0))).collect())
+    }
+
+    async fn create_game(&self, game: &Game) -> Result<(), RepositoryError> {
+        let client = self.db.client().await?;
+        insert_game(&client, game).await?;
+
+        Ok(())
+    }
+
+    async fn update_game(
+        &self,
+        game: &Game,
+        record: Option<&MoveRecord>,
+    ) -> Result<(), RepositoryError> {
+        let mut client = self.db.client().await?;
+        let tx = client.transaction().await.map_err(db_error)?;
+        PostgresGameRepository::update_game_on(&tx, game).await?;
+        if let Some(record) = record {
+            complete_move_key(&tx, record).await?;
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update_game_with_move(
+        &self,
+        game: &Game,
+        move_bit: u64,
+        move_no: u64,
+        record: Option<&MoveRecord>,
+    ) -> Result<(), RepositoryError> {
+        let mut client = self.db.client().await?;
+        let tx = client.transaction().await.map_err(db_error)?;
+        PostgresGameRepository::update_game_on(&tx, game).await?;
+        insert_move(
+            &tx,
+            &game.game_uuid,
+            move_bit,
+            move_no,
+            game.position_black,
+            game.position_white,
+        )
+        .await?;
+        if let Some(record) = record {
+            complete_move_key(&tx, record).await?;
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn create_match(
+        &self,
+        game_match: &GameMatch,
+        games: &[NewMatchGame],
+    ) -> Result<(), RepositoryError> {
+        let match_uuid: Uuid = parse_uuid(&game_match.match_uuid)?;
+        let mut client = self.db.client().await?;
+        let tx = client.transaction().await.map_err(db_error)?;
+        tx.execute(
+            r#"
+            INSERT INTO matches (
+                match_uuid,
+                player_a_uuid,
+                player_b_uuid,
+                games,
+                time_control,
+                start_date
+            )
+            VALUES ($1, $2, $3, $4, $5, NOW())
+            "#,
+            &[
+                &match_uuid,
+                &parse_uuid(&game_match.player_a_uuid)?,
+                &parse_uuid(&game_match.player_b_uuid)?,
+                &to_db(game_match.games),
+                &game_match.time_control,
+            ],
+        )
+        .await
+        .map_err(db_error)?;
+        for new_game in games {
+            insert_game(&tx, &new_game.game).await?;
+            for (idx, m) in new_game.moves.iter().enumerate() {
+                insert_move(
+                    &tx,
+                    &new_game.game.game_uuid,
+                    m.move_bit,
+                    idx as u64 + 1,
+                    m.position_black,
+                    m.position_white,
+                )
+                .await?;
+            }
+            tx.execute(
+                r#"
+                INSERT INTO match_games (match_uuid, game_no, game_uuid, opening)
+                VALUES ($1, $2, $3, $4)
+                "#,
+                &[
+                    &match_uuid,
+                    &to_db(new_game.game_no),
+                    &parse_uuid(&new_game.game.game_uuid)?,
+                    &new_game.opening,
+                ],
+            )
+            .await
+            .map_err(db_error)?;
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn save_annotations(
+        &self,
+        game_uuid: &str,
+        annotations: &[MoveAnnotation],
+    ) -> Result<(), RepositoryError> {
+        let game_uuid: Uuid = parse_uuid(game_uuid)?;
+        let mut client = self.db.client().await?;
+        let tx = client.transaction().await.map_err(db_error)?;
+        for annotation in annotations {
+            tx.execute(
+                r#"
+                INSERT INTO move_annotations (
+                    game_uuid,
+                    move_number,
+                    played_move,
+                    best_move,
+                    best_score,
+                    played_score,
+                    exact
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (game_uuid, move_number) DO UPDATE SET
+                    played_move = EXCLUDED.played_move,
+                    best_move = EXCLUDED.best_move,
+                    best_score = EXCLUDED.best_score,
+                    played_score = EXCLUDED.played_score,
+                    exact = EXCLUDED.exact
+                "#,
+                &[
+                    &game_uuid,
+                    &to_db(annotation.move_number),
+                    &to_db(annotation.played_move),
+                    &to_db(annotation.best_move),
+                    &annotation.best_score,
+                    &annotation.played_score,
+                    &annotation.exact,
+                ],
+            )
+            .await
+            .map_err(db_error)?;
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn get_annotations(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Vec<MoveAnnotation>, RepositoryError> {
+        let client = self.db.client().await?;
+        let rows: Vec<Row> = client
+            .query(
+                r#"
+                SELECT
+                    move_number,
+                    played_move,
+                    best_move,
+                    best_score,
+                    played_score,
+                    exact
+                FROM move_annotations
+                WHERE game_uuid = $1
+                ORDER BY move_number ASC
+                "#,
+                &[&parse_uuid(game_uuid)?],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows
+            .iter()
+            .map(|r| MoveAnnotation {
+                move_number: from_db(r.get(0)),
+                played_move: from_db(r.get(1)),
+                best_move: from_db(r.get(2)),
+                best_score: r.get(3),
+                played_score: r.get(4),
+                exact: r.get(5),
+            })
+            .collect())
+    }
+
+    async fn queue_annotation_job(&self, game_uuid: &str) -> Result<(), RepositoryError> {
+        let client = self.db.client().await?;
+        client
+            .execute(
+                r#"
+                INSERT INTO annotation_jobs (game_uuid, status, queued_at)
+                VALUES ($1, 'queued', NOW())
+                ON CONFLICT (game_uuid) DO UPDATE SET
+                    status = 'queued',
+                    error = NULL,
+                    queued_at = NOW(),
+                    finished_at = NULL
+                "#,
+                &[&parse_uuid(game_uuid)?],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update_annotation_job(
+        &self,
+        game_uuid: &str,
+        status: &str,
+        error: &str,
+    ) -> Result<(), RepositoryError> {
+        let client = self.db.client().await?;
+        client
+            .execute(
+                r#"
+                UPDATE annotation_jobs
+                SET
+                    status = $2::text,
+                    error = NULLIF($3::text, ''),
+                    finished_at = CASE WHEN $2::text IN ('done', 'failed') THEN NOW() END
+                WHERE game_uuid = $1
+                "#,
+                &[&parse_uuid(game_uuid)?, &status, &error],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn get_annotation_job(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Option<AnnotationJob>, RepositoryError> {
+        let client = self.db.client().await?;
+        let row: Option<Row> = client
+            .query_opt(
+                r#"
+                SELECT game_uuid, status, COALESCE(error, '')
+                FROM annotation_jobs
+                WHERE game_uuid = $1
+                "#,
+                &[&parse_uuid(game_uuid)?],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(row.map(|r| AnnotationJob {
+            game_uuid: r.get::<_, Uuid>(0).to_string(),
+            status: r.get(1),
+            error: r.get(2),
+        }))
+    }
+
+    async fn unfinished_annotation_jobs(&self) -> Result<Vec<String>, RepositoryError> {
+        let client = self.db.client().await?;
+        let rows: Vec<Row> = client
+            .query(
+                r#"
+                SELECT game_uuid
+                FROM annotation_jobs
+                WHERE status IN ('queued', 'running')
+                ORDER BY queued_at ASC
+                "#,
+                &[],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows
+            .iter()
+            .map(|r| r.get::<_, Uuid>(0).to_string())
+            .collect())
+    }
+
+    async fn player_stats(
+        &self,
+        player_uuid: &str,
+    ) -> Result<Option<PlayerStats>, RepositoryError> {
+        let player: Uuid = match Uuid::parse_str(player_uuid) {
+            Ok(u) => u,
+            Err(_) => return Ok(None),
+        };
+        let client = self.db.client().await?;
+        let rating: Option<i32> = client
+            .query_opt(
+                "SELECT rating FROM players WHERE player_uuid = $1",
+                &[&player],
+            )
+            .await
+            .map_err(db_error)?
+            .map(|r| r.get(0));
+        let rating: u32 = match rating {
+            Some(r) => r as u32,
+            None => return Ok(None),
+        };
+
+        // Disc counts are taken in Rust, bit_count() only works on bit strings.
+        let rows: Vec<Row> = client
+            .query(
+                &format!(
+                    r#"
+                    SELECT {}
+                    FROM games
+                    WHERE state IN (3, 4, 5) AND (black_uuid = $1 OR white_uuid = $1)
+                    "#,
+                    GAME_COLUMNS
+                ),
+                &[&player],
+            )
+            .await
+            .map_err(db_error)?;
+
+        let mut stats: PlayerStats = PlayerStats {
+            player_id: player_uuid.to_string(),
+            rating,
+            ..Default::default()
+        };
+        for row in &rows {
+            stats.record_game(&game_from_row(row));
+        }
+
+        Ok(Some(stats))
+    }
+
+    async fn leaderboard(
+        &self,
+        offset: u64,
+        limit: u64,
+        time_control: &str,
+    ) -> Result<Vec<LeaderboardEntry>, RepositoryError> {
+        let client = self.db.client().await?;
+        let rows: Vec<Row> = client
+            .query(
+                r#"
+                SELECT
+                    p.player_uuid,
+                    p.rating,
+                    COUNT(*) AS games_played,
+                    COUNT(*) FILTER (
+                        WHERE (g.black_uuid = p.player_uuid AND g.state = 3)
+                        OR (g.white_uuid = p.player_uuid AND g.state = 4)
+                    ) AS wins,
+                    COUNT(*) FILTER (
+                        WHERE (g.black_uuid = p.player_uuid AND g.state = 4)
+                        OR (g.white_uuid = p.player_uuid AND g.state = 3)
+                    ) AS losses,
+                    COUNT(*) FILTER (WHERE g.state = 5) AS draws
+                FROM players p
+                JOIN games g
+                    ON (g.black_uuid = p.player_uuid OR g.white_uuid = p.player_uuid)
+                    AND g.state IN (3, 4, 5)
+                    AND ($1 = '' OR g.time_control = $1)
+                GROUP BY p.player_uuid, p.rating
+                ORDER BY p.rating DESC, wins DESC, games_played DESC, p.player_uuid ASC
+                LIMIT $2 OFFSET $3
+                "#,
+                &[&time_control, &to_db(limit), &to_db(offset)],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows
+            .iter()
+            .enumerate()
+            .map(|(idx, r)| LeaderboardEntry {
+                rank: offset + idx as u64 + 1,
+                player_id: r.get::<_, Uuid>(0).to_string(),
+                rating: r.get::<_, i32>(1) as u32,
+                games_played: from_db(r.get(2)),
+                wins: from_db(r.get(3)),
+                losses: from_db(r.get(4)),
+                draws: from_db(r.get(5)),
+            })
+            .collect())
+    }
+}
+
+impl PostgresGameRepository {
+    pub fn new(db: PostgresDatabase) -> Self {
+        PostgresGameRepository { db }
+    }
+}
+
+pub struct PostgresPlayerRepository {
+    db: PostgresDatabase,
+}
+
+#[async_trait]
+impl PlayerRepository for PostgresPlayerRepository {
+    async fn player_list(&self) -> Result<Vec<User>, RepositoryError> {
+        let client = self.db.client().await?;
+        let rows: Vec<Row> = client
+            .query(
+                "SELECT player_uuid, COALESCE(comment, '') FROM players",
+                &[],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows
+            .iter()
+            .map(|r| User {
+                player_uuid: r.get::<_, Uuid>(0).to_string(),
+                comment: r.get(1),
+            })
+            .collect())
+    }
+
+    async fn get_rating(&self, player_uuid: &str) -> Result<Option<u32>, RepositoryError> {
+        let client = self.db.client().await?;
+        let rating: Option<i32> = client
+            .query_opt(
+                "SELECT rating FROM players WHERE player_uuid = $1",
+                &[&parse_uuid(player_uuid)?],
+            )
+            .await
+            .map_err(db_error)?
+            .map(|r| r.get(0));
+
+        Ok(rating.map(|r| r as u32))
+    }
+
+    async fn create_player(&self, player: &User) -> Result<(), RepositoryError> {
+        let client = self.db.client().await?;
+        client
+            .execute(
+                "INSERT INTO players (player_uuid, comment) VALUES ($1, $2)",
+                &[&parse_uuid(&player.player_uuid)?, &player.comment],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+impl PostgresPlayerRepository {
+    pub fn new(db: PostgresDatabase) -> Self {
+        PostgresPlayerRepository { db }
+    }
+}
+
+fn tournament_from_row(row: &Row) -> Tournament {
+    Tournament {
+        tournament_uuid: row.get::<_, Uuid>(0).to_string(),
+        name: row.get(1),
+        format: row.get(2),
+        rounds: from_db(row.get(3)),
+        current_round: from_db(row.get(4)),
+        state: from_db(row.get(5)),
+        time_control: row.get(6),
+        best_of: from_db(row.get(7)),
+        tiebreak: row.get(8),
+    }
+}
+
+fn pairing_from_row(row: &Row) -> TournamentPairing {
+    TournamentPairing {
+        round: from_db(row.get(0)),
+        black_uuid: row.get::<_, Uuid>(1).to_string(),
+        white_uuid: uuid_string(row.get(2)),
+        game_uuid: uuid_string(row.get(3)),
+        game_state: from_db(row.get(4)),
+        position_black: from_db(row.get(5)),
+        position_white: from_db(row.get(6)),
+        bracket_match: row.get(7),
+        game_no: from_db(row.get(8)),
+    }
+}
+
+pub struct PostgresTournamentRepository {
+    db: PostgresDatabase,
+}
+
+#[async_trait]
+impl TournamentRepository for PostgresTournamentRepository {
+    async fn create_tournament(&self, tournament: &Tournament) -> Result<(), RepositoryError> {
+        let client = self.db.client().await?;
+        client
+            .execute(
+                r#"
+                INSERT INTO tournaments (
+                    tournament_uuid,
+                    name,
+                    format,
+                    rounds,
+                    current_round,
+                    state,
+                    time_control,
+                    best_of,
+                    tiebreak,
+                    start_date
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW())
+                "#,
+                &[
+                    &parse_uuid(&tournament.tournament_uuid)?,
+                    &tournament.name,
+                    &tournament.format,
+                    &to_db(tournament.rounds),
+                    &to_db(tournament.current_round),
+                    &to_db(tournament.state),
+                    &tournament.time_control,
+                    &to_db(tournament.best_of),
+                    &tournament.tiebreak,
+                ],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn get_tournament(
+        &self,
+        tournament_uuid: &str,
+    ) -> Result<Option<Tournament>, RepositoryError> {
+        let client = self.db.client().await?;
+        let row: Option<Row> = client
+            .query_opt(
+                r#"
+                SELECT
+                    tournament_uuid,
+                    name,
+                    format,
+                    rounds,
+                    current_round,
+                    state,
+                    time_control,
+                    best_of,
+                    tiebreak
+                FROM tournaments
+                WHERE tournament_uuid = $1
+                "#,
+                &[&parse_uuid(tournament_uuid)?],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(row.as_ref().map(tournament_from_row))
+    }
+
+    async fn update_tournament(&self, tournament: &Tournament) -> Result<(), RepositoryError> {
+        let client = self.db.client().await?;
+        client
+            .execute(
+                r#"
+                UPDATE tournaments
+                SET
+                    rounds = $2,
+                    current_round = $3,
+                    state = $4
+                WHERE tournament_uuid = $1
+                "#,
+                &[
+                    &parse_uuid(&tournament.tournament_uuid)?,
+                    &to_db(tournament.rounds),
+                    &to_db(tournament.current_round),
+                    &to_db(tournament.state),
+                ],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn register_player(
+        &self,
+        tournament_uuid: &str,
+        player_uuid: &str,
+    ) -> Result<bool, RepositoryError> {
+        let client = self.db.client().await?;
+        let inserted: u64 = client
+            .execute(
+                r#"
+                INSERT INTO tournament_players (
+                    tournament_uuid,
+                    player_uuid,
+                    registration_date
+                )
+                VALUES ($1, $2, NOW())
+                ON CONFLICT (tournament_uuid, player_uuid) DO NOTHING
+                "#,
+                &[&parse_uuid(tournament_uuid)?, &parse_uuid(player_uuid)?],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(inserted > 0)
+    }
+
+    async fn get_players(&self, tournament_uuid: &str) -> Result<Vec<String>, RepositoryError> {
+        let client = self.db.client().await?;
+        let rows: Vec<Row> = client
+            .query(
+                r#"
+                SELECT player_uuid
+                FROM tournament_players
+                WHERE tournament_uuid = $1
+                ORDER BY registration_date ASC, player_uuid ASC
+                "#,
+                &[&parse_uuid(tournament_uuid)?],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows
+            .iter()
+            .map(|r| r.get::<_, Uuid>(0).to_string())
+            .collect())
+    }
+
+    async fn get_pairings(
+        &self,
+        tournament_uuid: &str,
+    ) -> Result<Vec<TournamentPairing>, RepositoryError> {
+        let client = self.db.client().await?;
+        let rows: Vec<Row> = client
+            .query(
+                r#"
+                SELECT
+                    p.round,
+                    p.black_uuid,
+                    p.white_uuid,
+                    p.game_uuid,
+                    COALESCE(g.state, 0) AS game_state,
+                    COALESCE(g.position_black, 0) AS position_black,
+                    COALESCE(g.position_white, 0) AS position_white,
+                    COALESCE(p.bracket_match, '') AS bracket_match,
+                    p.game_no
+                FROM tournament_pairings p
+                LEFT JOIN games g ON g.game_uuid = p.game_uuid
+                WHERE p.tournament_uuid = $1
+                ORDER BY p.round ASC, p.pairing_id ASC
+                "#,
+                &[&parse_uuid(tournament_uuid)?],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows.iter().map(pairing_from_row).collect())
+    }
+
+    async fn tournament_for_game(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Option<String>, RepositoryError> {
+        let client = self.db.client().await?;
+        let tournament_uuid: Option<Uuid> = client
+            .query_opt(
+                "SELECT tournament_uuid FROM tournament_pairings WHERE game_uuid = $1 LIMIT 1",
+                &[&parse_uuid(game_uuid)?],
+            )
+            .await
+            .map_err(db_error)?
+            .map(|r| r.get(0));
+
+        Ok(tournament_uuid.map(|u| u.to_string()))
+    }
+
+    async fn start_round(
+        &self,
+        tournament: &Tournament,
+        round: u64,
+        games: &[Game],
+        pairings: &[TournamentPairing],
+    ) -> Result<bool, RepositoryError> {
+        let tournament_uuid: Uuid = parse_uuid(&tournament.tournament_uuid)?;
+        let mut client = self.db.client().await?;
+        let tx = client.transaction().await.map_err(db_error)?;
+        let updated: u64 = tx
+            .execute(
+                r#"
+                UPDATE tournaments
+                SET
+                    rounds = $2,
+                    current_round = $3,
+                    state = 1
+                WHERE tournament_uuid = $1
+                    AND current_round = $4
+                "#,
+                &[
+                    &tournament_uuid,
+                    &to_db(tournament.rounds),
+                    &to_db(round),
+                    &to_db(tournament.current_round),
+                ],
+            )
+            .await
+            .map_err(db_error)?;
+        if updated == 0 {
+            tx.rollback().await.map_err(db_error)?;
+            return Ok(false);
+        }
+
+        for game in games {
+            insert_game(&tx, game).await?;
+        }
+        for pairing in pairings {
+            tx.execute(
+                r#"
+                INSERT INTO tournament_pairings (
+                    tournament_uuid,
+                    round,
+                    black_uuid,
+                    white_uuid,
+                    game_uuid
+                )
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+                &[
+                    &tournament_uuid,
+                    &to_db(pairing.round),
+                    &parse_uuid(&pairing.black_uuid)?,
+                    &parse_optional_uuid(&pairing.white_uuid)?,
+                    &parse_optional_uuid(&pairing.game_uuid)?,
+                ],
+            )
+            .await
+            .map_err(db_error)?;
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(true)
+    }
+
+    async fn add_games(
+        &self,
+        tournament: &Tournament,
+        games: &[Game],
+        pairings: &[TournamentPairing],
+    ) -> Result<bool, RepositoryError> {
+        let tournament_uuid: Uuid = parse_uuid(&tournament.tournament_uuid)?;
+        let mut client = self.db.client().await?;
+        let tx = client.transaction().await.map_err(db_error)?;
+        tx.execute(
+            r#"
+            UPDATE tournaments
+            SET
+                rounds = $2,
+                current_round = GREATEST(current_round, $3),
+                state = 1
+            WHERE tournament_uuid = $1
+            "#,
+            &[
+                &tournament_uuid,
+                &to_db(tournament.rounds),
+                &to_db(tournament.current_round),
+            ],
+        )
+        .await
+        .map_err(db_error)?;
+
+        let mut inserted: usize = 0;
+        for (game, pairing) in games.iter().zip(pairings) {
+            insert_game(&tx, game).await?;
+            // The unique (tournament, bracket match, game number) index makes a
+            // concurrent attempt to create the same bracket game a no-op.
+            let added: u64 = tx
+                .execute(
+                    r#"
+                    INSERT INTO tournament_pairings (
+                        tournament_uuid,
+                        round,
+                        black_uuid,
+                        white_uuid,
+                        game_uuid,
+                        bracket_match,
+                        game_no
+                    )
+                    VALUES ($1, $2, $3, $4, $5, $6, $7)
+                    ON CONFLICT (tournament_uuid, bracket_match, game_no) DO NOTHING
+                    "#,
+                    &[
+                        &tournament_uuid,
+                        &to_db(pairing.round),
+                        &parse_uuid(&pairing.black_uuid)?,
+                        &parse_uuid(&pairing.white_uuid)?,
+                        &parse_uuid(&pairing.game_uuid)?,
+                        &pairing.bracket_match,
+                        &to_db(pairing.game_no),
+                    ],
+                )
+                .await
+                .map_err(db_error)?;
+            if added == 0 {
+                // Another request created this bracket game, drop the duplicate.
+                tx.execute(
+                    "DELETE FROM games WHERE game_uuid = $1",
+                    &[&parse_uuid(&game.game_uuid)?],
+                )
+                .await
+                .map_err(db_error)?;
+            } else {
+                inserted += 1;
+            }
+        }
+
+        if inserted == 0 {
+            tx.rollback().await.map_err(db_error)?;
+            return Ok(false);
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(true)
+    }
+}
+
+impl PostgresTournamentRepository {
+    pub fn new(db: PostgresDatabase) -> Self {
+        PostgresTournamentRepository { db }
+    }
+}
+
+pub struct PostgresMatchRepository {
+    db: PostgresDatabase,
+}
+
+#[async_trait]
+impl MatchRepository for PostgresMatchRepository {
+    async fn get_match(&self, match_uuid: &str) -> Result<Option<GameMatch>, RepositoryError> {
+        let client = self.db.client().await?;
+        let row: Option<Row> = client
+            .query_opt(
+                r#"
+                SELECT match_uuid, player_a_uuid, player_b_uuid, games, time_control
+                FROM matches
+                WHERE match_uuid = $1
+                "#,
+                &[&parse_uuid(match_uuid)?],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(row.map(|r| GameMatch {
+            match_uuid: r.get::<_, Uuid>(0).to_string(),
+            player_a_uuid: r.get::<_, Uuid>(1).to_string(),
+            player_b_uuid: r.get::<_, Uuid>(2).to_string(),
+            games: from_db(r.get(3)),
+            time_control: r.get(4),
+        }))
+    }
+
+    async fn get_match_games(&self, match_uuid: &str) -> Result<Vec<MatchGame>, RepositoryError> {
+        let client = self.db.client().await?;
+        let rows: Vec<Row> = client
+            .query(
+                r#"
+                SELECT
+                    m.game_no,
+                    m.game_uuid,
+                    g.black_uuid,
+                    g.white_uuid,
+                    m.opening,
+                    g.state,
+                    g.position_black,
+                    g.position_white
+                FROM match_games m
+                JOIN games g ON g.game_uuid = m.game_uuid
+                WHERE m.match_uuid = $1
+                ORDER BY m.game_no ASC
+                "#,
+                &[&parse_uuid(match_uuid)?],
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows
+            .iter()
+            .map(|r| MatchGame {
+                game_no: from_db(r.get(0)),
+                game_uuid: r.get::<_, Uuid>(1).to_string(),
+                black_uuid: uuid_string(r.get(2)),
+                white_uuid: uuid_string(r.get(3)),
+                opening: r.get(4),
+                game_state: from_db(r.get(5)),
+                position_black: from_db(r.get(6)),
+                position_white: from_db(r.get(7)),
+            })
+            .collect())
+    }
+}
+
+impl PostgresMatchRepository {
+    pub fn new(db: PostgresDatabase) -> Self {
+        PostgresMatchRepository { db }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::conformance::*;
+
+    /// Runs against the database in `REVERSI_TEST_POSTGRES_URL`, in a schema of
+    /// its own that is migrated, rolled back and dropped afterwards. Skipped
+    /// without the variable.
+    #[tokio::test]
+    async fn conforms_to_the_repository_contract() {
+        let url: String = match std::env::var("REVERSI_TEST_POSTGRES_URL") {
+            Ok(url) => url,
+            Err(_) => {
+                println!("REVERSI_TEST_POSTGRES_URL is not set, skipping");
+                return;
+            }
+        };
+        let schema: String = format!("reversi_test_{}", Uuid::new_v4().simple());
+        let mut config: tokio_postgres::Config = url.parse().unwrap();
+        config.options(format!("-c search_path={}", schema));
+        let db: PostgresDatabase = PostgresDatabase::from_config(config, None).unwrap();
+
+        let players: [String; 2] = [Uuid::new_v4().to_string(), Uuid::new_v4().to_string()];
+        db.client()
+            .await
+            .unwrap()
+            .batch_execute(&format!("CREATE SCHEMA {}", schema))
+            .await
+            .unwrap();
+        migrate(&db).await.unwrap();
+        check_schema(&db).await.unwrap();
+        {
+            let client = db.client().await.unwrap();
+            for player in &players {
+                client
+                    .execute(
+                        "INSERT INTO players (player_uuid, comment) VALUES ($1, 'test')",
+                        &[&parse_uuid(player).unwrap()],
+                    )
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let players: [&str; 2] = [players[0].as_str(), players[1].as_str()];
+        check_player_repository(&PostgresPlayerRepository::new(db.clone()), players).await;
+        check_game_repository(&PostgresGameRepository::new(db.clone()), players).await;
+        check_tournament_repository(
+            &PostgresTournamentRepository::new(db.clone()),
+            &PostgresGameRepository::new(db.clone()),
+            players,
+        )
+        .await;
+        check_match_repository(
+            &PostgresMatchRepository::new(db.clone()),
+            &PostgresGameRepository::new(db.clone()),
+            players,
+        )
+        .await;
+
+        for migration in POSTGRES_MIGRATIONS[1..].iter().rev() {
+            assert_eq!(rollback(&db).await.unwrap().version, migration.version);
+        }
+        assert!(rollback(&db).await.is_err());
+
+        db.client()
+            .await
+            .unwrap()
+            .batch_execute(&format!("DROP SCHEMA {} CASCADE", schema))
+            .await
+            .unwrap();
+    }
+}
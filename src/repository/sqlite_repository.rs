@@ -0,0 +1,1557 @@
+use crate::model::{
+    AnnotationJob, Game, GameMatch, LeaderboardEntry, MatchGame, MoveAnnotation, MoveRecord,
+    PlayerStats, Tournament, TournamentPairing, User,
+};
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::sync::{Arc, Mutex};
+
+use crate::rating::{finishes, updated_ratings};
+use crate::repository::db_errors::*;
+use crate::repository::game_repository::GameRepository;
+use crate::repository::match_repository::{MatchRepository, NewMatchGame};
+use crate::repository::migrations::*;
+use crate::repository::player_repository::PlayerRepository;
+use crate::repository::tournament_repository::TournamentRepository;
+
+const GAME_COLUMNS: &str = r#"
+    game_uuid,
+    IFNULL(black_uuid, '') AS black_uuid,
+    IFNULL(white_uuid, '') AS white_uuid,
+    position_black,
+    position_white,
+    state,
+    time_control
+"#;
+
+/// Versions of the files counted by `PRAGMA user_version` in databases created
+/// before `schema_migrations`, in the order they were applied.
+const LEGACY_VERSIONS: [u64; 5] = [0, 1, 2, 6, 7];
+
+/// Migrations a database created before `schema_migrations` has seen.
+fn legacy_migrations(user_version: i64) -> impl Iterator<Item = &'static Migration> {
+    LEGACY_VERSIONS
+        .iter()
+        .take(user_version as usize)
+        .filter_map(|v| SQLITE_MIGRATIONS.iter().find(|m| m.version == *v))
+}
+
+/// A SQLite database shared by the repositories. Connections are not thread
+/// safe, so queries take turns on a single connection from the blocking pool.
+#[derive(Clone)]
+pub struct SqliteDatabase {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteDatabase {
+    /// Opens the database file, creating it if needed, or a private in-memory
+    /// database for ":memory:".
+    pub fn open(path: &str) -> Result<SqliteDatabase, RepositoryError> {
+        let conn: Connection = if path == ":memory:" {
+            Connection::open_in_memory()
+        } else {
+            Connection::open(path)
+        }
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(SqliteDatabase {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    pub async fn ping(&self) -> Result<(), RepositoryError> {
+        self.call(|conn| conn.query_row("SELECT 1", [], |_| Ok(())))
+            .await
+    }
+
+    async fn call<T, F>(&self, f: F) -> Result<T, RepositoryError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut guard = conn.lock().unwrap();
+            f(&mut guard)
+        })
+        .await
+        .map_err(|e| RepositoryError::Other(e.to_string()))?
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl MigrationTarget for SqliteDatabase {
+    fn migrations(&self) -> &'static [Migration] {
+        SQLITE_MIGRATIONS
+    }
+
+    async fn applied_versions(&self) -> Result<Vec<u64>, RepositoryError> {
+        self.call(|conn| {
+            let tx = conn.transaction()?;
+            let exists: bool = tx
+                .query_row(
+                    "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'schema_migrations'",
+                    [],
+                    |row| row.get::<_, i64>(0),
+                )?
+                > 0;
+            if !exists {
+                tx.execute_batch(
+                    r#"
+                    CREATE TABLE schema_migrations (
+                        version INTEGER NOT NULL PRIMARY KEY,
+                        name TEXT NOT NULL,
+                        applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+                    );
+                    "#,
+                )?;
+                // Databases created before `schema_migrations` counted the
+                // applied files in `PRAGMA user_version`.
+                let legacy: i64 = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+                for migration in legacy_migrations(legacy) {
+                    tx.execute(
+                        "INSERT INTO schema_migrations (version, name) VALUES (?1, ?2)",
+                        params![to_db(migration.version), migration.name],
+                    )?;
+                }
+            }
+            let versions: Vec<u64> = tx
+                .prepare("SELECT version FROM schema_migrations ORDER BY version")?
+                .query_map([], |row| Ok(from_db(row.get(0)?)))?
+                .collect::<rusqlite::Result<Vec<u64>>>()?;
+            tx.commit()?;
+            Ok(versions)
+        })
+        .await
+    }
+
+    async fn current_versions(&self) -> Result<Vec<u64>, RepositoryError> {
+        self.call(|conn| {
+            let exists: bool = conn.query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'schema_migrations'",
+                [],
+                |row| row.get::<_, i64>(0),
+            )? > 0;
+            if !exists {
+                // Older databases count the applied files in `PRAGMA user_version`.
+                let legacy: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+                return Ok(legacy_migrations(legacy).map(|m| m.version).collect());
+            }
+            let versions: Vec<u64> = conn
+                .prepare("SELECT version FROM schema_migrations ORDER BY version")?
+                .query_map([], |row| Ok(from_db(row.get(0)?)))?
+                .collect::<rusqlite::Result<Vec<u64>>>()?;
+            Ok(versions)
+        })
+        .await
+    }
+
+    async fn apply(&self, migration: &Migration) -> Result<(), RepositoryError> {
+        let (version, name, up) = (migration.version, migration.name, migration.up);
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute_batch(up)?;
+            tx.execute(
+                "INSERT INTO schema_migrations (version, name) VALUES (?1, ?2)",
+                params![to_db(version), name],
+            )?;
+            tx.commit()
+        })
+        .await
+    }
+
+    async fn revert(&self, migration: &Migration) -> Result<(), RepositoryError> {
+        let (version, down) = (migration.version, migration.down.unwrap_or_default());
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute_batch(down)?;
+            tx.execute(
+                "DELETE FROM schema_migrations WHERE version = ?1",
+                params![to_db(version)],
+            )?;
+            tx.commit()
+        })
+        .await
+    }
+}
+
+// SQLite integers are signed, bitboards and the pass marker are stored with
+// the same bits.
+fn to_db(value: u64) -> i64 {
+    value as i64
+}
+
+fn from_db(value: i64) -> u64 {
+    value as u64
+}
+
+fn game_from_row(row: &Row) -> rusqlite::Result<Game> {
+    Ok(Game {
+        game_uuid: row.get(0)?,
+        black_uuid: row.get(1)?,
+        white_uuid: row.get(2)?,
+        position_black: from_db(row.get(3)?),
+        position_white: from_db(row.get(4)?),
+        state: from_db(row.get(5)?),
+        time_control: row.get(6)?,
+    })
+}
+
+const UPDATE_GAME: &str = r#"
+    UPDATE games
+    SET
+        black_uuid = NULLIF(?2, ''),
+        white_uuid = NULLIF(?3, ''),
+        position_black = ?4,
+        position_white = ?5,
+        state = ?6,
+        end_date = datetime('now')
+    WHERE game_uuid = ?1
+"#;
+
+/// Fills in the response of the move's reserved idempotency key.
+fn complete_move_key(conn: &Connection, record: &MoveRecord) -> rusqlite::Result<usize> {
+    conn.execute(
+        r#"
+        UPDATE move_idempotency_keys
+        SET response = ?3
+        WHERE game_uuid = ?1 AND idempotency_key = ?2
+        "#,
+        params![record.game_uuid, record.idempotency_key, record.response],
+    )
+}
+
+/// Updates the game; call it within a transaction. The update that finishes
+/// a rated game also updates the ratings of both players.
+fn update_game(conn: &Connection, game: &Game) -> rusqlite::Result<usize> {
+    let previous: Option<i64> = conn
+        .query_row(
+            "SELECT state FROM games WHERE game_uuid = ?1",
+            params![game.game_uuid],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let updated: usize = conn.execute(
+        UPDATE_GAME,
+        params![
+            game.game_uuid,
+            game.black_uuid,
+            game.white_uuid,
+            to_db(game.position_black),
+            to_db(game.position_white),
+            to_db(game.state),
+        ],
+    )?;
+
+    let finished: bool = previous.is_some_and(|p| finishes(from_db(p), game.state));
+    if !finished || game.black_uuid.is_empty() || game.white_uuid.is_empty() {
+        return Ok(updated);
+    }
+    let mut ratings: Vec<u32> = Vec::new();
+    for player in [&game.black_uuid, &game.white_uuid] {
+        let rating: Option<u32> = conn
+            .query_row(
+                "SELECT rating FROM players WHERE player_uuid = ?1",
+                params![player],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match rating {
+            Some(r) => ratings.push(r),
+            None => return Ok(updated),
+        }
+    }
+    let (black, white) = updated_ratings(ratings[0], ratings[1], game.state);
+    for (player, rating) in [(&game.black_uuid, black), (&game.white_uuid, white)] {
+        conn.execute(
+            "UPDATE players SET rating = ?2 WHERE player_uuid = ?1",
+            params![player, rating],
+        )?;
+    }
+    Ok(updated)
+}
+
+fn insert_game(conn: &Connection, game: &Game) -> rusqlite::Result<usize> {
+    conn.execute(
+        r#"
+        INSERT INTO games (
+            game_uuid,
+            black_uuid,
+            white_uuid,
+            position_black,
+            position_white,
+            state,
+            time_control,
+            start_date
+        )
+        VALUES (?1, NULLIF(?2, ''), NULLIF(?3, ''), ?4, ?5, ?6, ?7, datetime('now'))
+        "#,
+        params![
+            game.game_uuid,
+            game.black_uuid,
+            game.white_uuid,
+            to_db(game.position_black),
+            to_db(game.position_white),
+            to_db(game.state),
+            game.time_control,
+        ],
+    )
+}
+
+/// Records a move of the game, the positions are the ones after the move.
+fn insert_move(
+    conn: &Connection,
+    game_uuid: &str,
+    move_bit: u64,
+    move_no: u64,
+    position_black: u64,
+    position_white: u64,
+) -> rusqlite::Result<usize> {
+    conn.execute(
+        r#"
+        INSERT INTO moves (
+            game_uuid,
+            move_number,
+            move_position,
+            position_black,
+            position_white,
+            move_date
+        ) VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))
+        "#,
+        params![
+            game_uuid,
+            to_db(move_no),
+            to_db(move_bit),
+            to_db(position_black),
+            to_db(position_white),
+        ],
+    )
+}
+
+pub struct SqliteGameRepository {
+    db: SqliteDatabase,
+}
+
+#[async_trait]
+impl GameRepository for SqliteGameRepository {
+    async fn pending_games(&self, player_uuid: String) -> Result<Vec<Game>, RepositoryError> {
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    r#"
+                    SELECT {}
+                    FROM games
+                    WHERE state = 0 AND IFNULL(black_uuid, white_uuid) <> ?1
+                    ORDER BY start_date ASC
+                    "#,
+                    GAME_COLUMNS
+                ))?;
+                let games = stmt
+                    .query_map(params![player_uuid], game_from_row)?
+                    .collect::<rusqlite::Result<Vec<Game>>>()?;
+                Ok(games)
+            })
+            .await
+    }
+
+    async fn get_move_record(
+        &self,
+        game_uuid: &str,
+        idempotency_key: &str,
+        ttl_secs: u64,
+    ) -> Result<Option<MoveRecord>, RepositoryError> {
+        let game_uuid: String = game_uuid.to_string();
+        let idempotency_key: String = idempotency_key.to_string();
+        self.db
+            .call(move |conn| {
+                conn.query_row(
+                    r#"
+                    SELECT game_uuid, idempotency_key, player_uuid, played_move, response
+                    FROM move_idempotency_keys
+                    WHERE game_uuid = ?1
+                        AND idempotency_key = ?2
+                        AND created_at >= datetime('now', '-' || ?3 || ' seconds')
+                    "#,
+                    params![game_uuid, idempotency_key, ttl_secs as i64],
+                    |row| {
+                        Ok(MoveRecord {
+                            game_uuid: row.get(0)?,
+                            idempotency_key: row.get(1)?,
+                            player_uuid: row.get(2)?,
+                            played_move: row.get(3)?,
+                            response: row.get(4)?,
+                        })
+                    },
+                )
+                .optional()
+            })
+            .await
+    }
+
+    async fn reserve_move_key(
+        &self,
+        record: &MoveRecord,
+        ttl_secs: u64,
+    ) -> Result<bool, RepositoryError> {
+        let record: MoveRecord = record.clone();
+        let inserted: usize = self
+            .db
+            .call(move |conn| {
+                conn.execute(
+                    "DELETE FROM move_idempotency_keys WHERE created_at < datetime('now', '-' || ?1 || ' seconds')",
+                    params![ttl_secs as i64],
+                )?;
+                conn.execute(
+                    r#"
+                    INSERT INTO move_idempotency_keys (
+                        game_uuid,
+                        idempotency_key,
+                        player_uuid,
+                        played_move,
+                        response,
+                        created_at
+                    )
+                    VALUES (?1, ?2, ?3, ?4, '', datetime('now'))
+                    ON CONFLICT (game_uuid, idempotency_key) DO NOTHING
+                    "#,
+                    params![
+                        record.game_uuid,
+                        record.idempotency_key,
+                        record.player_uuid,
+                        record.played_move
+                    ],
+                )
+            })
+            .await?;
+
+        Ok(inserted == 1)
+    }
+
+    async fn release_move_key(
+        &self,
+        game_uuid: &str,
+        idempotency_key: &str,
+    ) -> Result<(), RepositoryError> {
+        let game_uuid: String = game_uuid.to_string();
+        let idempotency_key: String = idempotency_key.to_string();
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    r#"
+                    DELETE FROM move_idempotency_keys
+                    WHERE game_uuid = ?1 AND idempotency_key = ?2 AND response = ''
+                    "#,
+                    params![game_uuid, idempotency_key],
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn count_games_by_state(&self) -> Result<Vec<(u64, u64)>, RepositoryError> {
+        self.db
+            .call(|conn| {
+                let mut stmt = conn
+                    .prepare("SELECT state, COUNT(*) FROM games GROUP BY state ORDER BY state")?;
+                let counts = stmt
+                    .query_map([], |r| Ok((from_db(r.get(0)?), from_db(r.get(1)?))))?
+                    .collect::<rusqlite::Result<Vec<(u64, u64)>>>()?;
+                Ok(counts)
+            })
+            .await
+    }
+
+    async fn get_game(&self, game_uuid: &str) -> Result<Option<Game>, RepositoryError> {
+        let game_uuid: String = game_uuid.to_string();
+        self.db
+            .call(move |conn| {
+                conn.query_row(
+                    &format!("SELECT {} FROM games WHERE game_uuid = ?1", GAME_COLUMNS),
+                    params![game_uuid],
+                    game_from_row,
+                )
+                .optional()
+            })
+            .await
+    }
+
+    async fn get_max_move_no(&self, game_uuid: &str) -> Result<u64, RepositoryError> {
+        let game_uuid: String = game_uuid.to_string();
+        let max_move: Option<i64> = self
+            .db
+            .call(move |conn| {
+                conn.query_row(
+                    "SELECT MAX(move_number) FROM moves WHERE game_uuid = ?1",
+                    params![game_uuid],
+                    |row| row.get(0),
+                )
+            })
+            .await?;
+
+        Ok(max_move.map(from_db).unwrap_or(0))
+    }
+
+    async fn get_last_move(&self, game_uuid: &str) -> Result<u64, RepositoryError> {
+        let game_uuid: String = game_uuid.to_string();
+        let last_move: Option<i64> = self
+            .db
+            .call(move |conn| {
+                conn.query_row(
+                    r#"
+                    SELECT move_position
+                    FROM moves
+                    WHERE game_uuid = ?1
+                    ORDER BY move_number DESC
+                    LIMIT 1
+                    "#,
+                    params![game_uuid],
+                    |row| row.get(0),
+                )
+                .optional()
+            })
+            .await?;
+
+        Ok(last_move.map(from_db).unwrap_or(0))
+    }
+
+    async fn get_moves(&self, game_uuid: &str) -> Result<Vec<u64>, RepositoryError> {
+        let game_uuid: String = game_uuid.to_string();
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT move_position
+                    FROM moves
+                    WHERE game_uuid = ?1
+                    ORDER BY move_number ASC
+                    "#,
+                )?;
+                let moves = stmt
+                    .query_map(params![game_uuid], |row| row.get::<_, i64>(0).map(from_db))?
+                    .collect::<rusqlite::Result<Vec<u64>>>()?;
+                Ok(moves)
+            })
+            .await
+    }
+
+    async fn create_game(&self, game: &Game) -> Result<(), RepositoryError> {
+        let game: Game = game.clone();
+        self.db.call(move |conn| insert_game(conn, &game)).await?;
+
+        Ok(())
+    }
+
+    async fn update_game(
+        &self,
+        game: &Game,
+        record: Option<&MoveRecord>,
+    ) -> Result<(), RepositoryError> {
+        let game: Game = game.clone();
+        let record: Option<MoveRecord> = record.cloned();
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                update_game(&tx, &game)?;
+                if let Some(record) = &record {
+                    complete_move_key(&tx, record)?;
+                }
+                tx.commit()
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_game_with_move(
+        &self,
+        game: &Game,
+        move_bit: u64,
+        move_no: u64,
+        record: Option<&MoveRecord>,
+    ) -> Result<(), RepositoryError> {
+        let game: Game = game.clone();
+        let record: Option<MoveRecord> = record.cloned();
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                update_game(&tx, &game)?;
+                insert_move(
+                    &tx,
+                    &game.game_uuid,
+                    move_bit,
+                    move_no,
+                    game.position_black,
+                    game.position_white,
+                )?;
+                if let Some(record) = &record {
+                    complete_move_key(&tx, record)?;
+                }
+                tx.commit()
+            })
+            .await
+    }
+
+    async fn create_match(
+        &self,
+        game_match: &GameMatch,
+        games: &[NewMatchGame],
+    ) -> Result<(), RepositoryError> {
+        let game_match: GameMatch = game_match.clone();
+        let games: Vec<NewMatchGame> = games.to_vec();
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                tx.execute(
+                    r#"
+                    INSERT INTO matches (
+                        match_uuid,
+                        player_a_uuid,
+                        player_b_uuid,
+                        games,
+                        time_control,
+                        start_date
+                    )
+                    VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))
+                    "#,
+                    params![
+                        game_match.match_uuid,
+                        game_match.player_a_uuid,
+                        game_match.player_b_uuid,
+                        to_db(game_match.games),
+                        game_match.time_control,
+                    ],
+                )?;
+                for new_game in &games {
+                    insert_game(&tx, &new_game.game)?;
+                    for (idx, m) in new_game.moves.iter().enumerate() {
+                        insert_move(
+                            &tx,
+                            &new_game.game.game_uuid,
+                            m.move_bit,
+                            idx as u64 + 1,
+                            m.position_black,
+                            m.position_white,
+                        )?;
+                    }
+                    tx.execute(
+                        r#"
+                        INSERT INTO match_games (match_uuid, game_no, game_uuid, opening)
+                        VALUES (?1, ?2, ?3, ?4)
+                        "#,
+                        params![
+                            game_match.match_uuid,
+                            to_db(new_game.game_no),
+                            new_game.game.game_uuid,
+                            new_game.opening,
+                        ],
+                    )?;
+                }
+                tx.commit()
+            })
+            .await
+    }
+
+    async fn save_annotations(
+        &self,
+        game_uuid: &str,
+        annotations: &[MoveAnnotation],
+    ) -> Result<(), RepositoryError> {
+        let game_uuid: String = game_uuid.to_string();
+        let annotations: Vec<MoveAnnotation> = annotations.to_vec();
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                for annotation in &annotations {
+                    tx.execute(
+                        r#"
+                        INSERT OR REPLACE INTO move_annotations (
+                            game_uuid,
+                            move_number,
+                            played_move,
+                            best_move,
+                            best_score,
+                            played_score,
+                            exact
+                        )
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                        "#,
+                        params![
+                            game_uuid,
+                            to_db(annotation.move_number),
+                            to_db(annotation.played_move),
+                            to_db(annotation.best_move),
+                            annotation.best_score,
+                            annotation.played_score,
+                            annotation.exact,
+                        ],
+                    )?;
+                }
+                tx.commit()
+            })
+            .await
+    }
+
+    async fn get_annotations(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Vec<MoveAnnotation>, RepositoryError> {
+        let game_uuid: String = game_uuid.to_string();
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT
+                        move_number,
+                        played_move,
+                        best_move,
+                        best_score,
+                        played_score,
+                        exact
+                    FROM move_annotations
+                    WHERE game_uuid = ?1
+                    ORDER BY move_number ASC
+                    "#,
+                )?;
+                let annotations = stmt
+                    .query_map(params![game_uuid], |row| {
+                        Ok(MoveAnnotation {
+                            move_number: from_db(row.get(0)?),
+                            played_move: from_db(row.get(1)?),
+                            best_move: from_db(row.get(2)?),
+                            best_score: row.get(3)?,
+                            played_score: row.get(4)?,
+                            exact: row.get(5)?,
+                        })
+                    })?
+                    .collect::<rusqlite::Result<Vec<MoveAnnotation>>>()?;
+                Ok(annotations)
+            })
+            .await
+    }
+
+    async fn queue_annotation_job(&self, game_uuid: &str) -> Result<(), RepositoryError> {
+        let game_uuid: String = game_uuid.to_string();
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT INTO annotation_jobs (game_uuid, status, queued_at)
+                    VALUES (?1, 'queued', datetime('now'))
+                    ON CONFLICT (game_uuid) DO UPDATE SET
+                        status = 'queued',
+                        error = NULL,
+                        queued_at = datetime('now'),
+                        finished_at = NULL
+                    "#,
+                    params![game_uuid],
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_annotation_job(
+        &self,
+        game_uuid: &str,
+        status: &str,
+        error: &str,
+    ) -> Result<(), RepositoryError> {
+        let game_uuid: String = game_uuid.to_string();
+        let status: String = status.to_string();
+        let error: String = error.to_string();
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    r#"
+                    UPDATE annotation_jobs
+                    SET
+                        status = ?2,
+                        error = NULLIF(?3, ''),
+                        finished_at = CASE WHEN ?2 IN ('done', 'failed') THEN datetime('now') END
+                    WHERE game_uuid = ?1
+                    "#,
+                    params![game_uuid, status, error],
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_annotation_job(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Option<AnnotationJob>, RepositoryError> {
+        let game_uuid: String = game_uuid.to_string();
+        self.db
+            .call(move |conn| {
+                conn.query_row(
+                    r#"
+                    SELECT game_uuid, status, IFNULL(error, '')
+                    FROM annotation_jobs
+                    WHERE game_uuid = ?1
+                    "#,
+                    params![game_uuid],
+                    |row| {
+                        Ok(AnnotationJob {
+                            game_uuid: row.get(0)?,
+                            status: row.get(1)?,
+                            error: row.get(2)?,
+                        })
+                    },
+                )
+                .optional()
+            })
+            .await
+    }
+
+    async fn unfinished_annotation_jobs(&self) -> Result<Vec<String>, RepositoryError> {
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT game_uuid
+                    FROM annotation_jobs
+                    WHERE status IN ('queued', 'running')
+                    ORDER BY queued_at ASC
+                    "#,
+                )?;
+                let games = stmt
+                    .query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()?;
+                Ok(games)
+            })
+            .await
+    }
+
+    async fn player_stats(
+        &self,
+        player_uuid: &str,
+    ) -> Result<Option<PlayerStats>, RepositoryError> {
+        let player_uuid: String = player_uuid.to_string();
+        self.db
+            .call(move |conn| {
+                let rating: Option<u32> = conn
+                    .query_row(
+                        "SELECT rating FROM players WHERE player_uuid = ?1",
+                        params![player_uuid],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                let rating: u32 = match rating {
+                    Some(r) => r,
+                    None => return Ok(None),
+                };
+
+                // SQLite has no popcount, the disc differential is computed here.
+                let mut stmt = conn.prepare(&format!(
+                    r#"
+                    SELECT {}
+                    FROM games
+                    WHERE state IN (3, 4, 5) AND (black_uuid = ?1 OR white_uuid = ?1)
+                    "#,
+                    GAME_COLUMNS
+                ))?;
+                let mut stats: PlayerStats = PlayerStats {
+                    player_id: player_uuid.clone(),
+                    rating,
+                    ..Default::default()
+                };
+                for game in stmt.query_map(params![player_uuid], game_from_row)? {
+                    stats.record_game(&game?);
+                }
+                Ok(Some(stats))
+            })
+            .await
+    }
+
+    async fn leaderboard(
+        &self,
+        offset: u64,
+        limit: u64,
+        time_control: &str,
+    ) -> Result<Vec<LeaderboardEntry>, RepositoryError> {
+        let time_control: String = time_control.to_string();
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT
+                        p.player_uuid,
+                        p.rating,
+                        COUNT(*) AS games_played,
+                        IFNULL(SUM(
+                            (g.black_uuid = p.player_uuid AND g.state = 3)
+                            OR (g.white_uuid = p.player_uuid AND g.state = 4)
+                        ), 0) AS wins,
+                        IFNULL(SUM(
+                            (g.black_uuid = p.player_uuid AND g.state = 4)
+                            OR (g.white_uuid = p.player_uuid AND g.state = 3)
+                        ), 0) AS losses,
+                        IFNULL(SUM(g.state = 5), 0) AS draws
+                    FROM players p
+                    JOIN games g
+                        ON (g.black_uuid = p.player_uuid OR g.white_uuid = p.player_uuid)
+                        AND g.state IN (3, 4, 5)
+                        AND (?1 = '' OR g.time_control = ?1)
+                    GROUP BY p.player_uuid, p.rating
+                    ORDER BY p.rating DESC, wins DESC, games_played DESC, p.player_uuid ASC
+                    LIMIT ?2 OFFSET ?3
+                    "#,
+                )?;
+                let entries = stmt
+                    .query_map(params![time_control, to_db(limit), to_db(offset)], |row| {
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            row.get::<_, u32>(1)?,
+                            from_db(row.get(2)?),
+                            from_db(row.get(3)?),
+                            from_db(row.get(4)?),
+                            from_db(row.get(5)?),
+                        ))
+                    })?
+                    .enumerate()
+                    .map(|(idx, row)| {
+                        row.map(|(player_id, rating, games_played, wins, losses, draws)| {
+                            LeaderboardEntry {
+                                rank: offset + idx as u64 + 1,
+                                player_id,
+                                rating,
+                                games_played,
+                                wins,
+                                losses,
+                                draws,
+                            }
+                        })
+                    })
+                    .collect::<rusqlite::Result<Vec<LeaderboardEntry>>>()?;
+                Ok(entries)
+            })
+            .await
+    }
+}
+
+impl SqliteGameRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        SqliteGameRepository { db }
+    }
+}
+
+pub struct SqlitePlayerRepository {
+    db: SqliteDatabase,
+}
+
+#[async_trait]
+impl PlayerRepository for SqlitePlayerRepository {
+    async fn player_list(&self) -> Result<Vec<User>, RepositoryError> {
+        self.db
+            .call(move |conn| {
+                let mut stmt =
+                    conn.prepare("SELECT player_uuid, IFNULL(comment, '') FROM players")?;
+                let users = stmt
+                    .query_map([], |row| {
+                        Ok(User {
+                            player_uuid: row.get(0)?,
+                            comment: row.get(1)?,
+                        })
+                    })?
+                    .collect::<rusqlite::Result<Vec<User>>>()?;
+                Ok(users)
+            })
+            .await
+    }
+
+    async fn get_rating(&self, player_uuid: &str) -> Result<Option<u32>, RepositoryError> {
+        let player_uuid: String = player_uuid.to_string();
+        self.db
+            .call(move |conn| {
+                conn.query_row(
+                    "SELECT rating FROM players WHERE player_uuid = ?1",
+                    params![player_uuid],
+                    |row| row.get(0),
+                )
+                .optional()
+            })
+            .await
+    }
+
+    async fn create_player(&self, player: &User) -> Result<(), RepositoryError> {
+        let player_uuid: String = player.player_uuid.clone();
+        let comment: String = player.comment.clone();
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO players (player_uuid, comment) VALUES (?1, ?2)",
+                    params![player_uuid, comment],
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl SqlitePlayerRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        SqlitePlayerRepository { db }
+    }
+}
+
+fn tournament_from_row(row: &Row) -> rusqlite::Result<Tournament> {
+    Ok(Tournament {
+        tournament_uuid: row.get(0)?,
+        name: row.get(1)?,
+        format: row.get(2)?,
+        rounds: from_db(row.get(3)?),
+        current_round: from_db(row.get(4)?),
+        state: from_db(row.get(5)?),
+        time_control: row.get(6)?,
+        best_of: from_db(row.get(7)?),
+        tiebreak: row.get(8)?,
+    })
+}
+
+fn pairing_from_row(row: &Row) -> rusqlite::Result<TournamentPairing> {
+    Ok(TournamentPairing {
+        round: from_db(row.get(0)?),
+        black_uuid: row.get(1)?,
+        white_uuid: row.get(2)?,
+        game_uuid: row.get(3)?,
+        game_state: from_db(row.get(4)?),
+        position_black: from_db(row.get(5)?),
+        position_white: from_db(row.get(6)?),
+        bracket_match: row.get(7)?,
+        game_no: from_db(row.get(8)?),
+    })
+}
+
+pub struct SqliteTournamentRepository {
+    db: SqliteDatabase,
+}
+
+#[async_trait]
+impl TournamentRepository for SqliteTournamentRepository {
+    async fn create_tournament(&self, tournament: &Tournament) -> Result<(), RepositoryError> {
+        let tournament: Tournament = tournament.clone();
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT INTO tournaments (
+                        tournament_uuid,
+                        name,
+                        format,
+                        rounds,
+                        current_round,
+                        state,
+                        time_control,
+                        best_of,
+                        tiebreak,
+                        start_date
+                    )
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, datetime('now'))
+                    "#,
+                    params![
+                        tournament.tournament_uuid,
+                        tournament.name,
+                        tournament.format,
+                        to_db(tournament.rounds),
+                        to_db(tournament.current_round),
+                        to_db(tournament.state),
+                        tournament.time_control,
+                        to_db(tournament.best_of),
+                        tournament.tiebreak,
+                    ],
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_tournament(
+        &self,
+        tournament_uuid: &str,
+    ) -> Result<Option<Tournament>, RepositoryError> {
+        let tournament_uuid: String = tournament_uuid.to_string();
+        self.db
+            .call(move |conn| {
+                conn.query_row(
+                    r#"
+                    SELECT
+                        tournament_uuid,
+                        name,
+                        format,
+                        rounds,
+                        current_round,
+                        state,
+                        time_control,
+                        best_of,
+                        tiebreak
+                    FROM tournaments
+                    WHERE tournament_uuid = ?1
+                    "#,
+                    params![tournament_uuid],
+                    tournament_from_row,
+                )
+                .optional()
+            })
+            .await
+    }
+
+    async fn update_tournament(&self, tournament: &Tournament) -> Result<(), RepositoryError> {
+        let tournament: Tournament = tournament.clone();
+        self.db
+            .call(move |conn| {
+                conn.execute(
+                    r#"
+                    UPDATE tournaments
+                    SET
+                        rounds = ?2,
+                        current_round = ?3,
+                        state = ?4
+                    WHERE tournament_uuid = ?1
+                    "#,
+                    params![
+                        tournament.tournament_uuid,
+                        to_db(tournament.rounds),
+                        to_db(tournament.current_round),
+                        to_db(tournament.state),
+                    ],
+                )
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn register_player(
+        &self,
+        tournament_uuid: &str,
+        player_uuid: &str,
+    ) -> Result<bool, RepositoryError> {
+        let tournament_uuid: String = tournament_uuid.to_string();
+        let player_uuid: String = player_uuid.to_string();
+        let inserted: usize = self
+            .db
+            .call(move |conn| {
+                conn.execute(
+                    r#"
+                    INSERT OR IGNORE INTO tournament_players (
+                        tournament_uuid,
+                        player_uuid,
+                        registration_date
+                    )
+                    VALUES (?1, ?2, datetime('now'))
+                    "#,
+                    params![tournament_uuid, player_uuid],
+                )
+            })
+            .await?;
+
+        Ok(inserted > 0)
+    }
+
+    async fn get_players(&self, tournament_uuid: &str) -> Result<Vec<String>, RepositoryError> {
+        let tournament_uuid: String = tournament_uuid.to_string();
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT player_uuid
+                    FROM tournament_players
+                    WHERE tournament_uuid = ?1
+                    ORDER BY registration_date ASC, player_uuid ASC
+                    "#,
+                )?;
+                let players = stmt
+                    .query_map(params![tournament_uuid], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()?;
+                Ok(players)
+            })
+            .await
+    }
+
+    async fn get_pairings(
+        &self,
+        tournament_uuid: &str,
+    ) -> Result<Vec<TournamentPairing>, RepositoryError> {
+        let tournament_uuid: String = tournament_uuid.to_string();
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT
+                        p.round,
+                        p.black_uuid,
+                        IFNULL(p.white_uuid, '') AS white_uuid,
+                        IFNULL(p.game_uuid, '') AS game_uuid,
+                        IFNULL(g.state, 0) AS game_state,
+                        IFNULL(g.position_black, 0) AS position_black,
+                        IFNULL(g.position_white, 0) AS position_white,
+                        IFNULL(p.bracket_match, '') AS bracket_match,
+                        p.game_no
+                    FROM tournament_pairings p
+                    LEFT JOIN games g ON g.game_uuid = p.game_uuid
+                    WHERE p.tournament_uuid = ?1
+                    ORDER BY p.round ASC, p.pairing_id ASC
+                    "#,
+                )?;
+                let pairings = stmt
+                    .query_map(params![tournament_uuid], pairing_from_row)?
+                    .collect::<rusqlite::Result<Vec<TournamentPairing>>>()?;
+                Ok(pairings)
+            })
+            .await
+    }
+
+    async fn tournament_for_game(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Option<String>, RepositoryError> {
+        let game_uuid: String = game_uuid.to_string();
+        self.db
+            .call(move |conn| {
+                conn.query_row(
+                    "SELECT tournament_uuid FROM tournament_pairings WHERE game_uuid = ?1",
+                    params![game_uuid],
+                    |row| row.get(0),
+                )
+                .optional()
+            })
+            .await
+    }
+
+    async fn start_round(
+        &self,
+        tournament: &Tournament,
+        round: u64,
+        games: &[Game],
+        pairings: &[TournamentPairing],
+    ) -> Result<bool, RepositoryError> {
+        let tournament: Tournament = tournament.clone();
+        let games: Vec<Game> = games.to_vec();
+        let pairings: Vec<TournamentPairing> = pairings.to_vec();
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let updated: usize = tx.execute(
+                    r#"
+                    UPDATE tournaments
+                    SET
+                        rounds = ?2,
+                        current_round = ?3,
+                        state = 1
+                    WHERE tournament_uuid = ?1
+                        AND current_round = ?4
+                    "#,
+                    params![
+                        tournament.tournament_uuid,
+                        to_db(tournament.rounds),
+                        to_db(round),
+                        to_db(tournament.current_round),
+                    ],
+                )?;
+                if updated == 0 {
+                    return Ok(false);
+                }
+                for game in &games {
+                    insert_game(&tx, game)?;
+                }
+                for pairing in &pairings {
+                    tx.execute(
+                        r#"
+                        INSERT INTO tournament_pairings (
+                            tournament_uuid,
+                            round,
+                            black_uuid,
+                            white_uuid,
+                            game_uuid
+                        )
+                        VALUES (?1, ?2, ?3, NULLIF(?4, ''), NULLIF(?5, ''))
+                        "#,
+                        params![
+                            tournament.tournament_uuid,
+                            to_db(pairing.round),
+                            pairing.black_uuid,
+                            pairing.white_uuid,
+                            pairing.game_uuid,
+                        ],
+                    )?;
+                }
+                tx.commit()?;
+                Ok(true)
+            })
+            .await
+    }
+
+    async fn add_games(
+        &self,
+        tournament: &Tournament,
+        games: &[Game],
+        pairings: &[TournamentPairing],
+    ) -> Result<bool, RepositoryError> {
+        let tournament: Tournament = tournament.clone();
+        let games: Vec<Game> = games.to_vec();
+        let pairings: Vec<TournamentPairing> = pairings.to_vec();
+        self.db
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                tx.execute(
+                    r#"
+                    UPDATE tournaments
+                    SET
+                        rounds = ?2,
+                        current_round = MAX(current_round, ?3),
+                        state = 1
+                    WHERE tournament_uuid = ?1
+                    "#,
+                    params![
+                        tournament.tournament_uuid,
+                        to_db(tournament.rounds),
+                        to_db(tournament.current_round),
+                    ],
+                )?;
+
+                let mut inserted: usize = 0;
+                for (game, pairing) in games.iter().zip(&pairings) {
+                    insert_game(&tx, game)?;
+                    // The unique (tournament, bracket match, game number) index
+                    // skips a bracket game that already exists.
+                    let added: usize = tx.execute(
+                        r#"
+                        INSERT OR IGNORE INTO tournament_pairings (
+                            tournament_uuid,
+                            round,
+                            black_uuid,
+                            white_uuid,
+                            game_uuid,
+                            bracket_match,
+                            game_no
+                        )
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                        "#,
+                        params![
+                            tournament.tournament_uuid,
+                            to_db(pairing.round),
+                            pairing.black_uuid,
+                            pairing.white_uuid,
+                            pairing.game_uuid,
+                            pairing.bracket_match,
+                            to_db(pairing.game_no),
+                        ],
+                    )?;
+                    if added == 0 {
+                        tx.execute(
+                            "DELETE FROM games WHERE game_uuid = ?1",
+                            params![game.game_uuid],
+                        )?;
+                    } else {
+                        inserted += 1;
+                    }
+                }
+
+                if inserted == 0 {
+                    return Ok(false);
+                }
+                tx.commit()?;
+                Ok(true)
+            })
+            .await
+    }
+}
+
+impl SqliteTournamentRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        SqliteTournamentRepository { db }
+    }
+}
+
+pub struct SqliteMatchRepository {
+    db: SqliteDatabase,
+}
+
+#[async_trait]
+impl MatchRepository for SqliteMatchRepository {
+    async fn get_match(&self, match_uuid: &str) -> Result<Option<GameMatch>, RepositoryError> {
+        let match_uuid: String = match_uuid.to_string();
+        self.db
+            .call(move |conn| {
+                conn.query_row(
+                    r#"
+                    SELECT match_uuid, player_a_uuid, player_b_uuid, games, time_control
+                    FROM matches
+                    WHERE match_uuid = ?1
+                    "#,
+                    params![match_uuid],
+                    |row| {
+                        Ok(GameMatch {
+                            match_uuid: row.get(0)?,
+                            player_a_uuid: row.get(1)?,
+                            player_b_uuid: row.get(2)?,
+                            games: from_db(row.get(3)?),
+                            time_control: row.get(4)?,
+                        })
+                    },
+                )
+                .optional()
+            })
+            .await
+    }
+
+    async fn get_match_games(&self, match_uuid: &str) -> Result<Vec<MatchGame>, RepositoryError> {
+        let match_uuid: String = match_uuid.to_string();
+        self.db
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT
+                        m.game_no,
+                        m.game_uuid,
+                        IFNULL(g.black_uuid, '') AS black_uuid,
+                        IFNULL(g.white_uuid, '') AS white_uuid,
+                        m.opening,
+                        g.state,
+                        g.position_black,
+                        g.position_white
+                    FROM match_games m
+                    JOIN games g ON g.game_uuid = m.game_uuid
+                    WHERE m.match_uuid = ?1
+                    ORDER BY m.game_no ASC
+                    "#,
+                )?;
+                let games = stmt
+                    .query_map(params![match_uuid], |row| {
+                        Ok(MatchGame {
+                            game_no: from_db(row.get(0)?),
+                            game_uuid: row.get(1)?,
+                            black_uuid: row.get(2)?,
+                            white_uuid: row.get(3)?,
+                            opening: row.get(4)?,
+                            game_state: from_db(row.get(5)?),
+                            position_black: from_db(row.get(6)?),
+                            position_white: from_db(row.get(7)?),
+                        })
+                    })?
+                    .collect::<rusqlite::Result<Vec<MatchGame>>>()?;
+                Ok(games)
+            })
+            .await
+    }
+}
+
+impl SqliteMatchRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        SqliteMatchRepository { db }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::conformance::*;
+
+    async fn database_with_players(players: [&str; 2]) -> SqliteDatabase {
+        let db: SqliteDatabase = SqliteDatabase::open(":memory:").unwrap();
+        migrate(&db).await.unwrap();
+        let players: Vec<String> = players.iter().map(|p| p.to_string()).collect();
+        db.call(move |conn| {
+            for player in &players {
+                conn.execute(
+                    "INSERT INTO players (player_uuid, comment) VALUES (?1, 'test')",
+                    params![player],
+                )?;
+            }
+            Ok(())
+        })
+        .await
+        .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn conforms_to_the_repository_contract() {
+        let players: [&str; 2] = [
+            "2d4f1c3e-0a6b-4a8e-9d1e-3f5b7c9a1b2c",
+            "7e8f9a0b-1c2d-4e3f-8a5b-6c7d8e9f0a1b",
+        ];
+        let db: SqliteDatabase = database_with_players(players).await;
+        check_player_repository(&SqlitePlayerRepository::new(db.clone()), players).await;
+        check_game_repository(&SqliteGameRepository::new(db.clone()), players).await;
+        check_tournament_repository(
+            &SqliteTournamentRepository::new(db.clone()),
+            &SqliteGameRepository::new(db.clone()),
+            players,
+        )
+        .await;
+        check_match_repository(
+            &SqliteMatchRepository::new(db.clone()),
+            &SqliteGameRepository::new(db),
+            players,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn migrates_and_rolls_back() {
+        let db: SqliteDatabase = SqliteDatabase::open(":memory:").unwrap();
+        assert!(check_schema(&db).await.is_err());
+        // The check does not create `schema_migrations`.
+        assert!(db.current_versions().await.unwrap().is_empty());
+        let tables: i64 = db
+            .call(|conn| conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get(0)))
+            .await
+            .unwrap();
+        assert_eq!(tables, 0);
+        assert_eq!(migrate(&db).await.unwrap().len(), SQLITE_MIGRATIONS.len());
+        assert!(migrate(&db).await.unwrap().is_empty());
+        check_schema(&db).await.unwrap();
+
+        for migration in SQLITE_MIGRATIONS[1..].iter().rev() {
+            assert_eq!(rollback(&db).await.unwrap().version, migration.version);
+        }
+        assert!(rollback(&db).await.is_err());
+        assert_eq!(
+            migrate(&db).await.unwrap().len(),
+            SQLITE_MIGRATIONS.len() - 1
+        );
+    }
+
+    #[tokio::test]
+    async fn adopts_databases_counted_in_user_version() {
+        let db: SqliteDatabase = SqliteDatabase::open(":memory:").unwrap();
+        db.call(|conn| {
+            for migration in legacy_migrations(4) {
+                conn.execute_batch(migration.up)?;
+            }
+            conn.execute_batch("PRAGMA user_version = 4")
+        })
+        .await
+        .unwrap();
+        assert_eq!(db.current_versions().await.unwrap(), vec![0, 1, 2, 6]);
+        let applied: Vec<&Migration> = migrate(&db).await.unwrap();
+        let versions: Vec<u64> = applied.iter().map(|m| m.version).collect();
+        assert_eq!(versions, vec![3, 4, 5, 7, 8]);
+    }
+
+    #[tokio::test]
+    async fn refuses_a_newer_schema() {
+        let db: SqliteDatabase = SqliteDatabase::open(":memory:").unwrap();
+        migrate(&db).await.unwrap();
+        db.call(|conn| {
+            conn.execute(
+                "INSERT INTO schema_migrations (version, name) VALUES (99, '0099_future')",
+                [],
+            )
+        })
+        .await
+        .unwrap();
+        assert!(check_schema(&db).await.is_err());
+        assert!(migrate(&db).await.is_err());
+    }
+}
@@ -0,0 +1,531 @@
+use crate::model::{Game, Tournament, TournamentPairing};
+use async_trait::async_trait;
+use mysql_async::{params, prelude::*, TxOpts};
+
+use crate::repository::db_errors::*;
+use crate::repository::game_repository::insert_game;
+use crate::repository::mysql_pool::MySqlPool;
+
+#[async_trait]
+pub trait TournamentRepository {
+    async fn create_tournament(&self, tournament: &Tournament) -> Result<(), RepositoryError>;
+    async fn get_tournament(
+        &self,
+        tournament_uuid: &str,
+    ) -> Result<Option<Tournament>, RepositoryError>;
+    async fn update_tournament(&self, tournament: &Tournament) -> Result<(), RepositoryError>;
+    /// Returns false if the player has already been registered.
+    async fn register_player(
+        &self,
+        tournament_uuid: &str,
+        player_uuid: &str,
+    ) -> Result<bool, RepositoryError>;
+    /// Registered players in registration order.
+    async fn get_players(&self, tournament_uuid: &str) -> Result<Vec<String>, RepositoryError>;
+    async fn get_pairings(
+        &self,
+        tournament_uuid: &str,
+    ) -> Result<Vec<TournamentPairing>, RepositoryError>;
+    async fn tournament_for_game(&self, game_uuid: &str)
+        -> Result<Option<String>, RepositoryError>;
+    /// Atomically moves the tournament from `tournament.current_round` to `round`,
+    /// creating the games and pairings of the new round. Returns false if another
+    /// request has already started that round.
+    async fn start_round(
+        &self,
+        tournament: &Tournament,
+        round: u64,
+        games: &[Game],
+        pairings: &[TournamentPairing],
+    ) -> Result<bool, RepositoryError>;
+    /// Creates bracket games together with their pairings and stores the tournament
+    /// progress. Bracket games that already exist are skipped; returns false if
+    /// all of them did.
+    async fn add_games(
+        &self,
+        tournament: &Tournament,
+        games: &[Game],
+        pairings: &[TournamentPairing],
+    ) -> Result<bool, RepositoryError>;
+}
+
+pub struct MySqlTournamentRepository {
+    pool: MySqlPool,
+}
+
+#[async_trait]
+impl TournamentRepository for MySqlTournamentRepository {
+    async fn create_tournament(&self, tournament: &Tournament) -> Result<(), RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        conn.exec_drop(
+            r#"
+            INSERT INTO tournaments (
+                tournament_uuid,
+                name,
+                format,
+                rounds,
+                current_round,
+                state,
+                time_control,
+                best_of,
+                tiebreak,
+                start_date
+            )
+            VALUES (
+                UUID_TO_BIN(:tournament_uuid),
+                :name,
+                :format,
+                :rounds,
+                :current_round,
+                :state,
+                :time_control,
+                :best_of,
+                :tiebreak,
+                NOW()
+            )
+            "#,
+            params! {
+                "tournament_uuid" => &tournament.tournament_uuid,
+                "name" => &tournament.name,
+                "format" => &tournament.format,
+                "rounds" => tournament.rounds,
+                "current_round" => tournament.current_round,
+                "state" => tournament.state,
+                "time_control" => &tournament.time_control,
+                "best_of" => tournament.best_of,
+                "tiebreak" => &tournament.tiebreak,
+            },
+        )
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn get_tournament(
+        &self,
+        tournament_uuid: &str,
+    ) -> Result<Option<Tournament>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let tournament: Option<Tournament> = conn
+            .exec_first(
+                r#"
+            SELECT
+                BIN_TO_UUID(tournament_uuid) AS tournament_uuid,
+                name,
+                format,
+                rounds,
+                current_round,
+                state,
+                time_control,
+                best_of,
+                tiebreak
+            FROM tournaments
+            WHERE tournament_uuid = UUID_TO_BIN(:tournament_uuid)
+            "#,
+                params! {
+                    "tournament_uuid" => tournament_uuid,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?
+            .map(
+                |(
+                    tournament_uuid,
+                    name,
+                    format,
+                    rounds,
+                    current_round,
+                    state,
+                    time_control,
+                    best_of,
+                    tiebreak,
+                )| Tournament {
+                    tournament_uuid,
+                    name,
+                    format,
+                    rounds,
+                    current_round,
+                    state,
+                    time_control,
+                    best_of,
+                    tiebreak,
+                },
+            );
+
+        Ok(tournament)
+    }
+
+    async fn update_tournament(&self, tournament: &Tournament) -> Result<(), RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        conn.exec_drop(
+            r#"
+            UPDATE tournaments
+            SET
+                rounds = :rounds,
+                current_round = :current_round,
+                state = :state
+            WHERE tournament_uuid = UUID_TO_BIN(:tournament_uuid)
+            "#,
+            params! {
+                "tournament_uuid" => &tournament.tournament_uuid,
+                "rounds" => tournament.rounds,
+                "current_round" => tournament.current_round,
+                "state" => tournament.state,
+            },
+        )
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn register_player(
+        &self,
+        tournament_uuid: &str,
+        player_uuid: &str,
+    ) -> Result<bool, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        conn.exec_drop(
+            r#"
+            INSERT IGNORE INTO tournament_players (
+                tournament_uuid,
+                player_uuid,
+                registration_date
+            )
+            VALUES (UUID_TO_BIN(:tournament_uuid), UUID_TO_BIN(:player_uuid), NOW())
+            "#,
+            params! {
+                "tournament_uuid" => tournament_uuid,
+                "player_uuid" => player_uuid,
+            },
+        )
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(conn.affected_rows() > 0)
+    }
+
+    async fn get_players(&self, tournament_uuid: &str) -> Result<Vec<String>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let players: Vec<String> = conn
+            .exec(
+                r#"
+            SELECT BIN_TO_UUID(player_uuid) AS player_uuid
+            FROM tournament_players
+            WHERE tournament_uuid = UUID_TO_BIN(:tournament_uuid)
+            ORDER BY registration_date ASC, player_uuid ASC
+            "#,
+                params! {
+                    "tournament_uuid" => tournament_uuid,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(players)
+    }
+
+    async fn get_pairings(
+        &self,
+        tournament_uuid: &str,
+    ) -> Result<Vec<TournamentPairing>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let pairings: Vec<TournamentPairing> = conn
+            .exec_map(
+                r#"
+            SELECT
+                p.round,
+                BIN_TO_UUID(p.black_uuid) AS black_uuid,
+                IFNULL(BIN_TO_UUID(p.white_uuid), '') AS white_uuid,
+                IFNULL(BIN_TO_UUID(p.game_uuid), '') AS game_uuid,
+                IFNULL(g.state, 0) AS game_state,
+                IFNULL(g.position_black, 0) AS position_black,
+                IFNULL(g.position_white, 0) AS position_white,
+                IFNULL(p.bracket_match, '') AS bracket_match,
+                p.game_no
+            FROM tournament_pairings p
+            LEFT JOIN games g ON g.game_uuid = p.game_uuid
+            WHERE p.tournament_uuid = UUID_TO_BIN(:tournament_uuid)
+            ORDER BY p.round ASC, p.pairing_id ASC
+            "#,
+                params! {
+                    "tournament_uuid" => tournament_uuid,
+                },
+                |(
+                    round,
+                    black_uuid,
+                    white_uuid,
+                    game_uuid,
+                    game_state,
+                    position_black,
+                    position_white,
+                    bracket_match,
+                    game_no,
+                )| TournamentPairing {
+                    round,
+                    black_uuid,
+                    white_uuid,
+                    game_uuid,
+                    game_state,
+                    position_black,
+                    position_white,
+                    bracket_match,
+                    game_no,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(pairings)
+    }
+
+    async fn tournament_for_game(
+        &self,
+        game_uuid: &str,
+    ) -> Result<Option<String>, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let tournament_uuid: Option<String> = conn
+            .exec_first(
+                "SELECT BIN_TO_UUID(tournament_uuid) FROM tournament_pairings WHERE game_uuid = UUID_TO_BIN(:game_uuid)",
+                params! {
+                    "game_uuid" => game_uuid,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(tournament_uuid)
+    }
+
+    async fn start_round(
+        &self,
+        tournament: &Tournament,
+        round: u64,
+        games: &[Game],
+        pairings: &[TournamentPairing],
+    ) -> Result<bool, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        let mut tx = conn
+            .start_transaction(TxOpts::default())
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        tx.exec_drop(
+            r#"
+            UPDATE tournaments
+            SET
+                rounds = :rounds,
+                current_round = :round,
+                state = 1
+            WHERE tournament_uuid = UUID_TO_BIN(:tournament_uuid)
+                AND current_round = :current_round
+            "#,
+            params! {
+                "tournament_uuid" => &tournament.tournament_uuid,
+                "rounds" => tournament.rounds,
+                "round" => round,
+                "current_round" => tournament.current_round,
+            },
+        )
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        if tx.affected_rows() == 0 {
+            tx.rollback()
+                .await
+                .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+            return Ok(false);
+        }
+
+        for game in games {
+            insert_game(&mut tx, game).await?;
+        }
+
+        for pairing in pairings {
+            tx.exec_drop(
+                r#"
+                INSERT INTO tournament_pairings (
+                    tournament_uuid,
+                    round,
+                    black_uuid,
+                    white_uuid,
+                    game_uuid
+                )
+                VALUES (
+                    UUID_TO_BIN(:tournament_uuid),
+                    :round,
+                    UUID_TO_BIN(:black_uuid),
+                    IF(:white_uuid = '', NULL, UUID_TO_BIN(:white_uuid)),
+                    IF(:game_uuid = '', NULL, UUID_TO_BIN(:game_uuid))
+                )
+                "#,
+                params! {
+                    "tournament_uuid" => &tournament.tournament_uuid,
+                    "round" => pairing.round,
+                    "black_uuid" => &pairing.black_uuid,
+                    "white_uuid" => &pairing.white_uuid,
+                    "game_uuid" => &pairing.game_uuid,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(true)
+    }
+    async fn add_games(
+        &self,
+        tournament: &Tournament,
+        games: &[Game],
+        pairings: &[TournamentPairing],
+    ) -> Result<bool, RepositoryError> {
+        let mut conn = self
+            .pool
+            .get_conn()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+        let mut tx = conn
+            .start_transaction(TxOpts::default())
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        tx.exec_drop(
+            r#"
+            UPDATE tournaments
+            SET
+                rounds = :rounds,
+                current_round = GREATEST(current_round, :current_round),
+                state = 1
+            WHERE tournament_uuid = UUID_TO_BIN(:tournament_uuid)
+            "#,
+            params! {
+                "tournament_uuid" => &tournament.tournament_uuid,
+                "rounds" => tournament.rounds,
+                "current_round" => tournament.current_round,
+            },
+        )
+        .await
+        .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        let mut inserted: usize = 0;
+        for (game, pairing) in games.iter().zip(pairings) {
+            insert_game(&mut tx, game).await?;
+
+            // The unique (tournament, bracket match, game number) index makes a
+            // concurrent attempt to create the same bracket game a no-op.
+            tx.exec_drop(
+                r#"
+                INSERT INTO tournament_pairings (
+                    tournament_uuid,
+                    round,
+                    black_uuid,
+                    white_uuid,
+                    game_uuid,
+                    bracket_match,
+                    game_no
+                )
+                VALUES (
+                    UUID_TO_BIN(:tournament_uuid),
+                    :round,
+                    UUID_TO_BIN(:black_uuid),
+                    UUID_TO_BIN(:white_uuid),
+                    UUID_TO_BIN(:game_uuid),
+                    :bracket_match,
+                    :game_no
+                )
+                ON DUPLICATE KEY UPDATE tournament_uuid = tournament_uuid
+                "#,
+                params! {
+                    "tournament_uuid" => &tournament.tournament_uuid,
+                    "round" => pairing.round,
+                    "black_uuid" => &pairing.black_uuid,
+                    "white_uuid" => &pairing.white_uuid,
+                    "game_uuid" => &pairing.game_uuid,
+                    "bracket_match" => &pairing.bracket_match,
+                    "game_no" => pairing.game_no,
+                },
+            )
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+            let paired: Option<u8> = tx
+                .exec_first(
+                    "SELECT 1 FROM tournament_pairings WHERE game_uuid = UUID_TO_BIN(:game_uuid)",
+                    params! { "game_uuid" => &game.game_uuid },
+                )
+                .await
+                .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+            if paired.is_none() {
+                // Another request created this bracket game, drop the duplicate.
+                tx.exec_drop(
+                    "DELETE FROM games WHERE game_uuid = UUID_TO_BIN(:game_uuid)",
+                    params! { "game_uuid" => &game.game_uuid },
+                )
+                .await
+                .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+            } else {
+                inserted += 1;
+            }
+        }
+
+        if inserted == 0 {
+            tx.rollback()
+                .await
+                .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+            return Ok(false);
+        }
+        tx.commit()
+            .await
+            .map_err(|e| RepositoryError::DatabaseError(e.to_string()))?;
+
+        Ok(true)
+    }
+}
+
+impl MySqlTournamentRepository {
+    pub fn new(pool: MySqlPool) -> Self {
+        MySqlTournamentRepository { pool }
+    }
+}
@@ -0,0 +1,511 @@
+//! Game rules shared by every frontend: creating, joining and playing games on
+//! top of a `GameRepository`. The HTTP handlers are one user of it.
+
+use rand::Rng;
+use reversi_tools::position::*;
+use uuid::Uuid;
+
+use crate::game_match::{play_opening, state_after_opening, OpeningMove};
+use crate::idempotency::move_record;
+use crate::model::{
+    Game, GameMatch, MoveRecord, MoveResponse, MoveResult, ResponseError, INITIAL_POSITION_BLACK,
+    INITIAL_POSITION_WHITE,
+};
+use crate::opening_book::{algebraic_moves, OpeningBook};
+use crate::repository::db_errors::RepositoryError;
+use crate::repository::game_repository::GameRepository;
+use crate::repository::match_repository::NewMatchGame;
+
+/// Names of the game states, indexed by `Game::state`.
+pub const STATE_NAMES: [&str; 6] = [
+    "pending",
+    "black",
+    "white",
+    "black_won",
+    "white_won",
+    "draw",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum GameError {
+    #[error("Game UUID not found")]
+    NotFound,
+    /// The request breaks the rules, e.g. an illegal move or opening.
+    #[error("{0}")]
+    Rejected(String),
+    /// The request clashes with another one still in progress.
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    Repository(#[from] RepositoryError),
+}
+
+impl GameError {
+    /// Error code reported by the API.
+    pub fn code(&self) -> u32 {
+        match self {
+            GameError::NotFound => 404,
+            GameError::Rejected(_) => 400,
+            GameError::Conflict(_) => 409,
+            GameError::Repository(_) => 500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreatedGame {
+    pub game: Game,
+    /// Color of the creator, "black" or "white".
+    pub color: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinedGame {
+    pub game: Game,
+    /// Color of the joining player, "black" or "white".
+    pub color: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveOutcome {
+    /// The game after the move.
+    pub game: Game,
+    /// The move ended the game.
+    pub finished: bool,
+    /// "black", "white" or "draw" when a resignation or a pass ended the game,
+    /// empty otherwise.
+    pub winner: String,
+}
+
+impl MoveOutcome {
+    /// The response the API sends for the move.
+    pub fn response(&self) -> MoveResponse {
+        MoveResponse {
+            status: "ok".to_string(),
+            error: ResponseError {
+                code: 200,
+                message: String::new(),
+            },
+            result: MoveResult {
+                ok: true,
+                r#continue: !self.finished,
+                winner: self.winner.clone(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameStatus {
+    pub game: Game,
+    /// One of `STATE_NAMES`.
+    pub status: String,
+    /// Last move in algebraic notation, "pass", or empty before the first move.
+    pub last_move: String,
+    /// Name of the book opening the game started with, empty if unknown.
+    pub opening: String,
+}
+
+/// Game state once the position says the game is over, `None` while it goes on.
+fn final_state(position_white: u64, position_black: u64, white_moved: bool) -> Option<u64> {
+    let game_status: u64 = check_game_status(position_white, position_black, white_moved);
+    if game_status == (u64::MAX - 2) {
+        Some(4)
+    } else if game_status == (u64::MAX - 1) {
+        Some(3)
+    } else if game_status == (u64::MAX - 3) {
+        Some(5)
+    } else {
+        None
+    }
+}
+
+/// A game between two known players, black to move. Matchmaking, tournaments
+/// and matches all start their games this way.
+pub fn paired_game(black_uuid: &str, white_uuid: &str, time_control: &str) -> Game {
+    Game {
+        game_uuid: Uuid::new_v4().to_string(),
+        black_uuid: black_uuid.to_string(),
+        white_uuid: white_uuid.to_string(),
+        position_black: INITIAL_POSITION_BLACK,
+        position_white: INITIAL_POSITION_WHITE,
+        state: 1,
+        time_control: time_control.to_string(),
+    }
+}
+
+pub struct GameService<'a, R: GameRepository + Send + Sync + ?Sized> {
+    repo: &'a R,
+    book: &'a OpeningBook,
+}
+
+impl<'a, R: GameRepository + Send + Sync + ?Sized> GameService<'a, R> {
+    pub fn new(repo: &'a R, book: &'a OpeningBook) -> Self {
+        GameService { repo, book }
+    }
+
+    /// Creates a pending game with the player on a random color. A non-empty
+    /// `opening`, a book name or a move prefix, is played right away.
+    pub async fn create_game(
+        &self,
+        player_id: &str,
+        opening: &str,
+        time_control: &str,
+    ) -> Result<CreatedGame, GameError> {
+        let mut opening_moves: Vec<OpeningMove> = Vec::new();
+        if !opening.is_empty() {
+            opening_moves = match self.book.resolve(opening) {
+                Some(squares) => play_opening(&squares),
+                None => Err(format!("Unknown opening: {}", opening)),
+            }
+            .map_err(GameError::Rejected)?;
+        }
+
+        let mut game: Game = Game {
+            game_uuid: Uuid::new_v4().to_string(),
+            black_uuid: String::new(),
+            white_uuid: String::new(),
+            position_black: INITIAL_POSITION_BLACK,
+            position_white: INITIAL_POSITION_WHITE,
+            state: 0,
+            time_control: time_control.to_string(),
+        };
+        let color: String;
+        if rand::thread_rng().gen_range(0..2) == 1 {
+            color = "white".to_string();
+            game.white_uuid = player_id.to_string();
+        } else {
+            color = "black".to_string();
+            game.black_uuid = player_id.to_string();
+        }
+
+        let mut game: Game = self.store_game(&game).await?;
+        self.record_opening(&mut game, &opening_moves).await?;
+        Ok(CreatedGame { game, color })
+    }
+
+    /// Creates a game between two players that starts right away.
+    pub async fn create_paired_game(
+        &self,
+        black_uuid: &str,
+        white_uuid: &str,
+        time_control: &str,
+    ) -> Result<Game, GameError> {
+        self.store_game(&paired_game(black_uuid, white_uuid, time_control))
+            .await
+    }
+
+    /// Stores a new game and reads it back, the common path for every way a game
+    /// gets created.
+    pub async fn store_game(&self, game: &Game) -> Result<Game, GameError> {
+        self.repo.create_game(game).await?;
+        match self.repo.get_game(game.game_uuid.as_str()).await? {
+            Some(g) => Ok(g),
+            None => Err(GameError::NotFound),
+        }
+    }
+
+    /// Records the forced opening moves of a freshly created game. Pending games
+    /// stay pending, otherwise the side to move follows from the number of moves.
+    pub async fn record_opening(
+        &self,
+        game: &mut Game,
+        moves: &[OpeningMove],
+    ) -> Result<(), GameError> {
+        for (idx, m) in moves.iter().enumerate() {
+            game.position_black = m.position_black;
+            game.position_white = m.position_white;
+            if game.state != 0 {
+                game.state = state_after_opening(idx + 1);
+            }
+            self.repo
+                .update_game_with_move(game, m.move_bit, idx as u64 + 1, None)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Stores a match with its games in one go. Each game is stored as it
+    /// stands after its opening moves, like `record_opening` would leave it.
+    pub async fn store_match(
+        &self,
+        game_match: &GameMatch,
+        mut games: Vec<NewMatchGame>,
+    ) -> Result<(), GameError> {
+        for new_game in games.iter_mut() {
+            if let Some(last) = new_game.moves.last() {
+                new_game.game.position_black = last.position_black;
+                new_game.game.position_white = last.position_white;
+                new_game.game.state = state_after_opening(new_game.moves.len());
+            }
+        }
+        self.repo.create_match(game_match, &games).await?;
+        Ok(())
+    }
+
+    /// Takes the free color of a pending game.
+    pub async fn join_game(&self, game_id: &str, player_id: &str) -> Result<JoinedGame, GameError> {
+        // TODO(1): add player validation
+        // TODO(3): make sure the joining player is different from the game creator
+        // TODO(4): make sure the game is in pending state
+        let mut game: Game = match self.repo.get_game(game_id).await? {
+            Some(g) => g,
+            None => return Err(GameError::NotFound),
+        };
+        let color: String;
+        if game.black_uuid.is_empty() {
+            color = "black".to_string();
+            game.black_uuid = player_id.to_string();
+        } else {
+            color = "white".to_string();
+            game.white_uuid = player_id.to_string();
+        }
+        // Games created with an opening already contain its forced moves.
+        let moves: u64 = self.repo.get_max_move_no(game_id).await?;
+        game.state = state_after_opening(moves as usize);
+        self.repo.update_game(&game, None).await?;
+        Ok(JoinedGame { game, color })
+    }
+
+    /// Plays a move in algebraic notation, "pass" or "resign" for the player.
+    /// With an idempotency key reserved for the move, its response is stored
+    /// along with the move.
+    pub async fn make_move(
+        &self,
+        game_id: &str,
+        player_id: &str,
+        played: &str,
+        key: Option<&str>,
+    ) -> Result<MoveOutcome, GameError> {
+        let mut game: Game = match self.repo.get_game(game_id).await? {
+            Some(g) => g,
+            None => return Err(GameError::NotFound),
+        };
+        let white_moves: bool = game.white_uuid == player_id;
+
+        if played == "resign" {
+            game.state = if white_moves { 3 } else { 4 };
+            let winner: &str = if white_moves { "black" } else { "white" };
+            let outcome: MoveOutcome = MoveOutcome {
+                game,
+                finished: true,
+                winner: winner.to_string(),
+            };
+            let record: Option<MoveRecord> = outcome_record(&outcome, key, player_id, played)?;
+            self.repo
+                .update_game(&outcome.game, record.as_ref())
+                .await?;
+            return Ok(outcome);
+        }
+
+        let move_bit: u64;
+        let mut finished: bool = false;
+        let mut winner: String = String::new();
+        game.state = 3 - game.state;
+        if played == "pass" {
+            move_bit = u64::MAX;
+            if let Some(state) =
+                final_state(game.position_white, game.position_black, game.state == 2)
+            {
+                game.state = state;
+                finished = true;
+                winner = STATE_NAMES[state as usize]
+                    .trim_end_matches("_won")
+                    .to_string();
+            }
+        } else {
+            move_bit = move_to_bitmap(played).map_err(|e| GameError::Rejected(e.to_string()))?;
+            let (new_white, new_black) = apply_move(
+                game.position_white,
+                game.position_black,
+                move_bit,
+                white_moves,
+            )
+            .map_err(|e| GameError::Rejected(e.to_string()))?;
+            game.position_white = new_white;
+            game.position_black = new_black;
+            if let Some(state) = final_state(game.position_white, game.position_black, white_moves)
+            {
+                game.state = state;
+                finished = true;
+            }
+        }
+
+        let max_move: u64 = self.repo.get_max_move_no(game_id).await?;
+        let outcome: MoveOutcome = MoveOutcome {
+            game,
+            finished,
+            winner,
+        };
+        let record: Option<MoveRecord> = outcome_record(&outcome, key, player_id, played)?;
+        self.repo
+            .update_game_with_move(&outcome.game, move_bit, max_move + 1, record.as_ref())
+            .await?;
+        Ok(outcome)
+    }
+
+    pub async fn game_status(&self, game_id: &str) -> Result<GameStatus, GameError> {
+        let last_move: String = match self.repo.get_last_move(game_id).await? {
+            0 => String::new(),
+            u64::MAX => "pass".to_string(),
+            m => move_to_algebraic(m).map_err(|e| GameError::Rejected(e.to_string()))?,
+        };
+        let game: Game = match self.repo.get_game(game_id).await? {
+            Some(g) => g,
+            None => return Err(GameError::NotFound),
+        };
+        let moves: Vec<u64> = self.repo.get_moves(game_id).await?;
+        let opening: String = self
+            .book
+            .name_for(&algebraic_moves(&moves))
+            .unwrap_or_default()
+            .to_string();
+        Ok(GameStatus {
+            status: STATE_NAMES[game.state as usize].to_string(),
+            game,
+            last_move,
+            opening,
+        })
+    }
+}
+
+/// The record of a move played with an idempotency key.
+fn outcome_record(
+    outcome: &MoveOutcome,
+    key: Option<&str>,
+    player_id: &str,
+    played: &str,
+) -> Result<Option<MoveRecord>, GameError> {
+    match key {
+        Some(key) => Ok(Some(move_record(
+            &outcome.game.game_uuid,
+            key,
+            player_id,
+            played,
+            &outcome.response(),
+        )?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory_repository::*;
+
+    const CREATOR: &str = "11111111-1111-4111-8111-111111111111";
+    const OPPONENT: &str = "22222222-2222-4222-8222-222222222222";
+
+    fn repository() -> MemoryGameRepository {
+        let db: MemoryDatabase = MemoryDatabase::new();
+        db.add_player(CREATOR, "");
+        db.add_player(OPPONENT, "");
+        MemoryGameRepository::new(db)
+    }
+
+    #[tokio::test]
+    async fn joining_takes_the_free_color() {
+        let repo = repository();
+        let book = OpeningBook::builtin();
+        let service = GameService::new(&repo, &book);
+
+        let created = service.create_game(CREATOR, "", "blitz").await.unwrap();
+        assert_eq!(created.game.state, 0);
+        assert_eq!(created.game.time_control, "blitz");
+        let joined = service
+            .join_game(&created.game.game_uuid, OPPONENT)
+            .await
+            .unwrap();
+        assert_ne!(joined.color, created.color);
+        assert_eq!(joined.game.state, 1);
+        assert!(!joined.game.black_uuid.is_empty());
+        assert!(!joined.game.white_uuid.is_empty());
+
+        let status = service.game_status(&created.game.game_uuid).await.unwrap();
+        assert_eq!(status.status, "black");
+        assert_eq!(status.last_move, "");
+    }
+
+    #[tokio::test]
+    async fn paired_games_start_with_black_to_move() {
+        let repo = repository();
+        let book = OpeningBook::builtin();
+        let service = GameService::new(&repo, &book);
+
+        let game = service
+            .create_paired_game(CREATOR, OPPONENT, "rapid")
+            .await
+            .unwrap();
+        assert_eq!(game.black_uuid, CREATOR);
+        assert_eq!(game.white_uuid, OPPONENT);
+        assert_eq!(game.time_control, "rapid");
+        let status = service.game_status(&game.game_uuid).await.unwrap();
+        assert_eq!(status.status, "black");
+        assert_eq!(status.last_move, "");
+    }
+
+    #[tokio::test]
+    async fn resigning_ends_the_game() {
+        let repo = repository();
+        let book = OpeningBook::builtin();
+        let service = GameService::new(&repo, &book);
+        let created = service.create_game(CREATOR, "", "").await.unwrap();
+        let game_id: &str = created.game.game_uuid.as_str();
+        let joined = service.join_game(game_id, OPPONENT).await.unwrap();
+
+        let outcome = service
+            .make_move(game_id, &joined.game.white_uuid, "resign", None)
+            .await
+            .unwrap();
+        assert!(outcome.finished);
+        assert_eq!(outcome.winner, "black");
+        assert_eq!(
+            service.game_status(game_id).await.unwrap().status,
+            "black_won"
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_games_and_bad_moves_are_errors() {
+        let repo = repository();
+        let book = OpeningBook::builtin();
+        let service = GameService::new(&repo, &book);
+        let missing: String = Uuid::new_v4().to_string();
+        assert_eq!(
+            service
+                .join_game(&missing, OPPONENT)
+                .await
+                .unwrap_err()
+                .code(),
+            404
+        );
+        assert_eq!(
+            service
+                .make_move(&missing, OPPONENT, "c4", None)
+                .await
+                .unwrap_err()
+                .code(),
+            404
+        );
+        assert_eq!(
+            service
+                .create_game(CREATOR, "no such opening", "")
+                .await
+                .unwrap_err()
+                .code(),
+            400
+        );
+
+        let created = service.create_game(CREATOR, "", "").await.unwrap();
+        let game_id: &str = created.game.game_uuid.as_str();
+        let joined = service.join_game(game_id, OPPONENT).await.unwrap();
+        assert_eq!(
+            service
+                .make_move(game_id, &joined.game.black_uuid, "a1", None)
+                .await
+                .unwrap_err()
+                .code(),
+            400
+        );
+    }
+}
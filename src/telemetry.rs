@@ -0,0 +1,130 @@
+//! Structured logging. Every request gets an id, taken from its `X-Request-Id`
+//! header or generated, which is echoed back and recorded in the spans of the
+//! handlers so that all their log lines carry it.
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request, Response};
+use std::fmt;
+use std::time::Instant;
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+use crate::metrics::RequestStart;
+use crate::settings::{LogFormat, LogSettings};
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Installs the global subscriber. The JSON format writes one object per line,
+/// the pretty one human readable text. The filter was checked by
+/// `Settings::validate`, e.g. `info,reversi_server=debug` shows repository
+/// call timings.
+pub fn init_tracing(settings: &LogSettings) {
+    let filter: EnvFilter =
+        EnvFilter::try_new(&settings.filter).unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let result = match settings.format {
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .try_init(),
+        LogFormat::Pretty => builder.pretty().try_init(),
+    };
+    if let Err(e) = result {
+        eprintln!("Logging is already set up: {}", e);
+    }
+}
+
+/// Id of the request being handled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// Keeps the id the client sent if it is sane, otherwise makes up one.
+    pub fn from_header(header: Option<&str>) -> RequestId {
+        match header {
+            Some(id)
+                if !id.is_empty()
+                    && id.len() <= 128
+                    && id.bytes().all(|b| b.is_ascii_graphic()) =>
+            {
+                RequestId(id.to_string())
+            }
+            _ => RequestId(Uuid::new_v4().to_string()),
+        }
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn request_id(request: &Request<'_>) -> RequestId {
+    request
+        .local_cache(|| RequestId::from_header(request.headers().get_one(REQUEST_ID_HEADER)))
+        .clone()
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(request_id(request))
+    }
+}
+
+/// Assigns request ids, echoes them in the response and logs every finished
+/// request with its status and duration.
+pub struct RequestIdFairing;
+
+#[rocket::async_trait]
+impl Fairing for RequestIdFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request ids",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| RequestStart(Instant::now()));
+        request_id(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let id: RequestId = request_id(request);
+        let start: &RequestStart = request.local_cache(|| RequestStart(Instant::now()));
+        tracing::info!(
+            request_id = %id,
+            method = %request.method(),
+            path = %request.uri().path(),
+            status = response.status().code,
+            elapsed_ms = start.0.elapsed().as_secs_f64() * 1000.0,
+            "request finished"
+        );
+        response.set_raw_header(REQUEST_ID_HEADER, id.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_sane_ids_only() {
+        assert_eq!(
+            RequestId::from_header(Some("abc-123")),
+            RequestId("abc-123".to_string())
+        );
+        for bad in [None, Some(""), Some("has space"), Some("é")] {
+            let id: RequestId = RequestId::from_header(bad);
+            assert!(Uuid::parse_str(&id.0).is_ok());
+        }
+        let long: String = "x".repeat(129);
+        assert_ne!(RequestId::from_header(Some(&long)).0, long);
+    }
+}
@@ -0,0 +1,708 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::bracket::{bracket_rounds, compute_bracket, Bracket, BracketConfig, Tiebreak};
+use crate::model::{Game, Standing, Tournament, TournamentPairing};
+use crate::repository::db_errors::RepositoryError;
+use crate::repository::tournament_repository::TournamentRepository;
+use crate::service::paired_game;
+
+/// Upper bound on the number of pairing attempts before Swiss pairing gives up
+/// on avoiding rematches.
+const SWISS_SEARCH_BUDGET: u32 = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TournamentFormat {
+    RoundRobin,
+    DoubleRoundRobin,
+    Swiss,
+    SingleElimination,
+    DoubleElimination,
+}
+
+impl TournamentFormat {
+    pub fn parse(format: &str) -> Option<TournamentFormat> {
+        match format {
+            "round_robin" => Some(TournamentFormat::RoundRobin),
+            "double_round_robin" => Some(TournamentFormat::DoubleRoundRobin),
+            "swiss" => Some(TournamentFormat::Swiss),
+            "single_elimination" => Some(TournamentFormat::SingleElimination),
+            "double_elimination" => Some(TournamentFormat::DoubleElimination),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TournamentFormat::RoundRobin => "round_robin",
+            TournamentFormat::DoubleRoundRobin => "double_round_robin",
+            TournamentFormat::Swiss => "swiss",
+            TournamentFormat::SingleElimination => "single_elimination",
+            TournamentFormat::DoubleElimination => "double_elimination",
+        }
+    }
+
+    pub fn is_elimination(&self) -> bool {
+        matches!(
+            self,
+            TournamentFormat::SingleElimination | TournamentFormat::DoubleElimination
+        )
+    }
+
+    /// Number of rounds for `players` participants. `requested` is only honoured
+    /// by Swiss tournaments, round robins always play the full schedule. For
+    /// elimination formats this is the number of winners bracket rounds.
+    pub fn total_rounds(&self, players: usize, requested: u64) -> u64 {
+        let slots: u64 = (players + players % 2) as u64;
+        match self {
+            TournamentFormat::RoundRobin => slots.saturating_sub(1),
+            TournamentFormat::DoubleRoundRobin => 2 * slots.saturating_sub(1),
+            TournamentFormat::Swiss => {
+                if requested > 0 {
+                    requested
+                } else {
+                    (usize::BITS - players.saturating_sub(1).leading_zeros()).max(1) as u64
+                }
+            }
+            TournamentFormat::SingleElimination | TournamentFormat::DoubleElimination => {
+                bracket_rounds(players)
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TournamentError {
+    #[error("Tournament not found")]
+    NotFound,
+    #[error("Unknown tournament format: {0}")]
+    UnknownFormat(String),
+    #[error("Unknown tiebreak rule: {0}")]
+    UnknownTiebreak(String),
+    #[error("At least two players are needed to start a tournament")]
+    NotEnoughPlayers,
+    #[error("Round {0} is still in progress")]
+    RoundInProgress(u64),
+    #[error("Tournament has already finished")]
+    Finished,
+    #[error(transparent)]
+    Repository(#[from] RepositoryError),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RoundOutcome {
+    /// Games of the given round have been created.
+    Started(u64),
+    /// All rounds have been played, the tournament is over.
+    Finished,
+    /// A concurrent request has already advanced the tournament.
+    AlreadyAdvanced,
+}
+
+pub fn tournament_status_name(state: u64) -> String {
+    match state {
+        0 => "registration".to_string(),
+        1 => "running".to_string(),
+        _ => "finished".to_string(),
+    }
+}
+
+pub fn pairing_result(pairing: &TournamentPairing) -> String {
+    if pairing.white_uuid.is_empty() {
+        return "bye".to_string();
+    }
+    match pairing.game_state {
+        3 => "black_won".to_string(),
+        4 => "white_won".to_string(),
+        5 => "draw".to_string(),
+        _ => "playing".to_string(),
+    }
+}
+
+fn is_finished(pairing: &TournamentPairing) -> bool {
+    pairing.white_uuid.is_empty() || (3..=5).contains(&pairing.game_state)
+}
+
+pub fn round_complete(pairings: &[TournamentPairing], round: u64) -> bool {
+    pairings
+        .iter()
+        .filter(|p| p.round == round)
+        .all(is_finished)
+}
+
+/// Bracket of an elimination tournament, `None` for the other formats.
+pub fn tournament_bracket(
+    tournament: &Tournament,
+    players: &[String],
+    pairings: &[TournamentPairing],
+) -> Result<Option<Bracket>, TournamentError> {
+    let format: TournamentFormat = TournamentFormat::parse(&tournament.format)
+        .ok_or_else(|| TournamentError::UnknownFormat(tournament.format.clone()))?;
+    if !format.is_elimination() {
+        return Ok(None);
+    }
+    let tiebreak: Tiebreak = Tiebreak::parse(&tournament.tiebreak)
+        .ok_or_else(|| TournamentError::UnknownTiebreak(tournament.tiebreak.clone()))?;
+    let config: BracketConfig = BracketConfig {
+        double_elimination: format == TournamentFormat::DoubleElimination,
+        best_of: tournament.best_of,
+        tiebreak,
+    };
+    Ok(Some(compute_bracket(players, pairings, config)))
+}
+
+/// Pairings of a round robin round (1-based) using the circle method. Each pair is
+/// `(black, white)`, a `None` opponent is a bye. The second cycle of a double round
+/// robin repeats the first one with colors swapped.
+pub fn round_robin_round(
+    players: &[String],
+    round: u64,
+    double: bool,
+) -> Vec<(String, Option<String>)> {
+    let mut slots: Vec<Option<&String>> = players.iter().map(Some).collect();
+    if slots.len() % 2 == 1 {
+        slots.push(None);
+    }
+    let m: usize = slots.len();
+    if m < 2 {
+        return Vec::new();
+    }
+    let cycle: usize = m - 1;
+    let r: usize = (round.saturating_sub(1) as usize) % cycle;
+    let swap: bool = double && round.saturating_sub(1) as usize >= cycle;
+
+    let mut order: Vec<Option<&String>> = Vec::with_capacity(m);
+    order.push(slots[0]);
+    for i in 0..cycle {
+        order.push(slots[1 + (i + cycle - r) % cycle]);
+    }
+
+    let mut pairs: Vec<(String, Option<String>)> = Vec::new();
+    for i in 0..m / 2 {
+        let (mut black, mut white) = (order[i], order[m - 1 - i]);
+        if (r + i) % 2 == 1 {
+            std::mem::swap(&mut black, &mut white);
+        }
+        if swap {
+            std::mem::swap(&mut black, &mut white);
+        }
+        match (black, white) {
+            (Some(b), Some(w)) => pairs.push((b.clone(), Some(w.clone()))),
+            (Some(p), None) | (None, Some(p)) => pairs.push((p.clone(), None)),
+            (None, None) => {}
+        }
+    }
+    pairs
+}
+
+/// Pairings for the next Swiss round: players are ranked by the current standings and
+/// paired top-down avoiding rematches, the bye goes to the lowest ranked player who has
+/// not had one yet. Colors go to whoever has played the other color more often.
+pub fn swiss_pairings(
+    players: &[String],
+    history: &[TournamentPairing],
+) -> Vec<(String, Option<String>)> {
+    let ranked: Vec<String> = compute_standings(players, history)
+        .into_iter()
+        .map(|s| s.player_id)
+        .collect();
+
+    let mut played: HashSet<(String, String)> = HashSet::new();
+    let mut had_bye: HashSet<String> = HashSet::new();
+    let mut balance: HashMap<String, i64> = HashMap::new();
+    for p in history {
+        if p.white_uuid.is_empty() {
+            had_bye.insert(p.black_uuid.clone());
+            continue;
+        }
+        played.insert((p.black_uuid.clone(), p.white_uuid.clone()));
+        played.insert((p.white_uuid.clone(), p.black_uuid.clone()));
+        *balance.entry(p.black_uuid.clone()).or_default() += 1;
+        *balance.entry(p.white_uuid.clone()).or_default() -= 1;
+    }
+
+    let mut pool: Vec<String> = ranked;
+    let mut result: Vec<(String, Option<String>)> = Vec::new();
+    let mut bye: Option<String> = None;
+    if pool.len() % 2 == 1 {
+        let idx: usize = pool
+            .iter()
+            .rposition(|p| !had_bye.contains(p))
+            .unwrap_or(pool.len() - 1);
+        bye = Some(pool.remove(idx));
+    }
+
+    let mut budget: u32 = SWISS_SEARCH_BUDGET;
+    let pairs: Vec<(String, String)> = match pair_up(&pool, &played, &mut budget) {
+        Some(p) => p,
+        None => pool
+            .chunks(2)
+            .map(|c| (c[0].clone(), c[1].clone()))
+            .collect(),
+    };
+    for (higher, lower) in pairs {
+        let higher_balance: i64 = balance.get(&higher).copied().unwrap_or(0);
+        let lower_balance: i64 = balance.get(&lower).copied().unwrap_or(0);
+        if higher_balance > lower_balance {
+            result.push((lower, Some(higher)));
+        } else {
+            result.push((higher, Some(lower)));
+        }
+    }
+    if let Some(p) = bye {
+        result.push((p, None));
+    }
+    result
+}
+
+fn pair_up(
+    pool: &[String],
+    played: &HashSet<(String, String)>,
+    budget: &mut u32,
+) -> Option<Vec<(String, String)>> {
+    if pool.is_empty() {
+        return Some(Vec::new());
+    }
+    let first: &String = &pool[0];
+    for j in 1..pool.len() {
+        if *budget == 0 {
+            return None;
+        }
+        *budget -= 1;
+        if played.contains(&(first.clone(), pool[j].clone())) {
+            continue;
+        }
+        let rest: Vec<String> = pool[1..]
+            .iter()
+            .enumerate()
+            .filter(|(k, _)| k + 1 != j)
+            .map(|(_, p)| p.clone())
+            .collect();
+        if let Some(mut pairs) = pair_up(&rest, played, budget) {
+            pairs.insert(0, (first.clone(), pool[j].clone()));
+            return Some(pairs);
+        }
+    }
+    None
+}
+
+/// Standings ordered by score, then Buchholz, Sonneborn-Berger and disc differential.
+/// A win scores one point, a draw half a point and a bye counts as a win without an
+/// opponent, so it does not contribute to the tie-breaks.
+pub fn compute_standings(players: &[String], pairings: &[TournamentPairing]) -> Vec<Standing> {
+    let mut standings: HashMap<String, Standing> = HashMap::new();
+    let mut order: Vec<String> = Vec::new();
+    for p in players {
+        if !standings.contains_key(p) {
+            order.push(p.clone());
+            standings.insert(
+                p.clone(),
+                Standing {
+                    player_id: p.clone(),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+    // Opponent and points scored against them, for the tie-breaks.
+    let mut results: HashMap<String, Vec<(String, f64)>> = HashMap::new();
+
+    for pairing in pairings {
+        for p in [&pairing.black_uuid, &pairing.white_uuid] {
+            if !p.is_empty() && !standings.contains_key(p) {
+                order.push(p.clone());
+                standings.insert(
+                    p.clone(),
+                    Standing {
+                        player_id: p.clone(),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        if pairing.white_uuid.is_empty() {
+            let s = standings.get_mut(&pairing.black_uuid).unwrap();
+            s.score += 1.0;
+            s.byes += 1;
+            continue;
+        }
+        let (black_points, white_points): (f64, f64) = match pairing.game_state {
+            3 => (1.0, 0.0),
+            4 => (0.0, 1.0),
+            5 => (0.5, 0.5),
+            _ => continue,
+        };
+        let black_discs: i64 = pairing.position_black.count_ones() as i64;
+        let white_discs: i64 = pairing.position_white.count_ones() as i64;
+        for (player, points, differential) in [
+            (&pairing.black_uuid, black_points, black_discs - white_discs),
+            (&pairing.white_uuid, white_points, white_discs - black_discs),
+        ] {
+            let s = standings.get_mut(player).unwrap();
+            s.score += points;
+            s.games_played += 1;
+            s.disc_differential += differential;
+            if points == 1.0 {
+                s.wins += 1;
+            } else if points == 0.5 {
+                s.draws += 1;
+            } else {
+                s.losses += 1;
+            }
+        }
+        results
+            .entry(pairing.black_uuid.clone())
+            .or_default()
+            .push((pairing.white_uuid.clone(), black_points));
+        results
+            .entry(pairing.white_uuid.clone())
+            .or_default()
+            .push((pairing.black_uuid.clone(), white_points));
+    }
+
+    let scores: HashMap<String, f64> = standings
+        .iter()
+        .map(|(p, s)| (p.clone(), s.score))
+        .collect();
+    for (player, games) in &results {
+        let s = standings.get_mut(player).unwrap();
+        for (opponent, points) in games {
+            let opponent_score: f64 = scores.get(opponent).copied().unwrap_or(0.0);
+            s.buchholz += opponent_score;
+            s.sonneborn_berger += points * opponent_score;
+        }
+    }
+
+    let mut ranked: Vec<Standing> = order
+        .into_iter()
+        .map(|p| standings.remove(&p).unwrap())
+        .collect();
+    // The sort is stable, so fully tied players keep their registration order.
+    ranked.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.buchholz.total_cmp(&a.buchholz))
+            .then(b.sonneborn_berger.total_cmp(&a.sonneborn_berger))
+            .then(b.disc_differential.cmp(&a.disc_differential))
+    });
+    for (idx, s) in ranked.iter_mut().enumerate() {
+        s.rank = idx as u64 + 1;
+    }
+    ranked
+}
+
+/// Closes registration or, once the current round is over, pairs and creates the
+/// games of the next round. Finishes the tournament after its last round.
+pub async fn advance_tournament<R>(
+    repo: &R,
+    tournament_uuid: &str,
+) -> Result<RoundOutcome, TournamentError>
+where
+    R: TournamentRepository + Sync + ?Sized,
+{
+    let mut tournament: Tournament = repo
+        .get_tournament(tournament_uuid)
+        .await?
+        .ok_or(TournamentError::NotFound)?;
+    if tournament.state == 2 {
+        return Err(TournamentError::Finished);
+    }
+    let format: TournamentFormat = TournamentFormat::parse(&tournament.format)
+        .ok_or_else(|| TournamentError::UnknownFormat(tournament.format.clone()))?;
+    let players: Vec<String> = repo.get_players(tournament_uuid).await?;
+    let history: Vec<TournamentPairing> = repo.get_pairings(tournament_uuid).await?;
+
+    if tournament.state == 0 {
+        if players.len() < 2 {
+            return Err(TournamentError::NotEnoughPlayers);
+        }
+        tournament.rounds = format.total_rounds(players.len(), tournament.rounds);
+    }
+    if format.is_elimination() {
+        return advance_bracket(repo, tournament, &players, &history).await;
+    }
+    if tournament.state != 0 && !round_complete(&history, tournament.current_round) {
+        return Err(TournamentError::RoundInProgress(tournament.current_round));
+    }
+
+    if tournament.current_round >= tournament.rounds {
+        tournament.state = 2;
+        repo.update_tournament(&tournament).await?;
+        return Ok(RoundOutcome::Finished);
+    }
+
+    let round: u64 = tournament.current_round + 1;
+    let pairs: Vec<(String, Option<String>)> = match format {
+        TournamentFormat::RoundRobin => round_robin_round(&players, round, false),
+        TournamentFormat::DoubleRoundRobin => round_robin_round(&players, round, true),
+        TournamentFormat::Swiss => swiss_pairings(&players, &history),
+        // Elimination brackets are advanced by `advance_bracket`.
+        TournamentFormat::SingleElimination | TournamentFormat::DoubleElimination => {
+            return Err(TournamentError::UnknownFormat(tournament.format.clone()));
+        }
+    };
+
+    let mut games: Vec<Game> = Vec::new();
+    let mut pairings: Vec<TournamentPairing> = Vec::new();
+    for (black, white) in pairs {
+        match white {
+            Some(white) => {
+                let game: Game = paired_game(&black, &white, &tournament.time_control);
+                pairings.push(TournamentPairing {
+                    round,
+                    black_uuid: black,
+                    white_uuid: white,
+                    game_uuid: game.game_uuid.clone(),
+                    game_state: game.state,
+                    position_black: game.position_black,
+                    position_white: game.position_white,
+                    bracket_match: String::new(),
+                    game_no: 0,
+                });
+                games.push(game);
+            }
+            None => pairings.push(TournamentPairing {
+                round,
+                black_uuid: black,
+                white_uuid: String::new(),
+                game_uuid: String::new(),
+                game_state: 0,
+                position_black: 0,
+                position_white: 0,
+                bracket_match: String::new(),
+                game_no: 0,
+            }),
+        }
+    }
+
+    if repo
+        .start_round(&tournament, round, &games, &pairings)
+        .await?
+    {
+        Ok(RoundOutcome::Started(round))
+    } else {
+        Ok(RoundOutcome::AlreadyAdvanced)
+    }
+}
+
+/// Creates the next games of every bracket match that can go on, or finishes the
+/// tournament once the champion is known.
+async fn advance_bracket<R>(
+    repo: &R,
+    mut tournament: Tournament,
+    players: &[String],
+    history: &[TournamentPairing],
+) -> Result<RoundOutcome, TournamentError>
+where
+    R: TournamentRepository + Sync + ?Sized,
+{
+    let bracket: Bracket = match tournament_bracket(&tournament, players, history)? {
+        Some(b) => b,
+        None => return Err(TournamentError::UnknownFormat(tournament.format.clone())),
+    };
+    if bracket.champion.is_some() {
+        tournament.state = 2;
+        repo.update_tournament(&tournament).await?;
+        return Ok(RoundOutcome::Finished);
+    }
+    if bracket.next_games.is_empty() {
+        return Err(TournamentError::RoundInProgress(tournament.current_round));
+    }
+
+    let mut games: Vec<Game> = Vec::new();
+    let mut pairings: Vec<TournamentPairing> = Vec::new();
+    for next in bracket.next_games {
+        let game: Game = paired_game(&next.black_uuid, &next.white_uuid, &tournament.time_control);
+        if next.match_id.starts_with('W') {
+            tournament.current_round = tournament.current_round.max(next.round);
+        }
+        pairings.push(TournamentPairing {
+            round: next.round,
+            black_uuid: next.black_uuid,
+            white_uuid: next.white_uuid,
+            game_uuid: game.game_uuid.clone(),
+            game_state: game.state,
+            position_black: game.position_black,
+            position_white: game.position_white,
+            bracket_match: next.match_id,
+            game_no: next.game_no,
+        });
+        games.push(game);
+    }
+
+    if repo.add_games(&tournament, &games, &pairings).await? {
+        Ok(RoundOutcome::Started(tournament.current_round))
+    } else {
+        Ok(RoundOutcome::AlreadyAdvanced)
+    }
+}
+
+/// Called when a game reaches a terminal state: advances the tournament the game
+/// belongs to if it was the last unfinished game of its round, or right away for
+/// elimination brackets.
+pub async fn on_game_finished<R>(
+    repo: &R,
+    game_uuid: &str,
+) -> Result<Option<RoundOutcome>, TournamentError>
+where
+    R: TournamentRepository + Sync + ?Sized,
+{
+    let tournament_uuid: String = match repo.tournament_for_game(game_uuid).await? {
+        Some(t) => t,
+        None => return Ok(None),
+    };
+    match advance_tournament(repo, &tournament_uuid).await {
+        Ok(outcome) => Ok(Some(outcome)),
+        Err(TournamentError::RoundInProgress(_)) | Err(TournamentError::Finished) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("p{}", i)).collect()
+    }
+
+    fn finished(round: u64, black: &str, white: &str, state: u64) -> TournamentPairing {
+        TournamentPairing {
+            round,
+            black_uuid: black.to_string(),
+            white_uuid: white.to_string(),
+            game_uuid: format!("{}-{}-{}", round, black, white),
+            game_state: state,
+            position_black: 0x000000ffffffffff,
+            position_white: 0xffffff0000000000,
+            bracket_match: String::new(),
+            game_no: 0,
+        }
+    }
+
+    #[test]
+    fn round_robin_meets_everyone_once() {
+        for n in 2..=9 {
+            let players = names(n);
+            let rounds = TournamentFormat::RoundRobin.total_rounds(n, 0);
+            let mut met: HashSet<(String, String)> = HashSet::new();
+            let mut byes: HashMap<String, u64> = HashMap::new();
+            for round in 1..=rounds {
+                let pairs = round_robin_round(&players, round, false);
+                let mut seen: HashSet<String> = HashSet::new();
+                for (black, white) in pairs {
+                    assert!(seen.insert(black.clone()));
+                    match white {
+                        Some(white) => {
+                            assert!(seen.insert(white.clone()));
+                            let key = if black < white {
+                                (black, white)
+                            } else {
+                                (white, black)
+                            };
+                            assert!(met.insert(key), "rematch with {} players", n);
+                        }
+                        None => *byes.entry(black).or_default() += 1,
+                    }
+                }
+                assert_eq!(seen.len(), n);
+            }
+            assert_eq!(met.len(), n * (n - 1) / 2);
+            assert!(byes.values().all(|b| *b == 1));
+            assert_eq!(byes.len(), n % 2 * n);
+        }
+    }
+
+    #[test]
+    fn double_round_robin_swaps_colors() {
+        let players = names(4);
+        let rounds = TournamentFormat::DoubleRoundRobin.total_rounds(4, 0);
+        assert_eq!(rounds, 6);
+        for round in 1..=3 {
+            let first = round_robin_round(&players, round, true);
+            let second = round_robin_round(&players, round + 3, true);
+            for ((b1, w1), (b2, w2)) in first.into_iter().zip(second) {
+                assert_eq!(Some(b1), w2);
+                assert_eq!(w1, Some(b2));
+            }
+        }
+    }
+
+    #[test]
+    fn swiss_rounds_default_to_log2() {
+        assert_eq!(TournamentFormat::Swiss.total_rounds(2, 0), 1);
+        assert_eq!(TournamentFormat::Swiss.total_rounds(8, 0), 3);
+        assert_eq!(TournamentFormat::Swiss.total_rounds(9, 0), 4);
+        assert_eq!(TournamentFormat::Swiss.total_rounds(9, 7), 7);
+    }
+
+    #[test]
+    fn swiss_avoids_rematches_and_repeated_byes() {
+        let players = names(5);
+        let history = vec![
+            finished(1, "p0", "p1", 3),
+            finished(1, "p2", "p3", 4),
+            TournamentPairing {
+                round: 1,
+                black_uuid: "p4".to_string(),
+                white_uuid: String::new(),
+                game_uuid: String::new(),
+                game_state: 0,
+                position_black: 0,
+                position_white: 0,
+                bracket_match: String::new(),
+                game_no: 0,
+            },
+        ];
+        let pairs = swiss_pairings(&players, &history);
+        assert_eq!(pairs.len(), 3);
+        let bye: Vec<&String> = pairs
+            .iter()
+            .filter(|(_, w)| w.is_none())
+            .map(|(b, _)| b)
+            .collect();
+        assert_eq!(bye.len(), 1);
+        assert_ne!(bye[0], "p4");
+        for (black, white) in &pairs {
+            if let Some(white) = white {
+                let pair = [black.as_str(), white.as_str()];
+                assert!(!(pair.contains(&"p0") && pair.contains(&"p1")));
+                assert!(!(pair.contains(&"p2") && pair.contains(&"p3")));
+            }
+        }
+        // p0 played black in round 1, so it gets white now.
+        let p0 = pairs
+            .iter()
+            .find(|(b, w)| b == "p0" || w.as_deref() == Some("p0"))
+            .unwrap();
+        assert_eq!(p0.1.as_deref(), Some("p0"));
+    }
+
+    #[test]
+    fn standings_use_tie_breaks() {
+        let players = names(4);
+        let pairings = vec![
+            finished(1, "p0", "p1", 3),
+            finished(1, "p2", "p3", 3),
+            finished(2, "p0", "p2", 5),
+            finished(2, "p1", "p3", 3),
+        ];
+        let standings = compute_standings(&players, &pairings);
+        let order: Vec<&str> = standings.iter().map(|s| s.player_id.as_str()).collect();
+        // p0 and p2 both have 1.5 points, p0 beat p1 (1 point) while p2 beat p3 (0 points).
+        assert_eq!(order, vec!["p0", "p2", "p1", "p3"]);
+        assert_eq!(standings[0].score, 1.5);
+        assert_eq!(standings[0].buchholz, 2.5);
+        assert_eq!(standings[0].sonneborn_berger, 1.75);
+        assert_eq!(standings[1].sonneborn_berger, 0.75);
+        assert_eq!(standings[0].disc_differential, 32);
+        assert_eq!(standings[3].losses, 2);
+        assert_eq!(standings[0].rank, 1);
+    }
+
+    #[test]
+    fn round_is_complete_when_all_games_finished() {
+        let pairings = vec![finished(1, "p0", "p1", 3), finished(1, "p2", "p3", 2)];
+        assert!(!round_complete(&pairings, 1));
+        assert!(round_complete(&pairings[..1], 1));
+    }
+}
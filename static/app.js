@@ -0,0 +1,333 @@
+// Browser client for the reversi API. It only uses the public endpoints below
+// /reversi/v1 and the game event stream, so it doubles as an example client.
+"use strict";
+
+const API = "/reversi/v1";
+const FILES = "abcdefgh";
+const DIRECTIONS = [
+  [-1, -1], [-1, 0], [-1, 1], [0, -1], [0, 1], [1, -1], [1, 0], [1, 1],
+];
+
+const state = {
+  player: localStorage.getItem("reversi.player") || "",
+  // Our color in the games we created or joined, by game id.
+  colors: JSON.parse(localStorage.getItem("reversi.colors") || "{}"),
+  game: null,
+  status: "",
+  moves: [],
+  events: null,
+};
+
+const $ = (id) => document.getElementById(id);
+
+function say(message) {
+  $("message").textContent = message || "";
+}
+
+async function post(path, body, headers) {
+  const response = await fetch(API + path, {
+    method: "POST",
+    headers: Object.assign({ "Content-Type": "application/json" }, headers || {}),
+    body: JSON.stringify(body),
+  });
+  if (response.status === 429) {
+    const wait = response.headers.get("Retry-After") || "a few";
+    throw new Error(`Too many requests, try again in ${wait} seconds`);
+  }
+  const data = await response.json();
+  if (data.status !== "ok") {
+    throw new Error(data.error.message || "Request failed");
+  }
+  return data.result;
+}
+
+// Board replay ---------------------------------------------------------------
+
+function initialBoard() {
+  const board = Array.from({ length: 8 }, () => Array(8).fill(null));
+  board[3][3] = "white";
+  board[4][4] = "white";
+  board[3][4] = "black";
+  board[4][3] = "black";
+  return board;
+}
+
+// Row 0 is rank 1, column 0 is file a.
+function parseSquare(square) {
+  return [Number(square[1]) - 1, FILES.indexOf(square[0])];
+}
+
+function flips(board, row, col, color) {
+  if (board[row][col]) {
+    return [];
+  }
+  const other = color === "black" ? "white" : "black";
+  const flipped = [];
+  for (const [dr, dc] of DIRECTIONS) {
+    const line = [];
+    let r = row + dr;
+    let c = col + dc;
+    while (r >= 0 && r < 8 && c >= 0 && c < 8 && board[r][c] === other) {
+      line.push([r, c]);
+      r += dr;
+      c += dc;
+    }
+    if (line.length && r >= 0 && r < 8 && c >= 0 && c < 8 && board[r][c] === color) {
+      flipped.push(...line);
+    }
+  }
+  return flipped;
+}
+
+function replay(moves) {
+  const board = initialBoard();
+  let color = "black";
+  for (const move of moves) {
+    if (move !== "pass") {
+      const [row, col] = parseSquare(move);
+      for (const [r, c] of flips(board, row, col, color)) {
+        board[r][c] = color;
+      }
+      board[row][col] = color;
+    }
+    color = color === "black" ? "white" : "black";
+  }
+  return board;
+}
+
+// Rendering ------------------------------------------------------------------
+
+function myTurn() {
+  const color = state.colors[state.game];
+  return (state.status === "black" || state.status === "white") &&
+    (!color || color === state.status);
+}
+
+function describe(status) {
+  const color = state.colors[state.game];
+  const you = color ? ` You play ${color}.` : "";
+  switch (status) {
+    case "pending": return "Waiting for an opponent." + you;
+    case "black": return "Black to move." + you;
+    case "white": return "White to move." + you;
+    case "black_won": return "Black won.";
+    case "white_won": return "White won.";
+    case "draw": return "Draw.";
+    default: return status;
+  }
+}
+
+function render() {
+  const board = replay(state.moves);
+  const last = state.moves.length ? state.moves[state.moves.length - 1] : "";
+  const grid = $("board");
+  grid.replaceChildren();
+  let black = 0;
+  let white = 0;
+  for (let row = 7; row >= 0; row--) {
+    for (let col = 0; col < 8; col++) {
+      const square = FILES[col] + (row + 1);
+      const cell = document.createElement("button");
+      cell.type = "button";
+      cell.className = "cell";
+      cell.title = square;
+      const disc = board[row][col];
+      if (disc) {
+        const piece = document.createElement("span");
+        piece.className = `disc ${disc}`;
+        cell.appendChild(piece);
+        if (disc === "black") black++; else white++;
+      } else if (myTurn() && flips(board, row, col, state.status).length) {
+        cell.classList.add("legal");
+        cell.addEventListener("click", () => play(square));
+      }
+      if (square === last) {
+        cell.classList.add("last");
+      }
+      grid.appendChild(cell);
+    }
+  }
+  $("status").textContent = describe(state.status);
+  $("score").textContent = `Black ${black} : ${white} White`;
+  $("moves").textContent = state.moves.join(" ");
+  $("pass").disabled = !myTurn();
+  $("resign").disabled = !myTurn();
+}
+
+// Actions --------------------------------------------------------------------
+
+async function refreshGame() {
+  const request = { player_id: state.player, game_id: state.game };
+  const [status, history] = await Promise.all([
+    post("/game_status", request),
+    post("/game_history", request),
+  ]);
+  state.status = status.status;
+  state.moves = history.moves;
+  render();
+}
+
+// Sends a move with an idempotency key, so that a retry after a network error
+// cannot play it twice.
+async function play(move) {
+  const body = { player_id: state.player, game_id: state.game, move: move };
+  const headers = { "Idempotency-Key": crypto.randomUUID() };
+  say("");
+  try {
+    try {
+      await post("/move", body, headers);
+    } catch (e) {
+      if (!(e instanceof TypeError)) throw e;
+      await post("/move", body, headers);
+    }
+    await refreshGame();
+  } catch (e) {
+    say(e.message);
+  }
+}
+
+function subscribe() {
+  if (state.events) {
+    state.events.close();
+  }
+  state.events = new EventSource(`${API}/games/${state.game}/events`);
+  state.events.addEventListener("message", () => refreshGame().catch((e) => say(e.message)));
+  state.events.addEventListener("resync", () => refreshGame().catch((e) => say(e.message)));
+}
+
+async function openGame(gameId) {
+  state.game = gameId;
+  $("game-id").textContent = gameId;
+  $("lobby").hidden = true;
+  $("game").hidden = false;
+  history.replaceState(null, "", `#${gameId}`);
+  subscribe();
+  await refreshGame();
+}
+
+function leaveGame() {
+  if (state.events) {
+    state.events.close();
+    state.events = null;
+  }
+  state.game = null;
+  history.replaceState(null, "", "#");
+  $("game").hidden = true;
+  $("lobby").hidden = false;
+  refreshLobby();
+}
+
+function remember(gameId, color) {
+  state.colors[gameId] = color;
+  localStorage.setItem("reversi.colors", JSON.stringify(state.colors));
+}
+
+async function refreshLobby() {
+  const list = $("games");
+  list.replaceChildren();
+  if (!state.player) {
+    return;
+  }
+  const games = await post("/game_list", { player_id: state.player });
+  for (const game of games) {
+    const item = document.createElement("li");
+    const join = document.createElement("button");
+    join.type = "button";
+    join.textContent = "Join";
+    join.addEventListener("click", async () => {
+      try {
+        const joined = await post("/join", { player_id: state.player, game_id: game.game_id });
+        remember(game.game_id, joined.color);
+        await openGame(game.game_id);
+      } catch (e) {
+        say(e.message);
+      }
+    });
+    item.append(join, ` ${game.game_id} by ${game.first_player}`);
+    list.appendChild(item);
+  }
+  if (!games.length) {
+    list.textContent = "No open games.";
+  }
+}
+
+async function loadPlayers() {
+  const response = await fetch(`${API}/players`);
+  const data = await response.json();
+  const options = $("players");
+  for (const user of data.result || []) {
+    const option = document.createElement("option");
+    option.value = user.player_uuid;
+    option.label = user.comment;
+    options.appendChild(option);
+  }
+}
+
+// Wiring ---------------------------------------------------------------------
+
+function guard(action) {
+  return async (event) => {
+    event.preventDefault();
+    say("");
+    if (!state.player) {
+      say("Choose your player id first.");
+      return;
+    }
+    try {
+      await action();
+    } catch (e) {
+      say(e.message);
+    }
+  };
+}
+
+function usePlayer(player) {
+  state.player = player;
+  localStorage.setItem("reversi.player", state.player);
+  $("player-id").value = state.player;
+}
+
+$("player-form").addEventListener("submit", (event) => {
+  event.preventDefault();
+  usePlayer($("player-id").value.trim());
+  say("");
+  refreshLobby().catch((e) => say(e.message));
+});
+
+$("register-form").addEventListener("submit", async (event) => {
+  event.preventDefault();
+  say("");
+  try {
+    const player = await post("/players/register", { comment: $("comment").value.trim() });
+    usePlayer(player.player_uuid);
+    $("comment").value = "";
+    say(`Registered as ${player.player_uuid}.`);
+    await loadPlayers();
+    await refreshLobby();
+  } catch (e) {
+    say(e.message);
+  }
+});
+
+$("create-form").addEventListener("submit", guard(async () => {
+  const created = await post("/create_game", {
+    player_id: state.player,
+    opening: $("opening").value.trim(),
+  });
+  remember(created.game_id, created.color);
+  await openGame(created.game_id);
+}));
+
+$("open-form").addEventListener("submit", guard(() => openGame($("open-id").value.trim())));
+$("refresh").addEventListener("click", guard(refreshLobby));
+$("pass").addEventListener("click", () => play("pass"));
+$("resign").addEventListener("click", () => play("resign"));
+$("leave").addEventListener("click", leaveGame);
+
+$("player-id").value = state.player;
+loadPlayers().catch(() => {});
+if (state.player && location.hash.length > 1) {
+  openGame(location.hash.slice(1)).catch((e) => say(e.message));
+} else {
+  refreshLobby().catch((e) => say(e.message));
+}